@@ -0,0 +1 @@
+DROP TABLE cell_changes;
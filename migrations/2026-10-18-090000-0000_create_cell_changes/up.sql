@@ -0,0 +1,12 @@
+-- Records which cells each sync inserted, modified or removed
+CREATE TABLE cell_changes (
+  radio ENUM('gsm','umts','lte','nr','cdma') NOT NULL,
+  mcc SMALLINT UNSIGNED NOT NULL,
+  net SMALLINT UNSIGNED NOT NULL,
+  area INT UNSIGNED NOT NULL,
+  cell BIGINT UNSIGNED NOT NULL,
+  changed_at DATETIME NOT NULL,
+  removed BOOLEAN NOT NULL,
+  PRIMARY KEY (mcc, net, area, cell, radio),
+  INDEX cell_changes_changed_at (changed_at, mcc, net, area, cell, radio)
+);
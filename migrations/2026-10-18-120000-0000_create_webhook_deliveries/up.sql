@@ -0,0 +1,16 @@
+-- Log of every attempt to deliver a webhook
+CREATE TABLE webhook_deliveries (
+  id BIGINT UNSIGNED NOT NULL AUTO_INCREMENT,
+  delivery_id CHAR(32) NOT NULL,
+  url VARCHAR(2048) NOT NULL,
+  event VARCHAR(32) NOT NULL,
+  attempt INT UNSIGNED NOT NULL,
+  status_code SMALLINT UNSIGNED,
+  error TEXT,
+  succeeded BOOLEAN NOT NULL,
+  payload TEXT NOT NULL,
+  attempted_at DATETIME NOT NULL,
+  PRIMARY KEY (id),
+  INDEX webhook_deliveries_delivery_id (delivery_id),
+  INDEX webhook_deliveries_attempted_at (attempted_at)
+);
@@ -0,0 +1 @@
+DROP INDEX cells_lat_lon ON cells;
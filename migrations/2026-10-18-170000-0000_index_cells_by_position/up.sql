@@ -0,0 +1,4 @@
+-- Lets the bounding box queries of `within` (neighbours, coverage, routes,
+-- venues, nearest cells) scan the latitude range instead of the whole table,
+-- with the longitude checked on the index entries
+CREATE INDEX cells_lat_lon ON cells (lat, lon);
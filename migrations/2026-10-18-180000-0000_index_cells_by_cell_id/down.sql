@@ -0,0 +1 @@
+DROP INDEX cells_mcc_net_cell ON cells;
@@ -0,0 +1,3 @@
+-- Lets fuzzy lookups find a cell ID in any area of an operator without
+-- scanning all of its cells
+CREATE INDEX cells_mcc_net_cell ON cells (mcc, net, cell);
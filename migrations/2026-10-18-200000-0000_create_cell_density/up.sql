@@ -0,0 +1,12 @@
+-- Cell counts per geohash bucket of every resolution, rebuilt after each sync
+CREATE TABLE cell_density (
+  resolution TINYINT UNSIGNED NOT NULL,
+  lat_index BIGINT NOT NULL,
+  lon_index BIGINT NOT NULL,
+  radio ENUM('gsm','umts','lte','nr','cdma') NOT NULL,
+  mcc SMALLINT UNSIGNED NOT NULL,
+  net SMALLINT UNSIGNED NOT NULL,
+  cells BIGINT NOT NULL,
+  samples BIGINT UNSIGNED NOT NULL,
+  PRIMARY KEY (resolution, lat_index, lon_index, radio, mcc, net)
+);
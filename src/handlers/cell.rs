@@ -1,18 +1,65 @@
 use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
 
+use super::matching::{query_candidates, CellCandidate, MatchMode, ReportedCell};
+use crate::utils::caching::{CacheValidators, Conditions};
 use crate::utils::config::Config;
-use crate::{models::*, utils::db::establish_connection};
+use crate::utils::db::get_last_update_with_connection;
+use crate::utils::errors::{ApiError, ErrorBody};
+use crate::utils::negotiation::{encode, Format};
+use crate::utils::query::Validate;
+use crate::{models::*, utils::db::try_establish_connection};
 use diesel::prelude::*;
 use diesel::MysqlConnection;
 use tracing::instrument;
 
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct GetCellQuery {
+    /// Mobile Country Code
     pub mcc: u16,
+    /// Mobile Network Code
     pub net: u16,
+    /// Location Area Code (GSM/UMTS) or Tracking Area Code (LTE/NR)
     pub area: u32,
+    /// Cell ID
     pub cell: u64,
+    /// Radio type
     pub radio: Option<Radio>,
+    /// `fuzzy` returns ranked candidates if there is no exact match
+    #[serde(rename = "match")]
+    pub match_mode: Option<MatchMode>,
+}
+
+impl Validate for GetCellQuery {}
+
+impl From<&GetCellQuery> for ReportedCell {
+    fn from(query: &GetCellQuery) -> Self {
+        ReportedCell {
+            mcc: query.mcc,
+            net: query.net,
+            area: query.area,
+            cell: query.cell,
+            radio: query.radio.clone(),
+        }
+    }
+}
+
+/// Response of `/cell` with `match=fuzzy`.
+#[derive(Serialize, Debug, ToSchema)]
+pub struct FuzzyCellResponse {
+    /// The exact match, if any
+    pub cell: Option<Cell>,
+    /// Ranked candidates, only filled when there is no exact match
+    pub candidates: Vec<CellCandidate>,
+}
+
+/// Response of `/cell`: the cell, or the candidates with `match=fuzzy`.
+#[derive(Serialize, Debug, ToSchema)]
+#[serde(untagged)]
+pub enum GetCellResponse {
+    Cell(Cell),
+    Fuzzy(FuzzyCellResponse),
 }
 
 /// Queries a cell from the database. Extracted for testability.
@@ -42,17 +89,80 @@ pub fn query_cell(
     }
 }
 
+/// Adds fuzzy candidates to the result of an exact lookup, if it missed.
+pub fn fuzzy_cell(
+    query: &GetCellQuery,
+    exact: Option<Cell>,
+    connection: &mut MysqlConnection,
+) -> Result<FuzzyCellResponse, diesel::result::Error> {
+    if exact.is_some() {
+        return Ok(FuzzyCellResponse {
+            cell: exact,
+            candidates: vec![],
+        });
+    }
+
+    let candidates = query_candidates(&[ReportedCell::from(query)], connection)?
+        .pop()
+        .unwrap_or_default();
+    Ok(FuzzyCellResponse {
+        cell: None,
+        candidates,
+    })
+}
+
+/// Get a single cell by its identifiers.
+#[utoipa::path(
+    get,
+    path = "/v1/cell",
+    params(GetCellQuery),
+    responses(
+        (status = 200, description = "The cell, or ranked candidates with `match=fuzzy`. Protobuf responses are a `Cell` or `FuzzyCellResponse` message", content((GetCellResponse = "application/json"), (GetCellResponse = "application/msgpack"), (GetCellResponse = "application/cbor"))),
+        (status = 304, description = "The cached copy is still fresh"),
+        (status = 400, description = "Invalid parameters", body = ErrorBody),
+        (status = 404, description = "No such cell and no candidates", body = ErrorBody),
+        (status = 406, description = "None of the accepted formats is supported", body = ErrorBody),
+        (status = 503, description = "The database is unavailable", body = ErrorBody),
+    ),
+    tag = "cells"
+)]
 #[instrument(skip(config))]
 pub async fn handle_get_cell(
     query: GetCellQuery,
+    conditions: Conditions,
+    format: Format,
     config: Config,
 ) -> Result<impl warp::Reply, warp::Rejection> {
-    let connection = &mut establish_connection(config.clone());
+    let connection = &mut try_establish_connection(config.clone()).map_err(ApiError::from)?;
+
+    let last_update = get_last_update_with_connection(connection).map_err(ApiError::from)?;
+    let validators = CacheValidators::new(
+        &format.cache_route("cell"),
+        &query,
+        last_update,
+        chrono::Utc::now(),
+    );
+    if validators.is_not_modified(&conditions) {
+        return Ok(validators.not_modified());
+    }
+
+    let entry = query_cell(&query, connection).map_err(ApiError::from)?;
 
-    match query_cell(&query, connection) {
-        Ok(Some(entry)) => Ok(warp::reply::json(&entry)),
-        Ok(None) => Ok(warp::reply::json(&serde_json::Value::Null)),
-        Err(_) => Ok(warp::reply::json(&serde_json::Value::Null)),
+    if query.match_mode == Some(MatchMode::Fuzzy) {
+        let response = fuzzy_cell(&query, entry, connection).map_err(ApiError::from)?;
+        if response.cell.is_none() && response.candidates.is_empty() {
+            return Err(ApiError::NotFound("Cell not found".to_string()).into());
+        }
+        return Ok(validators.apply(encode(format, &GetCellResponse::Fuzzy(response))));
+    }
+
+    match entry {
+        Some(entry) => Ok(validators.apply(encode(format, &GetCellResponse::Cell(entry)))),
+        // Older clients expect `null` instead of a 404
+        None if config.legacy_not_found_null => {
+            Ok(validators.apply(encode(format, &None::<GetCellResponse>)))
+        }
+        None => Err(ApiError::NotFound("Cell not found".to_string()).into()),
     }
 }
 
@@ -125,6 +235,15 @@ mod tests {
             assert!(matches!(query.radio, Some(Radio::Gsm)));
         }
 
+        #[test]
+        fn test_deserialize_match_mode_from_query_string() {
+            let query_string = "mcc=262&net=1&area=100&cell=200&match=fuzzy";
+
+            let query: GetCellQuery = serde_urlencoded::from_str(query_string).unwrap();
+
+            assert_eq!(query.match_mode, Some(MatchMode::Fuzzy));
+        }
+
         #[test]
         fn test_deserialize_from_query_string_without_radio() {
             let query_string = "mcc=310&net=410&area=1000&cell=999";
@@ -158,6 +277,7 @@ mod tests {
                 area: 100,
                 cell: 200,
                 radio: Some(Radio::Umts),
+                match_mode: None,
             };
 
             let json = serde_json::to_string(&query).unwrap();
@@ -233,6 +353,7 @@ mod tests {
                 area: 12345,
                 cell: 67890,
                 radio: None,
+                match_mode: None,
             };
             let result = query_cell(&query, &mut conn).unwrap();
 
@@ -254,6 +375,7 @@ mod tests {
                 area: 999,
                 cell: 999,
                 radio: None,
+                match_mode: None,
             };
             let result = query_cell(&query, &mut conn).unwrap();
 
@@ -283,6 +405,7 @@ mod tests {
                 area: 100,
                 cell: 200,
                 radio: Some(Radio::Lte),
+                match_mode: None,
             };
             let result = query_cell(&query, &mut conn).unwrap();
 
@@ -296,6 +419,7 @@ mod tests {
                 area: 100,
                 cell: 200,
                 radio: Some(Radio::Gsm),
+                match_mode: None,
             };
             let result_gsm = query_cell(&query_gsm, &mut conn).unwrap();
             assert!(result_gsm.is_none());
@@ -318,6 +442,7 @@ mod tests {
                 area: 5000,
                 cell: 6000,
                 radio: None,
+                match_mode: None,
             };
             assert!(query_cell(&query, &mut conn).unwrap().is_none());
 
@@ -328,6 +453,7 @@ mod tests {
                 area: 5000,
                 cell: 6000,
                 radio: None,
+                match_mode: None,
             };
             assert!(query_cell(&query, &mut conn).unwrap().is_none());
 
@@ -338,6 +464,7 @@ mod tests {
                 area: 9999,
                 cell: 6000,
                 radio: None,
+                match_mode: None,
             };
             assert!(query_cell(&query, &mut conn).unwrap().is_none());
 
@@ -348,6 +475,7 @@ mod tests {
                 area: 5000,
                 cell: 9999,
                 radio: None,
+                match_mode: None,
             };
             assert!(query_cell(&query, &mut conn).unwrap().is_none());
 
@@ -358,8 +486,39 @@ mod tests {
                 area: 5000,
                 cell: 6000,
                 radio: None,
+                match_mode: None,
             };
             assert!(query_cell(&query, &mut conn).unwrap().is_some());
         }
+
+        #[test]
+        fn test_fuzzy_cell_finds_cell_in_other_area() {
+            let (_container, mut conn) = get_test_connection();
+
+            let test_cell = sample_cell(262, 1, 200, 6000, Radio::Lte);
+            diesel::insert_into(cells::table)
+                .values(&test_cell)
+                .execute(&mut conn)
+                .unwrap();
+
+            // Stale area and wrong radio reported by the modem
+            let query = GetCellQuery {
+                mcc: 262,
+                net: 1,
+                area: 100,
+                cell: 6000,
+                radio: Some(Radio::Umts),
+                match_mode: Some(MatchMode::Fuzzy),
+            };
+            let exact = query_cell(&query, &mut conn).unwrap();
+            let response = fuzzy_cell(&query, exact, &mut conn).unwrap();
+
+            assert!(response.cell.is_none());
+            assert_eq!(response.candidates.len(), 1);
+            assert_eq!(
+                response.candidates[0].reason,
+                crate::handlers::matching::MatchReason::AreaAndRadioMismatch
+            );
+        }
     }
 }
@@ -1,18 +1,35 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, NaiveDateTime};
+use hmac::{Hmac, Mac};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use tracing::instrument;
+use utoipa::{IntoParams, ToSchema};
 
+use crate::handlers::coverage::{query_coverage_features, CoverageFeature};
+use crate::utils::caching::{CacheValidators, Conditions};
+use crate::utils::confidence::min_confidence;
 use crate::utils::config::Config;
-use crate::{models::*, utils::db::establish_connection};
+use crate::utils::coverage::CoverageShape;
+use crate::utils::db::get_last_update_with_connection;
+use crate::utils::errors::{ApiError, ErrorBody, FieldError};
+use crate::utils::geojson::{geojson_reply, FeatureCollection};
+use crate::utils::negotiation::{encode, Format};
+use crate::utils::query::{check_confidence, check_coordinate, check_range, into_result, Validate};
+use crate::{models::*, utils::db::try_establish_connection};
 use diesel::prelude::*;
 use diesel::MysqlConnection;
 
 /// Query parameters for fetching multiple cells with pagination and filtering.
-#[derive(Deserialize, Serialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct GetCellsQuery {
     /// Mobile Country Code filter
     pub mcc: Option<u16>,
     /// Mobile Network Code filter
     pub mnc: Option<u16>,
+    /// Location or Tracking Area Code filter
+    pub area: Option<u32>,
     /// Minimum latitude for geofence
     pub min_lat: Option<f32>,
     /// Maximum latitude for geofence
@@ -23,14 +40,74 @@ pub struct GetCellsQuery {
     pub max_lon: Option<f32>,
     /// Radio type filter
     pub radio: Option<Radio>,
+    /// Minimum confidence from 0 to 1
+    pub min_confidence: Option<f64>,
     /// Cursor for pagination (cell ID to start after)
     pub cursor: Option<String>,
     /// Number of items per page (default: 100, max: 1000)
     pub limit: Option<u32>,
 }
 
+impl Validate for GetCellsQuery {
+    fn validate(&self) -> Result<(), Vec<FieldError>> {
+        let mut errors = vec![];
+        check_coordinate(&mut errors, "min_lat", self.min_lat, 90.0);
+        check_coordinate(&mut errors, "max_lat", self.max_lat, 90.0);
+        check_coordinate(&mut errors, "min_lon", self.min_lon, 180.0);
+        check_coordinate(&mut errors, "max_lon", self.max_lon, 180.0);
+        check_range(
+            &mut errors,
+            "min_lat",
+            self.min_lat,
+            "max_lat",
+            self.max_lat,
+        );
+        check_range(
+            &mut errors,
+            "min_lon",
+            self.min_lon,
+            "max_lon",
+            self.max_lon,
+        );
+        check_confidence(&mut errors, self.min_confidence);
+        if self.limit == Some(0) {
+            errors.push(FieldError::new("limit", "must be at least 1"));
+        }
+        into_result(errors)
+    }
+}
+
+/// Alternative representations of `/cells`, independent of the `Accept` header.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum CellsFormat {
+    /// A GeoJSON `FeatureCollection` of coverage polygons
+    Geojson,
+}
+
+/// Query parameters selecting the representation of `/cells`.
+#[derive(Deserialize, Serialize, Debug, Clone, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct CellsOutputQuery {
+    /// `geojson` returns the estimated coverage of each cell
+    pub format: Option<CellsFormat>,
+    /// Coverage estimate with `format=geojson`, `voronoi` clips the circles
+    /// against same-operator cells of the same radio
+    pub shape: Option<CoverageShape>,
+}
+
+impl Validate for CellsOutputQuery {
+    fn validate(&self) -> Result<(), Vec<FieldError>> {
+        let mut errors = vec![];
+        if self.shape.is_some() && self.format.is_none() {
+            errors.push(FieldError::new("shape", "requires format=geojson"));
+        }
+        into_result(errors)
+    }
+}
+
 /// Response for paginated cells endpoint.
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Debug, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct GetCellsResponse {
     /// The list of cells
@@ -41,7 +118,81 @@ pub struct GetCellsResponse {
     pub has_more: bool,
 }
 
+/// The order in which a paginated listing is traversed. It is bound into
+/// every cursor, so a cursor can't be replayed against a different ordering.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortMode {
+    /// Ordered by the composite primary key
+    Key = 1,
+    /// Ordered by change time, then by the composite primary key
+    Changes = 2,
+}
+
+impl SortMode {
+    fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            1 => Some(SortMode::Key),
+            2 => Some(SortMode::Changes),
+            _ => None,
+        }
+    }
+
+    /// Length of the signed payload of a cursor in this mode.
+    fn payload_len(self) -> usize {
+        match self {
+            SortMode::Key => PAYLOAD_LEN,
+            SortMode::Changes => PAYLOAD_LEN + CHANGED_AT_LEN,
+        }
+    }
+}
+
+/// What a cursor is valid for: a sort mode and a hash of the filter parameters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CursorScope {
+    pub sort: SortMode,
+    pub filter_hash: [u8; FILTER_HASH_LEN],
+}
+
+impl CursorScope {
+    /// Builds a scope from any serializable set of filter parameters.
+    pub fn new<F: Serialize>(sort: SortMode, filters: &F) -> Self {
+        let canonical = serde_urlencoded::to_string(filters).unwrap_or_default();
+        let digest = Sha256::digest(canonical.as_bytes());
+        let mut filter_hash = [0; FILTER_HASH_LEN];
+        filter_hash.copy_from_slice(&digest[..FILTER_HASH_LEN]);
+        CursorScope { sort, filter_hash }
+    }
+}
+
+/// Reasons a cursor is rejected.
+#[derive(Debug, PartialEq, Eq)]
+pub enum CursorError {
+    /// Not a cursor issued by this service
+    Malformed,
+    /// The cursor was modified or signed with another secret
+    InvalidSignature,
+    /// The cursor belongs to a different filter set or sort mode
+    ScopeMismatch,
+}
+
+impl CursorError {
+    pub fn into_field_error(self) -> FieldError {
+        let message = match self {
+            CursorError::Malformed => "is malformed",
+            CursorError::InvalidSignature => "has an invalid signature",
+            CursorError::ScopeMismatch => {
+                "was issued for different filters or sort order, restart pagination without it"
+            }
+        };
+        FieldError::new("cursor", message)
+    }
+}
+
 /// Represents a cursor for pagination, encoding the composite primary key.
+///
+/// Encoded cursors are opaque: a binary payload of version, sort mode, filter
+/// hash and key, followed by a truncated HMAC-SHA256 over the payload.
+/// Cursors in `SortMode::Changes` additionally carry the change time.
 #[derive(Debug, Clone)]
 pub struct CellCursor {
     pub radio: Radio,
@@ -49,51 +200,103 @@ pub struct CellCursor {
     pub net: u16,
     pub area: u32,
     pub cell: u64,
+    pub changed_at: Option<NaiveDateTime>,
+}
+
+const CURSOR_VERSION: u8 = 1;
+const FILTER_HASH_LEN: usize = 8;
+const MAC_LEN: usize = 16;
+const KEY_LEN: usize = 1 + 2 + 2 + 4 + 8;
+const PAYLOAD_LEN: usize = 2 + FILTER_HASH_LEN + KEY_LEN;
+const CHANGED_AT_LEN: usize = 8;
+
+fn cursor_mac(secret: &[u8], payload: &[u8]) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts any key length");
+    mac.update(payload);
+    mac
 }
 
 impl CellCursor {
-    /// Encode the cursor as a base64 string.
-    pub fn encode(&self) -> String {
-        let radio_str = match self.radio {
-            Radio::Gsm => "GSM",
-            Radio::Umts => "UMTS",
-            Radio::Cdma => "CDMA",
-            Radio::Lte => "LTE",
-            Radio::Nr => "NR",
+    /// Encode and sign the cursor for the given scope.
+    pub fn encode(&self, scope: &CursorScope, secret: &[u8]) -> String {
+        let radio_code: u8 = match self.radio {
+            Radio::Gsm => 1,
+            Radio::Umts => 2,
+            Radio::Cdma => 3,
+            Radio::Lte => 4,
+            Radio::Nr => 5,
         };
-        let raw = format!(
-            "{}:{}:{}:{}:{}",
-            radio_str, self.mcc, self.net, self.area, self.cell
-        );
-        use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
-        URL_SAFE_NO_PAD.encode(raw.as_bytes())
+
+        let mut bytes = Vec::with_capacity(scope.sort.payload_len() + MAC_LEN);
+        bytes.push(CURSOR_VERSION);
+        bytes.push(scope.sort as u8);
+        bytes.extend_from_slice(&scope.filter_hash);
+        bytes.push(radio_code);
+        bytes.extend_from_slice(&self.mcc.to_be_bytes());
+        bytes.extend_from_slice(&self.net.to_be_bytes());
+        bytes.extend_from_slice(&self.area.to_be_bytes());
+        bytes.extend_from_slice(&self.cell.to_be_bytes());
+        if scope.sort == SortMode::Changes {
+            let changed_at = self.changed_at.unwrap_or_default().and_utc().timestamp();
+            bytes.extend_from_slice(&changed_at.to_be_bytes());
+        }
+
+        let tag = cursor_mac(secret, &bytes).finalize().into_bytes();
+        bytes.extend_from_slice(&tag[..MAC_LEN]);
+
+        URL_SAFE_NO_PAD.encode(bytes)
     }
 
-    /// Decode a cursor from a base64 string.
-    pub fn decode(encoded: &str) -> Option<Self> {
-        use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
-        let bytes = URL_SAFE_NO_PAD.decode(encoded).ok()?;
-        let raw = String::from_utf8(bytes).ok()?;
-        let parts: Vec<&str> = raw.split(':').collect();
-        if parts.len() != 5 {
-            return None;
-        }
-
-        let radio = match parts[0] {
-            "GSM" => Radio::Gsm,
-            "UMTS" => Radio::Umts,
-            "CDMA" => Radio::Cdma,
-            "LTE" => Radio::Lte,
-            "NR" => Radio::Nr,
-            _ => return None,
+    /// Decode a cursor and verify its signature and scope.
+    pub fn decode(encoded: &str, scope: &CursorScope, secret: &[u8]) -> Result<Self, CursorError> {
+        let bytes = URL_SAFE_NO_PAD
+            .decode(encoded)
+            .map_err(|_| CursorError::Malformed)?;
+        if bytes.len() < 2 || bytes[0] != CURSOR_VERSION {
+            return Err(CursorError::Malformed);
+        }
+        let sort = SortMode::from_byte(bytes[1]).ok_or(CursorError::Malformed)?;
+        if bytes.len() != sort.payload_len() + MAC_LEN {
+            return Err(CursorError::Malformed);
+        }
+
+        let (payload, tag) = bytes.split_at(sort.payload_len());
+        cursor_mac(secret, payload)
+            .verify_truncated_left(tag)
+            .map_err(|_| CursorError::InvalidSignature)?;
+
+        if sort != scope.sort || payload[2..2 + FILTER_HASH_LEN] != scope.filter_hash {
+            return Err(CursorError::ScopeMismatch);
+        }
+
+        let key = &payload[2 + FILTER_HASH_LEN..];
+        let radio = match key[0] {
+            1 => Radio::Gsm,
+            2 => Radio::Umts,
+            3 => Radio::Cdma,
+            4 => Radio::Lte,
+            5 => Radio::Nr,
+            _ => return Err(CursorError::Malformed),
+        };
+        let changed_at = match sort {
+            SortMode::Key => None,
+            SortMode::Changes => {
+                let seconds = i64::from_be_bytes(key[KEY_LEN..].try_into().unwrap());
+                Some(
+                    DateTime::from_timestamp(seconds, 0)
+                        .ok_or(CursorError::Malformed)?
+                        .naive_utc(),
+                )
+            }
         };
 
-        Some(CellCursor {
+        Ok(CellCursor {
             radio,
-            mcc: parts[1].parse().ok()?,
-            net: parts[2].parse().ok()?,
-            area: parts[3].parse().ok()?,
-            cell: parts[4].parse().ok()?,
+            mcc: u16::from_be_bytes([key[1], key[2]]),
+            net: u16::from_be_bytes([key[3], key[4]]),
+            area: u32::from_be_bytes(key[5..9].try_into().unwrap()),
+            cell: u64::from_be_bytes(key[9..17].try_into().unwrap()),
+            changed_at,
         })
     }
 
@@ -105,21 +308,45 @@ impl CellCursor {
             net: cell.net,
             area: cell.area,
             cell: cell.cell,
+            changed_at: None,
         }
     }
 }
 
-const DEFAULT_PAGE_SIZE: u32 = 100;
-const MAX_PAGE_SIZE: u32 = 1000;
+impl GetCellsQuery {
+    /// The scope cursors of this query are bound to. Page size and cursor
+    /// itself may change between pages, all other parameters may not.
+    pub fn cursor_scope(&self) -> CursorScope {
+        let filters = GetCellsQuery {
+            cursor: None,
+            limit: None,
+            ..self.clone()
+        };
+        CursorScope::new(SortMode::Key, &filters)
+    }
+}
+
+pub(crate) const DEFAULT_PAGE_SIZE: u32 = 100;
+pub(crate) const MAX_PAGE_SIZE: u32 = 1000;
 
 /// Queries multiple cells from the database with pagination and filtering.
-#[instrument(skip(connection))]
+#[instrument(skip(cursor_secret, connection))]
 pub fn query_cells(
     query: &GetCellsQuery,
+    cursor_secret: &[u8],
     connection: &mut MysqlConnection,
-) -> Result<GetCellsResponse, diesel::result::Error> {
+) -> Result<GetCellsResponse, ApiError> {
     use crate::schema::cells::dsl::*;
 
+    let scope = query.cursor_scope();
+    let cursor = match query.cursor {
+        Some(ref cursor_str) => Some(
+            CellCursor::decode(cursor_str, &scope, cursor_secret)
+                .map_err(|e| ApiError::BadRequest(vec![e.into_field_error()]))?,
+        ),
+        None => None,
+    };
+
     let page_limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).min(MAX_PAGE_SIZE);
     // Fetch one extra to check if there are more results
     let fetch_limit = (page_limit + 1) as i64;
@@ -136,6 +363,11 @@ pub fn query_cells(
         db_query = db_query.filter(net.eq(mnc_filter));
     }
 
+    // Apply area filter
+    if let Some(area_filter) = query.area {
+        db_query = db_query.filter(area.eq(area_filter));
+    }
+
     // Apply radio filter
     if let Some(ref radio_filter) = query.radio {
         db_query = db_query.filter(radio.eq(radio_filter));
@@ -155,41 +387,44 @@ pub fn query_cells(
         db_query = db_query.filter(lon.le(max_lon_filter));
     }
 
+    // Apply confidence filter
+    if let Some(min) = query.min_confidence {
+        db_query = db_query.filter(min_confidence(min, chrono::Utc::now().naive_utc()));
+    }
+
     // Apply cursor-based pagination
     // We order by the composite primary key (radio, mcc, net, area, cell)
     // and use tuple comparison for cursor
-    if let Some(ref cursor_str) = query.cursor {
-        if let Some(cursor) = CellCursor::decode(cursor_str) {
-            // For cursor pagination with composite keys, we need to find rows
-            // that come after the cursor in the sorted order.
-            // Using tuple comparison: (radio, mcc, net, area, cell) > (cursor values)
-            let cursor_radio = cursor.radio.clone();
-            let cursor_mcc = cursor.mcc;
-            let cursor_net = cursor.net;
-            let cursor_area = cursor.area;
-            let cursor_cell = cursor.cell;
-
-            db_query = db_query.filter(
-                radio
-                    .gt(cursor_radio.clone())
-                    .or(radio.eq(cursor_radio.clone()).and(mcc.gt(cursor_mcc)))
-                    .or(radio
-                        .eq(cursor_radio.clone())
-                        .and(mcc.eq(cursor_mcc))
-                        .and(net.gt(cursor_net)))
-                    .or(radio
-                        .eq(cursor_radio.clone())
-                        .and(mcc.eq(cursor_mcc))
-                        .and(net.eq(cursor_net))
-                        .and(area.gt(cursor_area)))
-                    .or(radio
-                        .eq(cursor_radio)
-                        .and(mcc.eq(cursor_mcc))
-                        .and(net.eq(cursor_net))
-                        .and(area.eq(cursor_area))
-                        .and(cell.gt(cursor_cell))),
-            );
-        }
+    if let Some(cursor) = cursor {
+        // For cursor pagination with composite keys, we need to find rows
+        // that come after the cursor in the sorted order.
+        // Using tuple comparison: (radio, mcc, net, area, cell) > (cursor values)
+        let cursor_radio = cursor.radio.clone();
+        let cursor_mcc = cursor.mcc;
+        let cursor_net = cursor.net;
+        let cursor_area = cursor.area;
+        let cursor_cell = cursor.cell;
+
+        db_query = db_query.filter(
+            radio
+                .gt(cursor_radio.clone())
+                .or(radio.eq(cursor_radio.clone()).and(mcc.gt(cursor_mcc)))
+                .or(radio
+                    .eq(cursor_radio.clone())
+                    .and(mcc.eq(cursor_mcc))
+                    .and(net.gt(cursor_net)))
+                .or(radio
+                    .eq(cursor_radio.clone())
+                    .and(mcc.eq(cursor_mcc))
+                    .and(net.eq(cursor_net))
+                    .and(area.gt(cursor_area)))
+                .or(radio
+                    .eq(cursor_radio)
+                    .and(mcc.eq(cursor_mcc))
+                    .and(net.eq(cursor_net))
+                    .and(area.eq(cursor_area))
+                    .and(cell.gt(cursor_cell))),
+        );
     }
 
     // Order by composite primary key for consistent pagination
@@ -197,7 +432,7 @@ pub fn query_cells(
         .order((radio.asc(), mcc.asc(), net.asc(), area.asc(), cell.asc()))
         .limit(fetch_limit);
 
-    let mut results: Vec<Cell> = db_query.load(connection)?;
+    let mut results: Vec<Cell> = db_query.load(connection).map_err(ApiError::from)?;
 
     // Check if there are more results
     let has_more = results.len() > page_limit as usize;
@@ -207,7 +442,9 @@ pub fn query_cells(
 
     // Generate next cursor from the last item
     let next_cursor = if has_more {
-        results.last().map(|c| CellCursor::from_cell(c).encode())
+        results
+            .last()
+            .map(|c| CellCursor::from_cell(c).encode(&scope, cursor_secret))
     } else {
         None
     };
@@ -219,23 +456,68 @@ pub fn query_cells(
     })
 }
 
+/// List cells with optional filters, paginated by cursor.
+#[utoipa::path(
+    get,
+    path = "/v1/cells",
+    params(GetCellsQuery, CellsOutputQuery),
+    responses(
+        (status = 200, description = "A page of cells. Protobuf responses are a `GetCellsResponse` message. With `format=geojson`, a GeoJSON `FeatureCollection` of coverage polygons with `nextCursor` and `hasMore` as foreign members", content((GetCellsResponse = "application/json"), (GetCellsResponse = "application/msgpack"), (GetCellsResponse = "application/cbor"), (FeatureCollection<Cell> = "application/geo+json"))),
+        (status = 304, description = "The cached copy is still fresh"),
+        (status = 400, description = "Invalid parameters or cursor", body = ErrorBody),
+        (status = 406, description = "None of the accepted formats is supported", body = ErrorBody),
+        (status = 503, description = "The database is unavailable", body = ErrorBody),
+    ),
+    tag = "cells"
+)]
 #[instrument(skip(config))]
 pub async fn handle_get_cells(
     query: GetCellsQuery,
+    output: CellsOutputQuery,
+    conditions: Conditions,
+    format: Format,
     config: Config,
 ) -> Result<impl warp::Reply, warp::Rejection> {
-    let connection = &mut establish_connection(config.clone());
-
-    match query_cells(&query, connection) {
-        Ok(response) => Ok(warp::reply::json(&response)),
-        Err(_) => Ok(warp::reply::json(&GetCellsResponse {
-            cells: vec![],
-            next_cursor: None,
-            has_more: false,
-        })),
+    let connection = &mut try_establish_connection(config.clone()).map_err(ApiError::from)?;
+
+    let last_update = get_last_update_with_connection(connection).map_err(ApiError::from)?;
+    let route = match output.format {
+        Some(CellsFormat::Geojson) => {
+            format!("cells/coverage/{:?}", output.shape.unwrap_or_default())
+        }
+        None => format.cache_route("cells"),
+    };
+    let validators = CacheValidators::new(&route, &query, last_update, chrono::Utc::now());
+    if validators.is_not_modified(&conditions) {
+        return Ok(validators.not_modified());
+    }
+
+    let response = query_cells(&query, config.cursor_secret.as_bytes(), connection)?;
+    match output.format {
+        Some(CellsFormat::Geojson) => {
+            let collection =
+                coverage_collection(response, output.shape.unwrap_or_default(), connection)
+                    .map_err(ApiError::from)?;
+            Ok(validators.apply(geojson_reply(&collection)))
+        }
+        None => Ok(validators.apply(encode(format, &response))),
     }
 }
 
+/// A page of cells as coverage features, keeping the paging fields.
+fn coverage_collection(
+    page: GetCellsResponse,
+    shape: CoverageShape,
+    connection: &mut MysqlConnection,
+) -> Result<FeatureCollection<Cell>, diesel::result::Error> {
+    let features: Vec<CoverageFeature> = query_coverage_features(page.cells, shape, connection)?;
+    Ok(FeatureCollection {
+        next_cursor: page.next_cursor,
+        has_more: Some(page.has_more),
+        ..FeatureCollection::new(features)
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -243,18 +525,30 @@ mod tests {
     mod cell_cursor {
         use super::*;
 
-        #[test]
-        fn test_encode_decode_roundtrip() {
-            let cursor = CellCursor {
+        const SECRET: &[u8] = b"test-secret";
+
+        fn sample_cursor() -> CellCursor {
+            CellCursor {
                 radio: Radio::Lte,
                 mcc: 262,
                 net: 1,
                 area: 12345,
                 cell: 67890,
-            };
+                changed_at: None,
+            }
+        }
 
-            let encoded = cursor.encode();
-            let decoded = CellCursor::decode(&encoded).unwrap();
+        fn scope_for(query_string: &str) -> CursorScope {
+            serde_urlencoded::from_str::<GetCellsQuery>(query_string)
+                .unwrap()
+                .cursor_scope()
+        }
+
+        #[test]
+        fn test_encode_decode_roundtrip() {
+            let scope = scope_for("mcc=262");
+            let encoded = sample_cursor().encode(&scope, SECRET);
+            let decoded = CellCursor::decode(&encoded, &scope, SECRET).unwrap();
 
             assert!(matches!(decoded.radio, Radio::Lte));
             assert_eq!(decoded.mcc, 262);
@@ -263,67 +557,142 @@ mod tests {
             assert_eq!(decoded.cell, 67890);
         }
 
+        #[test]
+        fn test_encoded_cursor_is_opaque() {
+            let encoded = sample_cursor().encode(&scope_for(""), SECRET);
+            let raw = URL_SAFE_NO_PAD.decode(&encoded).unwrap();
+
+            assert!(!String::from_utf8_lossy(&raw).contains("LTE"));
+            assert!(!String::from_utf8_lossy(&raw).contains("67890"));
+        }
+
         #[test]
         fn test_decode_invalid_base64() {
-            let result = CellCursor::decode("not-valid-base64!!!");
-            assert!(result.is_none());
+            let result = CellCursor::decode("not-valid-base64!!!", &scope_for(""), SECRET);
+            assert_eq!(result.unwrap_err(), CursorError::Malformed);
         }
 
         #[test]
-        fn test_decode_invalid_format() {
-            use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
-            let invalid = URL_SAFE_NO_PAD.encode(b"only:two:parts");
-            let result = CellCursor::decode(&invalid);
-            assert!(result.is_none());
+        fn test_decode_legacy_plain_cursor() {
+            let legacy = URL_SAFE_NO_PAD.encode(b"LTE:262:1:12345:67890");
+            let result = CellCursor::decode(&legacy, &scope_for(""), SECRET);
+            assert_eq!(result.unwrap_err(), CursorError::Malformed);
         }
 
         #[test]
-        fn test_decode_invalid_radio() {
-            use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
-            let invalid = URL_SAFE_NO_PAD.encode(b"INVALID:262:1:100:200");
-            let result = CellCursor::decode(&invalid);
-            assert!(result.is_none());
+        fn test_decode_tampered_cursor() {
+            let scope = scope_for("");
+            let encoded = sample_cursor().encode(&scope, SECRET);
+            let mut bytes = URL_SAFE_NO_PAD.decode(&encoded).unwrap();
+            // Flip a bit in the cell id
+            bytes[PAYLOAD_LEN - 1] ^= 1;
+            let tampered = URL_SAFE_NO_PAD.encode(bytes);
+
+            let result = CellCursor::decode(&tampered, &scope, SECRET);
+            assert_eq!(result.unwrap_err(), CursorError::InvalidSignature);
         }
 
         #[test]
-        fn test_decode_invalid_numbers() {
-            use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
-            let invalid = URL_SAFE_NO_PAD.encode(b"LTE:abc:1:100:200");
-            let result = CellCursor::decode(&invalid);
-            assert!(result.is_none());
+        fn test_decode_with_other_secret() {
+            let scope = scope_for("");
+            let encoded = sample_cursor().encode(&scope, SECRET);
+
+            let result = CellCursor::decode(&encoded, &scope, b"other-secret");
+            assert_eq!(result.unwrap_err(), CursorError::InvalidSignature);
         }
 
         #[test]
-        fn test_all_radio_types() {
-            let radio_types = vec![
-                (Radio::Gsm, "GSM"),
-                (Radio::Umts, "UMTS"),
-                (Radio::Cdma, "CDMA"),
-                (Radio::Lte, "LTE"),
-                (Radio::Nr, "NR"),
-            ];
+        fn test_decode_with_different_filters() {
+            let encoded = sample_cursor().encode(&scope_for("mcc=262"), SECRET);
+
+            let result = CellCursor::decode(&encoded, &scope_for("mcc=310"), SECRET);
+            assert_eq!(result.unwrap_err(), CursorError::ScopeMismatch);
+        }
+
+        #[test]
+        fn test_scope_ignores_limit_and_cursor() {
+            assert_eq!(
+                scope_for("mcc=262&limit=10"),
+                scope_for("mcc=262&limit=50&cursor=abc")
+            );
+        }
+
+        #[test]
+        fn test_scope_includes_all_filters() {
+            let base = scope_for("mcc=262&mnc=1&radio=LTE&min_lat=52.0");
+
+            assert_ne!(base, scope_for("mcc=262&mnc=1&radio=LTE"));
+            assert_ne!(base, scope_for("mcc=262&mnc=2&radio=LTE&min_lat=52.0"));
+            assert_ne!(base, scope_for("mcc=262&mnc=1&radio=GSM&min_lat=52.0"));
+        }
 
-            for (radio, expected_str) in radio_types {
+        #[test]
+        fn test_changes_cursor_carries_change_time() {
+            let changes_scope = |query_string: &str| {
+                serde_urlencoded::from_str::<crate::handlers::changes::GetChangesQuery>(
+                    query_string,
+                )
+                .unwrap()
+                .cursor_scope()
+            };
+            let scope = changes_scope("since=2025-12-01T00:00:00Z");
+            assert_ne!(scope, changes_scope("since=2025-12-02T00:00:00Z"));
+            let changed_at = DateTime::from_timestamp(1_766_203_200, 0)
+                .unwrap()
+                .naive_utc();
+            let cursor = CellCursor {
+                changed_at: Some(changed_at),
+                ..sample_cursor()
+            };
+
+            let decoded =
+                CellCursor::decode(&cursor.encode(&scope, SECRET), &scope, SECRET).unwrap();
+
+            assert_eq!(decoded.changed_at, Some(changed_at));
+            assert_eq!(decoded.cell, 67890);
+        }
+
+        #[test]
+        fn test_decode_with_different_sort_mode() {
+            let encoded = sample_cursor().encode(&scope_for(""), SECRET);
+            let changes = CursorScope {
+                sort: SortMode::Changes,
+                ..scope_for("")
+            };
+
+            let result = CellCursor::decode(&encoded, &changes, SECRET);
+            assert_eq!(result.unwrap_err(), CursorError::ScopeMismatch);
+        }
+
+        #[test]
+        fn test_all_radio_types() {
+            let scope = scope_for("");
+            for radio in [Radio::Gsm, Radio::Umts, Radio::Cdma, Radio::Lte, Radio::Nr] {
                 let cursor = CellCursor {
                     radio: radio.clone(),
                     mcc: 1,
                     net: 2,
                     area: 3,
                     cell: 4,
+                    changed_at: None,
                 };
-                let encoded = cursor.encode();
-                let decoded = CellCursor::decode(&encoded).unwrap();
-
-                // Verify the encoded string contains the expected radio type
-                use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
-                let raw = String::from_utf8(URL_SAFE_NO_PAD.decode(&encoded).unwrap()).unwrap();
-                assert!(raw.starts_with(expected_str));
-
-                // Verify roundtrip works
-                assert_eq!(decoded.mcc, 1);
+                let decoded =
+                    CellCursor::decode(&cursor.encode(&scope, SECRET), &scope, SECRET).unwrap();
+
+                assert_eq!(
+                    std::mem::discriminant(&decoded.radio),
+                    std::mem::discriminant(&radio)
+                );
+                assert_eq!(decoded.cell, 4);
             }
         }
 
+        #[test]
+        fn test_cursor_errors_map_to_cursor_field() {
+            let error = CursorError::ScopeMismatch.into_field_error();
+            assert_eq!(error.field, "cursor");
+        }
+
         #[test]
         fn test_from_cell() {
             let cell = Cell {
@@ -429,6 +798,74 @@ mod tests {
         }
     }
 
+    mod validation {
+        use super::*;
+        use crate::utils::query::parse_query;
+
+        fn field_names(query_string: &str) -> Vec<String> {
+            match parse_query::<GetCellsQuery>(query_string) {
+                Err(ApiError::BadRequest(fields)) => fields.into_iter().map(|f| f.field).collect(),
+                Err(e) => panic!("unexpected error: {:?}", e),
+                Ok(_) => vec![],
+            }
+        }
+
+        #[test]
+        fn test_min_confidence_is_a_fraction() {
+            assert!(field_names("min_confidence=0.5").is_empty());
+            assert_eq!(field_names("min_confidence=1.5"), vec!["min_confidence"]);
+            assert_eq!(field_names("min_confidence=-0.1"), vec!["min_confidence"]);
+        }
+
+        #[test]
+        fn test_shape_requires_geojson() {
+            let error = parse_query::<CellsOutputQuery>("mcc=262&shape=voronoi").unwrap_err();
+            assert!(matches!(error, ApiError::BadRequest(fields) if fields[0].field == "shape"));
+
+            let output = parse_query::<CellsOutputQuery>("format=geojson&shape=voronoi").unwrap();
+            assert_eq!(output.format, Some(CellsFormat::Geojson));
+            assert_eq!(output.shape, Some(CoverageShape::Voronoi));
+        }
+
+        #[test]
+        fn test_valid_query_passes() {
+            assert!(field_names("mcc=262&min_lat=52.0&max_lat=53.0&limit=10").is_empty());
+        }
+
+        #[test]
+        fn test_latitude_out_of_range() {
+            assert_eq!(field_names("min_lat=-91"), vec!["min_lat"]);
+        }
+
+        #[test]
+        fn test_longitude_out_of_range() {
+            assert_eq!(field_names("max_lon=181"), vec!["max_lon"]);
+        }
+
+        #[test]
+        fn test_inverted_bounds() {
+            assert_eq!(
+                field_names("min_lat=53&max_lat=52&min_lon=14&max_lon=13"),
+                vec!["min_lat", "min_lon"]
+            );
+        }
+
+        #[test]
+        fn test_zero_limit() {
+            assert_eq!(field_names("limit=0"), vec!["limit"]);
+        }
+
+        #[test]
+        fn test_unparseable_value_names_field() {
+            assert_eq!(field_names("mcc=abc"), vec!["mcc"]);
+        }
+
+        #[test]
+        fn test_unknown_radio_names_field() {
+            assert_eq!(field_names("radio=WIFI"), vec!["radio"]);
+        }
+    }
+
     /// Integration tests for query_cells using testcontainers.
     #[cfg(feature = "integration_tests")]
     mod query_cells_integration {
@@ -437,6 +874,8 @@ mod tests {
         use crate::utils::test_db::get_test_connection;
         use chrono::TimeZone;
 
+        const TEST_CURSOR_SECRET: &[u8] = b"test-secret";
+
         fn sample_cell_with_location(
             mcc_val: u16,
             net_val: u16,
@@ -486,16 +925,18 @@ mod tests {
             let query = GetCellsQuery {
                 mcc: None,
                 mnc: None,
+                area: None,
                 min_lat: None,
                 max_lat: None,
                 min_lon: None,
                 max_lon: None,
                 radio: None,
+                min_confidence: None,
                 cursor: None,
                 limit: None,
             };
 
-            let result = query_cells(&query, &mut conn).unwrap();
+            let result = query_cells(&query, TEST_CURSOR_SECRET, &mut conn).unwrap();
 
             assert_eq!(result.cells.len(), 5);
             assert!(!result.has_more);
@@ -521,16 +962,18 @@ mod tests {
             let query = GetCellsQuery {
                 mcc: Some(262),
                 mnc: None,
+                area: None,
                 min_lat: None,
                 max_lat: None,
                 min_lon: None,
                 max_lon: None,
                 radio: None,
+                min_confidence: None,
                 cursor: None,
                 limit: None,
             };
 
-            let result = query_cells(&query, &mut conn).unwrap();
+            let result = query_cells(&query, TEST_CURSOR_SECRET, &mut conn).unwrap();
 
             assert_eq!(result.cells.len(), 1);
             assert_eq!(result.cells[0].mcc, 262);
@@ -554,21 +997,58 @@ mod tests {
             let query = GetCellsQuery {
                 mcc: None,
                 mnc: Some(2),
+                area: None,
                 min_lat: None,
                 max_lat: None,
                 min_lon: None,
                 max_lon: None,
                 radio: None,
+                min_confidence: None,
                 cursor: None,
                 limit: None,
             };
 
-            let result = query_cells(&query, &mut conn).unwrap();
+            let result = query_cells(&query, TEST_CURSOR_SECRET, &mut conn).unwrap();
 
             assert_eq!(result.cells.len(), 1);
             assert_eq!(result.cells[0].net, 2);
         }
 
+        #[test]
+        fn test_query_cells_filters_by_area() {
+            let (_container, mut conn) = get_test_connection();
+
+            let cell1 = sample_cell_with_location(262, 1, 100, 1, Radio::Lte, 52.0, 13.0);
+            let cell2 = sample_cell_with_location(262, 1, 200, 2, Radio::Lte, 52.0, 13.0);
+            diesel::insert_into(cells::table)
+                .values(&cell1)
+                .execute(&mut conn)
+                .unwrap();
+            diesel::insert_into(cells::table)
+                .values(&cell2)
+                .execute(&mut conn)
+                .unwrap();
+
+            let query = GetCellsQuery {
+                mcc: Some(262),
+                mnc: Some(1),
+                area: Some(200),
+                min_lat: None,
+                max_lat: None,
+                min_lon: None,
+                max_lon: None,
+                radio: None,
+                min_confidence: None,
+                cursor: None,
+                limit: None,
+            };
+
+            let result = query_cells(&query, TEST_CURSOR_SECRET, &mut conn).unwrap();
+
+            assert_eq!(result.cells.len(), 1);
+            assert_eq!(result.cells[0].area, 200);
+        }
+
         #[test]
         fn test_query_cells_filters_by_geofence() {
             let (_container, mut conn) = get_test_connection();
@@ -597,16 +1077,18 @@ mod tests {
             let query = GetCellsQuery {
                 mcc: None,
                 mnc: None,
+                area: None,
                 min_lat: Some(52.0),
                 max_lat: Some(53.0),
                 min_lon: Some(13.0),
                 max_lon: Some(14.0),
                 radio: None,
+                min_confidence: None,
                 cursor: None,
                 limit: None,
             };
 
-            let result = query_cells(&query, &mut conn).unwrap();
+            let result = query_cells(&query, TEST_CURSOR_SECRET, &mut conn).unwrap();
 
             assert_eq!(result.cells.len(), 1);
             assert_eq!(result.cells[0].cell, 1);
@@ -628,16 +1110,18 @@ mod tests {
             let query = GetCellsQuery {
                 mcc: None,
                 mnc: None,
+                area: None,
                 min_lat: None,
                 max_lat: None,
                 min_lon: None,
                 max_lon: None,
                 radio: None,
+                min_confidence: None,
                 cursor: None,
                 limit: Some(5),
             };
 
-            let result = query_cells(&query, &mut conn).unwrap();
+            let result = query_cells(&query, TEST_CURSOR_SECRET, &mut conn).unwrap();
 
             assert_eq!(result.cells.len(), 5);
             assert!(result.has_more);
@@ -661,16 +1145,18 @@ mod tests {
             let query1 = GetCellsQuery {
                 mcc: None,
                 mnc: None,
+                area: None,
                 min_lat: None,
                 max_lat: None,
                 min_lon: None,
                 max_lon: None,
                 radio: None,
+                min_confidence: None,
                 cursor: None,
                 limit: Some(5),
             };
 
-            let result1 = query_cells(&query1, &mut conn).unwrap();
+            let result1 = query_cells(&query1, TEST_CURSOR_SECRET, &mut conn).unwrap();
             assert_eq!(result1.cells.len(), 5);
             assert!(result1.has_more);
 
@@ -678,16 +1164,18 @@ mod tests {
             let query2 = GetCellsQuery {
                 mcc: None,
                 mnc: None,
+                area: None,
                 min_lat: None,
                 max_lat: None,
                 min_lon: None,
                 max_lon: None,
                 radio: None,
+                min_confidence: None,
                 cursor: result1.next_cursor.clone(),
                 limit: Some(5),
             };
 
-            let result2 = query_cells(&query2, &mut conn).unwrap();
+            let result2 = query_cells(&query2, TEST_CURSOR_SECRET, &mut conn).unwrap();
             assert_eq!(result2.cells.len(), 5);
             assert!(!result2.has_more);
             assert!(result2.next_cursor.is_none());
@@ -700,6 +1188,28 @@ mod tests {
             }
         }
 
+        #[test]
+        fn test_query_cells_rejects_cursor_with_changed_filters() {
+            let (_container, mut conn) = get_test_connection();
+
+            for i in 1..=4 {
+                let cell = sample_cell_with_location(262, 1, 100, i, Radio::Lte, 52.0, 13.0);
+                diesel::insert_into(cells::table)
+                    .values(&cell)
+                    .execute(&mut conn)
+                    .unwrap();
+            }
+
+            let mut query: GetCellsQuery = serde_urlencoded::from_str("mcc=262&limit=2").unwrap();
+            let first_page = query_cells(&query, TEST_CURSOR_SECRET, &mut conn).unwrap();
+
+            query.cursor = first_page.next_cursor;
+            query.mcc = Some(310);
+            let result = query_cells(&query, TEST_CURSOR_SECRET, &mut conn);
+
+            assert!(matches!(result, Err(ApiError::BadRequest(_))));
+        }
+
         #[test]
         fn test_query_cells_filters_by_radio() {
             let (_container, mut conn) = get_test_connection();
@@ -718,16 +1228,18 @@ mod tests {
             let query = GetCellsQuery {
                 mcc: None,
                 mnc: None,
+                area: None,
                 min_lat: None,
                 max_lat: None,
                 min_lon: None,
                 max_lon: None,
                 radio: Some(Radio::Gsm),
+                min_confidence: None,
                 cursor: None,
                 limit: None,
             };
 
-            let result = query_cells(&query, &mut conn).unwrap();
+            let result = query_cells(&query, TEST_CURSOR_SECRET, &mut conn).unwrap();
 
             assert_eq!(result.cells.len(), 1);
             assert!(matches!(result.cells[0].radio, Radio::Gsm));
@@ -757,16 +1269,18 @@ mod tests {
             let query = GetCellsQuery {
                 mcc: Some(262),
                 mnc: Some(1),
+                area: None,
                 min_lat: Some(52.0),
                 max_lat: Some(53.0),
                 min_lon: Some(13.0),
                 max_lon: Some(14.0),
                 radio: Some(Radio::Lte),
+                min_confidence: None,
                 cursor: None,
                 limit: None,
             };
 
-            let result = query_cells(&query, &mut conn).unwrap();
+            let result = query_cells(&query, TEST_CURSOR_SECRET, &mut conn).unwrap();
 
             assert_eq!(result.cells.len(), 1);
             assert_eq!(result.cells[0].cell, 1);
@@ -789,16 +1303,18 @@ mod tests {
             let query = GetCellsQuery {
                 mcc: None,
                 mnc: None,
+                area: None,
                 min_lat: None,
                 max_lat: None,
                 min_lon: None,
                 max_lon: None,
                 radio: None,
+                min_confidence: None,
                 cursor: None,
                 limit: Some(2000), // Exceeds MAX_PAGE_SIZE
             };
 
-            let result = query_cells(&query, &mut conn).unwrap();
+            let result = query_cells(&query, TEST_CURSOR_SECRET, &mut conn).unwrap();
 
             // Should be capped at MAX_PAGE_SIZE (1000)
             assert_eq!(result.cells.len(), 1000);
@@ -812,16 +1328,18 @@ mod tests {
             let query = GetCellsQuery {
                 mcc: Some(999),
                 mnc: None,
+                area: None,
                 min_lat: None,
                 max_lat: None,
                 min_lon: None,
                 max_lon: None,
                 radio: None,
+                min_confidence: None,
                 cursor: None,
                 limit: None,
             };
 
-            let result = query_cells(&query, &mut conn).unwrap();
+            let result = query_cells(&query, TEST_CURSOR_SECRET, &mut conn).unwrap();
 
             assert!(result.cells.is_empty());
             assert!(!result.has_more);
@@ -0,0 +1,566 @@
+use chrono::{DateTime, NaiveDateTime, Utc};
+use diesel::dsl::sql;
+use diesel::prelude::*;
+use diesel::sql_types::{Bool, Integer, Unsigned};
+use diesel::MysqlConnection;
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+use tracing::instrument;
+use utoipa::{IntoParams, ToSchema};
+
+use super::cells::{CellCursor, CursorScope, SortMode, DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE};
+use crate::utils::caching::{CacheValidators, Conditions};
+use crate::utils::config::Config;
+use crate::utils::db::get_last_update_with_connection;
+use crate::utils::errors::{ApiError, ErrorBody, FieldError};
+use crate::utils::negotiation::{encode, Format};
+use crate::utils::query::{into_result, Validate};
+use crate::{models::*, utils::db::try_establish_connection};
+
+/// Query parameters of the change feed.
+#[derive(Deserialize, Serialize, Debug, Clone, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct GetChangesQuery {
+    /// Only changes after this point in time (RFC 3339)
+    pub since: DateTime<Utc>,
+    /// Cursor for pagination
+    pub cursor: Option<String>,
+    /// Number of items per page (default: 100, max: 1000)
+    pub limit: Option<u32>,
+}
+
+impl Validate for GetChangesQuery {
+    fn validate(&self) -> Result<(), Vec<FieldError>> {
+        let mut errors = vec![];
+        if self.limit == Some(0) {
+            errors.push(FieldError::new("limit", "must be at least 1"));
+        }
+        into_result(errors)
+    }
+}
+
+impl GetChangesQuery {
+    /// The scope cursors of this query are bound to.
+    pub fn cursor_scope(&self) -> CursorScope {
+        let filters = GetChangesQuery {
+            cursor: None,
+            limit: None,
+            ..self.clone()
+        };
+        CursorScope::new(SortMode::Changes, &filters)
+    }
+}
+
+/// The primary key of a changed cell.
+#[derive(Serialize, Debug, Clone, ToSchema)]
+pub struct ChangeKey {
+    pub radio: Radio,
+    pub mcc: u16,
+    pub net: u16,
+    pub area: u32,
+    pub cell: u64,
+}
+
+/// A single entry of the change feed.
+#[serde_as]
+#[derive(Serialize, Debug, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CellChangeEntry {
+    /// Time of the sync that made the change
+    #[serde_as(as = "chrono::DateTime<chrono::Utc>")]
+    pub changed_at: NaiveDateTime,
+    /// Whether the cell was removed, the entry is a tombstone then
+    pub removed: bool,
+    pub key: ChangeKey,
+    /// The current cell data, `null` for tombstones
+    pub cell: Option<Cell>,
+}
+
+/// Response of the change feed.
+#[derive(Serialize, Debug, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GetChangesResponse {
+    /// The changes, oldest first
+    pub changes: Vec<CellChangeEntry>,
+    /// The cursor for the next page, if there are more results
+    pub next_cursor: Option<String>,
+    /// Whether there are more results
+    pub has_more: bool,
+}
+
+impl From<&CellChange> for ChangeKey {
+    fn from(change: &CellChange) -> Self {
+        ChangeKey {
+            radio: change.radio.clone(),
+            mcc: change.mcc,
+            net: change.net,
+            area: change.area,
+            cell: change.cell,
+        }
+    }
+}
+
+impl CellCursor {
+    /// Create a change feed cursor from a change record.
+    pub fn from_change(change: &CellChange) -> Self {
+        CellCursor {
+            radio: change.radio.clone(),
+            mcc: change.mcc,
+            net: change.net,
+            area: change.area,
+            cell: change.cell,
+            changed_at: Some(change.changed_at),
+        }
+    }
+}
+
+/// The radio column as its enum index. MySQL sorts an ENUM by index but
+/// compares it to a string alphabetically, so keyset comparisons use this.
+const RADIO_INDEX: &str = "CAST(cell_changes.radio AS UNSIGNED)";
+
+/// The index of a radio in the `cell_changes.radio` ENUM.
+fn radio_index(radio: &Radio) -> u32 {
+    match radio {
+        Radio::Gsm => 1,
+        Radio::Umts => 2,
+        Radio::Lte => 3,
+        Radio::Nr => 4,
+        Radio::Cdma => 5,
+    }
+}
+
+/// Pages through the changes after `query.since`, ordered by change time and key.
+#[instrument(skip(cursor_secret, connection))]
+pub fn query_changes(
+    query: &GetChangesQuery,
+    cursor_secret: &[u8],
+    connection: &mut MysqlConnection,
+) -> Result<GetChangesResponse, ApiError> {
+    use crate::schema::cell_changes::dsl::*;
+
+    let scope = query.cursor_scope();
+    let cursor = match query.cursor {
+        Some(ref cursor_str) => Some(
+            CellCursor::decode(cursor_str, &scope, cursor_secret)
+                .map_err(|e| ApiError::BadRequest(vec![e.into_field_error()]))?,
+        ),
+        None => None,
+    };
+
+    let page_limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).min(MAX_PAGE_SIZE);
+    // Fetch one extra to check if there are more results
+    let fetch_limit = (page_limit + 1) as i64;
+
+    let mut db_query = cell_changes
+        .filter(changed_at.gt(query.since.naive_utc()))
+        .into_boxed();
+
+    // Keyset pagination over (changed_at, mcc, net, area, cell, radio)
+    if let Some(cursor) = cursor {
+        let cursor_changed_at = cursor.changed_at.unwrap_or_default();
+        let cursor_radio = radio_index(&cursor.radio);
+
+        db_query = db_query.filter(
+            changed_at
+                .gt(cursor_changed_at)
+                .or(changed_at.eq(cursor_changed_at).and(mcc.gt(cursor.mcc)))
+                .or(changed_at
+                    .eq(cursor_changed_at)
+                    .and(mcc.eq(cursor.mcc))
+                    .and(net.gt(cursor.net)))
+                .or(changed_at
+                    .eq(cursor_changed_at)
+                    .and(mcc.eq(cursor.mcc))
+                    .and(net.eq(cursor.net))
+                    .and(area.gt(cursor.area)))
+                .or(changed_at
+                    .eq(cursor_changed_at)
+                    .and(mcc.eq(cursor.mcc))
+                    .and(net.eq(cursor.net))
+                    .and(area.eq(cursor.area))
+                    .and(cell.gt(cursor.cell)))
+                .or(changed_at
+                    .eq(cursor_changed_at)
+                    .and(mcc.eq(cursor.mcc))
+                    .and(net.eq(cursor.net))
+                    .and(area.eq(cursor.area))
+                    .and(cell.eq(cursor.cell))
+                    .and(
+                        sql::<Bool>(&format!("{} > ", RADIO_INDEX))
+                            .bind::<Unsigned<Integer>, _>(cursor_radio),
+                    )),
+        );
+    }
+
+    let mut changes: Vec<CellChange> = db_query
+        .order((
+            changed_at.asc(),
+            mcc.asc(),
+            net.asc(),
+            area.asc(),
+            cell.asc(),
+            sql::<Unsigned<Integer>>(RADIO_INDEX).asc(),
+        ))
+        .limit(fetch_limit)
+        .select(CellChange::as_select())
+        .load(connection)
+        .map_err(ApiError::from)?;
+
+    let has_more = changes.len() > page_limit as usize;
+    if has_more {
+        changes.pop();
+    }
+
+    let next_cursor = if has_more {
+        changes
+            .last()
+            .map(|c| CellCursor::from_change(c).encode(&scope, cursor_secret))
+    } else {
+        None
+    };
+
+    let current = query_current_cells(&changes, connection).map_err(ApiError::from)?;
+    let changes = changes
+        .iter()
+        .map(|change| CellChangeEntry {
+            changed_at: change.changed_at,
+            removed: change.removed,
+            key: ChangeKey::from(change),
+            cell: current
+                .iter()
+                .find(|c| {
+                    !change.removed
+                        && c.radio == change.radio
+                        && c.mcc == change.mcc
+                        && c.net == change.net
+                        && c.area == change.area
+                        && c.cell == change.cell
+                })
+                .cloned(),
+        })
+        .collect();
+
+    Ok(GetChangesResponse {
+        changes,
+        next_cursor,
+        has_more,
+    })
+}
+
+/// Loads the current data of all changed cells that weren't removed.
+fn query_current_cells(
+    changes: &[CellChange],
+    connection: &mut MysqlConnection,
+) -> Result<Vec<Cell>, diesel::result::Error> {
+    use crate::schema::cells::dsl::*;
+
+    let present: Vec<&CellChange> = changes.iter().filter(|c| !c.removed).collect();
+    if present.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let mut db_query = cells.into_boxed();
+    for change in present {
+        db_query = db_query.or_filter(
+            radio
+                .eq(change.radio.clone())
+                .and(mcc.eq(change.mcc))
+                .and(net.eq(change.net))
+                .and(area.eq(change.area))
+                .and(cell.eq(change.cell)),
+        );
+    }
+    db_query.load(connection)
+}
+
+/// Page through the cells inserted, modified or removed by syncs after `since`.
+#[utoipa::path(
+    get,
+    path = "/v1/cells/changes",
+    params(GetChangesQuery),
+    responses(
+        (status = 200, description = "A page of changes. Protobuf responses are a `GetChangesResponse` message", content((GetChangesResponse = "application/json"), (GetChangesResponse = "application/msgpack"), (GetChangesResponse = "application/cbor"))),
+        (status = 304, description = "The cached copy is still fresh"),
+        (status = 400, description = "Invalid parameters or cursor", body = ErrorBody),
+        (status = 406, description = "None of the accepted formats is supported", body = ErrorBody),
+        (status = 503, description = "The database is unavailable", body = ErrorBody),
+    ),
+    tag = "cells"
+)]
+#[instrument(skip(config))]
+pub async fn handle_get_changes(
+    query: GetChangesQuery,
+    conditions: Conditions,
+    format: Format,
+    config: Config,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let connection = &mut try_establish_connection(config.clone()).map_err(ApiError::from)?;
+
+    let last_update = get_last_update_with_connection(connection).map_err(ApiError::from)?;
+    let validators = CacheValidators::new(
+        &format.cache_route("cells/changes"),
+        &query,
+        last_update,
+        Utc::now(),
+    );
+    if validators.is_not_modified(&conditions) {
+        return Ok(validators.not_modified());
+    }
+
+    let response = query_changes(&query, config.cursor_secret.as_bytes(), connection)?;
+    Ok(validators.apply(encode(format, &response)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn change(removed: bool) -> CellChange {
+        CellChange {
+            radio: Radio::Lte,
+            mcc: 262,
+            net: 1,
+            area: 100,
+            cell: 1,
+            changed_at: Utc
+                .with_ymd_and_hms(2025, 12, 20, 4, 0, 0)
+                .unwrap()
+                .naive_utc(),
+            removed,
+        }
+    }
+
+    mod changes_query {
+        use super::*;
+        use crate::utils::query::parse_query;
+
+        #[test]
+        fn test_parse_since() {
+            let query: GetChangesQuery = parse_query("since=2025-12-01T00:00:00Z").unwrap();
+
+            assert_eq!(
+                query.since,
+                Utc.with_ymd_and_hms(2025, 12, 1, 0, 0, 0).unwrap()
+            );
+        }
+
+        #[test]
+        fn test_since_is_required() {
+            match parse_query::<GetChangesQuery>("limit=10") {
+                Err(ApiError::BadRequest(fields)) => assert_eq!(fields[0].field, "since"),
+                other => panic!("expected bad request, got {:?}", other),
+            }
+        }
+
+        #[test]
+        fn test_invalid_since_names_field() {
+            match parse_query::<GetChangesQuery>("since=yesterday") {
+                Err(ApiError::BadRequest(fields)) => assert_eq!(fields[0].field, "since"),
+                other => panic!("expected bad request, got {:?}", other),
+            }
+        }
+
+        #[test]
+        fn test_scope_is_bound_to_since() {
+            let scope = |raw: &str| parse_query::<GetChangesQuery>(raw).unwrap().cursor_scope();
+
+            assert_eq!(
+                scope("since=2025-12-01T00:00:00Z&limit=5"),
+                scope("since=2025-12-01T00:00:00Z&limit=50")
+            );
+            assert_ne!(
+                scope("since=2025-12-01T00:00:00Z"),
+                scope("since=2025-12-02T00:00:00Z")
+            );
+        }
+    }
+
+    #[test]
+    fn test_radio_index_follows_enum_order() {
+        let order = [Radio::Gsm, Radio::Umts, Radio::Lte, Radio::Nr, Radio::Cdma];
+
+        let indices: Vec<u32> = order.iter().map(radio_index).collect();
+
+        assert_eq!(indices, vec![1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn test_tombstone_serialization() {
+        let tombstone = change(true);
+        let entry = CellChangeEntry {
+            changed_at: tombstone.changed_at,
+            removed: true,
+            key: ChangeKey::from(&tombstone),
+            cell: None,
+        };
+
+        let json = serde_json::to_value(&entry).unwrap();
+
+        assert_eq!(json["changedAt"], "2025-12-20T04:00:00Z");
+        assert_eq!(json["removed"], true);
+        assert_eq!(json["key"]["radio"], "LTE");
+        assert_eq!(json["key"]["cell"], 1);
+        assert!(json["cell"].is_null());
+    }
+
+    /// Integration tests for query_changes using testcontainers.
+    #[cfg(feature = "integration_tests")]
+    mod changes_integration {
+        use super::*;
+        use crate::schema::{cell_changes, cells};
+        use crate::utils::test_db::get_test_connection;
+
+        const SECRET: &[u8] = b"test-secret";
+
+        fn sample_cell(cell_val: u64) -> Cell {
+            Cell {
+                radio: Radio::Lte,
+                mcc: 262,
+                net: 1,
+                area: 100,
+                cell: cell_val,
+                unit: None,
+                lon: 13.405,
+                lat: 52.52,
+                cell_range: 1000,
+                samples: 10,
+                changeable: true,
+                created: Utc
+                    .with_ymd_and_hms(2024, 1, 15, 10, 30, 0)
+                    .unwrap()
+                    .naive_utc(),
+                updated: Utc
+                    .with_ymd_and_hms(2025, 12, 20, 14, 0, 0)
+                    .unwrap()
+                    .naive_utc(),
+                average_signal: None,
+            }
+        }
+
+        fn changed(cell_val: u64, day: u32, removed: bool) -> CellChange {
+            CellChange {
+                cell: cell_val,
+                changed_at: Utc
+                    .with_ymd_and_hms(2025, 12, day, 4, 0, 0)
+                    .unwrap()
+                    .naive_utc(),
+                ..change(removed)
+            }
+        }
+
+        fn since(day: u32) -> GetChangesQuery {
+            GetChangesQuery {
+                since: Utc.with_ymd_and_hms(2025, 12, day, 0, 0, 0).unwrap(),
+                cursor: None,
+                limit: None,
+            }
+        }
+
+        #[test]
+        fn test_changes_since_timestamp() {
+            let (_container, mut conn) = get_test_connection();
+            diesel::insert_into(cells::table)
+                .values(&vec![sample_cell(1), sample_cell(2)])
+                .execute(&mut conn)
+                .unwrap();
+            diesel::insert_into(cell_changes::table)
+                .values(&vec![
+                    changed(1, 1, false),
+                    changed(2, 3, false),
+                    changed(3, 3, true),
+                ])
+                .execute(&mut conn)
+                .unwrap();
+
+            let response = query_changes(&since(2), SECRET, &mut conn).unwrap();
+
+            assert_eq!(response.changes.len(), 2);
+            assert_eq!(response.changes[0].key.cell, 2);
+            assert_eq!(response.changes[0].cell.as_ref().unwrap().cell, 2);
+            assert_eq!(response.changes[1].key.cell, 3);
+            assert!(response.changes[1].removed);
+            assert!(response.changes[1].cell.is_none());
+        }
+
+        #[test]
+        fn test_changes_pagination() {
+            let (_container, mut conn) = get_test_connection();
+            diesel::insert_into(cell_changes::table)
+                .values(&(1..=5).map(|i| changed(i, 3, true)).collect::<Vec<_>>())
+                .execute(&mut conn)
+                .unwrap();
+
+            let first = query_changes(
+                &GetChangesQuery {
+                    limit: Some(3),
+                    ..since(1)
+                },
+                SECRET,
+                &mut conn,
+            )
+            .unwrap();
+            assert!(first.has_more);
+
+            let second = query_changes(
+                &GetChangesQuery {
+                    limit: Some(3),
+                    cursor: first.next_cursor,
+                    ..since(1)
+                },
+                SECRET,
+                &mut conn,
+            )
+            .unwrap();
+
+            assert!(!second.has_more);
+            let cells: Vec<u64> = first
+                .changes
+                .iter()
+                .chain(&second.changes)
+                .map(|c| c.key.cell)
+                .collect();
+            assert_eq!(cells, vec![1, 2, 3, 4, 5]);
+        }
+
+        #[test]
+        fn test_changes_pagination_across_radios() {
+            let (_container, mut conn) = get_test_connection();
+            let radios = [Radio::Gsm, Radio::Umts, Radio::Lte, Radio::Nr, Radio::Cdma];
+            diesel::insert_into(cell_changes::table)
+                .values(
+                    &radios
+                        .iter()
+                        .map(|r| CellChange {
+                            radio: r.clone(),
+                            ..changed(1, 3, true)
+                        })
+                        .collect::<Vec<_>>(),
+                )
+                .execute(&mut conn)
+                .unwrap();
+
+            let mut seen = vec![];
+            let mut cursor = None;
+            loop {
+                let page = query_changes(
+                    &GetChangesQuery {
+                        limit: Some(1),
+                        cursor,
+                        ..since(1)
+                    },
+                    SECRET,
+                    &mut conn,
+                )
+                .unwrap();
+                seen.extend(page.changes.iter().map(|c| c.key.radio.clone()));
+                if !page.has_more {
+                    break;
+                }
+                cursor = page.next_cursor;
+            }
+
+            assert_eq!(seen, radios.to_vec());
+        }
+    }
+}
@@ -0,0 +1,562 @@
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+
+use diesel::prelude::*;
+use diesel::MysqlConnection;
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+use utoipa::{IntoParams, ToSchema};
+
+use super::cell::{query_cell, GetCellQuery};
+use super::neighbours::{within, within_any};
+use crate::models::{Cell, Radio};
+use crate::utils::caching::{CacheValidators, Conditions};
+use crate::utils::config::Config;
+use crate::utils::coverage::{
+    coverage_polygon, coverage_radius, CoverageShape, MAX_COVERAGE_RADIUS_M,
+};
+use crate::utils::db::{get_last_update_with_connection, try_establish_connection};
+use crate::utils::errors::{ApiError, ErrorBody, FieldError};
+use crate::utils::geo::{haversine_distance, BoundingBox};
+use crate::utils::geohash::{tiles, Tile, TILES_PER_QUERY};
+use crate::utils::geojson::{geojson_reply, Feature};
+use crate::utils::negotiation::{encode, Format};
+use crate::utils::query::{check_coordinate, into_result, Validate};
+use crate::utils::ranging::signal_at;
+
+/// Largest margin `/coverage` adds to coverage radii.
+pub const MAX_COVERAGE_MARGIN_M: f64 = 5_000.0;
+
+/// A cell's coverage polygon, with the cell as its properties.
+pub type CoverageFeature = Feature<Cell>;
+
+/// Radio generation, MCC and net of the cells that can clip each other.
+type OperatorKey = (u8, u16, u16);
+
+/// Tiles of cell indices per radio and operator.
+type VoronoiGroups = BTreeMap<OperatorKey, Vec<Tile>>;
+
+/// Query parameters of the coverage of a single cell.
+#[derive(Deserialize, Serialize, Debug, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct GetCellCoverageQuery {
+    /// Mobile Country Code
+    pub mcc: u16,
+    /// Mobile Network Code
+    pub net: u16,
+    /// Location Area Code (GSM/UMTS) or Tracking Area Code (LTE/NR)
+    pub area: u32,
+    /// Cell ID
+    pub cell: u64,
+    /// Radio type
+    pub radio: Option<Radio>,
+    /// `voronoi` clips the circle against same-operator cells of the same radio
+    pub shape: Option<CoverageShape>,
+}
+
+impl Validate for GetCellCoverageQuery {}
+
+impl From<&GetCellCoverageQuery> for GetCellQuery {
+    fn from(query: &GetCellCoverageQuery) -> Self {
+        GetCellQuery {
+            mcc: query.mcc,
+            net: query.net,
+            area: query.area,
+            cell: query.cell,
+            radio: query.radio.clone(),
+            match_mode: None,
+        }
+    }
+}
+
+/// Query parameters of the cells covering a point.
+#[derive(Deserialize, Serialize, Debug, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct GetPointCoverageQuery {
+    /// Latitude of the point
+    pub lat: f32,
+    /// Longitude of the point
+    pub lon: f32,
+    /// Meters added to every coverage radius (default: 0, max: 5000)
+    pub margin: Option<f64>,
+    /// Mobile Country Code filter
+    pub mcc: Option<u16>,
+    /// Mobile Network Code filter
+    pub mnc: Option<u16>,
+    /// Radio type filter
+    pub radio: Option<Radio>,
+}
+
+impl Validate for GetPointCoverageQuery {
+    fn validate(&self) -> Result<(), Vec<FieldError>> {
+        let mut errors = vec![];
+        check_coordinate(&mut errors, "lat", Some(self.lat), 90.0);
+        check_coordinate(&mut errors, "lon", Some(self.lon), 180.0);
+        if let Some(margin) = self.margin {
+            if !(0.0..=MAX_COVERAGE_MARGIN_M).contains(&margin) {
+                errors.push(FieldError::new(
+                    "margin",
+                    format!("must be between 0 and {}", MAX_COVERAGE_MARGIN_M),
+                ));
+            }
+        }
+        into_result(errors)
+    }
+}
+
+/// A cell whose coverage contains the point.
+#[derive(Serialize, Debug, Clone, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CoveringCell {
+    /// Distance from the point in meters
+    pub distance: f64,
+    /// Estimated signal at the point in dBm, RSRP (LTE/NR), RSCP (UMTS) or RSSI (GSM, CDMA)
+    pub signal_dbm: i16,
+    pub cell: Cell,
+}
+
+/// The cells of one operator and radio covering the point.
+#[derive(Serialize, Debug, Clone, ToSchema)]
+pub struct CoverageGroup {
+    pub mcc: u16,
+    pub net: u16,
+    pub radio: Radio,
+    /// Strongest estimated signal first
+    pub cells: Vec<CoveringCell>,
+}
+
+/// Response of the cells covering a point.
+#[derive(Serialize, Debug, ToSchema)]
+pub struct PointCoverageResponse {
+    /// By operator, newest radio first
+    pub groups: Vec<CoverageGroup>,
+}
+
+/// Groups the `cells` whose coverage radius plus `margin` reaches the point
+/// by operator and radio, and ranks them by their estimated signal there.
+pub fn covering_cells(lat: f64, lon: f64, margin: f64, cells: Vec<Cell>) -> Vec<CoverageGroup> {
+    let mut groups: BTreeMap<(u16, u16, u8), CoverageGroup> = BTreeMap::new();
+    for cell in cells {
+        let distance = haversine_distance(lat, lon, f64::from(cell.lat), f64::from(cell.lon));
+        if distance > coverage_radius(&cell) + margin {
+            continue;
+        }
+        let signal_dbm = signal_at(&cell, distance).round() as i16;
+        groups
+            .entry((cell.mcc, cell.net, u8::MAX - cell.radio.generation()))
+            .or_insert_with(|| CoverageGroup {
+                mcc: cell.mcc,
+                net: cell.net,
+                radio: cell.radio.clone(),
+                cells: vec![],
+            })
+            .cells
+            .push(CoveringCell {
+                distance,
+                signal_dbm,
+                cell,
+            });
+    }
+
+    let mut groups: Vec<CoverageGroup> = groups.into_values().collect();
+    for group in &mut groups {
+        group.cells.sort_by(|a, b| {
+            b.signal_dbm.cmp(&a.signal_dbm).then(
+                a.distance
+                    .partial_cmp(&b.distance)
+                    .unwrap_or(Ordering::Equal),
+            )
+        });
+    }
+    groups
+}
+
+/// Finds the cells covering a point. Candidates are loaded from the box the
+/// largest coverage radius can reach.
+#[instrument(skip(connection))]
+pub fn query_point_coverage(
+    query: &GetPointCoverageQuery,
+    connection: &mut MysqlConnection,
+) -> Result<PointCoverageResponse, diesel::result::Error> {
+    use crate::schema::cells::dsl;
+
+    let (lat, lon) = (f64::from(query.lat), f64::from(query.lon));
+    let margin = query.margin.unwrap_or(0.0);
+    let bbox = BoundingBox::around(lat, lon, MAX_COVERAGE_RADIUS_M + margin);
+
+    let mut db_query = dsl::cells.into_boxed().filter(within(&bbox));
+    if let Some(mcc) = query.mcc {
+        db_query = db_query.filter(dsl::mcc.eq(mcc));
+    }
+    if let Some(mnc) = query.mnc {
+        db_query = db_query.filter(dsl::net.eq(mnc));
+    }
+    if let Some(ref radio) = query.radio {
+        db_query = db_query.filter(dsl::radio.eq(radio));
+    }
+    let rows: Vec<Cell> = db_query.load(connection)?;
+
+    Ok(PointCoverageResponse {
+        groups: covering_cells(lat, lon, margin, rows),
+    })
+}
+
+/// Groups the indices of `cells` by radio, operator and tile. Each tile has
+/// the box around its cells extended by twice their largest coverage radius:
+/// the reach of the cells that can clip them.
+fn voronoi_tiles(cells: &[Cell]) -> VoronoiGroups {
+    let mut groups: BTreeMap<OperatorKey, Vec<usize>> = BTreeMap::new();
+    for (index, cell) in cells.iter().enumerate() {
+        groups
+            .entry((cell.radio.generation(), cell.mcc, cell.net))
+            .or_default()
+            .push(index);
+    }
+
+    let reach = |ids: &[usize]| {
+        2.0 * ids
+            .iter()
+            .map(|&index| coverage_radius(&cells[index]))
+            .fold(0.0, f64::max)
+    };
+    groups
+        .into_iter()
+        .map(|(key, indices)| {
+            let points = indices.into_iter().map(|index| {
+                (
+                    index,
+                    f64::from(cells[index].lat),
+                    f64::from(cells[index].lon),
+                )
+            });
+            (key, tiles(points, &reach))
+        })
+        .collect()
+}
+
+/// Cells of one radio and operator in one tile, and the cells that may clip
+/// their coverage in Voronoi mode.
+#[derive(Debug)]
+pub struct VoronoiTile {
+    /// Indices into the cells the tiles were built from
+    pub cells: Vec<usize>,
+    pub neighbours: Vec<Cell>,
+}
+
+/// Loads the cells that may clip the coverage of `cells` in Voronoi mode.
+/// Nearby cells share a box, and the boxes of several tiles of one radio and
+/// operator share a query, so cells spread across a country don't load all
+/// cells in between.
+#[instrument(skip_all, fields(cells = cells.len()))]
+pub fn query_voronoi_neighbours(
+    cells: &[Cell],
+    connection: &mut MysqlConnection,
+) -> Result<Vec<VoronoiTile>, diesel::result::Error> {
+    use crate::schema::cells::dsl;
+
+    let mut result = vec![];
+    for tiles in voronoi_tiles(cells).into_values() {
+        let first = &cells[tiles[0].ids[0]];
+        for batch in tiles.chunks(TILES_PER_QUERY) {
+            let bboxes: Vec<BoundingBox> = batch.iter().map(|tile| tile.bbox).collect();
+            let rows: Vec<Cell> = dsl::cells
+                .filter(dsl::radio.eq(first.radio.clone()))
+                .filter(dsl::mcc.eq(first.mcc))
+                .filter(dsl::net.eq(first.net))
+                .filter(within_any(&bboxes))
+                .load(connection)?;
+
+            for Tile { bbox, ids } in batch {
+                result.push(VoronoiTile {
+                    cells: ids.clone(),
+                    neighbours: rows
+                        .iter()
+                        .filter(|cell| bbox.contains(f64::from(cell.lat), f64::from(cell.lon)))
+                        .cloned()
+                        .collect(),
+                });
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// The coverage features of `cells`, loading the cells that clip them if needed.
+pub fn query_coverage_features(
+    cells: Vec<Cell>,
+    shape: CoverageShape,
+    connection: &mut MysqlConnection,
+) -> Result<Vec<CoverageFeature>, diesel::result::Error> {
+    let tiles = match shape {
+        CoverageShape::Circle => vec![],
+        CoverageShape::Voronoi => query_voronoi_neighbours(&cells, connection)?,
+    };
+    let mut others = vec![&[][..]; cells.len()];
+    for tile in &tiles {
+        for &index in &tile.cells {
+            others[index] = &tile.neighbours[..];
+        }
+    }
+
+    let polygons: Vec<_> = cells
+        .iter()
+        .zip(others)
+        .map(|(cell, others)| coverage_polygon(cell, shape, others))
+        .collect();
+    Ok(cells
+        .into_iter()
+        .zip(polygons)
+        .map(|(cell, polygon)| Feature::new(polygon, cell))
+        .collect())
+}
+
+/// Get the estimated coverage area of a single cell as a GeoJSON feature.
+#[utoipa::path(
+    get,
+    path = "/v1/cell/coverage",
+    params(GetCellCoverageQuery),
+    responses(
+        (status = 200, description = "A GeoJSON `Feature` with the coverage polygon and the cell as properties", body = CoverageFeature, content_type = "application/geo+json"),
+        (status = 304, description = "The cached copy is still fresh"),
+        (status = 400, description = "Invalid parameters", body = ErrorBody),
+        (status = 404, description = "No such cell", body = ErrorBody),
+        (status = 503, description = "The database is unavailable", body = ErrorBody),
+    ),
+    tag = "cells"
+)]
+#[instrument(skip(config))]
+pub async fn handle_get_cell_coverage(
+    query: GetCellCoverageQuery,
+    conditions: Conditions,
+    config: Config,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let connection = &mut try_establish_connection(config.clone()).map_err(ApiError::from)?;
+
+    let last_update = get_last_update_with_connection(connection).map_err(ApiError::from)?;
+    let validators = CacheValidators::new("cell/coverage", &query, last_update, chrono::Utc::now());
+    if validators.is_not_modified(&conditions) {
+        return Ok(validators.not_modified());
+    }
+
+    let cell = query_cell(&GetCellQuery::from(&query), connection)
+        .map_err(ApiError::from)?
+        .ok_or_else(|| ApiError::NotFound("Cell not found".to_string()))?;
+    let feature = query_coverage_features(vec![cell], query.shape.unwrap_or_default(), connection)
+        .map_err(ApiError::from)?
+        .pop();
+    Ok(validators.apply(geojson_reply(&feature)))
+}
+
+/// Get the cells whose estimated coverage contains a point.
+#[utoipa::path(
+    get,
+    path = "/v1/coverage",
+    params(GetPointCoverageQuery),
+    responses(
+        (status = 200, description = "Covering cells by operator and radio. Protobuf responses are a `PointCoverageResponse` message", content((PointCoverageResponse = "application/json"), (PointCoverageResponse = "application/msgpack"), (PointCoverageResponse = "application/cbor"))),
+        (status = 304, description = "The cached copy is still fresh"),
+        (status = 400, description = "Invalid parameters", body = ErrorBody),
+        (status = 406, description = "None of the accepted formats is supported", body = ErrorBody),
+        (status = 503, description = "The database is unavailable", body = ErrorBody),
+    ),
+    tag = "cells"
+)]
+#[instrument(skip(config))]
+pub async fn handle_get_point_coverage(
+    query: GetPointCoverageQuery,
+    conditions: Conditions,
+    format: Format,
+    config: Config,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let connection = &mut try_establish_connection(config.clone()).map_err(ApiError::from)?;
+
+    let last_update = get_last_update_with_connection(connection).map_err(ApiError::from)?;
+    let validators = CacheValidators::new(
+        &format.cache_route("coverage"),
+        &query,
+        last_update,
+        chrono::Utc::now(),
+    );
+    if validators.is_not_modified(&conditions) {
+        return Ok(validators.not_modified());
+    }
+
+    let response = query_point_coverage(&query, connection).map_err(ApiError::from)?;
+    Ok(validators.apply(encode(format, &response)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::query::parse_query;
+
+    fn cell_at(lat: f32, lon: f32, range: u32) -> Cell {
+        let now = chrono::Utc::now().naive_utc();
+        Cell {
+            radio: Radio::Lte,
+            mcc: 262,
+            net: 1,
+            area: 1,
+            cell: 1,
+            unit: None,
+            lon,
+            lat,
+            cell_range: range,
+            samples: 10,
+            changeable: true,
+            created: now,
+            updated: now,
+            average_signal: None,
+        }
+    }
+
+    #[test]
+    fn test_shape_from_query_string() {
+        let query =
+            parse_query::<GetCellCoverageQuery>("mcc=262&net=1&area=1&cell=1&shape=voronoi")
+                .unwrap();
+
+        assert_eq!(query.shape, Some(CoverageShape::Voronoi));
+    }
+
+    #[test]
+    fn test_voronoi_tile_reaches_twice_the_largest_radius() {
+        let mut b = cell_at(52.01, 13.02, 3000);
+        b.cell = 2;
+
+        let tiles = voronoi_tiles(&[cell_at(52.0, 13.0, 1000), b]);
+
+        let Tile { bbox, ids } = &tiles[&(Radio::Lte.generation(), 262, 1)][0];
+        assert_eq!(ids, &vec![0, 1]);
+        let south = haversine_distance(52.0, 13.0, bbox.min_lat, 13.0);
+        let north = haversine_distance(52.01, 13.02, bbox.max_lat, 13.02);
+        assert!((south - 6000.0).abs() < 1.0, "{}", south);
+        assert!((north - 6000.0).abs() < 1.0, "{}", north);
+        assert!(bbox.min_lon < 13.0 && bbox.max_lon > 13.02);
+    }
+
+    #[test]
+    fn test_distant_cells_get_their_own_voronoi_tiles() {
+        let mut munich = cell_at(48.14, 11.58, 1000);
+        munich.cell = 2;
+        let mut gsm = cell_at(52.52, 13.4, 1000);
+        gsm.radio = Radio::Gsm;
+
+        let tiles = voronoi_tiles(&[cell_at(52.52, 13.4, 1000), munich, gsm]);
+
+        let lte = &tiles[&(Radio::Lte.generation(), 262, 1)];
+        assert_eq!(lte.len(), 2);
+        for tile in lte {
+            assert_eq!(tile.ids.len(), 1);
+            assert!(tile.bbox.max_lat - tile.bbox.min_lat < 0.1);
+        }
+        assert_eq!(tiles[&(Radio::Gsm.generation(), 262, 1)][0].ids, vec![2]);
+    }
+
+    #[test]
+    fn test_voronoi_tile_across_the_antimeridian_spans_all_longitudes() {
+        let tiles = voronoi_tiles(&[cell_at(-17.7, 179.99, 1000)]);
+
+        let bbox = tiles[&(Radio::Lte.generation(), 262, 1)][0].bbox;
+        assert_eq!((bbox.min_lon, bbox.max_lon), (-180.0, 180.0));
+    }
+
+    #[test]
+    fn test_point_query_validation() {
+        let fields = |query_string: &str| match parse_query::<GetPointCoverageQuery>(query_string) {
+            Err(ApiError::BadRequest(fields)) => fields.into_iter().map(|f| f.field).collect(),
+            Err(e) => panic!("unexpected error: {:?}", e),
+            Ok(_) => vec![],
+        };
+
+        assert!(fields("lat=52.5&lon=13.4&margin=500").is_empty());
+        assert_eq!(fields("lat=91&lon=181"), vec!["lat", "lon"]);
+        assert_eq!(fields("lat=52.5&lon=13.4&margin=-1"), vec!["margin"]);
+        assert_eq!(fields("lat=52.5&lon=13.4&margin=5001"), vec!["margin"]);
+    }
+
+    #[test]
+    fn test_covering_cells_respect_range_and_margin() {
+        // About 1.1 km north of the point
+        let cell = cell_at(52.51, 13.4, 1000);
+
+        assert!(covering_cells(52.5, 13.4, 0.0, vec![cell.clone()]).is_empty());
+        let groups = covering_cells(52.5, 13.4, 200.0, vec![cell]);
+        assert_eq!(groups.len(), 1);
+        assert!((groups[0].cells[0].distance - 1112.0).abs() < 1.0);
+    }
+
+    #[test]
+    fn test_covering_cells_are_grouped_and_ranked() {
+        let far = cell_at(52.505, 13.4, 2000);
+        let mut near = cell_at(52.501, 13.4, 2000);
+        near.cell = 2;
+        let mut gsm = cell_at(52.5, 13.4, 2000);
+        gsm.radio = Radio::Gsm;
+        let mut other_operator = cell_at(52.5, 13.4, 2000);
+        other_operator.net = 2;
+
+        let groups = covering_cells(52.5, 13.4, 0.0, vec![gsm, far, other_operator, near]);
+
+        let keys: Vec<(u16, Radio)> = groups.iter().map(|g| (g.net, g.radio.clone())).collect();
+        assert_eq!(
+            keys,
+            vec![(1, Radio::Lte), (1, Radio::Gsm), (2, Radio::Lte)]
+        );
+        let ids: Vec<u64> = groups[0].cells.iter().map(|c| c.cell.cell).collect();
+        assert_eq!(ids, vec![2, 1]);
+        assert!(groups[0].cells[0].signal_dbm > groups[0].cells[1].signal_dbm);
+    }
+
+    /// Integration tests for the coverage queries using testcontainers.
+    #[cfg(feature = "integration_tests")]
+    mod coverage_integration {
+        use super::*;
+        use crate::schema::cells;
+        use crate::utils::test_db::get_test_connection;
+
+        #[test]
+        fn test_voronoi_neighbours_share_radio_and_operator() {
+            let (_container, mut conn) = get_test_connection();
+            let mut near = cell_at(52.505, 13.4, 1000);
+            near.cell = 2;
+            let mut far = cell_at(53.0, 13.4, 1000);
+            far.cell = 3;
+            let mut gsm = cell_at(52.505, 13.4, 1000);
+            gsm.cell = 4;
+            gsm.radio = Radio::Gsm;
+            diesel::insert_into(cells::table)
+                .values(&vec![cell_at(52.5, 13.4, 1000), near, far, gsm])
+                .execute(&mut conn)
+                .unwrap();
+
+            let tiles = query_voronoi_neighbours(&[cell_at(52.5, 13.4, 1000)], &mut conn).unwrap();
+            assert_eq!(tiles.len(), 1);
+            assert_eq!(tiles[0].cells, vec![0]);
+            let mut ids: Vec<u64> = tiles[0].neighbours.iter().map(|c| c.cell).collect();
+            ids.sort();
+
+            assert_eq!(ids, vec![1, 2]);
+        }
+
+        #[test]
+        fn test_point_coverage_filters_by_operator() {
+            let (_container, mut conn) = get_test_connection();
+            let mut other_operator = cell_at(52.5, 13.4, 1000);
+            other_operator.net = 2;
+            let mut distant = cell_at(52.6, 13.4, 1000);
+            distant.cell = 2;
+            diesel::insert_into(cells::table)
+                .values(&vec![cell_at(52.5, 13.4, 1000), other_operator, distant])
+                .execute(&mut conn)
+                .unwrap();
+
+            let query = parse_query::<GetPointCoverageQuery>("lat=52.501&lon=13.4&mnc=1").unwrap();
+            let response = query_point_coverage(&query, &mut conn).unwrap();
+
+            assert_eq!(response.groups.len(), 1);
+            assert_eq!(response.groups[0].cells.len(), 1);
+            assert_eq!(response.groups[0].cells[0].cell.cell, 1);
+        }
+    }
+}
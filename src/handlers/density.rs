@@ -0,0 +1,574 @@
+use std::collections::BTreeMap;
+
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Double, TinyInt, Unsigned};
+use diesel::MysqlConnection;
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+use utoipa::{IntoParams, ToSchema};
+
+use super::cells::CellsFormat;
+use crate::models::Radio;
+use crate::schema::cell_density;
+use crate::utils::caching::{CacheValidators, Conditions};
+use crate::utils::config::Config;
+use crate::utils::db::{get_last_update_with_connection, try_establish_connection};
+use crate::utils::errors::{ApiError, ErrorBody, FieldError};
+use crate::utils::geo::BoundingBox;
+use crate::utils::geohash::{GeohashGrid, MAX_PRECISION};
+use crate::utils::geojson::{geojson_reply, Feature, FeatureCollection, Polygon};
+use crate::utils::negotiation::{encode, Format};
+use crate::utils::query::{into_result, Validate};
+
+/// Largest number of buckets a box may overlap at the requested resolution.
+pub const MAX_DENSITY_BUCKETS: u64 = 65_536;
+
+/// Query parameters of the cell density.
+#[derive(Deserialize, Serialize, Debug, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct GetDensityQuery {
+    /// Geohash length of the buckets, 1 to 8
+    pub resolution: u8,
+    /// `min_lon,min_lat,max_lon,max_lat`, the whole world if omitted. Boxes
+    /// across the antimeridian have `min_lon > max_lon`
+    pub bbox: Option<String>,
+    /// `geojson` returns the buckets as a GeoJSON `FeatureCollection`
+    pub format: Option<CellsFormat>,
+}
+
+impl GetDensityQuery {
+    /// The parsed `bbox`.
+    pub fn bounds(&self) -> Result<BoundingBox, String> {
+        let Some(bbox) = &self.bbox else {
+            return Ok(BoundingBox {
+                min_lat: -90.0,
+                max_lat: 90.0,
+                min_lon: -180.0,
+                max_lon: 180.0,
+            });
+        };
+        let values: Vec<f64> = bbox
+            .split(',')
+            .map(|value| value.trim().parse::<f64>())
+            .collect::<Result<_, _>>()
+            .map_err(|_| "must be min_lon,min_lat,max_lon,max_lat".to_string())?;
+        let [min_lon, min_lat, max_lon, max_lat] = values[..] else {
+            return Err("must be min_lon,min_lat,max_lon,max_lat".to_string());
+        };
+        if [min_lat, max_lat]
+            .iter()
+            .any(|lat| !(-90.0..=90.0).contains(lat))
+        {
+            return Err("latitudes must be between -90 and 90".to_string());
+        }
+        if [min_lon, max_lon]
+            .iter()
+            .any(|lon| !(-180.0..=180.0).contains(lon))
+        {
+            return Err("longitudes must be between -180 and 180".to_string());
+        }
+        if min_lat > max_lat {
+            return Err("min_lat must not be greater than max_lat".to_string());
+        }
+        Ok(BoundingBox {
+            min_lat,
+            max_lat,
+            min_lon,
+            max_lon,
+        })
+    }
+}
+
+impl Validate for GetDensityQuery {
+    fn validate(&self) -> Result<(), Vec<FieldError>> {
+        let mut errors = vec![];
+        if !(1..=MAX_PRECISION).contains(&self.resolution) {
+            errors.push(FieldError::new(
+                "resolution",
+                format!("must be between 1 and {}", MAX_PRECISION),
+            ));
+        }
+        match self.bounds() {
+            Err(message) => errors.push(FieldError::new("bbox", message)),
+            Ok(bbox) if errors.is_empty() => {
+                let buckets = GeohashGrid::new(self.resolution).bucket_count(&bbox);
+                if buckets > MAX_DENSITY_BUCKETS {
+                    errors.push(FieldError::new(
+                        "resolution",
+                        format!(
+                            "the box spans {} buckets at this resolution, at most {} are allowed",
+                            buckets, MAX_DENSITY_BUCKETS
+                        ),
+                    ));
+                }
+            }
+            Ok(_) => {}
+        }
+        into_result(errors)
+    }
+}
+
+/// Number of cells of one radio in a bucket.
+#[derive(Serialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct RadioCount {
+    pub radio: Radio,
+    pub cells: u64,
+}
+
+/// Number of cells of one operator in a bucket.
+#[derive(Serialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct OperatorCount {
+    pub mcc: u16,
+    pub net: u16,
+    pub cells: u64,
+}
+
+/// The cells within one geohash.
+#[derive(Serialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct DensityBucket {
+    pub geohash: String,
+    /// Latitude of the center
+    pub lat: f64,
+    /// Longitude of the center
+    pub lon: f64,
+    pub cells: u64,
+    /// Measurements behind all cells of the bucket
+    pub samples: u64,
+    /// Newest radio first
+    pub radios: Vec<RadioCount>,
+    /// By MCC and MNC
+    pub operators: Vec<OperatorCount>,
+}
+
+/// Response of the cell density.
+#[derive(Serialize, Debug, ToSchema)]
+pub struct GetDensityResponse {
+    pub resolution: u8,
+    /// Buckets with at least one cell, by geohash
+    pub buckets: Vec<DensityBucket>,
+}
+
+/// Cell counts of one grid bucket, radio and operator.
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = cell_density)]
+#[diesel(check_for_backend(diesel::mysql::Mysql))]
+pub struct DensityRow {
+    pub lat_index: i64,
+    pub lon_index: i64,
+    pub radio: Radio,
+    pub mcc: u16,
+    pub net: u16,
+    pub cells: i64,
+    pub samples: u64,
+}
+
+/// Merges the rows of each bucket.
+pub fn density_buckets(grid: &GeohashGrid, rows: Vec<DensityRow>) -> Vec<DensityBucket> {
+    let mut buckets: BTreeMap<String, DensityBucket> = BTreeMap::new();
+    for row in rows {
+        let (lat_index, lon_index) = grid.clamp_index(row.lat_index, row.lon_index);
+        let geohash = grid.geohash(lat_index, lon_index);
+        let bucket = buckets.entry(geohash.clone()).or_insert_with(|| {
+            let bounds = grid.bounds(lat_index, lon_index);
+            DensityBucket {
+                geohash,
+                lat: (bounds.min_lat + bounds.max_lat) / 2.0,
+                lon: (bounds.min_lon + bounds.max_lon) / 2.0,
+                cells: 0,
+                samples: 0,
+                radios: vec![],
+                operators: vec![],
+            }
+        });
+        let cells = row.cells.max(0) as u64;
+        bucket.cells += cells;
+        bucket.samples += row.samples;
+        match bucket
+            .radios
+            .iter_mut()
+            .find(|count| count.radio == row.radio)
+        {
+            Some(count) => count.cells += cells,
+            None => bucket.radios.push(RadioCount {
+                radio: row.radio,
+                cells,
+            }),
+        }
+        match bucket
+            .operators
+            .iter_mut()
+            .find(|count| (count.mcc, count.net) == (row.mcc, row.net))
+        {
+            Some(count) => count.cells += cells,
+            None => bucket.operators.push(OperatorCount {
+                mcc: row.mcc,
+                net: row.net,
+                cells,
+            }),
+        }
+    }
+
+    let mut buckets: Vec<DensityBucket> = buckets.into_values().collect();
+    for bucket in &mut buckets {
+        bucket
+            .radios
+            .sort_by_key(|count| std::cmp::Reverse(count.radio.generation()));
+        bucket.operators.sort_by_key(|count| (count.mcc, count.net));
+    }
+    buckets
+}
+
+/// Counts cells per geohash, radio and operator in the buckets overlapping
+/// the box. Only sums the rows of `cell_density` at the grid's precision.
+#[instrument(skip(connection))]
+pub fn query_density(
+    grid: &GeohashGrid,
+    bbox: &BoundingBox,
+    connection: &mut MysqlConnection,
+) -> Result<Vec<DensityBucket>, diesel::result::Error> {
+    let (min_lat_index, min_lon_index) = grid.index(bbox.min_lat, bbox.min_lon);
+    let (max_lat_index, max_lon_index) = grid.index(bbox.max_lat, bbox.max_lon);
+    let mut query = cell_density::table
+        .filter(cell_density::resolution.eq(grid.precision))
+        .filter(cell_density::lat_index.between(i64::from(min_lat_index), i64::from(max_lat_index)))
+        .select(DensityRow::as_select())
+        .into_boxed();
+    query = if bbox.crosses_antimeridian() {
+        query.filter(
+            cell_density::lon_index
+                .ge(i64::from(min_lon_index))
+                .or(cell_density::lon_index.le(i64::from(max_lon_index))),
+        )
+    } else {
+        query.filter(
+            cell_density::lon_index.between(i64::from(min_lon_index), i64::from(max_lon_index)),
+        )
+    };
+    let rows = query.load(connection)?;
+
+    Ok(density_buckets(grid, rows))
+}
+
+/// Rebuilds `cell_density` from the cells, after a sync changed them. The
+/// finest resolution groups the cells by grid indices, which are portable where
+/// geohash functions are not, and every coarser one sums the next finer. The
+/// new table replaces the old one at once, so readers never see it half built.
+#[instrument(skip(connection))]
+pub fn refresh_density(connection: &mut MysqlConnection) -> Result<(), diesel::result::Error> {
+    diesel::sql_query("DROP TABLE IF EXISTS cell_density_next, cell_density_old")
+        .execute(connection)?;
+    diesel::sql_query("CREATE TABLE cell_density_next LIKE cell_density").execute(connection)?;
+
+    let finest = GeohashGrid::new(MAX_PRECISION);
+    let (last_lat_index, last_lon_index) = finest.index(90.0, 180.0);
+    diesel::sql_query(
+        "INSERT INTO cell_density_next
+            (resolution, lat_index, lon_index, radio, mcc, net, cells, samples)
+         SELECT ?, LEAST(FLOOR((lat + 90) / ?), ?) AS lat_bucket,
+                LEAST(FLOOR((lon + 180) / ?), ?) AS lon_bucket,
+                radio, mcc, net, COUNT(*), SUM(samples)
+         FROM cells
+         GROUP BY lat_bucket, lon_bucket, radio, mcc, net",
+    )
+    .bind::<Unsigned<TinyInt>, _>(finest.precision)
+    .bind::<Double, _>(finest.lat_step())
+    .bind::<BigInt, _>(i64::from(last_lat_index))
+    .bind::<Double, _>(finest.lon_step())
+    .bind::<BigInt, _>(i64::from(last_lon_index))
+    .execute(connection)?;
+
+    for precision in (1..MAX_PRECISION).rev() {
+        let (grid, finer) = (GeohashGrid::new(precision), GeohashGrid::new(precision + 1));
+        diesel::sql_query(
+            "INSERT INTO cell_density_next
+                (resolution, lat_index, lon_index, radio, mcc, net, cells, samples)
+             SELECT ?, lat_index DIV ? AS lat_bucket, lon_index DIV ? AS lon_bucket,
+                    radio, mcc, net, SUM(cells), SUM(samples)
+             FROM cell_density_next
+             WHERE resolution = ?
+             GROUP BY lat_bucket, lon_bucket, radio, mcc, net",
+        )
+        .bind::<Unsigned<TinyInt>, _>(grid.precision)
+        .bind::<BigInt, _>((grid.lat_step() / finer.lat_step()).round() as i64)
+        .bind::<BigInt, _>((grid.lon_step() / finer.lon_step()).round() as i64)
+        .bind::<Unsigned<TinyInt>, _>(finer.precision)
+        .execute(connection)?;
+    }
+
+    diesel::sql_query(
+        "RENAME TABLE cell_density TO cell_density_old, cell_density_next TO cell_density",
+    )
+    .execute(connection)?;
+    diesel::sql_query("DROP TABLE cell_density_old").execute(connection)?;
+    Ok(())
+}
+
+/// The buckets as GeoJSON features with their rectangles.
+fn density_collection(
+    grid: &GeohashGrid,
+    buckets: Vec<DensityBucket>,
+) -> FeatureCollection<DensityBucket> {
+    let features = buckets
+        .into_iter()
+        .map(|bucket| {
+            let (lat_index, lon_index) = grid.index(bucket.lat, bucket.lon);
+            let b = grid.bounds(lat_index, lon_index);
+            let ring = vec![
+                [b.min_lon, b.min_lat],
+                [b.max_lon, b.min_lat],
+                [b.max_lon, b.max_lat],
+                [b.min_lon, b.max_lat],
+                [b.min_lon, b.min_lat],
+            ];
+            Feature::new(Polygon::new(ring), bucket)
+        })
+        .collect();
+    FeatureCollection::new(features)
+}
+
+/// Get the number of cells per geohash, radio and operator.
+#[utoipa::path(
+    get,
+    path = "/v1/cells/density",
+    params(GetDensityQuery),
+    responses(
+        (status = 200, description = "Buckets with at least one cell. Protobuf responses are a `GetDensityResponse` message. With `format=geojson`, a GeoJSON `FeatureCollection` of the bucket rectangles", content((GetDensityResponse = "application/json"), (GetDensityResponse = "application/msgpack"), (GetDensityResponse = "application/cbor"), (FeatureCollection<DensityBucket> = "application/geo+json"))),
+        (status = 304, description = "The cached copy is still fresh"),
+        (status = 400, description = "Invalid parameters", body = ErrorBody),
+        (status = 406, description = "None of the accepted formats is supported", body = ErrorBody),
+        (status = 503, description = "The database is unavailable", body = ErrorBody),
+    ),
+    tag = "cells"
+)]
+#[instrument(skip(config))]
+pub async fn handle_get_density(
+    query: GetDensityQuery,
+    conditions: Conditions,
+    format: Format,
+    config: Config,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let bbox = query
+        .bounds()
+        .map_err(|message| ApiError::BadRequest(vec![FieldError::new("bbox", message)]))?;
+    let grid = GeohashGrid::new(query.resolution);
+    let connection = &mut try_establish_connection(config.clone()).map_err(ApiError::from)?;
+
+    let last_update = get_last_update_with_connection(connection).map_err(ApiError::from)?;
+    let route = match query.format {
+        Some(CellsFormat::Geojson) => "cells/density/geojson".to_string(),
+        None => format.cache_route("cells/density"),
+    };
+    let validators = CacheValidators::new(&route, &query, last_update, chrono::Utc::now());
+    if validators.is_not_modified(&conditions) {
+        return Ok(validators.not_modified());
+    }
+
+    let buckets = query_density(&grid, &bbox, connection).map_err(ApiError::from)?;
+    match query.format {
+        Some(CellsFormat::Geojson) => {
+            Ok(validators.apply(geojson_reply(&density_collection(&grid, buckets))))
+        }
+        None => Ok(validators.apply(encode(
+            format,
+            &GetDensityResponse {
+                resolution: query.resolution,
+                buckets,
+            },
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::geojson::Geometry;
+    use crate::utils::query::parse_query;
+
+    fn field_names(query_string: &str) -> Vec<String> {
+        match parse_query::<GetDensityQuery>(query_string) {
+            Err(ApiError::BadRequest(fields)) => fields.into_iter().map(|f| f.field).collect(),
+            Err(e) => panic!("unexpected error: {:?}", e),
+            Ok(_) => vec![],
+        }
+    }
+
+    fn row(lat: f64, lon: f64, radio: Radio, net: u16, cells: i64) -> DensityRow {
+        let grid = GeohashGrid::new(5);
+        let (lat_index, lon_index) = grid.index(lat, lon);
+        DensityRow {
+            lat_index: i64::from(lat_index),
+            lon_index: i64::from(lon_index),
+            radio,
+            mcc: 262,
+            net,
+            cells,
+            samples: 10 * cells as u64,
+        }
+    }
+
+    #[test]
+    fn test_query_validation() {
+        assert!(field_names("resolution=4&bbox=5.9,47.3,15.0,55.1").is_empty());
+        assert!(field_names("resolution=2").is_empty());
+        assert_eq!(field_names("resolution=0"), vec!["resolution"]);
+        assert_eq!(field_names("resolution=4&bbox=1,2,3"), vec!["bbox"]);
+        assert_eq!(field_names("resolution=4&bbox=0,91,1,92"), vec!["bbox"]);
+        assert_eq!(field_names("resolution=4&bbox=0,50,1,40"), vec!["bbox"]);
+    }
+
+    #[test]
+    fn test_country_boxes_are_limited_by_resolution() {
+        let germany = "bbox=5.9,47.3,15.0,55.1";
+
+        assert!(field_names(&format!("resolution=5&{}", germany)).is_empty());
+        assert_eq!(
+            field_names(&format!("resolution=6&{}", germany)),
+            vec!["resolution"]
+        );
+        assert_eq!(field_names("resolution=5"), vec!["resolution"]);
+    }
+
+    #[test]
+    fn test_bbox_across_the_antimeridian() {
+        let query = parse_query::<GetDensityQuery>("resolution=3&bbox=170,-20,-170,-10").unwrap();
+
+        assert!(query.bounds().unwrap().crosses_antimeridian());
+    }
+
+    #[test]
+    fn test_rows_are_merged_per_bucket() {
+        let grid = GeohashGrid::new(5);
+        let rows = vec![
+            row(52.52, 13.405, Radio::Gsm, 1, 2),
+            row(52.52, 13.405, Radio::Lte, 1, 3),
+            row(52.52, 13.405, Radio::Lte, 2, 4),
+            row(48.137, 11.575, Radio::Nr, 1, 1),
+        ];
+
+        let buckets = density_buckets(&grid, rows);
+
+        assert_eq!(buckets.len(), 2);
+        let berlin = buckets.iter().find(|b| b.geohash == "u33dc").unwrap();
+        assert_eq!((berlin.cells, berlin.samples), (9, 90));
+        assert_eq!(
+            berlin.radios,
+            vec![
+                RadioCount {
+                    radio: Radio::Lte,
+                    cells: 7
+                },
+                RadioCount {
+                    radio: Radio::Gsm,
+                    cells: 2
+                },
+            ]
+        );
+        let operators: Vec<(u16, u64)> =
+            berlin.operators.iter().map(|o| (o.net, o.cells)).collect();
+        assert_eq!(operators, vec![(1, 5), (2, 4)]);
+    }
+
+    #[test]
+    fn test_geojson_features_are_the_bucket_rectangles() {
+        let grid = GeohashGrid::new(5);
+        let buckets = density_buckets(&grid, vec![row(52.52, 13.405, Radio::Lte, 1, 1)]);
+
+        let collection = density_collection(&grid, buckets);
+
+        let Geometry::Polygon(ref polygon) = collection.features[0].geometry else {
+            panic!("bucket is not a polygon");
+        };
+        let ring = &polygon.coordinates[0];
+        assert_eq!(ring.len(), 5);
+        assert!((ring[2][0] - ring[0][0] - grid.lon_step()).abs() < 1e-9);
+        assert!((ring[2][1] - ring[0][1] - grid.lat_step()).abs() < 1e-9);
+        assert!(ring[0][0] <= 13.405 && 13.405 < ring[2][0]);
+    }
+
+    /// Integration tests for the density query using testcontainers.
+    #[cfg(feature = "integration_tests")]
+    mod density_integration {
+        use super::*;
+        use crate::models::Cell;
+        use crate::schema::cells;
+        use crate::utils::test_db::get_test_connection;
+
+        fn cell(id: u64, lat: f32, lon: f32, radio: Radio) -> Cell {
+            let now = chrono::Utc::now().naive_utc();
+            Cell {
+                radio,
+                mcc: 262,
+                net: 1,
+                area: 1,
+                cell: id,
+                unit: None,
+                lon,
+                lat,
+                cell_range: 1000,
+                samples: 10,
+                changeable: true,
+                created: now,
+                updated: now,
+                average_signal: None,
+            }
+        }
+
+        #[test]
+        fn test_density_counts_cells_in_the_box() {
+            let (_container, mut conn) = get_test_connection();
+            diesel::insert_into(cells::table)
+                .values(&vec![
+                    cell(1, 52.52, 13.405, Radio::Lte),
+                    cell(2, 52.521, 13.406, Radio::Gsm),
+                    cell(3, 48.137, 11.575, Radio::Lte),
+                ])
+                .execute(&mut conn)
+                .unwrap();
+            refresh_density(&mut conn).unwrap();
+            let grid = GeohashGrid::new(5);
+            let bbox = BoundingBox {
+                min_lat: 52.0,
+                max_lat: 53.0,
+                min_lon: 13.0,
+                max_lon: 14.0,
+            };
+
+            let buckets = query_density(&grid, &bbox, &mut conn).unwrap();
+
+            assert_eq!(buckets.len(), 1);
+            assert_eq!(buckets[0].geohash, "u33dc");
+            assert_eq!((buckets[0].cells, buckets[0].samples), (2, 20));
+        }
+
+        #[test]
+        fn test_coarser_resolutions_sum_the_finer_ones() {
+            let (_container, mut conn) = get_test_connection();
+            diesel::insert_into(cells::table)
+                .values(&vec![
+                    cell(1, 52.52, 13.405, Radio::Lte),
+                    cell(2, 48.137, 11.575, Radio::Lte),
+                    cell(3, -33.87, 151.21, Radio::Nr),
+                ])
+                .execute(&mut conn)
+                .unwrap();
+            refresh_density(&mut conn).unwrap();
+            let world = GetDensityQuery {
+                resolution: 1,
+                bbox: None,
+                format: None,
+            }
+            .bounds()
+            .unwrap();
+
+            for precision in 1..=MAX_PRECISION {
+                let buckets =
+                    query_density(&GeohashGrid::new(precision), &world, &mut conn).unwrap();
+                let cells: u64 = buckets.iter().map(|bucket| bucket.cells).sum();
+                assert_eq!(cells, 3, "resolution {}", precision);
+            }
+            let buckets = query_density(&GeohashGrid::new(1), &world, &mut conn).unwrap();
+            let europe = buckets.iter().find(|b| b.geohash == "u").unwrap();
+            assert_eq!(europe.cells, 2);
+        }
+    }
+}
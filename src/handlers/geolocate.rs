@@ -0,0 +1,377 @@
+use diesel::MysqlConnection;
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+use utoipa::ToSchema;
+
+use super::lookup::{query_lookup, CellKey, MAX_LOOKUP_KEYS};
+use crate::models::Cell;
+use crate::utils::confidence::confidence;
+use crate::utils::errors::FieldError;
+use crate::utils::geo::LocalFrame;
+use crate::utils::query::{into_result, Validate};
+use crate::utils::ranging::{cell_range, estimate_range, Measurement, Range};
+
+/// Iterations of the position fit, it usually converges within a few.
+const MAX_ITERATIONS: usize = 50;
+/// The fit stops once an iteration moves the estimate less than this.
+const CONVERGENCE_M: f64 = 0.1;
+/// Floor for confidence weights, so that a position is found even if all
+/// cells are doubtful.
+const MIN_WEIGHT_CONFIDENCE: f64 = 0.01;
+
+/// A cell a device observes, with its signal strength and timing advance if known.
+#[derive(Deserialize, Serialize, Debug, Clone, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Observation {
+    #[serde(flatten)]
+    pub key: CellKey,
+    /// Received signal strength in dBm: RSRP (LTE/NR), RSCP (UMTS) or RSSI (GSM, CDMA)
+    pub signal_dbm: Option<i16>,
+    /// Timing advance as reported by GSM, LTE or NR modems
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timing_advance: Option<u16>,
+}
+
+impl Observation {
+    pub fn measurement(&self) -> Measurement {
+        Measurement {
+            signal_dbm: self.signal_dbm,
+            timing_advance: self.timing_advance,
+        }
+    }
+}
+
+/// Request to estimate a position from observed cells.
+#[derive(Debug, Clone)]
+pub struct GeolocateRequest {
+    pub observations: Vec<Observation>,
+}
+
+impl Validate for GeolocateRequest {
+    fn validate(&self) -> Result<(), Vec<FieldError>> {
+        let mut errors = vec![];
+        if self.observations.is_empty() {
+            errors.push(FieldError::new("observations", "must not be empty"));
+        }
+        // The lookup resolves no more keys, the rest would be left out of the fit
+        if self.observations.len() > MAX_LOOKUP_KEYS {
+            errors.push(FieldError::new(
+                "observations",
+                format!("must not have more than {} entries", MAX_LOOKUP_KEYS),
+            ));
+        }
+        into_result(errors)
+    }
+}
+
+/// An estimated position.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Position {
+    pub lat: f64,
+    pub lon: f64,
+    /// Estimated accuracy radius in meters
+    pub accuracy: f64,
+    /// Number of observations that resolved to a known cell
+    pub cells_used: u32,
+}
+
+/// The distance to keep from a cell. Without a measurement the device is
+/// somewhere within the cell's range, which pulls the estimate towards it.
+fn target_range(cell: &Cell, measurement: Measurement) -> Range {
+    estimate_range(cell, measurement).unwrap_or(Range {
+        distance: 0.0,
+        uncertainty: cell_range(cell),
+    })
+}
+
+/// Estimates a position by fitting it to the distance from each observed cell,
+/// see `crate::utils::ranging`. Each iteration moves every cell's pull onto
+/// its range circle and takes the mean weighted by inverse variance, which
+/// never increases the weighted squared error. Without any measurements this
+/// is the centroid weighted by cell size. Weights are scaled by each cell's
+/// confidence, see `crate::utils::confidence`.
+///
+/// The accuracy is the weighted mean of how far the estimate is off each
+/// range plus the range's uncertainty. Returns `None` if no cells are given.
+pub fn estimate_position(cells: &[(Cell, Measurement)]) -> Option<Position> {
+    let (first, _) = cells.first()?;
+    let frame = LocalFrame::new(f64::from(first.lat), f64::from(first.lon));
+    let circles: Vec<((f64, f64), Range)> = cells
+        .iter()
+        .map(|(cell, measurement)| {
+            let center = frame.to_local(f64::from(cell.lat), f64::from(cell.lon));
+            (center, target_range(cell, *measurement))
+        })
+        .collect();
+    let weights: Vec<f64> = circles
+        .iter()
+        .zip(cells)
+        .map(|((_, range), (cell, _))| {
+            confidence(cell).max(MIN_WEIGHT_CONFIDENCE) / range.uncertainty.powi(2)
+        })
+        .collect();
+    let total: f64 = weights.iter().sum();
+    let weighted_mean = |points: &mut dyn Iterator<Item = (f64, f64)>| {
+        points
+            .zip(&weights)
+            .fold((0.0, 0.0), |(x, y), ((px, py), w)| (x + px * w, y + py * w))
+    };
+
+    let (x, y) = weighted_mean(&mut circles.iter().map(|(center, _)| *center));
+    let mut estimate = (x / total, y / total);
+    for _ in 0..MAX_ITERATIONS {
+        let (x, y) = weighted_mean(&mut circles.iter().map(|((cx, cy), range)| {
+            let (dx, dy) = (estimate.0 - cx, estimate.1 - cy);
+            let distance = dx.hypot(dy);
+            if distance < f64::EPSILON {
+                // Any point of the circle is as close, stay at the center
+                (*cx, *cy)
+            } else {
+                let scale = range.distance / distance;
+                (cx + dx * scale, cy + dy * scale)
+            }
+        }));
+        let next = (x / total, y / total);
+        let moved = (next.0 - estimate.0).hypot(next.1 - estimate.1);
+        estimate = next;
+        if moved < CONVERGENCE_M {
+            break;
+        }
+    }
+
+    let accuracy = circles
+        .iter()
+        .zip(&weights)
+        .map(|(((cx, cy), range), w)| {
+            let distance = (estimate.0 - cx).hypot(estimate.1 - cy);
+            ((distance - range.distance).abs() + range.uncertainty) * w
+        })
+        .sum::<f64>()
+        / total;
+    let (lat, lon) = frame.to_geo(estimate.0, estimate.1);
+
+    Some(Position {
+        lat,
+        lon,
+        accuracy,
+        cells_used: cells.len() as u32,
+    })
+}
+
+/// Resolves the observed cells and estimates the position from those that are known.
+#[instrument(skip(connection))]
+pub fn query_geolocate(
+    request: &GeolocateRequest,
+    connection: &mut MysqlConnection,
+) -> Result<Option<Position>, diesel::result::Error> {
+    let keys: Vec<CellKey> = request.observations.iter().map(|o| o.key.clone()).collect();
+    let found = query_lookup(&keys, connection)?;
+
+    let cells: Vec<(Cell, Measurement)> = found
+        .into_iter()
+        .zip(&request.observations)
+        .filter_map(|(cell, observation)| cell.map(|c| (c, observation.measurement())))
+        .collect();
+    Ok(estimate_position(&cells))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Radio;
+    use crate::utils::geo::haversine_distance;
+    use crate::utils::ranging::MIN_RANGE_M;
+
+    fn cell_at(lat: f32, lon: f32, range: u32) -> Cell {
+        let now = chrono::Utc::now().naive_utc();
+        Cell {
+            radio: Radio::Lte,
+            mcc: 262,
+            net: 1,
+            area: 1,
+            cell: 1,
+            unit: None,
+            lon,
+            lat,
+            cell_range: range,
+            samples: 10,
+            changeable: true,
+            created: now,
+            updated: now,
+            average_signal: None,
+        }
+    }
+
+    fn signal(signal_dbm: i16) -> Measurement {
+        Measurement {
+            signal_dbm: Some(signal_dbm),
+            timing_advance: None,
+        }
+    }
+
+    fn timing_advance(timing_advance: u16) -> Measurement {
+        Measurement {
+            signal_dbm: None,
+            timing_advance: Some(timing_advance),
+        }
+    }
+
+    mod estimate_position {
+        use super::*;
+
+        #[test]
+        fn test_no_cells() {
+            assert_eq!(estimate_position(&[]), None);
+        }
+
+        #[test]
+        fn test_single_cell_is_its_position() {
+            let position =
+                estimate_position(&[(cell_at(52.5, 13.4, 1000), Measurement::default())]).unwrap();
+
+            assert!((position.lat - 52.5).abs() < 1e-6);
+            assert!((position.lon - 13.4).abs() < 1e-6);
+            assert!((position.accuracy - 1000.0).abs() < 1e-6);
+            assert_eq!(position.cells_used, 1);
+        }
+
+        #[test]
+        fn test_equal_cells_meet_in_the_middle() {
+            let position = estimate_position(&[
+                (cell_at(52.0, 13.0, 500), Measurement::default()),
+                (cell_at(52.2, 13.0, 500), Measurement::default()),
+            ])
+            .unwrap();
+
+            assert!((position.lat - 52.1).abs() < 1e-4);
+            assert_eq!(position.cells_used, 2);
+        }
+
+        #[test]
+        fn test_stronger_signal_pulls_the_estimate() {
+            let position = estimate_position(&[
+                (cell_at(52.0, 13.0, 500), signal(-60)),
+                (cell_at(52.2, 13.0, 500), signal(-100)),
+            ])
+            .unwrap();
+
+            assert!(position.lat < 52.05, "{}", position.lat);
+        }
+
+        #[test]
+        fn test_smaller_cell_pulls_the_estimate() {
+            let position = estimate_position(&[
+                (cell_at(52.0, 13.0, 200), Measurement::default()),
+                (cell_at(52.2, 13.0, 5000), Measurement::default()),
+            ])
+            .unwrap();
+
+            assert!(position.lat < 52.05, "{}", position.lat);
+        }
+
+        #[test]
+        fn test_doubtful_cell_is_down_weighted() {
+            let mut stale = cell_at(52.2, 13.0, 500);
+            stale.samples = 1;
+            stale.updated -= chrono::Duration::days(3 * 365);
+
+            let position = estimate_position(&[
+                (cell_at(52.0, 13.0, 500), Measurement::default()),
+                (stale, Measurement::default()),
+            ])
+            .unwrap();
+
+            assert!(position.lat < 52.05, "{}", position.lat);
+        }
+
+        #[test]
+        fn test_timing_advance_narrows_a_single_cell() {
+            // 10 LTE steps are 781 m, the position stays at the cell
+            let position =
+                estimate_position(&[(cell_at(52.5, 13.4, 5000), timing_advance(10))]).unwrap();
+
+            assert!((position.lat - 52.5).abs() < 1e-6);
+            assert!(
+                (position.accuracy - 888.0).abs() < 1.0,
+                "{}",
+                position.accuracy
+            );
+        }
+
+        #[test]
+        fn test_timing_advance_trilaterates() {
+            let device = (52.5, 13.4);
+            let frame = LocalFrame::new(device.0, device.1);
+            let towers = [(1000.0, 0.0), (-600.0, 800.0), (-200.0, -1500.0)];
+            let cells: Vec<(Cell, Measurement)> = towers
+                .iter()
+                .map(|(east, north)| {
+                    let (lat, lon) = frame.to_geo(*east, *north);
+                    // One LTE timing advance step is 78.07 m
+                    let steps = (f64::hypot(*east, *north) / 78.07).round() as u16;
+                    (cell_at(lat as f32, lon as f32, 5000), timing_advance(steps))
+                })
+                .collect();
+
+            let position = estimate_position(&cells).unwrap();
+
+            let error = haversine_distance(position.lat, position.lon, device.0, device.1);
+            assert!(error < 60.0, "{}", error);
+            assert!(position.accuracy < 150.0, "{}", position.accuracy);
+        }
+
+        #[test]
+        fn test_measured_cell_outweighs_unmeasured() {
+            let position = estimate_position(&[
+                (cell_at(52.0, 13.0, 1000), timing_advance(0)),
+                (cell_at(52.01, 13.0, 1000), Measurement::default()),
+            ])
+            .unwrap();
+
+            assert!(position.lat < 52.001, "{}", position.lat);
+        }
+
+        #[test]
+        fn test_unknown_range_uses_the_minimum() {
+            let position =
+                estimate_position(&[(cell_at(52.5, 13.4, 0), Measurement::default())]).unwrap();
+
+            assert!((position.accuracy - MIN_RANGE_M).abs() < 1e-6);
+        }
+    }
+
+    mod validation {
+        use super::*;
+
+        #[test]
+        fn test_empty_observations_are_rejected() {
+            let request = GeolocateRequest {
+                observations: vec![],
+            };
+
+            let errors = request.validate().unwrap_err();
+            assert_eq!(errors[0].field, "observations");
+        }
+
+        #[test]
+        fn test_too_many_observations_are_rejected() {
+            let observation = Observation {
+                key: CellKey {
+                    mcc: 262,
+                    mnc: 1,
+                    lac: 1,
+                    cid: 1,
+                },
+                signal_dbm: None,
+                timing_advance: None,
+            };
+            let request = |count| GeolocateRequest {
+                observations: vec![observation.clone(); count],
+            };
+
+            assert!(request(MAX_LOOKUP_KEYS).validate().is_ok());
+            let errors = request(MAX_LOOKUP_KEYS + 1).validate().unwrap_err();
+            assert_eq!(errors[0].field, "observations");
+        }
+    }
+}
@@ -0,0 +1,622 @@
+use std::sync::Mutex;
+
+use async_graphql::connection::{Connection, Edge};
+use async_graphql::{
+    Context, EmptyMutation, EmptySubscription, Enum, ErrorExtensions, Object, Schema, SimpleObject,
+};
+use async_graphql_warp::GraphQLResponse;
+use chrono::{DateTime, Utc};
+use diesel::dsl::{count, count_star, max, min};
+use diesel::prelude::*;
+use diesel::MysqlConnection;
+use tracing::instrument;
+
+use super::cell::{query_cell, GetCellQuery};
+use super::cells::{query_cells, CellCursor, GetCellsQuery, DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE};
+use crate::models::{Cell, Radio};
+use crate::utils::confidence::confidence;
+use crate::utils::config::Config;
+use crate::utils::db::try_establish_connection;
+use crate::utils::errors::ApiError;
+use crate::utils::query::Validate;
+
+/// Deepest selection allowed, enough for `cells > edges > node > area > cells > edges > node > field`.
+pub const MAX_DEPTH: usize = 10;
+/// Highest complexity allowed per query. Every field costs 1, lists multiply
+/// their children by the page size and aggregates cost `AGGREGATE_COMPLEXITY`.
+pub const MAX_COMPLEXITY: usize = 10_000;
+/// Complexity of a field that aggregates over many rows.
+const AGGREGATE_COMPLEXITY: usize = 100;
+
+pub type CellSchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+/// Builds the schema with depth and complexity limits.
+pub fn build_schema(config: Config) -> CellSchema {
+    Schema::build(QueryRoot, EmptyMutation, EmptySubscription)
+        .data(config)
+        .limit_depth(MAX_DEPTH)
+        .limit_complexity(MAX_COMPLEXITY)
+        .finish()
+}
+
+/// A database connection shared by all resolvers of one request, opened on first use.
+pub struct Database {
+    config: Config,
+    connection: Mutex<Option<MysqlConnection>>,
+}
+
+impl Database {
+    pub fn new(config: Config) -> Self {
+        Database {
+            config,
+            connection: Mutex::new(None),
+        }
+    }
+
+    fn run<T>(
+        &self,
+        f: impl FnOnce(&mut MysqlConnection) -> Result<T, ApiError>,
+    ) -> Result<T, ApiError> {
+        let mut connection = self.connection.lock().unwrap();
+        if connection.is_none() {
+            *connection = Some(try_establish_connection(self.config.clone())?);
+        }
+        f(connection.as_mut().unwrap())
+    }
+}
+
+/// The GraphQL argument name of a REST parameter.
+fn graphql_field(field: &str) -> String {
+    let field = match field {
+        "limit" => "first",
+        "cursor" => "after",
+        "mnc" => "net",
+        field => field,
+    };
+    let mut parts = field.split('_');
+    let mut name = parts.next().unwrap_or_default().to_string();
+    for part in parts {
+        let mut chars = part.chars();
+        if let Some(first) = chars.next() {
+            name.extend(first.to_uppercase());
+            name.push_str(chars.as_str());
+        }
+    }
+    name
+}
+
+/// Converts an API error into a GraphQL error with the REST error identifier as `code`.
+fn graphql_error(err: ApiError) -> async_graphql::Error {
+    let body = err.body();
+    let message = if body.fields.is_empty() {
+        body.message
+    } else {
+        body.fields
+            .iter()
+            .map(|f| format!("{}: {}", graphql_field(&f.field), f.message))
+            .collect::<Vec<_>>()
+            .join("; ")
+    };
+    async_graphql::Error::new(message).extend_with(|_, e| e.set("code", body.error))
+}
+
+fn run<T>(
+    ctx: &Context<'_>,
+    f: impl FnOnce(&mut MysqlConnection) -> Result<T, ApiError>,
+) -> async_graphql::Result<T> {
+    ctx.data::<Database>()?.run(f).map_err(graphql_error)
+}
+
+/// Complexity of a page of `first` items.
+fn page_complexity(first: Option<i32>, child_complexity: usize) -> usize {
+    let page_size = first.map_or(DEFAULT_PAGE_SIZE, |first| first.max(1) as u32);
+    page_size.min(MAX_PAGE_SIZE) as usize * child_complexity + 1
+}
+
+#[derive(Enum, Copy, Clone, Eq, PartialEq)]
+#[graphql(name = "Radio", remote = "crate::models::Radio")]
+enum GraphQLRadio {
+    Gsm,
+    Umts,
+    Cdma,
+    Lte,
+    Nr,
+}
+
+/// A cell tower.
+pub struct CellNode(Cell);
+
+#[Object(name = "Cell")]
+impl CellNode {
+    async fn radio(&self) -> GraphQLRadio {
+        self.0.radio.clone().into()
+    }
+
+    /// Mobile Country Code
+    async fn mcc(&self) -> u16 {
+        self.0.mcc
+    }
+
+    /// Mobile Network Code
+    async fn net(&self) -> u16 {
+        self.0.net
+    }
+
+    /// Cell ID
+    async fn cell(&self) -> u64 {
+        self.0.cell
+    }
+
+    async fn unit(&self) -> Option<u16> {
+        self.0.unit
+    }
+
+    async fn lat(&self) -> f32 {
+        self.0.lat
+    }
+
+    async fn lon(&self) -> f32 {
+        self.0.lon
+    }
+
+    /// Estimated range in meters
+    async fn range(&self) -> u32 {
+        self.0.cell_range
+    }
+
+    async fn samples(&self) -> u32 {
+        self.0.samples
+    }
+
+    async fn changeable(&self) -> bool {
+        self.0.changeable
+    }
+
+    async fn created(&self) -> DateTime<Utc> {
+        self.0.created.and_utc()
+    }
+
+    async fn updated(&self) -> DateTime<Utc> {
+        self.0.updated.and_utc()
+    }
+
+    async fn average_signal(&self) -> Option<i16> {
+        self.0.average_signal
+    }
+
+    /// Trust in the position from 0 to 1, derived from samples, range and age
+    async fn confidence(&self) -> f64 {
+        confidence(&self.0)
+    }
+
+    /// The location or tracking area the cell belongs to
+    async fn area(&self) -> AreaNode {
+        AreaNode {
+            mcc: self.0.mcc,
+            net: self.0.net,
+            code: self.0.area,
+        }
+    }
+
+    /// The network operator of the cell
+    async fn operator(&self) -> OperatorNode {
+        OperatorNode {
+            mcc: self.0.mcc,
+            net: self.0.net,
+        }
+    }
+}
+
+/// Bounding box of a set of cells.
+#[derive(SimpleObject)]
+pub struct Bounds {
+    pub min_lat: f32,
+    pub max_lat: f32,
+    pub min_lon: f32,
+    pub max_lon: f32,
+}
+
+/// A location or tracking area of an operator.
+pub struct AreaNode {
+    mcc: u16,
+    net: u16,
+    code: u32,
+}
+
+#[Object(name = "Area")]
+impl AreaNode {
+    async fn mcc(&self) -> u16 {
+        self.mcc
+    }
+
+    async fn net(&self) -> u16 {
+        self.net
+    }
+
+    /// Location Area Code (GSM/UMTS) or Tracking Area Code (LTE/NR)
+    async fn code(&self) -> u32 {
+        self.code
+    }
+
+    /// Number of cells in the area
+    #[graphql(complexity = "AGGREGATE_COMPLEXITY")]
+    async fn cell_count(&self, ctx: &Context<'_>) -> async_graphql::Result<i64> {
+        use crate::schema::cells::dsl::*;
+
+        run(ctx, |connection| {
+            cells
+                .filter(mcc.eq(self.mcc))
+                .filter(net.eq(self.net))
+                .filter(area.eq(self.code))
+                .select(count_star())
+                .first(connection)
+                .map_err(ApiError::from)
+        })
+    }
+
+    /// Bounding box of the cells in the area, `null` if it has none
+    #[graphql(complexity = "AGGREGATE_COMPLEXITY")]
+    async fn bounds(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<Bounds>> {
+        use crate::schema::cells::dsl::*;
+
+        let (min_lat, max_lat, min_lon, max_lon) = run(ctx, |connection| {
+            cells
+                .filter(mcc.eq(self.mcc))
+                .filter(net.eq(self.net))
+                .filter(area.eq(self.code))
+                .select((min(lat), max(lat), min(lon), max(lon)))
+                .first::<(Option<f32>, Option<f32>, Option<f32>, Option<f32>)>(connection)
+                .map_err(ApiError::from)
+        })?;
+
+        Ok(match (min_lat, max_lat, min_lon, max_lon) {
+            (Some(min_lat), Some(max_lat), Some(min_lon), Some(max_lon)) => Some(Bounds {
+                min_lat,
+                max_lat,
+                min_lon,
+                max_lon,
+            }),
+            _ => None,
+        })
+    }
+
+    async fn operator(&self) -> OperatorNode {
+        OperatorNode {
+            mcc: self.mcc,
+            net: self.net,
+        }
+    }
+
+    /// The cells in the area, paginated
+    #[graphql(complexity = "page_complexity(first, child_complexity)")]
+    async fn cells(
+        &self,
+        ctx: &Context<'_>,
+        radio: Option<GraphQLRadio>,
+        min_confidence: Option<f64>,
+        first: Option<i32>,
+        after: Option<String>,
+    ) -> async_graphql::Result<Connection<String, CellNode>> {
+        let query = GetCellsQuery {
+            mcc: Some(self.mcc),
+            mnc: Some(self.net),
+            area: Some(self.code),
+            min_lat: None,
+            max_lat: None,
+            min_lon: None,
+            max_lon: None,
+            radio: radio.map(Radio::from),
+            min_confidence,
+            cursor: after,
+            limit: first.map(|first| first.max(0) as u32),
+        };
+        cell_connection(ctx, query)
+    }
+}
+
+/// A network operator, identified by country and network code.
+pub struct OperatorNode {
+    mcc: u16,
+    net: u16,
+}
+
+#[Object(name = "Operator")]
+impl OperatorNode {
+    async fn mcc(&self) -> u16 {
+        self.mcc
+    }
+
+    async fn net(&self) -> u16 {
+        self.net
+    }
+
+    /// Number of cells of the operator
+    #[graphql(complexity = "AGGREGATE_COMPLEXITY")]
+    async fn cell_count(&self, ctx: &Context<'_>) -> async_graphql::Result<i64> {
+        use crate::schema::cells::dsl::*;
+
+        run(ctx, |connection| {
+            cells
+                .filter(mcc.eq(self.mcc))
+                .filter(net.eq(self.net))
+                .select(count_star())
+                .first(connection)
+                .map_err(ApiError::from)
+        })
+    }
+
+    /// Number of distinct areas of the operator
+    #[graphql(complexity = "AGGREGATE_COMPLEXITY")]
+    async fn area_count(&self, ctx: &Context<'_>) -> async_graphql::Result<i64> {
+        use crate::schema::cells::dsl::*;
+
+        run(ctx, |connection| {
+            cells
+                .filter(mcc.eq(self.mcc))
+                .filter(net.eq(self.net))
+                .select(count(area).aggregate_distinct())
+                .first(connection)
+                .map_err(ApiError::from)
+        })
+    }
+
+    /// One of the operator's areas
+    async fn area(&self, code: u32) -> AreaNode {
+        AreaNode {
+            mcc: self.mcc,
+            net: self.net,
+            code,
+        }
+    }
+
+    /// The cells of the operator, paginated
+    #[graphql(complexity = "page_complexity(first, child_complexity)")]
+    async fn cells(
+        &self,
+        ctx: &Context<'_>,
+        radio: Option<GraphQLRadio>,
+        min_confidence: Option<f64>,
+        first: Option<i32>,
+        after: Option<String>,
+    ) -> async_graphql::Result<Connection<String, CellNode>> {
+        let query = GetCellsQuery {
+            mcc: Some(self.mcc),
+            mnc: Some(self.net),
+            area: None,
+            min_lat: None,
+            max_lat: None,
+            min_lon: None,
+            max_lon: None,
+            radio: radio.map(Radio::from),
+            min_confidence,
+            cursor: after,
+            limit: first.map(|first| first.max(0) as u32),
+        };
+        cell_connection(ctx, query)
+    }
+}
+
+/// Loads one page of cells as a connection, with the same cursors as `/cells`.
+fn cell_connection(
+    ctx: &Context<'_>,
+    query: GetCellsQuery,
+) -> async_graphql::Result<Connection<String, CellNode>> {
+    query
+        .validate()
+        .map_err(|fields| graphql_error(ApiError::BadRequest(fields)))?;
+    let secret = ctx.data::<Config>()?.cursor_secret.as_bytes();
+
+    let page = run(ctx, |connection| query_cells(&query, secret, connection))?;
+
+    let scope = query.cursor_scope();
+    let mut connection = Connection::new(query.cursor.is_some(), page.has_more);
+    connection.edges.extend(page.cells.into_iter().map(|cell| {
+        let cursor = CellCursor::from_cell(&cell).encode(&scope, secret);
+        Edge::new(cursor, CellNode(cell))
+    }));
+    Ok(connection)
+}
+
+/// Execute a GraphQL request, its resolvers share one database connection.
+#[instrument(skip(schema, config))]
+pub async fn handle_graphql(
+    schema: CellSchema,
+    request: async_graphql::Request,
+    config: Config,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let response = schema.execute(request.data(Database::new(config))).await;
+    Ok(GraphQLResponse::from(response))
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// A single cell by its identifiers
+    async fn cell(
+        &self,
+        ctx: &Context<'_>,
+        mcc: u16,
+        net: u16,
+        area: u32,
+        cell: u64,
+        radio: Option<GraphQLRadio>,
+    ) -> async_graphql::Result<Option<CellNode>> {
+        let query = GetCellQuery {
+            mcc,
+            net,
+            area,
+            cell,
+            radio: radio.map(Radio::from),
+            match_mode: None,
+        };
+        let found = run(ctx, |connection| {
+            query_cell(&query, connection).map_err(ApiError::from)
+        })?;
+        Ok(found.map(CellNode))
+    }
+
+    /// Cells matching the filters, paginated
+    #[allow(clippy::too_many_arguments)]
+    #[graphql(complexity = "page_complexity(first, child_complexity)")]
+    async fn cells(
+        &self,
+        ctx: &Context<'_>,
+        mcc: Option<u16>,
+        net: Option<u16>,
+        area: Option<u32>,
+        radio: Option<GraphQLRadio>,
+        min_lat: Option<f32>,
+        max_lat: Option<f32>,
+        min_lon: Option<f32>,
+        max_lon: Option<f32>,
+        min_confidence: Option<f64>,
+        first: Option<i32>,
+        after: Option<String>,
+    ) -> async_graphql::Result<Connection<String, CellNode>> {
+        let query = GetCellsQuery {
+            mcc,
+            mnc: net,
+            area,
+            min_lat,
+            max_lat,
+            min_lon,
+            max_lon,
+            radio: radio.map(Radio::from),
+            min_confidence,
+            cursor: after,
+            limit: first.map(|first| first.max(0) as u32),
+        };
+        cell_connection(ctx, query)
+    }
+
+    /// A location or tracking area
+    async fn area(&self, mcc: u16, net: u16, code: u32) -> AreaNode {
+        AreaNode { mcc, net, code }
+    }
+
+    /// A network operator
+    async fn operator(&self, mcc: u16, net: u16) -> OperatorNode {
+        OperatorNode { mcc, net }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::config::test_config;
+
+    // Requests are rejected before any resolver runs, so no database is needed
+    async fn errors_of(query: &str) -> Vec<String> {
+        let request = async_graphql::Request::new(query).data(Database::new(test_config()));
+        build_schema(test_config())
+            .execute(request)
+            .await
+            .errors
+            .into_iter()
+            .map(|e| e.message)
+            .collect()
+    }
+
+    mod limits {
+        use super::*;
+
+        #[tokio::test]
+        async fn test_deep_queries_are_rejected() {
+            let errors = errors_of(
+                "{ operator(mcc: 262, net: 1) { area(code: 1) { operator { area(code: 2) {
+                    operator { area(code: 3) { operator { area(code: 4) { operator {
+                    area(code: 5) { code } } } } } } } } } } }",
+            )
+            .await;
+
+            assert_eq!(errors, vec!["Query is nested too deep."]);
+        }
+
+        #[tokio::test]
+        async fn test_nested_pages_are_too_complex() {
+            let errors = errors_of(
+                "{ cells(first: 1000) { edges { node { area { cells(first: 1000) {
+                    edges { node { mcc } } } } } } } }",
+            )
+            .await;
+
+            assert_eq!(errors, vec!["Query is too complex."]);
+        }
+
+        #[tokio::test]
+        async fn test_aggregates_per_cell_are_too_complex() {
+            let errors = errors_of(
+                "{ cells(first: 1000) { nodes { area { cellCount bounds { minLat } } } } }",
+            )
+            .await;
+
+            assert_eq!(errors, vec!["Query is too complex."]);
+        }
+
+        #[test]
+        fn test_page_complexity_is_capped_at_the_page_size() {
+            assert_eq!(page_complexity(None, 2), 201);
+            assert_eq!(page_complexity(Some(10), 2), 21);
+            assert_eq!(page_complexity(Some(1_000_000), 1), 1001);
+            assert_eq!(page_complexity(Some(-5), 3), 4);
+        }
+    }
+
+    mod validation {
+        use super::*;
+
+        #[tokio::test]
+        async fn test_invalid_geofence_is_rejected() {
+            let errors = errors_of("{ cells(minLat: 53, maxLat: 52) { nodes { mcc } } }").await;
+
+            assert_eq!(errors.len(), 1);
+            assert!(errors[0].starts_with("minLat: "), "{:?}", errors);
+        }
+
+        #[tokio::test]
+        async fn test_zero_page_size_is_rejected() {
+            let errors = errors_of("{ cells(first: 0) { nodes { mcc } } }").await;
+
+            assert_eq!(errors, vec!["first: must be at least 1"]);
+        }
+
+        #[tokio::test]
+        async fn test_min_confidence_above_one_is_rejected() {
+            let errors = errors_of("{ cells(minConfidence: 1.5) { nodes { mcc } } }").await;
+
+            assert_eq!(errors, vec!["minConfidence: must be between 0 and 1"]);
+        }
+
+        #[tokio::test]
+        async fn test_unknown_radio_is_rejected() {
+            let errors = errors_of("{ cells(radio: WIFI) { nodes { mcc } } }").await;
+
+            assert_eq!(errors.len(), 1);
+        }
+
+        #[test]
+        fn test_fields_use_graphql_names() {
+            assert_eq!(graphql_field("min_lat"), "minLat");
+            assert_eq!(graphql_field("limit"), "first");
+            assert_eq!(graphql_field("cursor"), "after");
+            assert_eq!(graphql_field("mnc"), "net");
+            assert_eq!(graphql_field("mcc"), "mcc");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_keys_resolve_without_database() {
+        let request = async_graphql::Request::new(
+            "{ operator(mcc: 262, net: 1) { mcc net area(code: 42) { code operator { net } } } }",
+        )
+        .data(Database::new(test_config()));
+
+        let response = build_schema(test_config()).execute(request).await;
+
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        let data = response.data.into_json().unwrap();
+        assert_eq!(data["operator"]["area"]["code"], 42);
+        assert_eq!(data["operator"]["area"]["operator"]["net"], 1);
+    }
+}
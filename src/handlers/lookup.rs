@@ -0,0 +1,363 @@
+use diesel::prelude::*;
+use diesel::MysqlConnection;
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+use utoipa::{IntoParams, ToSchema};
+
+use super::matching::{best_match_order, query_candidates, CellCandidate, MatchMode, ReportedCell};
+use crate::utils::confidence::confidence;
+use crate::utils::config::Config;
+use crate::utils::errors::{ApiError, ErrorBody, FieldError};
+use crate::utils::negotiation::{encode, Format};
+use crate::utils::query::{check_confidence, into_result, Validate};
+use crate::{models::*, utils::db::try_establish_connection};
+
+/// Maximum number of keys resolved per request. Excess keys are answered with `null`.
+pub const MAX_LOOKUP_KEYS: usize = 50;
+
+/// A cell identified the way modems report it.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Hash, ToSchema)]
+pub struct CellKey {
+    /// Mobile Country Code
+    pub mcc: u16,
+    /// Mobile Network Code
+    pub mnc: u16,
+    /// Location or Tracking Area Code
+    pub lac: u32,
+    /// Cell ID
+    pub cid: u64,
+}
+
+impl From<&CellKey> for ReportedCell {
+    fn from(key: &CellKey) -> Self {
+        ReportedCell {
+            mcc: key.mcc,
+            net: key.mnc,
+            area: key.lac,
+            cell: key.cid,
+            radio: None,
+        }
+    }
+}
+
+/// Request body of the batch lookup.
+#[derive(Deserialize, Serialize, Debug, Clone, ToSchema)]
+pub struct LookupCellsRequest {
+    pub cells: Vec<CellKey>,
+}
+
+impl Validate for LookupCellsRequest {}
+
+/// Query parameters of the batch lookup.
+#[derive(Deserialize, Serialize, Debug, Clone, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct LookupCellsQuery {
+    /// `fuzzy` adds ranked candidates for keys without exact match
+    #[serde(rename = "match")]
+    pub match_mode: Option<MatchMode>,
+    /// Matches below this confidence count as misses
+    pub min_confidence: Option<f64>,
+}
+
+impl Validate for LookupCellsQuery {
+    fn validate(&self) -> Result<(), Vec<FieldError>> {
+        let mut errors = vec![];
+        check_confidence(&mut errors, self.min_confidence);
+        into_result(errors)
+    }
+}
+
+/// Response of the batch lookup, aligned 1:1 with the requested keys.
+#[derive(Serialize, Debug, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct LookupCellsResponse {
+    /// The best match per key, `null` if there is none
+    pub cells: Vec<Option<Cell>>,
+    /// Fuzzy candidates per key, only present with `match=fuzzy`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub candidates: Option<Vec<Vec<CellCandidate>>>,
+}
+
+/// Resolves each key to its best matching row in a single query.
+/// The result is aligned with `keys`, keys beyond `MAX_LOOKUP_KEYS` resolve to `None`.
+#[instrument(skip(connection))]
+pub fn query_lookup(
+    keys: &[CellKey],
+    connection: &mut MysqlConnection,
+) -> Result<Vec<Option<Cell>>, diesel::result::Error> {
+    use crate::schema::cells::dsl::*;
+
+    let resolved_keys = &keys[..keys.len().min(MAX_LOOKUP_KEYS)];
+    let mut results: Vec<Option<Cell>> = vec![None; keys.len()];
+    if resolved_keys.is_empty() {
+        return Ok(results);
+    }
+
+    let mut db_query = cells.into_boxed();
+    for key in resolved_keys {
+        db_query = db_query.or_filter(
+            mcc.eq(key.mcc)
+                .and(net.eq(key.mnc))
+                .and(area.eq(key.lac))
+                .and(cell.eq(key.cid)),
+        );
+    }
+    let rows: Vec<Cell> = db_query.load(connection)?;
+
+    for (result, key) in results.iter_mut().zip(resolved_keys) {
+        *result = rows
+            .iter()
+            .filter(|row| {
+                row.mcc == key.mcc
+                    && row.net == key.mnc
+                    && row.area == key.lac
+                    && row.cell == key.cid
+            })
+            .min_by(|a, b| best_match_order(a, b))
+            .cloned();
+    }
+
+    Ok(results)
+}
+
+/// Looks up all keys and, in fuzzy mode, collects candidates for the misses.
+/// Matches below `min_confidence` are misses.
+pub fn lookup_cells(
+    request: &LookupCellsRequest,
+    match_mode: MatchMode,
+    min_confidence: Option<f64>,
+    connection: &mut MysqlConnection,
+) -> Result<LookupCellsResponse, diesel::result::Error> {
+    let mut cells = query_lookup(&request.cells, connection)?;
+    if let Some(min) = min_confidence {
+        for cell in cells.iter_mut() {
+            if cell.as_ref().is_some_and(|cell| confidence(cell) < min) {
+                *cell = None;
+            }
+        }
+    }
+
+    let candidates = match match_mode {
+        MatchMode::Exact => None,
+        MatchMode::Fuzzy => {
+            let misses: Vec<(usize, ReportedCell)> = request
+                .cells
+                .iter()
+                .take(MAX_LOOKUP_KEYS)
+                .enumerate()
+                .filter(|(index, _)| cells[*index].is_none())
+                .map(|(index, key)| (index, ReportedCell::from(key)))
+                .collect();
+            let reported: Vec<ReportedCell> = misses.iter().map(|(_, r)| r.clone()).collect();
+            let found = query_candidates(&reported, connection)?;
+
+            let mut candidates = vec![vec![]; request.cells.len()];
+            for ((index, _), found) in misses.iter().zip(found) {
+                candidates[*index] = found;
+            }
+            Some(candidates)
+        }
+    };
+
+    Ok(LookupCellsResponse { cells, candidates })
+}
+
+/// Look up the best match for up to 50 cell keys at once.
+#[utoipa::path(
+    post,
+    path = "/v1/cells/lookup",
+    params(LookupCellsQuery),
+    request_body = LookupCellsRequest,
+    responses(
+        (status = 200, description = "One entry per key, in request order. Protobuf responses are a `LookupCellsResponse` message", content((LookupCellsResponse = "application/json"), (LookupCellsResponse = "application/msgpack"), (LookupCellsResponse = "application/cbor"))),
+        (status = 400, description = "Invalid request body", body = ErrorBody),
+        (status = 406, description = "None of the accepted formats is supported", body = ErrorBody),
+        (status = 413, description = "The request body is too large", body = ErrorBody),
+        (status = 503, description = "The database is unavailable", body = ErrorBody),
+    ),
+    tag = "cells"
+)]
+#[instrument(skip(config))]
+pub async fn handle_lookup_cells(
+    query: LookupCellsQuery,
+    request: LookupCellsRequest,
+    format: Format,
+    config: Config,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let connection = &mut try_establish_connection(config.clone()).map_err(ApiError::from)?;
+
+    let response = lookup_cells(
+        &request,
+        query.match_mode.unwrap_or_default(),
+        query.min_confidence,
+        connection,
+    )
+    .map_err(ApiError::from)?;
+    Ok(encode(format, &response))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod lookup_request {
+        use super::*;
+        use crate::utils::query::parse_json;
+
+        #[test]
+        fn test_deserialize_request() {
+            let json = r#"{
+                "cells": [
+                    {"mcc": 262, "mnc": 1, "lac": 12345, "cid": 67890},
+                    {"mcc": 262, "mnc": 1, "lac": 124, "cid": 457}
+                ]
+            }"#;
+
+            let request: LookupCellsRequest = serde_json::from_str(json).unwrap();
+
+            assert_eq!(request.cells.len(), 2);
+            assert_eq!(request.cells[0].lac, 12345);
+            assert_eq!(request.cells[1].cid, 457);
+        }
+
+        #[test]
+        fn test_invalid_key_names_field() {
+            let json = br#"{"cells": [{"mcc": 262, "mnc": 1, "lac": -1, "cid": 1}]}"#;
+
+            match parse_json::<LookupCellsRequest>(json) {
+                Err(ApiError::BadRequest(fields)) => assert_eq!(fields[0].field, "cells[0].lac"),
+                other => panic!("expected bad request, got {:?}", other),
+            }
+        }
+
+        #[test]
+        fn test_exact_response_omits_candidates() {
+            let response = LookupCellsResponse {
+                cells: vec![None],
+                candidates: None,
+            };
+
+            let json = serde_json::to_string(&response).unwrap();
+
+            assert_eq!(json, r#"{"cells":[null]}"#);
+        }
+    }
+
+    /// Integration tests for query_lookup using testcontainers.
+    #[cfg(feature = "integration_tests")]
+    mod lookup_integration {
+        use super::*;
+        use crate::schema::cells;
+        use crate::utils::test_db::get_test_connection;
+        use chrono::TimeZone;
+
+        fn sample_cell(area_val: u32, cell_val: u64, radio_val: Radio, samples_val: u32) -> Cell {
+            Cell {
+                radio: radio_val,
+                mcc: 262,
+                net: 1,
+                area: area_val,
+                cell: cell_val,
+                unit: None,
+                lon: 13.405,
+                lat: 52.52,
+                cell_range: 1000,
+                samples: samples_val,
+                changeable: true,
+                created: chrono::Utc
+                    .with_ymd_and_hms(2024, 1, 15, 10, 30, 0)
+                    .unwrap()
+                    .naive_utc(),
+                updated: chrono::Utc
+                    .with_ymd_and_hms(2025, 12, 20, 14, 0, 0)
+                    .unwrap()
+                    .naive_utc(),
+                average_signal: None,
+            }
+        }
+
+        fn key(lac: u32, cid: u64) -> CellKey {
+            CellKey {
+                mcc: 262,
+                mnc: 1,
+                lac,
+                cid,
+            }
+        }
+
+        #[test]
+        fn test_lookup_is_aligned_with_request() {
+            let (_container, mut conn) = get_test_connection();
+            diesel::insert_into(cells::table)
+                .values(&vec![
+                    sample_cell(100, 1, Radio::Lte, 10),
+                    sample_cell(100, 2, Radio::Lte, 10),
+                ])
+                .execute(&mut conn)
+                .unwrap();
+
+            let result =
+                query_lookup(&[key(100, 2), key(999, 999), key(100, 1)], &mut conn).unwrap();
+
+            assert_eq!(result[0].as_ref().unwrap().cell, 2);
+            assert!(result[1].is_none());
+            assert_eq!(result[2].as_ref().unwrap().cell, 1);
+        }
+
+        #[test]
+        fn test_lookup_picks_best_row() {
+            let (_container, mut conn) = get_test_connection();
+            diesel::insert_into(cells::table)
+                .values(&vec![
+                    sample_cell(100, 1, Radio::Gsm, 5),
+                    sample_cell(100, 1, Radio::Lte, 50),
+                ])
+                .execute(&mut conn)
+                .unwrap();
+
+            let result = query_lookup(&[key(100, 1)], &mut conn).unwrap();
+
+            assert_eq!(result[0].as_ref().unwrap().radio, Radio::Lte);
+        }
+
+        #[test]
+        fn test_lookup_pads_excess_keys() {
+            let (_container, mut conn) = get_test_connection();
+            diesel::insert_into(cells::table)
+                .values(&sample_cell(100, 1, Radio::Lte, 10))
+                .execute(&mut conn)
+                .unwrap();
+
+            let keys = vec![key(100, 1); MAX_LOOKUP_KEYS + 2];
+            let result = query_lookup(&keys, &mut conn).unwrap();
+
+            assert_eq!(result.len(), MAX_LOOKUP_KEYS + 2);
+            assert!(result[MAX_LOOKUP_KEYS - 1].is_some());
+            assert!(result[MAX_LOOKUP_KEYS].is_none());
+        }
+
+        #[test]
+        fn test_fuzzy_lookup_returns_candidates_for_misses() {
+            let (_container, mut conn) = get_test_connection();
+            diesel::insert_into(cells::table)
+                .values(&vec![
+                    sample_cell(100, 1, Radio::Lte, 10),
+                    sample_cell(200, 2, Radio::Lte, 10),
+                ])
+                .execute(&mut conn)
+                .unwrap();
+
+            let request = LookupCellsRequest {
+                cells: vec![key(100, 1), key(100, 2)],
+            };
+            let response = lookup_cells(&request, MatchMode::Fuzzy, None, &mut conn).unwrap();
+            let candidates = response.candidates.unwrap();
+
+            assert!(response.cells[0].is_some());
+            assert!(candidates[0].is_empty());
+            assert!(response.cells[1].is_none());
+            assert_eq!(candidates[1].len(), 1);
+            assert_eq!(candidates[1][0].cell.area, 200);
+        }
+    }
+}
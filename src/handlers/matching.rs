@@ -0,0 +1,258 @@
+use std::cmp::Ordering;
+
+use diesel::prelude::*;
+use diesel::MysqlConnection;
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+use utoipa::ToSchema;
+
+use crate::models::*;
+
+/// Maximum number of candidates returned per looked up cell.
+pub const MAX_CANDIDATES: usize = 10;
+
+/// How strictly a lookup matches the requested identifiers.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchMode {
+    /// Only the exact identifiers match
+    #[default]
+    Exact,
+    /// Without an exact match, cells with the same `(mcc, net, cell)` in other
+    /// areas or radios are returned as ranked candidates
+    Fuzzy,
+}
+
+/// Why a candidate differs from the requested identifiers.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, ToSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum MatchReason {
+    /// Same area, but a different radio (e.g. a misreported RAT)
+    RadioMismatch,
+    /// Same radio, but a different area (e.g. a stale LAC/TAC after a handover)
+    AreaMismatch,
+    /// Both area and radio differ
+    AreaAndRadioMismatch,
+}
+
+/// A cell that may be the one a device reported.
+#[derive(Serialize, Debug, Clone, ToSchema)]
+pub struct CellCandidate {
+    pub reason: MatchReason,
+    pub cell: Cell,
+}
+
+/// The identifiers a device reported for a cell.
+#[derive(Debug, Clone)]
+pub struct ReportedCell {
+    pub mcc: u16,
+    pub net: u16,
+    pub area: u32,
+    pub cell: u64,
+    pub radio: Option<Radio>,
+}
+
+impl ReportedCell {
+    /// Classifies a cell with the same `(mcc, net, cell)`.
+    /// Returns `None` if the cell is an exact match.
+    fn reason_for(&self, candidate: &Cell) -> Option<MatchReason> {
+        let same_area = candidate.area == self.area;
+        let same_radio = self.radio.as_ref().is_none_or(|r| *r == candidate.radio);
+
+        match (same_area, same_radio) {
+            (true, true) => None,
+            (true, false) => Some(MatchReason::RadioMismatch),
+            (false, true) => Some(MatchReason::AreaMismatch),
+            (false, false) => Some(MatchReason::AreaAndRadioMismatch),
+        }
+    }
+
+    fn same_identity(&self, candidate: &Cell) -> bool {
+        candidate.mcc == self.mcc && candidate.net == self.net && candidate.cell == self.cell
+    }
+}
+
+/// Orders cells so the most trustworthy comes first: more samples, newer
+/// data, newer radio generation.
+pub fn best_match_order(a: &Cell, b: &Cell) -> Ordering {
+    b.samples
+        .cmp(&a.samples)
+        .then_with(|| b.updated.cmp(&a.updated))
+        .then_with(|| b.radio.generation().cmp(&a.radio.generation()))
+}
+
+/// Classifies and ranks the candidates for one reported cell.
+pub fn rank_candidates(reported: &ReportedCell, rows: &[Cell]) -> Vec<CellCandidate> {
+    let mut candidates: Vec<CellCandidate> = rows
+        .iter()
+        .filter(|row| reported.same_identity(row))
+        .filter_map(|row| {
+            reported.reason_for(row).map(|reason| CellCandidate {
+                reason,
+                cell: row.clone(),
+            })
+        })
+        .collect();
+
+    candidates.sort_by(|a, b| {
+        a.reason
+            .cmp(&b.reason)
+            .then_with(|| best_match_order(&a.cell, &b.cell))
+    });
+    candidates.truncate(MAX_CANDIDATES);
+    candidates
+}
+
+/// Finds fuzzy candidates for several reported cells in a single query, on
+/// the `(mcc, net, cell)` index.
+/// The result is aligned with `reported`.
+#[instrument(skip(connection))]
+pub fn query_candidates(
+    reported: &[ReportedCell],
+    connection: &mut MysqlConnection,
+) -> Result<Vec<Vec<CellCandidate>>, diesel::result::Error> {
+    use crate::schema::cells::dsl::*;
+
+    if reported.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let mut db_query = cells.into_boxed();
+    for key in reported {
+        db_query = db_query.or_filter(mcc.eq(key.mcc).and(net.eq(key.net)).and(cell.eq(key.cell)));
+    }
+    let rows: Vec<Cell> = db_query.load(connection)?;
+
+    Ok(reported
+        .iter()
+        .map(|key| rank_candidates(key, &rows))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn row(area: u32, radio: Radio, samples: u32) -> Cell {
+        Cell {
+            radio,
+            mcc: 262,
+            net: 1,
+            area,
+            cell: 67890,
+            unit: None,
+            lon: 13.405,
+            lat: 52.52,
+            cell_range: 1000,
+            samples,
+            changeable: true,
+            created: chrono::Utc
+                .with_ymd_and_hms(2024, 1, 15, 10, 30, 0)
+                .unwrap()
+                .naive_utc(),
+            updated: chrono::Utc
+                .with_ymd_and_hms(2025, 12, 20, 14, 0, 0)
+                .unwrap()
+                .naive_utc(),
+            average_signal: None,
+        }
+    }
+
+    fn reported(radio: Option<Radio>) -> ReportedCell {
+        ReportedCell {
+            mcc: 262,
+            net: 1,
+            area: 100,
+            cell: 67890,
+            radio,
+        }
+    }
+
+    #[test]
+    fn test_exact_matches_are_not_candidates() {
+        let candidates = rank_candidates(&reported(None), &[row(100, Radio::Lte, 10)]);
+
+        assert!(candidates.is_empty());
+    }
+
+    #[test]
+    fn test_reason_codes() {
+        let rows = vec![
+            row(100, Radio::Gsm, 10),
+            row(200, Radio::Lte, 10),
+            row(300, Radio::Umts, 10),
+        ];
+
+        let candidates = rank_candidates(&reported(Some(Radio::Lte)), &rows);
+        let reasons: Vec<MatchReason> = candidates.iter().map(|c| c.reason).collect();
+
+        assert_eq!(
+            reasons,
+            vec![
+                MatchReason::RadioMismatch,
+                MatchReason::AreaMismatch,
+                MatchReason::AreaAndRadioMismatch
+            ]
+        );
+    }
+
+    #[test]
+    fn test_without_reported_radio_only_area_mismatches() {
+        let rows = vec![row(200, Radio::Gsm, 10), row(300, Radio::Lte, 10)];
+
+        let candidates = rank_candidates(&reported(None), &rows);
+
+        assert!(candidates
+            .iter()
+            .all(|c| c.reason == MatchReason::AreaMismatch));
+    }
+
+    #[test]
+    fn test_same_reason_ranked_by_samples() {
+        let rows = vec![row(200, Radio::Lte, 5), row(300, Radio::Lte, 50)];
+
+        let candidates = rank_candidates(&reported(None), &rows);
+
+        assert_eq!(candidates[0].cell.area, 300);
+        assert_eq!(candidates[1].cell.area, 200);
+    }
+
+    #[test]
+    fn test_other_cells_are_ignored() {
+        let mut other = row(200, Radio::Lte, 10);
+        other.cell = 1;
+
+        assert!(rank_candidates(&reported(None), &[other]).is_empty());
+    }
+
+    #[test]
+    fn test_candidates_are_capped() {
+        let rows: Vec<Cell> = (0..20).map(|i| row(1000 + i, Radio::Lte, 10)).collect();
+
+        assert_eq!(
+            rank_candidates(&reported(None), &rows).len(),
+            MAX_CANDIDATES
+        );
+    }
+
+    #[test]
+    fn test_best_match_prefers_newer_generation_on_tie() {
+        let lte = row(100, Radio::Lte, 10);
+        let nr = row(100, Radio::Nr, 10);
+
+        assert_eq!(best_match_order(&nr, &lte), Ordering::Less);
+    }
+
+    #[test]
+    fn test_match_mode_from_query_string() {
+        #[derive(Deserialize)]
+        struct Query {
+            #[serde(rename = "match")]
+            match_mode: Option<MatchMode>,
+        }
+
+        let query: Query = serde_urlencoded::from_str("match=fuzzy").unwrap();
+        assert_eq!(query.match_mode, Some(MatchMode::Fuzzy));
+    }
+}
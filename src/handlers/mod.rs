@@ -1,2 +1,15 @@
 pub mod cell;
 pub mod cells;
+pub mod changes;
+pub mod coverage;
+pub mod density;
+pub mod geolocate;
+pub mod graphql;
+pub mod lookup;
+pub mod matching;
+pub mod modem;
+pub mod nearest;
+pub mod neighbours;
+pub mod route;
+pub mod tracks;
+pub mod venue;
@@ -0,0 +1,276 @@
+use diesel::MysqlConnection;
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+use utoipa::{IntoParams, ToSchema};
+
+use super::lookup::{query_lookup, CellKey};
+use super::neighbours::{query_neighbour_candidates, NeighbourCandidate, NeighbourKey};
+use crate::models::Cell;
+use crate::utils::config::Config;
+use crate::utils::db::try_establish_connection;
+use crate::utils::errors::{ApiError, ErrorBody, FieldError};
+use crate::utils::modem::{parse_modem_output, ModemCell, ModemCells};
+use crate::utils::negotiation::{encode, Format};
+use crate::utils::query::Validate;
+
+/// Request body of the modem output parser.
+#[derive(Deserialize, Serialize, Debug, Clone, ToSchema)]
+pub struct ParseModemRequest {
+    /// Raw response of `AT+QENG="servingcell"`, `AT+QENG="neighbourcell"`,
+    /// `AT+CPSI?` or `AT+UCGED?`, several responses may be concatenated
+    pub output: String,
+}
+
+impl ParseModemRequest {
+    fn parse(&self) -> Result<ModemCells, FieldError> {
+        parse_modem_output(&self.output)
+            .map_err(|error| FieldError::new("output", error.to_string()))
+    }
+}
+
+impl Validate for ParseModemRequest {
+    fn validate(&self) -> Result<(), Vec<FieldError>> {
+        self.parse().map(|_| ()).map_err(|error| vec![error])
+    }
+}
+
+/// Query parameters of the modem output parser.
+#[derive(Deserialize, Serialize, Debug, Clone, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ParseModemQuery {
+    /// Look up the parsed cells in the database
+    pub resolve: Option<bool>,
+}
+
+impl Validate for ParseModemQuery {}
+
+/// The database rows of the parsed cells, aligned 1:1 with them.
+/// Cells without a full identity resolve to `null`.
+#[derive(Serialize, Debug, ToSchema)]
+pub struct ResolvedModemCells {
+    pub serving: Vec<Option<Cell>>,
+    pub neighbours: Vec<Option<Cell>>,
+    /// Candidates for neighbours only reported by PCI or PSC, near the first
+    /// known serving cell, aligned 1:1 with `neighbours`
+    pub candidates: Vec<Vec<NeighbourCandidate>>,
+}
+
+/// Response of the modem output parser.
+#[derive(Serialize, Debug, ToSchema)]
+pub struct ParseModemResponse {
+    /// Usually one cell, two with LTE/NR dual connectivity
+    pub serving: Vec<ModemCell>,
+    pub neighbours: Vec<ModemCell>,
+    /// Only present with `resolve=true`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resolved: Option<ResolvedModemCells>,
+}
+
+/// The lookup key of a cell, if the modem reported its full identity.
+fn cell_key(cell: &ModemCell) -> Option<CellKey> {
+    Some(CellKey {
+        mcc: cell.mcc?,
+        mnc: cell.net?,
+        lac: cell.area?,
+        cid: cell.cell?,
+    })
+}
+
+/// The physical identity of a neighbour without a full identity.
+fn neighbour_key(cell: &ModemCell) -> Option<NeighbourKey> {
+    match (cell_key(cell), cell.unit) {
+        (None, Some(unit)) => Some(NeighbourKey {
+            radio: cell.radio.clone(),
+            unit,
+        }),
+        _ => None,
+    }
+}
+
+/// Resolves serving and neighbour cells with a single lookup, then the
+/// neighbours only known by PCI or PSC around the serving cell.
+#[instrument(skip_all)]
+pub fn query_resolve_modem_cells(
+    cells: &ModemCells,
+    connection: &mut MysqlConnection,
+) -> Result<ResolvedModemCells, diesel::result::Error> {
+    let all: Vec<&ModemCell> = cells.serving.iter().chain(&cells.neighbours).collect();
+    let keyed: Vec<(usize, CellKey)> = all
+        .iter()
+        .enumerate()
+        .filter_map(|(index, cell)| cell_key(cell).map(|key| (index, key)))
+        .collect();
+    let keys: Vec<CellKey> = keyed.iter().map(|(_, key)| key.clone()).collect();
+    let found = query_lookup(&keys, connection)?;
+
+    let mut resolved = vec![None; all.len()];
+    for ((index, _), row) in keyed.iter().zip(found) {
+        resolved[*index] = row;
+    }
+    let neighbours = resolved.split_off(cells.serving.len());
+
+    let mut candidates = vec![vec![]; cells.neighbours.len()];
+    if let Some(serving) = resolved.iter().flatten().next() {
+        let keyed: Vec<(usize, NeighbourKey)> = cells
+            .neighbours
+            .iter()
+            .enumerate()
+            .filter_map(|(index, cell)| neighbour_key(cell).map(|key| (index, key)))
+            .collect();
+        let keys: Vec<NeighbourKey> = keyed.iter().map(|(_, key)| key.clone()).collect();
+        let found = query_neighbour_candidates(serving, &keys, connection)?;
+        for ((index, _), found) in keyed.iter().zip(found) {
+            candidates[*index] = found;
+        }
+    }
+
+    Ok(ResolvedModemCells {
+        serving: resolved,
+        neighbours,
+        candidates,
+    })
+}
+
+/// Parse the cell information a modem prints in response to an AT command.
+#[utoipa::path(
+    post,
+    path = "/v1/modem/parse",
+    params(ParseModemQuery),
+    request_body = ParseModemRequest,
+    responses(
+        (status = 200, description = "The serving and neighbour cells. Protobuf responses are a `ParseModemResponse` message", content((ParseModemResponse = "application/json"), (ParseModemResponse = "application/msgpack"), (ParseModemResponse = "application/cbor"))),
+        (status = 400, description = "Invalid request body or unsupported modem output", body = ErrorBody),
+        (status = 406, description = "None of the accepted formats is supported", body = ErrorBody),
+        (status = 413, description = "The request body is too large", body = ErrorBody),
+        (status = 503, description = "The database is unavailable", body = ErrorBody),
+    ),
+    tag = "modem"
+)]
+#[instrument(skip_all, fields(resolve = query.resolve))]
+pub async fn handle_parse_modem(
+    query: ParseModemQuery,
+    request: ParseModemRequest,
+    format: Format,
+    config: Config,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let cells = request
+        .parse()
+        .map_err(|error| ApiError::BadRequest(vec![error]))?;
+
+    let resolved = if query.resolve.unwrap_or(false) {
+        let connection = &mut try_establish_connection(config.clone()).map_err(ApiError::from)?;
+        Some(query_resolve_modem_cells(&cells, connection).map_err(ApiError::from)?)
+    } else {
+        None
+    };
+
+    let response = ParseModemResponse {
+        serving: cells.serving,
+        neighbours: cells.neighbours,
+        resolved,
+    };
+    Ok(encode(format, &response))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::query::{parse_json, parse_query};
+
+    #[test]
+    fn test_unsupported_output_names_field() {
+        let json = br#"{"output": "+QENG: \"servingcell\",\"NOCONN\",\"CDMA\""}"#;
+
+        match parse_json::<ParseModemRequest>(json) {
+            Err(ApiError::BadRequest(fields)) => {
+                assert_eq!(fields[0].field, "output");
+                assert!(fields[0].message.starts_with("line 1: "));
+            }
+            other => panic!("expected bad request, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_resolve_flag() {
+        let query = parse_query::<ParseModemQuery>("resolve=true").unwrap();
+
+        assert_eq!(query.resolve, Some(true));
+    }
+
+    #[test]
+    fn test_partial_identity_has_no_key() {
+        let cells = parse_modem_output(
+            "+QENG: \"neighbourcell intra\",\"LTE\",1300,124,-12,-101,-71,8,30,5,12,2,62",
+        )
+        .unwrap();
+
+        assert_eq!(cell_key(&cells.neighbours[0]), None);
+    }
+
+    #[test]
+    fn test_unresolved_response_omits_resolved() {
+        let response = ParseModemResponse {
+            serving: vec![],
+            neighbours: vec![],
+            resolved: None,
+        };
+
+        let json = serde_json::to_string(&response).unwrap();
+
+        assert_eq!(json, r#"{"serving":[],"neighbours":[]}"#);
+    }
+
+    /// Integration tests for query_resolve_modem_cells using testcontainers.
+    #[cfg(feature = "integration_tests")]
+    mod modem_integration {
+        use super::*;
+        use crate::models::Radio;
+        use crate::schema::cells;
+        use crate::utils::test_db::get_test_connection;
+        use diesel::prelude::*;
+
+        fn sample_cell(area: u32, cell: u64) -> Cell {
+            let now = chrono::Utc::now().naive_utc();
+            Cell {
+                radio: Radio::Lte,
+                mcc: 262,
+                net: 1,
+                area,
+                cell,
+                unit: Some(123),
+                lon: 13.405,
+                lat: 52.52,
+                cell_range: 1000,
+                samples: 10,
+                changeable: true,
+                created: now,
+                updated: now,
+                average_signal: None,
+            }
+        }
+
+        #[test]
+        fn test_resolve_is_aligned_with_cells() {
+            let (_container, mut conn) = get_test_connection();
+            diesel::insert_into(cells::table)
+                .values(&vec![
+                    sample_cell(0x3A7D, 0x1A2D003),
+                    sample_cell(0x3A7D, 0x2BDB),
+                ])
+                .execute(&mut conn)
+                .unwrap();
+            let parsed = parse_modem_output(concat!(
+                "+QENG: \"servingcell\",\"NOCONN\",\"LTE\",\"FDD\",262,01,1A2D003,123,1300,3,5,5,3A7D,-97,-11,-67,16,36\n",
+                "+QENG: \"neighbourcell intra\",\"LTE\",1300,124,-12,-101,-71,8,30,5,12,2,62\n",
+                "+QENG: \"neighbourcell\",\"GSM\",262,01,3A7D,2BDB,20,52,-80,25,25,0,0",
+            ))
+            .unwrap();
+
+            let resolved = query_resolve_modem_cells(&parsed, &mut conn).unwrap();
+
+            assert_eq!(resolved.serving[0].as_ref().unwrap().cell, 0x1A2D003);
+            assert!(resolved.neighbours[0].is_none());
+            assert_eq!(resolved.neighbours[1].as_ref().unwrap().cell, 0x2BDB);
+        }
+    }
+}
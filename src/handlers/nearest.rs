@@ -0,0 +1,345 @@
+use std::collections::BTreeMap;
+
+use diesel::prelude::*;
+use diesel::MysqlConnection;
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+use utoipa::ToSchema;
+
+use super::neighbours::within_any;
+use crate::models::{Cell, Radio};
+use crate::utils::config::Config;
+use crate::utils::db::try_establish_connection;
+use crate::utils::errors::{ApiError, ErrorBody, FieldError};
+use crate::utils::geo::{haversine_distance, BoundingBox};
+use crate::utils::geohash::{tiles, Tile, TILES_PER_QUERY};
+use crate::utils::negotiation::{encode, Format};
+use crate::utils::query::{into_result, Validate};
+
+/// Maximum number of points per request.
+pub const MAX_NEAREST_POINTS: usize = 5000;
+/// Search radius if the request doesn't set one.
+pub const DEFAULT_NEAREST_DISTANCE_M: f64 = 5_000.0;
+/// Largest search radius, larger ones load too many cells per point.
+pub const MAX_NEAREST_DISTANCE_M: f64 = 20_000.0;
+
+/// A coordinate to find the nearest cells of.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, ToSchema)]
+pub struct GeoPoint {
+    pub lat: f64,
+    pub lon: f64,
+}
+
+/// Request body of the nearest cells of many points.
+#[derive(Deserialize, Serialize, Debug, Clone, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct NearestCellsRequest {
+    /// Up to 5000 points
+    pub points: Vec<GeoPoint>,
+    /// Mobile Country Code filter
+    pub mcc: Option<u16>,
+    /// Mobile Network Code filter
+    pub mnc: Option<u16>,
+    /// Radio type filter
+    pub radio: Option<Radio>,
+    /// Meters to search around each point (default: 5000, max: 20000)
+    pub max_distance: Option<f64>,
+}
+
+impl NearestCellsRequest {
+    pub fn max_distance(&self) -> f64 {
+        self.max_distance.unwrap_or(DEFAULT_NEAREST_DISTANCE_M)
+    }
+}
+
+impl Validate for NearestCellsRequest {
+    fn validate(&self) -> Result<(), Vec<FieldError>> {
+        let mut errors = vec![];
+        if self.points.is_empty() {
+            errors.push(FieldError::new("points", "must not be empty"));
+        }
+        if self.points.len() > MAX_NEAREST_POINTS {
+            errors.push(FieldError::new(
+                "points",
+                format!("must not have more than {} entries", MAX_NEAREST_POINTS),
+            ));
+        }
+        if let Some(index) = self.points.iter().position(|point| {
+            !(-90.0..=90.0).contains(&point.lat) || !(-180.0..=180.0).contains(&point.lon)
+        }) {
+            errors.push(FieldError::new(
+                &format!("points[{}]", index),
+                "must have a latitude between -90 and 90 and a longitude between -180 and 180",
+            ));
+        }
+        if let Some(max_distance) = self.max_distance {
+            if !(max_distance > 0.0 && max_distance <= MAX_NEAREST_DISTANCE_M) {
+                errors.push(FieldError::new(
+                    "maxDistance",
+                    format!(
+                        "must be greater than 0 and at most {}",
+                        MAX_NEAREST_DISTANCE_M
+                    ),
+                ));
+            }
+        }
+        into_result(errors)
+    }
+}
+
+/// The nearest cell of one radio.
+#[derive(Serialize, Debug, Clone, ToSchema)]
+pub struct NearestCell {
+    /// Distance from the point in meters
+    pub distance: f64,
+    pub cell: Cell,
+}
+
+/// The nearest cells of one point.
+#[derive(Serialize, Debug, Clone, Default, ToSchema)]
+pub struct PointNearestCells {
+    /// The nearest cell per radio, newest radio first. Empty if no cell is within `maxDistance`
+    pub cells: Vec<NearestCell>,
+}
+
+/// Response of the nearest cells, aligned 1:1 with the requested points.
+#[derive(Serialize, Debug, ToSchema)]
+pub struct NearestCellsResponse {
+    pub points: Vec<PointNearestCells>,
+}
+
+/// The nearest of `candidates` per radio within `max_distance` of a point.
+pub fn nearest_cells(
+    point: GeoPoint,
+    max_distance: f64,
+    candidates: &[&Cell],
+) -> PointNearestCells {
+    let mut nearest: BTreeMap<u8, (f64, &Cell)> = BTreeMap::new();
+    for &cell in candidates {
+        let distance = haversine_distance(
+            point.lat,
+            point.lon,
+            f64::from(cell.lat),
+            f64::from(cell.lon),
+        );
+        if distance > max_distance {
+            continue;
+        }
+        let radio = u8::MAX - cell.radio.generation();
+        if nearest
+            .get(&radio)
+            .is_none_or(|(closest, _)| distance < *closest)
+        {
+            nearest.insert(radio, (distance, cell));
+        }
+    }
+    PointNearestCells {
+        cells: nearest
+            .into_values()
+            .map(|(distance, cell)| NearestCell {
+                distance,
+                cell: cell.clone(),
+            })
+            .collect(),
+    }
+}
+
+/// Finds the nearest cells of all points. Nearby points share a box, and the
+/// boxes of several tiles share a query, so thousands of points need a
+/// handful of queries.
+pub fn query_nearest_cells(
+    request: &NearestCellsRequest,
+    connection: &mut MysqlConnection,
+) -> Result<NearestCellsResponse, diesel::result::Error> {
+    use crate::schema::cells::dsl;
+
+    let max_distance = request.max_distance();
+    let mut results = vec![PointNearestCells::default(); request.points.len()];
+    let points = request
+        .points
+        .iter()
+        .enumerate()
+        .map(|(index, point)| (index, point.lat, point.lon));
+    for batch in tiles(points, |_| max_distance).chunks(TILES_PER_QUERY) {
+        let bboxes: Vec<BoundingBox> = batch.iter().map(|tile| tile.bbox).collect();
+        let mut db_query = dsl::cells.into_boxed().filter(within_any(&bboxes));
+        if let Some(mcc) = request.mcc {
+            db_query = db_query.filter(dsl::mcc.eq(mcc));
+        }
+        if let Some(mnc) = request.mnc {
+            db_query = db_query.filter(dsl::net.eq(mnc));
+        }
+        if let Some(ref radio) = request.radio {
+            db_query = db_query.filter(dsl::radio.eq(radio));
+        }
+        let rows: Vec<Cell> = db_query.load(connection)?;
+
+        for Tile { bbox, ids } in batch {
+            let candidates: Vec<&Cell> = rows
+                .iter()
+                .filter(|cell| bbox.contains(f64::from(cell.lat), f64::from(cell.lon)))
+                .collect();
+            for &index in ids {
+                results[index] = nearest_cells(request.points[index], max_distance, &candidates);
+            }
+        }
+    }
+    Ok(NearestCellsResponse { points: results })
+}
+
+/// Find the nearest cell of each radio for many points at once.
+#[utoipa::path(
+    post,
+    path = "/v1/points/nearest-cells",
+    request_body = NearestCellsRequest,
+    responses(
+        (status = 200, description = "The nearest cells per point, in request order. Protobuf responses are a `NearestCellsResponse` message", content((NearestCellsResponse = "application/json"), (NearestCellsResponse = "application/msgpack"), (NearestCellsResponse = "application/cbor"))),
+        (status = 400, description = "Invalid request body", body = ErrorBody),
+        (status = 406, description = "None of the accepted formats is supported", body = ErrorBody),
+        (status = 413, description = "The request body is too large", body = ErrorBody),
+        (status = 503, description = "The database is unavailable", body = ErrorBody),
+    ),
+    tag = "cells"
+)]
+#[instrument(skip_all, fields(points = request.points.len()))]
+pub async fn handle_nearest_cells(
+    request: NearestCellsRequest,
+    format: Format,
+    config: Config,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let connection = &mut try_establish_connection(config.clone()).map_err(ApiError::from)?;
+
+    let response = query_nearest_cells(&request, connection).map_err(ApiError::from)?;
+    Ok(encode(format, &response))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::query::parse_json;
+
+    fn cell(id: u64, radio: Radio, lat: f32, lon: f32) -> Cell {
+        Cell {
+            radio,
+            mcc: 262,
+            net: 1,
+            area: 1,
+            cell: id,
+            unit: None,
+            lon,
+            lat,
+            cell_range: 1000,
+            samples: 10,
+            changeable: true,
+            created: chrono::Utc::now().naive_utc(),
+            updated: chrono::Utc::now().naive_utc(),
+            average_signal: None,
+        }
+    }
+
+    fn field_names(body: &str) -> Vec<String> {
+        match parse_json::<NearestCellsRequest>(body.as_bytes()) {
+            Ok(_) => vec![],
+            Err(ApiError::BadRequest(fields)) => fields.into_iter().map(|f| f.field).collect(),
+            Err(error) => panic!("unexpected error {:?}", error),
+        }
+    }
+
+    #[test]
+    fn test_request_validation() {
+        assert!(field_names(r#"{"points": [{"lat": 52.5, "lon": 13.4}]}"#).is_empty());
+        assert_eq!(field_names(r#"{"points": []}"#), vec!["points"]);
+        assert_eq!(
+            field_names(r#"{"points": [{"lat": 52.5, "lon": 13.4}, {"lat": 95, "lon": 0}]}"#),
+            vec!["points[1]"]
+        );
+        assert_eq!(
+            field_names(r#"{"points": [{"lat": 52.5, "lon": 13.4}], "maxDistance": 30000}"#),
+            vec!["maxDistance"]
+        );
+    }
+
+    #[test]
+    fn test_nearest_cell_per_radio() {
+        let cells = [
+            cell(1, Radio::Lte, 52.501, 13.4),
+            cell(2, Radio::Lte, 52.51, 13.4),
+            cell(3, Radio::Gsm, 52.52, 13.4),
+            cell(4, Radio::Nr, 52.6, 13.4),
+        ];
+        let candidates: Vec<&Cell> = cells.iter().collect();
+
+        let nearest = nearest_cells(
+            GeoPoint {
+                lat: 52.5,
+                lon: 13.4,
+            },
+            5_000.0,
+            &candidates,
+        );
+
+        let found: Vec<(u64, Radio)> = nearest
+            .cells
+            .iter()
+            .map(|n| (n.cell.cell, n.cell.radio.clone()))
+            .collect();
+        // The NR cell is 11 km away
+        assert_eq!(found, vec![(1, Radio::Lte), (3, Radio::Gsm)]);
+        assert!((nearest.cells[0].distance - 111.2).abs() < 0.5);
+    }
+
+    /// Integration tests for the batched query using testcontainers.
+    #[cfg(feature = "integration_tests")]
+    mod nearest_integration {
+        use super::*;
+        use crate::schema::cells;
+        use crate::utils::test_db::get_test_connection;
+
+        #[test]
+        fn test_results_are_aligned_with_the_points() {
+            let (_container, mut conn) = get_test_connection();
+            let mut other_operator = cell(3, Radio::Lte, 48.1, 11.6);
+            other_operator.net = 2;
+            diesel::insert_into(cells::table)
+                .values(&vec![
+                    cell(1, Radio::Lte, 52.501, 13.4),
+                    cell(2, Radio::Lte, 48.101, 11.6),
+                    other_operator,
+                ])
+                .execute(&mut conn)
+                .unwrap();
+            // More tiles than fit into one query
+            let mut points: Vec<GeoPoint> = (0..TILES_PER_QUERY)
+                .map(|i| GeoPoint {
+                    lat: 0.0,
+                    lon: i as f64,
+                })
+                .collect();
+            points.insert(
+                0,
+                GeoPoint {
+                    lat: 48.1,
+                    lon: 11.6,
+                },
+            );
+            points.push(GeoPoint {
+                lat: 52.5,
+                lon: 13.4,
+            });
+            let request = NearestCellsRequest {
+                points,
+                mcc: None,
+                mnc: Some(1),
+                radio: None,
+                max_distance: None,
+            };
+
+            let response = query_nearest_cells(&request, &mut conn).unwrap();
+
+            assert_eq!(response.points.len(), TILES_PER_QUERY + 2);
+            assert_eq!(response.points[0].cells[0].cell.cell, 2);
+            assert!(response.points[1].cells.is_empty());
+            assert_eq!(response.points.last().unwrap().cells[0].cell.cell, 1);
+        }
+    }
+}
@@ -0,0 +1,482 @@
+use std::cmp::Ordering;
+
+use diesel::mysql::Mysql;
+use diesel::prelude::*;
+use diesel::sql_types::Bool;
+use diesel::MysqlConnection;
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+use utoipa::ToSchema;
+
+use super::lookup::{query_lookup, CellKey, MAX_LOOKUP_KEYS};
+use crate::models::{Cell, Radio};
+use crate::schema::cells;
+use crate::utils::config::Config;
+use crate::utils::db::try_establish_connection;
+use crate::utils::errors::{ApiError, ErrorBody, FieldError};
+use crate::utils::geo::{haversine_distance, BoundingBox};
+use crate::utils::negotiation::{encode, Format};
+use crate::utils::query::{into_result, Validate};
+use crate::utils::ranging::cell_range;
+
+/// Neighbours further than this from the serving cell are not considered.
+pub const MAX_NEIGHBOUR_DISTANCE_M: f64 = 20_000.0;
+/// Maximum number of candidates returned per neighbour.
+pub const MAX_NEIGHBOUR_CANDIDATES: usize = 5;
+/// Weight of the possibility that the neighbour is missing from the database,
+/// relative to a candidate right at the serving cell.
+const UNKNOWN_NEIGHBOUR_WEIGHT: f64 = 0.2;
+/// Candidates below this confidence are dropped.
+const MIN_NEIGHBOUR_CONFIDENCE: f64 = 0.01;
+
+/// A neighbour cell as modems report it, by its physical identity only.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct NeighbourKey {
+    /// `UMTS`, `LTE` or `NR`
+    pub radio: Radio,
+    /// Primary Scrambling Code (UMTS) or Physical Cell ID (LTE/NR)
+    pub unit: u16,
+}
+
+impl NeighbourKey {
+    /// The largest PSC or PCI of the radio, `None` for radios without one.
+    fn max_unit(&self) -> Option<u16> {
+        match self.radio {
+            Radio::Umts => Some(511),
+            Radio::Lte => Some(503),
+            Radio::Nr => Some(1007),
+            Radio::Gsm | Radio::Cdma => None,
+        }
+    }
+}
+
+/// A cell that may be the reported neighbour.
+#[derive(Serialize, Debug, Clone, ToSchema)]
+pub struct NeighbourCandidate {
+    /// Probability in `[0, 1]` that this is the reported neighbour
+    pub confidence: f64,
+    /// Distance from the serving cell in meters
+    pub distance: f64,
+    pub cell: Cell,
+}
+
+/// Request body of the neighbour resolution.
+#[derive(Deserialize, Serialize, Debug, Clone, ToSchema)]
+pub struct ResolveNeighboursRequest {
+    pub serving: CellKey,
+    pub neighbours: Vec<NeighbourKey>,
+}
+
+impl Validate for ResolveNeighboursRequest {
+    fn validate(&self) -> Result<(), Vec<FieldError>> {
+        let mut errors = vec![];
+        if self.neighbours.len() > MAX_LOOKUP_KEYS {
+            errors.push(FieldError::new(
+                "neighbours",
+                format!("must not have more than {} entries", MAX_LOOKUP_KEYS),
+            ));
+        }
+        for (index, neighbour) in self.neighbours.iter().enumerate() {
+            match neighbour.max_unit() {
+                None => errors.push(FieldError::new(
+                    &format!("neighbours[{}].radio", index),
+                    "must be UMTS, LTE or NR",
+                )),
+                Some(max) if neighbour.unit > max => errors.push(FieldError::new(
+                    &format!("neighbours[{}].unit", index),
+                    format!("must be at most {}", max),
+                )),
+                Some(_) => {}
+            }
+        }
+        into_result(errors)
+    }
+}
+
+/// Response of the neighbour resolution.
+#[derive(Serialize, Debug, ToSchema)]
+pub struct ResolveNeighboursResponse {
+    /// The serving cell, `null` if it is unknown
+    pub serving: Option<Cell>,
+    /// Candidates per neighbour, aligned 1:1 with the request and most likely first
+    pub candidates: Vec<Vec<NeighbourCandidate>>,
+}
+
+/// Cells within a bounding box. The latitude range is always a plain
+/// `BETWEEN`, so the `(lat, lon)` index narrows the scan down to it.
+pub fn within(
+    bbox: &BoundingBox,
+) -> Box<dyn BoxableExpression<cells::table, Mysql, SqlType = Bool>> {
+    use crate::schema::cells::dsl::*;
+
+    let (min_lat, max_lat) = (bbox.min_lat as f32, bbox.max_lat as f32);
+    let (min_lon, max_lon) = (bbox.min_lon as f32, bbox.max_lon as f32);
+    if bbox.crosses_antimeridian() {
+        Box::new(
+            lat.between(min_lat, max_lat)
+                .and(lon.ge(min_lon).or(lon.le(max_lon))),
+        )
+    } else if bbox.min_lon <= -180.0 && bbox.max_lon >= 180.0 {
+        Box::new(lat.between(min_lat, max_lat))
+    } else {
+        Box::new(
+            lat.between(min_lat, max_lat)
+                .and(lon.between(min_lon, max_lon)),
+        )
+    }
+}
+
+/// Cells within any of several bounding boxes, one query for all of them.
+pub fn within_any(
+    bboxes: &[BoundingBox],
+) -> Box<dyn BoxableExpression<cells::table, Mysql, SqlType = Bool>> {
+    bboxes
+        .iter()
+        .map(within)
+        .reduce(|any, bbox| Box::new(any.or(bbox)))
+        .unwrap_or_else(|| Box::new(diesel::dsl::sql::<Bool>("FALSE")))
+}
+
+/// Relative likelihood that a cell at `distance` from the serving cell is its
+/// neighbour. Neighbours overlap the serving cell's coverage, so they are
+/// expected within both ranges.
+fn neighbour_weight(serving: &Cell, candidate: &Cell, distance: f64) -> f64 {
+    let expected = cell_range(serving) + cell_range(candidate);
+    (-0.5 * (distance / expected).powi(2)).exp()
+}
+
+/// Ranks the cells sharing a neighbour's PCI or PSC. Operators reuse them, so
+/// a candidate's confidence is its weight relative to all candidates and to
+/// the neighbour not being in the database at all.
+pub fn rank_neighbours(
+    serving: &Cell,
+    neighbour: &NeighbourKey,
+    rows: &[Cell],
+) -> Vec<NeighbourCandidate> {
+    let weighted: Vec<(f64, f64, &Cell)> = rows
+        .iter()
+        .filter(|row| {
+            row.radio == neighbour.radio
+                && row.unit == Some(neighbour.unit)
+                && row.mcc == serving.mcc
+                && row.net == serving.net
+                && !(row.area == serving.area && row.cell == serving.cell)
+        })
+        .filter_map(|row| {
+            let distance = haversine_distance(
+                f64::from(serving.lat),
+                f64::from(serving.lon),
+                f64::from(row.lat),
+                f64::from(row.lon),
+            );
+            (distance <= MAX_NEIGHBOUR_DISTANCE_M)
+                .then(|| (neighbour_weight(serving, row, distance), distance, row))
+        })
+        .collect();
+    let total = weighted.iter().map(|(w, _, _)| w).sum::<f64>() + UNKNOWN_NEIGHBOUR_WEIGHT;
+
+    let mut candidates: Vec<NeighbourCandidate> = weighted
+        .into_iter()
+        .map(|(weight, distance, row)| NeighbourCandidate {
+            confidence: weight / total,
+            distance,
+            cell: row.clone(),
+        })
+        .filter(|candidate| candidate.confidence >= MIN_NEIGHBOUR_CONFIDENCE)
+        .collect();
+    candidates.sort_by(|a, b| {
+        b.confidence
+            .partial_cmp(&a.confidence)
+            .unwrap_or(Ordering::Equal)
+    });
+    candidates.truncate(MAX_NEIGHBOUR_CANDIDATES);
+    candidates
+}
+
+/// Finds the candidates for all neighbours of a serving cell in a single query.
+/// The result is aligned with `neighbours`.
+#[instrument(skip_all, fields(neighbours = neighbours.len()))]
+pub fn query_neighbour_candidates(
+    serving: &Cell,
+    neighbours: &[NeighbourKey],
+    connection: &mut MysqlConnection,
+) -> Result<Vec<Vec<NeighbourCandidate>>, diesel::result::Error> {
+    use crate::schema::cells::dsl::*;
+
+    if neighbours.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let units: Vec<u16> = neighbours.iter().map(|n| n.unit).collect();
+    let bbox = BoundingBox::around(
+        f64::from(serving.lat),
+        f64::from(serving.lon),
+        MAX_NEIGHBOUR_DISTANCE_M,
+    );
+    let rows: Vec<Cell> = cells
+        .filter(mcc.eq(serving.mcc))
+        .filter(net.eq(serving.net))
+        .filter(unit.eq_any(units))
+        .filter(within(&bbox))
+        .load(connection)?;
+
+    Ok(neighbours
+        .iter()
+        .map(|neighbour| rank_neighbours(serving, neighbour, &rows))
+        .collect())
+}
+
+/// Resolves the serving cell, then the candidates for its neighbours.
+pub fn query_resolve_neighbours(
+    request: &ResolveNeighboursRequest,
+    connection: &mut MysqlConnection,
+) -> Result<ResolveNeighboursResponse, diesel::result::Error> {
+    let serving = query_lookup(std::slice::from_ref(&request.serving), connection)?
+        .pop()
+        .flatten();
+    let candidates = match &serving {
+        Some(serving) => query_neighbour_candidates(serving, &request.neighbours, connection)?,
+        None => vec![vec![]; request.neighbours.len()],
+    };
+    Ok(ResolveNeighboursResponse {
+        serving,
+        candidates,
+    })
+}
+
+/// Find the cells a serving cell's neighbours most likely are, by their PCI or PSC.
+#[utoipa::path(
+    post,
+    path = "/v1/cells/neighbours",
+    request_body = ResolveNeighboursRequest,
+    responses(
+        (status = 200, description = "Candidates per neighbour, in request order. Protobuf responses are a `ResolveNeighboursResponse` message", content((ResolveNeighboursResponse = "application/json"), (ResolveNeighboursResponse = "application/msgpack"), (ResolveNeighboursResponse = "application/cbor"))),
+        (status = 400, description = "Invalid request body", body = ErrorBody),
+        (status = 406, description = "None of the accepted formats is supported", body = ErrorBody),
+        (status = 413, description = "The request body is too large", body = ErrorBody),
+        (status = 503, description = "The database is unavailable", body = ErrorBody),
+    ),
+    tag = "cells"
+)]
+#[instrument(skip_all, fields(neighbours = request.neighbours.len()))]
+pub async fn handle_resolve_neighbours(
+    request: ResolveNeighboursRequest,
+    format: Format,
+    config: Config,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let connection = &mut try_establish_connection(config.clone()).map_err(ApiError::from)?;
+
+    let response = query_resolve_neighbours(&request, connection).map_err(ApiError::from)?;
+    Ok(encode(format, &response))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::geo::LocalFrame;
+    use crate::utils::query::parse_json;
+
+    fn cell_at(east: f64, north: f64, cell_id: u64, unit_value: u16) -> Cell {
+        let (lat, lon) = LocalFrame::new(52.52, 13.405).to_geo(east, north);
+        let now = chrono::Utc::now().naive_utc();
+        Cell {
+            radio: Radio::Lte,
+            mcc: 262,
+            net: 1,
+            area: 100,
+            cell: cell_id,
+            unit: Some(unit_value),
+            lon: lon as f32,
+            lat: lat as f32,
+            cell_range: 1000,
+            samples: 10,
+            changeable: true,
+            created: now,
+            updated: now,
+            average_signal: None,
+        }
+    }
+
+    fn lte(unit: u16) -> NeighbourKey {
+        NeighbourKey {
+            radio: Radio::Lte,
+            unit,
+        }
+    }
+
+    mod rank_neighbours {
+        use super::*;
+
+        #[test]
+        fn test_single_nearby_candidate() {
+            let serving = cell_at(0.0, 0.0, 1, 10);
+            let rows = vec![cell_at(500.0, 0.0, 2, 124)];
+
+            let candidates = rank_neighbours(&serving, &lte(124), &rows);
+
+            assert_eq!(candidates.len(), 1);
+            assert_eq!(candidates[0].cell.cell, 2);
+            assert!((candidates[0].distance - 500.0).abs() < 1.0);
+            // exp(-0.5 · (500 / 2000)²) / (that + 0.2)
+            assert!(
+                (candidates[0].confidence - 0.829).abs() < 0.001,
+                "{}",
+                candidates[0].confidence
+            );
+        }
+
+        #[test]
+        fn test_reused_pci_prefers_the_closer_cell() {
+            let serving = cell_at(0.0, 0.0, 1, 10);
+            let rows = vec![cell_at(5000.0, 0.0, 2, 124), cell_at(0.0, 800.0, 3, 124)];
+
+            let candidates = rank_neighbours(&serving, &lte(124), &rows);
+
+            assert_eq!(candidates[0].cell.cell, 3);
+            assert_eq!(candidates[1].cell.cell, 2);
+            assert!(candidates[0].confidence > 10.0 * candidates[1].confidence);
+            let total: f64 = candidates.iter().map(|c| c.confidence).sum();
+            assert!(total < 1.0);
+        }
+
+        #[test]
+        fn test_larger_cells_reach_further() {
+            let serving = cell_at(0.0, 0.0, 1, 10);
+            let mut rural = cell_at(5000.0, 0.0, 2, 124);
+            rural.cell_range = 8000;
+
+            let small = rank_neighbours(&serving, &lte(124), &[cell_at(5000.0, 0.0, 2, 124)]);
+            let large = rank_neighbours(&serving, &lte(124), &[rural]);
+
+            assert!(large[0].confidence > small[0].confidence);
+        }
+
+        #[test]
+        fn test_other_radios_operators_and_the_serving_cell_are_ignored() {
+            let serving = cell_at(0.0, 0.0, 1, 124);
+            let mut umts = cell_at(100.0, 0.0, 2, 124);
+            umts.radio = Radio::Umts;
+            let mut other_operator = cell_at(100.0, 0.0, 3, 124);
+            other_operator.net = 2;
+
+            let rows = vec![
+                serving.clone(),
+                umts,
+                other_operator,
+                cell_at(100.0, 0.0, 4, 125),
+            ];
+
+            assert!(rank_neighbours(&serving, &lte(124), &rows).is_empty());
+        }
+
+        #[test]
+        fn test_distant_cells_are_ignored() {
+            let serving = cell_at(0.0, 0.0, 1, 10);
+            let rows = vec![cell_at(MAX_NEIGHBOUR_DISTANCE_M + 100.0, 0.0, 2, 124)];
+
+            assert!(rank_neighbours(&serving, &lte(124), &rows).is_empty());
+        }
+
+        #[test]
+        fn test_candidates_are_capped() {
+            let serving = cell_at(0.0, 0.0, 1, 10);
+            let rows: Vec<Cell> = (0..10)
+                .map(|i| cell_at(100.0 * i as f64, 0.0, 100 + i, 124))
+                .collect();
+
+            let candidates = rank_neighbours(&serving, &lte(124), &rows);
+
+            assert_eq!(candidates.len(), MAX_NEIGHBOUR_CANDIDATES);
+            assert_eq!(candidates[0].cell.cell, 100);
+        }
+    }
+
+    mod validation {
+        use super::*;
+
+        #[test]
+        fn test_gsm_neighbours_are_rejected() {
+            let json = br#"{"serving": {"mcc": 262, "mnc": 1, "lac": 1, "cid": 1}, "neighbours": [{"radio": "LTE", "unit": 1}, {"radio": "GSM", "unit": 1}]}"#;
+
+            match parse_json::<ResolveNeighboursRequest>(json) {
+                Err(ApiError::BadRequest(fields)) => {
+                    assert_eq!(fields[0].field, "neighbours[1].radio")
+                }
+                other => panic!("expected bad request, got {:?}", other),
+            }
+        }
+
+        #[test]
+        fn test_pci_range_is_checked() {
+            let request = ResolveNeighboursRequest {
+                serving: CellKey {
+                    mcc: 262,
+                    mnc: 1,
+                    lac: 1,
+                    cid: 1,
+                },
+                neighbours: vec![lte(504)],
+            };
+
+            let errors = request.validate().unwrap_err();
+            assert_eq!(errors[0].field, "neighbours[0].unit");
+        }
+    }
+
+    /// Integration tests for query_resolve_neighbours using testcontainers.
+    #[cfg(feature = "integration_tests")]
+    mod neighbours_integration {
+        use super::*;
+        use crate::utils::test_db::get_test_connection;
+
+        #[test]
+        fn test_resolves_neighbours_near_the_serving_cell() {
+            let (_container, mut conn) = get_test_connection();
+            diesel::insert_into(cells::table)
+                .values(&vec![
+                    cell_at(0.0, 0.0, 1, 10),
+                    cell_at(700.0, 0.0, 2, 124),
+                    cell_at(30_000.0, 0.0, 3, 124),
+                    cell_at(0.0, -900.0, 4, 301),
+                ])
+                .execute(&mut conn)
+                .unwrap();
+            let request = ResolveNeighboursRequest {
+                serving: CellKey {
+                    mcc: 262,
+                    mnc: 1,
+                    lac: 100,
+                    cid: 1,
+                },
+                neighbours: vec![lte(124), lte(301), lte(7)],
+            };
+
+            let response = query_resolve_neighbours(&request, &mut conn).unwrap();
+
+            assert_eq!(response.serving.unwrap().cell, 1);
+            assert_eq!(response.candidates[0].len(), 1);
+            assert_eq!(response.candidates[0][0].cell.cell, 2);
+            assert_eq!(response.candidates[1][0].cell.cell, 4);
+            assert!(response.candidates[2].is_empty());
+        }
+
+        #[test]
+        fn test_unknown_serving_cell_has_no_candidates() {
+            let (_container, mut conn) = get_test_connection();
+            let request = ResolveNeighboursRequest {
+                serving: CellKey {
+                    mcc: 262,
+                    mnc: 1,
+                    lac: 100,
+                    cid: 1,
+                },
+                neighbours: vec![lte(124)],
+            };
+
+            let response = query_resolve_neighbours(&request, &mut conn).unwrap();
+
+            assert!(response.serving.is_none());
+            assert!(response.candidates[0].is_empty());
+        }
+    }
+}
@@ -0,0 +1,650 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ops::Range;
+
+use diesel::prelude::*;
+use diesel::MysqlConnection;
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+use utoipa::{IntoParams, ToSchema};
+
+use super::cells::CellsFormat;
+use super::neighbours::within;
+use crate::models::{Cell, Radio};
+use crate::utils::config::Config;
+use crate::utils::coverage::{coverage_radius, MAX_COVERAGE_RADIUS_M};
+use crate::utils::db::try_establish_connection;
+use crate::utils::errors::{ApiError, ErrorBody, FieldError};
+use crate::utils::geojson::{geojson_reply, Feature, FeatureCollection, LineString};
+use crate::utils::negotiation::{encode, Format};
+use crate::utils::query::{into_result, Validate};
+use crate::utils::route::{gaps, Route};
+
+/// Corridor width if the request doesn't set one.
+pub const DEFAULT_CORRIDOR_WIDTH_M: f64 = 1_000.0;
+/// Widest corridor, wider ones are an area query rather than a route.
+pub const MAX_CORRIDOR_WIDTH_M: f64 = 20_000.0;
+/// Length of route queried at once. A 200 km course becomes 40 small box
+/// queries instead of one box spanning the whole course.
+pub const CORRIDOR_CHUNK_M: f64 = 5_000.0;
+/// Length of route the coverage report queries at once. Its boxes reach
+/// `MAX_COVERAGE_RADIUS_M` beyond the chunk, so shorter chunks would load
+/// the same cells over and over.
+pub const COVERAGE_CHUNK_M: f64 = 2.0 * MAX_COVERAGE_RADIUS_M;
+
+/// Query parameters of the cells along a route.
+#[derive(Deserialize, Serialize, Debug, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct RouteCellsQuery {
+    /// Full width of the corridor around the route in meters (default: 1000, max: 20000)
+    pub width: Option<f64>,
+}
+
+impl RouteCellsQuery {
+    pub fn width(&self) -> f64 {
+        self.width.unwrap_or(DEFAULT_CORRIDOR_WIDTH_M)
+    }
+}
+
+impl Validate for RouteCellsQuery {
+    fn validate(&self) -> Result<(), Vec<FieldError>> {
+        let mut errors = vec![];
+        if let Some(width) = self.width {
+            if !(width > 0.0 && width <= MAX_CORRIDOR_WIDTH_M) {
+                errors.push(FieldError::new(
+                    "width",
+                    format!(
+                        "must be greater than 0 and at most {}",
+                        MAX_CORRIDOR_WIDTH_M
+                    ),
+                ));
+            }
+        }
+        into_result(errors)
+    }
+}
+
+/// A cell within the corridor.
+#[derive(Serialize, Debug, Clone, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RouteCell {
+    /// Route kilometre of the point of the route closest to the cell
+    pub route_km: f64,
+    /// Distance from the route in meters
+    pub distance: f64,
+    pub cell: Cell,
+}
+
+/// Response of the cells along a route.
+#[derive(Serialize, Debug, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RouteCellsResponse {
+    /// Length of the route in kilometres
+    pub length_km: f64,
+    /// Ordered by route kilometre
+    pub cells: Vec<RouteCell>,
+}
+
+/// Query parameters of the coverage report of a route.
+#[derive(Deserialize, Serialize, Debug, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct CoverageReportQuery {
+    /// Mobile Country Code filter
+    pub mcc: Option<u16>,
+    /// Mobile Network Code filter
+    pub mnc: Option<u16>,
+    /// Radio type filter
+    pub radio: Option<Radio>,
+    /// Gaps shorter than this in meters are not listed, they still count
+    /// towards the summary (default: 0)
+    pub min_gap: Option<f64>,
+    /// `geojson` returns the gaps as a GeoJSON `FeatureCollection` of lines
+    pub format: Option<CellsFormat>,
+}
+
+impl Validate for CoverageReportQuery {
+    fn validate(&self) -> Result<(), Vec<FieldError>> {
+        let mut errors = vec![];
+        if self
+            .min_gap
+            .is_some_and(|min_gap| !(0.0..).contains(&min_gap))
+        {
+            errors.push(FieldError::new("min_gap", "must not be negative"));
+        }
+        into_result(errors)
+    }
+}
+
+/// A stretch of the route without expected coverage.
+#[derive(Serialize, Debug, Clone, PartialEq, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CoverageGap {
+    pub start_km: f64,
+    pub end_km: f64,
+    pub length_km: f64,
+}
+
+/// How well one operator and radio covers the route.
+#[derive(Serialize, Debug, Clone, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct OperatorCoverage {
+    pub mcc: u16,
+    pub net: u16,
+    pub radio: Radio,
+    /// Number of cells covering part of the route
+    pub cells: u32,
+    /// Share of the route within the range of a cell, 0 to 100
+    pub covered_percent: f64,
+    pub longest_gap_km: f64,
+    /// Gaps of at least `min_gap`, in route order
+    pub gaps: Vec<CoverageGap>,
+}
+
+/// Response of the coverage report of a route.
+#[derive(Serialize, Debug, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CoverageReportResponse {
+    /// Length of the route in kilometres
+    pub length_km: f64,
+    /// By operator, newest radio first. Operators without any cell in range
+    /// of the route are missing
+    pub operators: Vec<OperatorCoverage>,
+}
+
+/// Properties of a gap in the GeoJSON coverage report.
+#[derive(Serialize, Debug, Clone, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CoverageGapProperties {
+    pub mcc: u16,
+    pub net: u16,
+    pub radio: Radio,
+    pub start_km: f64,
+    pub end_km: f64,
+    pub length_km: f64,
+    /// Share of the whole route the operator and radio cover, 0 to 100
+    pub covered_percent: f64,
+}
+
+/// The stretches of the route one operator and radio covers.
+#[derive(Debug)]
+struct CoveredStretches {
+    radio: Radio,
+    cells: HashSet<(u32, u64)>,
+    stretches: Vec<(f64, f64)>,
+}
+
+type CoverageGroups = BTreeMap<(u16, u16, u8), CoveredStretches>;
+
+/// Queries the corridor chunk by chunk. A cell near a bend can show up in
+/// several chunks, it is kept at its closest point.
+pub fn query_route_cells(
+    route: &Route,
+    width: f64,
+    connection: &mut MysqlConnection,
+) -> Result<RouteCellsResponse, diesel::result::Error> {
+    use crate::schema::cells::dsl;
+
+    let half_width = width / 2.0;
+    let mut found: HashMap<(u8, u16, u16, u32, u64), RouteCell> = HashMap::new();
+    for segments in route.chunks(CORRIDOR_CHUNK_M) {
+        let rows: Vec<Cell> = dsl::cells
+            .filter(within(&route.bbox(&segments, half_width)))
+            .load(connection)?;
+        for cell in rows {
+            let position = route.locate(f64::from(cell.lat), f64::from(cell.lon), segments.clone());
+            if position.offset > half_width {
+                continue;
+            }
+            let key = (
+                cell.radio.generation(),
+                cell.mcc,
+                cell.net,
+                cell.area,
+                cell.cell,
+            );
+            if found
+                .get(&key)
+                .is_some_and(|known| known.distance <= position.offset)
+            {
+                continue;
+            }
+            found.insert(
+                key,
+                RouteCell {
+                    route_km: position.along / 1000.0,
+                    distance: position.offset,
+                    cell,
+                },
+            );
+        }
+    }
+
+    let mut cells: Vec<RouteCell> = found.into_values().collect();
+    cells.sort_by(|a, b| {
+        a.route_km
+            .total_cmp(&b.route_km)
+            .then(a.distance.total_cmp(&b.distance))
+    });
+    Ok(RouteCellsResponse {
+        length_km: route.length() / 1000.0,
+        cells,
+    })
+}
+
+/// Adds the stretches of `segments` that `cells` cover to their operator and radio.
+fn collect_stretches(
+    route: &Route,
+    segments: Range<usize>,
+    cells: Vec<Cell>,
+    groups: &mut CoverageGroups,
+) {
+    for cell in cells {
+        let stretches = route.within_radius(
+            f64::from(cell.lat),
+            f64::from(cell.lon),
+            coverage_radius(&cell),
+            segments.clone(),
+        );
+        if stretches.is_empty() {
+            continue;
+        }
+        let group = groups
+            .entry((cell.mcc, cell.net, u8::MAX - cell.radio.generation()))
+            .or_insert_with(|| CoveredStretches {
+                radio: cell.radio.clone(),
+                cells: HashSet::new(),
+                stretches: vec![],
+            });
+        group.cells.insert((cell.area, cell.cell));
+        group.stretches.extend(stretches);
+    }
+}
+
+/// Summarizes the stretches per operator and radio into their gaps.
+fn coverage_report(route: &Route, groups: CoverageGroups, min_gap: f64) -> CoverageReportResponse {
+    let length = route.length();
+    let operators = groups
+        .into_iter()
+        .map(|((mcc, net, _), group)| {
+            let all_gaps = gaps(group.stretches, length);
+            let uncovered: f64 = all_gaps.iter().map(|(from, to)| to - from).sum();
+            let longest = all_gaps
+                .iter()
+                .map(|(from, to)| to - from)
+                .fold(0.0, f64::max);
+            OperatorCoverage {
+                mcc,
+                net,
+                radio: group.radio,
+                cells: group.cells.len() as u32,
+                covered_percent: 100.0 * (1.0 - uncovered / length),
+                longest_gap_km: longest / 1000.0,
+                gaps: all_gaps
+                    .into_iter()
+                    .filter(|(from, to)| to - from >= min_gap)
+                    .map(|(from, to)| CoverageGap {
+                        start_km: from / 1000.0,
+                        end_km: to / 1000.0,
+                        length_km: (to - from) / 1000.0,
+                    })
+                    .collect(),
+            }
+        })
+        .collect();
+    CoverageReportResponse {
+        length_km: length / 1000.0,
+        operators,
+    }
+}
+
+/// Finds the stretches of a route without coverage per operator and radio.
+/// Every chunk of the route loads the cells whose largest possible coverage
+/// radius could reach it, and only measures coverage on its own segments.
+/// Chunks are twice that radius long, so a cell is loaded by at most a few
+/// of them.
+pub fn query_coverage_report(
+    route: &Route,
+    query: &CoverageReportQuery,
+    connection: &mut MysqlConnection,
+) -> Result<CoverageReportResponse, diesel::result::Error> {
+    use crate::schema::cells::dsl;
+
+    let mut groups = CoverageGroups::new();
+    for segments in route.chunks(COVERAGE_CHUNK_M) {
+        let mut db_query = dsl::cells
+            .into_boxed()
+            .filter(within(&route.bbox(&segments, MAX_COVERAGE_RADIUS_M)));
+        if let Some(mcc) = query.mcc {
+            db_query = db_query.filter(dsl::mcc.eq(mcc));
+        }
+        if let Some(mnc) = query.mnc {
+            db_query = db_query.filter(dsl::net.eq(mnc));
+        }
+        if let Some(ref radio) = query.radio {
+            db_query = db_query.filter(dsl::radio.eq(radio));
+        }
+        let rows: Vec<Cell> = db_query.load(connection)?;
+        collect_stretches(route, segments, rows, &mut groups);
+    }
+    Ok(coverage_report(route, groups, query.min_gap.unwrap_or(0.0)))
+}
+
+/// The listed gaps as lines along the route.
+fn gap_collection(
+    route: &Route,
+    report: &CoverageReportResponse,
+) -> FeatureCollection<CoverageGapProperties> {
+    let features = report
+        .operators
+        .iter()
+        .flat_map(|operator| {
+            operator.gaps.iter().map(|gap| {
+                let section = route.section(gap.start_km * 1000.0, gap.end_km * 1000.0);
+                Feature::new(
+                    LineString::new(&section),
+                    CoverageGapProperties {
+                        mcc: operator.mcc,
+                        net: operator.net,
+                        radio: operator.radio.clone(),
+                        start_km: gap.start_km,
+                        end_km: gap.end_km,
+                        length_km: gap.length_km,
+                        covered_percent: operator.covered_percent,
+                    },
+                )
+            })
+        })
+        .collect();
+    FeatureCollection::new(features)
+}
+
+/// Get the cells within a corridor around a route, uploaded as GPX or GeoJSON.
+#[utoipa::path(
+    post,
+    path = "/v1/route/cells",
+    params(RouteCellsQuery),
+    request_body(description = "A GPX document with track or route points, or a GeoJSON `LineString`, `MultiLineString`, `Feature` or `FeatureCollection`", content((String = "application/gpx+xml"), (Object = "application/geo+json"))),
+    responses(
+        (status = 200, description = "Cells in the corridor by route kilometre. Protobuf responses are a `RouteCellsResponse` message", content((RouteCellsResponse = "application/json"), (RouteCellsResponse = "application/msgpack"), (RouteCellsResponse = "application/cbor"))),
+        (status = 400, description = "Invalid route or parameters", body = ErrorBody),
+        (status = 406, description = "None of the accepted formats is supported", body = ErrorBody),
+        (status = 413, description = "The request body is too large", body = ErrorBody),
+        (status = 503, description = "The database is unavailable", body = ErrorBody),
+    ),
+    tag = "routes"
+)]
+#[instrument(skip(route, config), fields(points = route.points().len()))]
+pub async fn handle_route_cells(
+    query: RouteCellsQuery,
+    route: Route,
+    format: Format,
+    config: Config,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let connection = &mut try_establish_connection(config.clone()).map_err(ApiError::from)?;
+
+    let response = query_route_cells(&route, query.width(), connection).map_err(ApiError::from)?;
+    Ok(encode(format, &response))
+}
+
+/// Get the stretches of a route without expected coverage, per operator and radio.
+#[utoipa::path(
+    post,
+    path = "/v1/route/coverage-report",
+    params(CoverageReportQuery),
+    request_body(description = "A GPX document with track or route points, or a GeoJSON `LineString`, `MultiLineString`, `Feature` or `FeatureCollection`", content((String = "application/gpx+xml"), (Object = "application/geo+json"))),
+    responses(
+        (status = 200, description = "Coverage and gaps per operator and radio. Protobuf responses are a `CoverageReportResponse` message. With `format=geojson`, a GeoJSON `FeatureCollection` of the gaps as lines", content((CoverageReportResponse = "application/json"), (CoverageReportResponse = "application/msgpack"), (CoverageReportResponse = "application/cbor"), (FeatureCollection<CoverageGapProperties> = "application/geo+json"))),
+        (status = 400, description = "Invalid route or parameters", body = ErrorBody),
+        (status = 406, description = "None of the accepted formats is supported", body = ErrorBody),
+        (status = 413, description = "The request body is too large", body = ErrorBody),
+        (status = 503, description = "The database is unavailable", body = ErrorBody),
+    ),
+    tag = "routes"
+)]
+#[instrument(skip(route, config), fields(points = route.points().len()))]
+pub async fn handle_coverage_report(
+    query: CoverageReportQuery,
+    route: Route,
+    format: Format,
+    config: Config,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let connection = &mut try_establish_connection(config.clone()).map_err(ApiError::from)?;
+
+    let report = query_coverage_report(&route, &query, connection).map_err(ApiError::from)?;
+    match query.format {
+        Some(CellsFormat::Geojson) => Ok(geojson_reply(&gap_collection(&route, &report))),
+        None => Ok(encode(format, &report)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::geojson::Geometry;
+    use crate::utils::query::parse_query;
+
+    #[test]
+    fn test_width_defaults_to_a_kilometre() {
+        let query = parse_query::<RouteCellsQuery>("").unwrap();
+
+        assert_eq!(query.width(), DEFAULT_CORRIDOR_WIDTH_M);
+    }
+
+    #[test]
+    fn test_width_is_bounded() {
+        for width in ["0", "-5", "20001"] {
+            let error = parse_query::<RouteCellsQuery>(&format!("width={}", width)).unwrap_err();
+
+            assert!(
+                matches!(error, ApiError::BadRequest(ref fields) if fields[0].field == "width"),
+                "width={}",
+                width
+            );
+        }
+        assert!(parse_query::<RouteCellsQuery>("width=20000").is_ok());
+    }
+
+    fn cell(id: u64, net: u16, radio: Radio, lat: f32, range: u32) -> Cell {
+        Cell {
+            radio,
+            mcc: 262,
+            net,
+            area: 1,
+            cell: id,
+            unit: None,
+            lon: 13.0,
+            lat,
+            cell_range: range,
+            samples: 10,
+            changeable: true,
+            created: chrono::Utc::now().naive_utc(),
+            updated: chrono::Utc::now().naive_utc(),
+            average_signal: None,
+        }
+    }
+
+    /// 22.2 km due north from 52°N.
+    fn course() -> Route {
+        Route::new(vec![(52.0, 13.0), (52.2, 13.0)]).unwrap()
+    }
+
+    fn report(cells: Vec<Cell>, min_gap: f64) -> CoverageReportResponse {
+        let route = course();
+        let mut groups = CoverageGroups::new();
+        // Short chunks, so that stretches across chunk borders are covered
+        for segments in route.chunks(CORRIDOR_CHUNK_M) {
+            collect_stretches(&route, segments, cells.clone(), &mut groups);
+        }
+        coverage_report(&route, groups, min_gap)
+    }
+
+    #[test]
+    fn test_gaps_between_cells() {
+        // Covers km 0-4 and km 9.1-17.1
+        let report = report(
+            vec![
+                cell(1, 1, Radio::Lte, 52.0, 4000),
+                cell(2, 1, Radio::Lte, 52.118, 4000),
+            ],
+            0.0,
+        );
+
+        let operator = &report.operators[0];
+        assert_eq!(operator.cells, 2);
+        let gaps: Vec<(f64, f64)> = operator
+            .gaps
+            .iter()
+            .map(|gap| (gap.start_km, gap.end_km))
+            .collect();
+        assert_eq!(gaps.len(), 2);
+        assert!((gaps[0].0 - 4.0).abs() < 0.01 && (gaps[0].1 - 9.12).abs() < 0.01);
+        assert!((gaps[1].0 - 17.12).abs() < 0.01 && (gaps[1].1 - 22.24).abs() < 0.01);
+        assert!((operator.longest_gap_km - 5.12).abs() < 0.01);
+        assert!((operator.covered_percent - 100.0 * 12.0 / 22.24).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_short_gaps_are_not_listed_but_counted() {
+        let report = report(
+            vec![
+                cell(1, 1, Radio::Lte, 52.0, 4000),
+                cell(2, 1, Radio::Lte, 52.118, 4000),
+            ],
+            5_500.0,
+        );
+
+        let operator = &report.operators[0];
+        assert!(operator.gaps.is_empty());
+        assert!((operator.longest_gap_km - 5.12).abs() < 0.01);
+        assert!(operator.covered_percent < 60.0);
+    }
+
+    #[test]
+    fn test_operators_and_radios_are_reported_separately() {
+        let report = report(
+            vec![
+                cell(1, 1, Radio::Gsm, 52.1, 35_000),
+                cell(2, 1, Radio::Lte, 52.1, 1000),
+                cell(3, 2, Radio::Lte, 52.1, 1000),
+                // Too far east to reach the route
+                Cell {
+                    lon: 14.0,
+                    ..cell(4, 3, Radio::Lte, 52.1, 1000)
+                },
+            ],
+            0.0,
+        );
+
+        let groups: Vec<(u16, Radio, u32)> = report
+            .operators
+            .iter()
+            .map(|operator| (operator.net, operator.radio.clone(), operator.cells))
+            .collect();
+        assert_eq!(
+            groups,
+            vec![(1, Radio::Lte, 1), (1, Radio::Gsm, 1), (2, Radio::Lte, 1)]
+        );
+        assert_eq!(report.operators[1].covered_percent, 100.0);
+        assert!(report.operators[1].gaps.is_empty());
+    }
+
+    #[test]
+    fn test_geojson_gaps_follow_the_route() {
+        let route = course();
+        let report = report(vec![cell(1, 1, Radio::Lte, 52.0, 4000)], 0.0);
+
+        let collection = gap_collection(&route, &report);
+
+        assert_eq!(collection.features.len(), 1);
+        let Geometry::LineString(ref line) = collection.features[0].geometry else {
+            panic!("gap is not a line");
+        };
+        assert!((line.coordinates[0][1] - 52.036).abs() < 0.001);
+        assert!((line.coordinates.last().unwrap()[1] - 52.2).abs() < 1e-9);
+        assert_eq!(collection.features[0].properties.net, 1);
+    }
+
+    #[test]
+    fn test_min_gap_must_not_be_negative() {
+        let error = parse_query::<CoverageReportQuery>("min_gap=-1").unwrap_err();
+
+        assert!(matches!(error, ApiError::BadRequest(fields) if fields[0].field == "min_gap"));
+        assert!(parse_query::<CoverageReportQuery>("min_gap=500&format=geojson").is_ok());
+    }
+
+    /// Integration tests for the chunked corridor query.
+    #[cfg(feature = "integration_tests")]
+    mod route_integration {
+        use super::*;
+        use crate::schema::cells;
+        use crate::utils::test_db::get_test_connection;
+
+        fn cell_at(id: u64, lat: f32, lon: f32) -> Cell {
+            Cell {
+                radio: Radio::Lte,
+                mcc: 262,
+                net: 1,
+                area: 1,
+                cell: id,
+                unit: None,
+                lon,
+                lat,
+                cell_range: 1000,
+                samples: 10,
+                changeable: true,
+                created: chrono::Utc::now().naive_utc(),
+                updated: chrono::Utc::now().naive_utc(),
+                average_signal: None,
+            }
+        }
+
+        #[test]
+        fn test_cells_are_ordered_along_a_long_route() {
+            let (_container, mut conn) = get_test_connection();
+            // 0.2° of latitude per point, a straight 44 km course north
+            diesel::insert_into(cells::table)
+                .values(&vec![
+                    cell_at(1, 52.35, 13.0),
+                    cell_at(2, 52.05, 13.001),
+                    cell_at(3, 52.2, 13.05),
+                    cell_at(4, 51.99, 13.0),
+                ])
+                .execute(&mut conn)
+                .unwrap();
+            let route = Route::new(vec![(52.0, 13.0), (52.2, 13.0), (52.4, 13.0)]).unwrap();
+
+            let response = query_route_cells(&route, 1000.0, &mut conn).unwrap();
+            let ids: Vec<u64> = response.cells.iter().map(|c| c.cell.cell).collect();
+
+            // Cell 3 is 3.4 km off the route, cell 4 before its start
+            assert_eq!(ids, vec![2, 1]);
+            assert!((response.cells[0].route_km - 5.56).abs() < 0.01);
+            assert!((response.cells[0].distance - 68.5).abs() < 1.0);
+            assert!((response.length_km - 44.48).abs() < 0.01);
+        }
+
+        #[test]
+        fn test_coverage_report_spans_chunks() {
+            let (_container, mut conn) = get_test_connection();
+            // A 10 km range cell 8 km east of the start covers the first 6 km
+            // of the 22 km course, more than its first chunk
+            let mut wide = cell_at(1, 52.0, 13.117);
+            wide.cell_range = 10_000;
+            let mut gsm = cell_at(2, 52.2, 13.0);
+            gsm.radio = Radio::Gsm;
+            diesel::insert_into(cells::table)
+                .values(&vec![wide, gsm])
+                .execute(&mut conn)
+                .unwrap();
+            let route = Route::new(vec![(52.0, 13.0), (52.2, 13.0)]).unwrap();
+            let query = parse_query::<CoverageReportQuery>("radio=LTE").unwrap();
+
+            let report = query_coverage_report(&route, &query, &mut conn).unwrap();
+
+            assert_eq!(report.operators.len(), 1);
+            let gaps = &report.operators[0].gaps;
+            assert_eq!(gaps.len(), 1);
+            assert!((gaps[0].start_km - 6.0).abs() < 0.1, "{:?}", gaps);
+            assert!((gaps[0].end_km - 22.24).abs() < 0.01);
+        }
+    }
+}
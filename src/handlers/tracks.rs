@@ -0,0 +1,735 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, Utc};
+use diesel::MysqlConnection;
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+use utoipa::ToSchema;
+
+use super::geolocate::{estimate_position, Observation, Position};
+use super::lookup::{query_lookup, CellKey, MAX_LOOKUP_KEYS};
+use crate::models::Cell;
+use crate::utils::config::Config;
+use crate::utils::db::try_establish_connection;
+use crate::utils::errors::{ApiError, ErrorBody, FieldError};
+use crate::utils::geo::LocalFrame;
+use crate::utils::negotiation::{encode, Format};
+use crate::utils::query::{into_result, Validate};
+use crate::utils::ranging::Measurement;
+
+/// Maximum number of points per track.
+pub const MAX_TRACK_POINTS: usize = 1000;
+/// Maximum number of distinct cells per track, they are resolved in batches of `MAX_LOOKUP_KEYS`.
+pub const MAX_TRACK_CELLS: usize = 1000;
+/// Speed limit if the request doesn't set one, 252 km/h.
+pub const DEFAULT_MAX_SPEED_MPS: f64 = 70.0;
+/// Upper bound for `maxSpeed`, about the speed of an airliner.
+const MAX_SPEED_LIMIT_MPS: f64 = 300.0;
+/// Standard deviation of the tracker's acceleration in m/s², the process noise
+/// of the constant velocity model.
+const ACCELERATION_MPS2: f64 = 2.0;
+
+/// The cells observed at one point in time.
+#[derive(Deserialize, Serialize, Debug, Clone, ToSchema)]
+pub struct TrackPoint {
+    pub timestamp: DateTime<Utc>,
+    /// Serving and neighbour cells, with their signal strength if known
+    pub cells: Vec<Observation>,
+}
+
+/// Request body of the track localisation.
+#[derive(Deserialize, Serialize, Debug, Clone, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct LocalizeTrackRequest {
+    /// Observations in chronological order
+    pub points: Vec<TrackPoint>,
+    /// Fastest plausible speed of the tracker in m/s, defaults to 70
+    pub max_speed: Option<f64>,
+}
+
+impl LocalizeTrackRequest {
+    fn distinct_keys(&self) -> Vec<CellKey> {
+        let mut seen = HashSet::new();
+        self.points
+            .iter()
+            .flat_map(|point| &point.cells)
+            .filter(|observation| seen.insert(&observation.key))
+            .map(|observation| observation.key.clone())
+            .collect()
+    }
+}
+
+impl Validate for LocalizeTrackRequest {
+    fn validate(&self) -> Result<(), Vec<FieldError>> {
+        let mut errors = vec![];
+        if self.points.is_empty() {
+            errors.push(FieldError::new("points", "must not be empty"));
+        }
+        if self.points.len() > MAX_TRACK_POINTS {
+            errors.push(FieldError::new(
+                "points",
+                format!("must not have more than {} entries", MAX_TRACK_POINTS),
+            ));
+        }
+        if let Some(index) = self
+            .points
+            .windows(2)
+            .position(|pair| pair[1].timestamp < pair[0].timestamp)
+        {
+            errors.push(FieldError::new(
+                &format!("points[{}].timestamp", index + 1),
+                "must not be before the previous point",
+            ));
+        }
+        if self.distinct_keys().len() > MAX_TRACK_CELLS {
+            errors.push(FieldError::new(
+                "points",
+                format!(
+                    "must not observe more than {} distinct cells",
+                    MAX_TRACK_CELLS
+                ),
+            ));
+        }
+        if let Some(max_speed) = self.max_speed {
+            if !(max_speed > 0.0 && max_speed <= MAX_SPEED_LIMIT_MPS) {
+                errors.push(FieldError::new(
+                    "maxSpeed",
+                    format!("must be greater than 0 and at most {}", MAX_SPEED_LIMIT_MPS),
+                ));
+            }
+        }
+        into_result(errors)
+    }
+}
+
+/// The estimated position at one point of the track.
+#[derive(Serialize, Debug, Clone, PartialEq, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct LocalizedPoint {
+    pub timestamp: DateTime<Utc>,
+    /// `null` until the first point with a known cell
+    pub lat: Option<f64>,
+    pub lon: Option<f64>,
+    /// Estimated accuracy radius in meters
+    pub accuracy: Option<f64>,
+    /// Number of observations of this point that resolved to a known cell
+    pub cells_used: u32,
+}
+
+/// Response of the track localisation, aligned 1:1 with the requested points.
+#[derive(Serialize, Debug, ToSchema)]
+pub struct LocalizeTrackResponse {
+    pub points: Vec<LocalizedPoint>,
+}
+
+type Mat2 = [[f64; 2]; 2];
+
+fn mul(a: Mat2, b: Mat2) -> Mat2 {
+    let cell = |i: usize, j: usize| a[i][0] * b[0][j] + a[i][1] * b[1][j];
+    [[cell(0, 0), cell(0, 1)], [cell(1, 0), cell(1, 1)]]
+}
+
+fn transpose(a: Mat2) -> Mat2 {
+    [[a[0][0], a[1][0]], [a[0][1], a[1][1]]]
+}
+
+fn add(a: Mat2, b: Mat2, sign: f64) -> Mat2 {
+    let cell = |i: usize, j: usize| a[i][j] + sign * b[i][j];
+    [[cell(0, 0), cell(0, 1)], [cell(1, 0), cell(1, 1)]]
+}
+
+fn inverse(a: Mat2) -> Option<Mat2> {
+    let det = a[0][0] * a[1][1] - a[0][1] * a[1][0];
+    if det.abs() < f64::EPSILON {
+        return None;
+    }
+    Some([
+        [a[1][1] / det, -a[0][1] / det],
+        [-a[1][0] / det, a[0][0] / det],
+    ])
+}
+
+fn transition(dt: f64) -> Mat2 {
+    [[1.0, dt], [0.0, 1.0]]
+}
+
+/// Position and velocity along one axis of the local frame. East and north are
+/// independent in the constant velocity model, so each gets its own filter.
+#[derive(Debug, Clone, Copy)]
+struct AxisState {
+    x: [f64; 2],
+    p: Mat2,
+}
+
+impl AxisState {
+    fn new(position: f64, position_variance: f64, velocity_variance: f64) -> Self {
+        AxisState {
+            x: [position, 0.0],
+            p: [[position_variance, 0.0], [0.0, velocity_variance]],
+        }
+    }
+
+    fn predict(&self, dt: f64) -> Self {
+        let q = ACCELERATION_MPS2.powi(2);
+        let noise = [
+            [dt.powi(4) / 4.0 * q, dt.powi(3) / 2.0 * q],
+            [dt.powi(3) / 2.0 * q, dt.powi(2) * q],
+        ];
+        let f = transition(dt);
+        AxisState {
+            x: [self.x[0] + dt * self.x[1], self.x[1]],
+            p: add(mul(mul(f, self.p), transpose(f)), noise, 1.0),
+        }
+    }
+
+    fn update(&self, measurement: f64, variance: f64) -> Self {
+        let s = self.p[0][0] + variance;
+        let gain = [self.p[0][0] / s, self.p[1][0] / s];
+        let innovation = measurement - self.x[0];
+        AxisState {
+            x: [
+                self.x[0] + gain[0] * innovation,
+                self.x[1] + gain[1] * innovation,
+            ],
+            p: [
+                [
+                    (1.0 - gain[0]) * self.p[0][0],
+                    (1.0 - gain[0]) * self.p[0][1],
+                ],
+                [
+                    self.p[1][0] - gain[1] * self.p[0][0],
+                    self.p[1][1] - gain[1] * self.p[0][1],
+                ],
+            ],
+        }
+    }
+
+    /// Rauch-Tung-Striebel step: refines this filtered state with the smoothed
+    /// next state and the prediction that was made from this one.
+    fn smooth(&self, dt: f64, predicted: &AxisState, next: &AxisState) -> Self {
+        let Some(predicted_inverse) = inverse(predicted.p) else {
+            return *self;
+        };
+        let c = mul(mul(self.p, transpose(transition(dt))), predicted_inverse);
+        let dx = [next.x[0] - predicted.x[0], next.x[1] - predicted.x[1]];
+        AxisState {
+            x: [
+                self.x[0] + c[0][0] * dx[0] + c[0][1] * dx[1],
+                self.x[1] + c[1][0] * dx[0] + c[1][1] * dx[1],
+            ],
+            p: add(
+                self.p,
+                mul(mul(c, add(next.p, predicted.p, -1.0)), transpose(c)),
+                1.0,
+            ),
+        }
+    }
+}
+
+/// East and north state of the tracker.
+type TrackState = [AxisState; 2];
+
+fn position_of(state: &TrackState) -> (f64, f64) {
+    (state[0].x[0], state[1].x[0])
+}
+
+/// Radial standard deviation of the position in meters.
+fn accuracy_of(state: &TrackState) -> f64 {
+    (state[0].p[0][0] + state[1].p[0][0]).sqrt()
+}
+
+/// Caps the velocity at `max_speed`, keeping its direction.
+fn limit_speed(state: &mut TrackState, max_speed: f64) {
+    let speed = state[0].x[1].hypot(state[1].x[1]);
+    if speed > max_speed {
+        let scale = max_speed / speed;
+        state[0].x[1] *= scale;
+        state[1].x[1] *= scale;
+    }
+}
+
+/// Smooths the per-point position fixes of a track with a constant velocity
+/// Kalman filter and a Rauch-Tung-Striebel smoother.
+///
+/// A fix's accuracy is used as the radial standard deviation of its position.
+/// Fixes further from the prediction than `max_speed` allows are trusted less,
+/// and the velocity never exceeds it. Points without a fix get the position the
+/// motion model interpolates, points before the first fix stay unknown.
+pub fn smooth_track(
+    timestamps: &[DateTime<Utc>],
+    fixes: &[Option<Position>],
+    max_speed: f64,
+) -> Vec<Option<Position>> {
+    let Some(start) = fixes.iter().position(Option::is_some) else {
+        return vec![None; fixes.len()];
+    };
+    let origin = fixes[start].as_ref().unwrap();
+    let frame = LocalFrame::new(origin.lat, origin.lon);
+    let dt = |k: usize| (timestamps[k] - timestamps[k - 1]).num_milliseconds() as f64 / 1000.0;
+
+    // Forward pass, `predicted[k]` is the prediction for `k` made from `k - 1`
+    let initial_variance = origin.accuracy.powi(2) / 2.0;
+    // The initial heading is unknown, any speed up to the limit is plausible
+    let velocity_variance = max_speed.powi(2);
+    let mut filtered: Vec<TrackState> = vec![[
+        AxisState::new(0.0, initial_variance, velocity_variance),
+        AxisState::new(0.0, initial_variance, velocity_variance),
+    ]];
+    let mut predicted: Vec<TrackState> = vec![filtered[0]];
+    for (k, fix) in fixes.iter().enumerate().skip(start + 1) {
+        let dt = dt(k);
+        let previous = filtered.last().unwrap();
+        let prediction = [previous[0].predict(dt), previous[1].predict(dt)];
+
+        let mut state = prediction;
+        if let Some(fix) = fix {
+            let (east, north) = frame.to_local(fix.lat, fix.lon);
+            let (predicted_east, predicted_north) = position_of(&prediction);
+            let jump = (east - predicted_east).hypot(north - predicted_north);
+            // What the speed limit and both uncertainties can't explain is treated as noise
+            let excess = jump - max_speed * dt - accuracy_of(&prediction) - fix.accuracy;
+            let variance = (fix.accuracy.powi(2) / 2.0).max(excess.max(0.0).powi(2));
+            state = [
+                prediction[0].update(east, variance),
+                prediction[1].update(north, variance),
+            ];
+        }
+        limit_speed(&mut state, max_speed);
+
+        predicted.push(prediction);
+        filtered.push(state);
+    }
+
+    // Backward pass
+    let mut smoothed = filtered.clone();
+    for i in (0..filtered.len() - 1).rev() {
+        let dt = dt(start + i + 1);
+        for axis in 0..2 {
+            smoothed[i][axis] =
+                filtered[i][axis].smooth(dt, &predicted[i + 1][axis], &smoothed[i + 1][axis]);
+        }
+    }
+
+    let mut positions = vec![None; start];
+    positions.extend(smoothed.iter().zip(&fixes[start..]).map(|(state, fix)| {
+        let (east, north) = position_of(state);
+        let (lat, lon) = frame.to_geo(east, north);
+        Some(Position {
+            lat,
+            lon,
+            accuracy: accuracy_of(state),
+            cells_used: fix.as_ref().map_or(0, |fix| fix.cells_used),
+        })
+    }));
+    positions
+}
+
+/// Resolves the distinct cells of a track, in as few queries as the lookup allows.
+fn resolve_cells(
+    keys: Vec<CellKey>,
+    connection: &mut MysqlConnection,
+) -> Result<HashMap<CellKey, Cell>, diesel::result::Error> {
+    let mut resolved = HashMap::new();
+    for chunk in keys.chunks(MAX_LOOKUP_KEYS) {
+        let found = query_lookup(chunk, connection)?;
+        resolved.extend(
+            chunk
+                .iter()
+                .zip(found)
+                .filter_map(|(key, cell)| cell.map(|cell| (key.clone(), cell))),
+        );
+    }
+    Ok(resolved)
+}
+
+/// Estimates a position per point from its known cells and smooths the track.
+#[instrument(skip_all, fields(points = request.points.len()))]
+pub fn query_localize_track(
+    request: &LocalizeTrackRequest,
+    connection: &mut MysqlConnection,
+) -> Result<LocalizeTrackResponse, diesel::result::Error> {
+    let cells = resolve_cells(request.distinct_keys(), connection)?;
+
+    let fixes: Vec<Option<Position>> = request
+        .points
+        .iter()
+        .map(|point| {
+            let observed: Vec<(Cell, Measurement)> = point
+                .cells
+                .iter()
+                .filter_map(|o| {
+                    cells
+                        .get(&o.key)
+                        .map(|cell| (cell.clone(), o.measurement()))
+                })
+                .collect();
+            estimate_position(&observed)
+        })
+        .collect();
+    let timestamps: Vec<DateTime<Utc>> = request.points.iter().map(|p| p.timestamp).collect();
+    let max_speed = request.max_speed.unwrap_or(DEFAULT_MAX_SPEED_MPS);
+
+    let points = smooth_track(&timestamps, &fixes, max_speed)
+        .into_iter()
+        .zip(timestamps)
+        .map(|(position, timestamp)| LocalizedPoint {
+            timestamp,
+            lat: position.as_ref().map(|p| p.lat),
+            lon: position.as_ref().map(|p| p.lon),
+            accuracy: position.as_ref().map(|p| p.accuracy),
+            cells_used: position.map_or(0, |p| p.cells_used),
+        })
+        .collect();
+    Ok(LocalizeTrackResponse { points })
+}
+
+/// Estimate one smoothed position per point of a buffered track of cell observations.
+#[utoipa::path(
+    post,
+    path = "/v1/tracks/localize",
+    request_body = LocalizeTrackRequest,
+    responses(
+        (status = 200, description = "One position per point, in request order. Protobuf responses are a `LocalizeTrackResponse` message", content((LocalizeTrackResponse = "application/json"), (LocalizeTrackResponse = "application/msgpack"), (LocalizeTrackResponse = "application/cbor"))),
+        (status = 400, description = "Invalid request body", body = ErrorBody),
+        (status = 406, description = "None of the accepted formats is supported", body = ErrorBody),
+        (status = 413, description = "The request body is too large", body = ErrorBody),
+        (status = 503, description = "The database is unavailable", body = ErrorBody),
+    ),
+    tag = "tracks"
+)]
+#[instrument(skip_all, fields(points = request.points.len()))]
+pub async fn handle_localize_track(
+    request: LocalizeTrackRequest,
+    format: Format,
+    config: Config,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let connection = &mut try_establish_connection(config.clone()).map_err(ApiError::from)?;
+
+    let response = query_localize_track(&request, connection).map_err(ApiError::from)?;
+    Ok(encode(format, &response))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::geo::haversine_distance;
+    use chrono::TimeZone;
+
+    fn timestamps(seconds: &[i64]) -> Vec<DateTime<Utc>> {
+        seconds
+            .iter()
+            .map(|s| Utc.timestamp_opt(1_760_000_000 + s, 0).unwrap())
+            .collect()
+    }
+
+    fn fix(lat: f64, lon: f64, accuracy: f64) -> Option<Position> {
+        Some(Position {
+            lat,
+            lon,
+            accuracy,
+            cells_used: 1,
+        })
+    }
+
+    /// Latitude after driving `meters` north of 52°N.
+    fn north_of_origin(meters: f64) -> f64 {
+        52.0 + (meters / crate::utils::geo::EARTH_RADIUS_M).to_degrees()
+    }
+
+    mod smooth_track {
+        use super::*;
+
+        #[test]
+        fn test_without_fixes() {
+            let positions = smooth_track(&timestamps(&[0, 10]), &[None, None], 30.0);
+
+            assert_eq!(positions, vec![None, None]);
+        }
+
+        #[test]
+        fn test_single_fix_is_kept() {
+            let positions = smooth_track(&timestamps(&[0]), &[fix(52.0, 13.0, 800.0)], 30.0);
+
+            let position = positions[0].as_ref().unwrap();
+            assert!((position.lat - 52.0).abs() < 1e-9);
+            assert!((position.lon - 13.0).abs() < 1e-9);
+            assert!((position.accuracy - 800.0).abs() < 1e-6);
+        }
+
+        #[test]
+        fn test_points_before_the_first_fix_are_unknown() {
+            let positions = smooth_track(
+                &timestamps(&[0, 10, 20]),
+                &[None, fix(52.0, 13.0, 500.0), fix(52.001, 13.0, 500.0)],
+                30.0,
+            );
+
+            assert!(positions[0].is_none());
+            assert!(positions[1].is_some());
+            assert_eq!(positions[2].as_ref().unwrap().cells_used, 1);
+        }
+
+        #[test]
+        fn test_noise_is_smoothed() {
+            // Driving north at 20 m/s, fixes alternate 600 m left and right of the road
+            let seconds: Vec<i64> = (0..30).map(|i| i * 10).collect();
+            let fixes: Vec<Option<Position>> = seconds
+                .iter()
+                .enumerate()
+                .map(|(i, s)| {
+                    let offset = if i % 2 == 0 { 0.0054 } else { -0.0054 } * 1.6;
+                    fix(north_of_origin(20.0 * *s as f64), 13.0 + offset, 1000.0)
+                })
+                .collect();
+
+            let positions = smooth_track(&timestamps(&seconds), &fixes, 50.0);
+
+            let error = |position: &Position, s: i64| {
+                haversine_distance(
+                    position.lat,
+                    position.lon,
+                    north_of_origin(20.0 * s as f64),
+                    13.0,
+                )
+            };
+            let raw: f64 = fixes
+                .iter()
+                .zip(&seconds)
+                .map(|(f, s)| error(f.as_ref().unwrap(), *s))
+                .sum();
+            let smoothed: f64 = positions
+                .iter()
+                .zip(&seconds)
+                .map(|(p, s)| error(p.as_ref().unwrap(), *s))
+                .sum();
+            assert!(smoothed < raw / 3.0, "{} vs {}", smoothed, raw);
+        }
+
+        #[test]
+        fn test_impossible_jumps_are_damped() {
+            // Standing still, one fix 20 km away after 10 seconds
+            let fixes = vec![
+                fix(52.0, 13.0, 500.0),
+                fix(52.0, 13.0, 500.0),
+                fix(52.18, 13.0, 500.0),
+                fix(52.0, 13.0, 500.0),
+                fix(52.0, 13.0, 500.0),
+            ];
+
+            let positions = smooth_track(&timestamps(&[0, 10, 20, 30, 40]), &fixes, 30.0);
+
+            let outlier = positions[2].as_ref().unwrap();
+            let distance = haversine_distance(outlier.lat, outlier.lon, 52.0, 13.0);
+            assert!(distance < 1000.0, "{}", distance);
+        }
+
+        #[test]
+        fn test_gaps_are_interpolated() {
+            // Driving north at 20 m/s with a tunnel in the middle of the track
+            let seconds: Vec<i64> = (0..9).map(|i| i * 25).collect();
+            let fixes: Vec<Option<Position>> = seconds
+                .iter()
+                .map(|s| match s {
+                    75..=125 => None,
+                    _ => fix(north_of_origin(20.0 * *s as f64), 13.0, 300.0),
+                })
+                .collect();
+
+            let positions = smooth_track(&timestamps(&seconds), &fixes, 30.0);
+
+            let middle = positions[4].as_ref().unwrap();
+            let to_start = haversine_distance(middle.lat, middle.lon, 52.0, 13.0);
+            assert!((to_start - 2000.0).abs() < 200.0, "{}", to_start);
+            assert!(middle.accuracy > positions[2].as_ref().unwrap().accuracy);
+            assert_eq!(middle.cells_used, 0);
+        }
+
+        #[test]
+        fn test_simultaneous_fixes_are_combined() {
+            let positions = smooth_track(
+                &timestamps(&[0, 0]),
+                &[fix(52.0, 13.0, 1000.0), fix(52.0, 13.0, 1000.0)],
+                30.0,
+            );
+
+            let position = positions[1].as_ref().unwrap();
+            assert!(position.accuracy < 1000.0, "{}", position.accuracy);
+        }
+    }
+
+    mod validation {
+        use super::*;
+
+        fn observation(cid: u64) -> Observation {
+            Observation {
+                key: CellKey {
+                    mcc: 262,
+                    mnc: 1,
+                    lac: 1,
+                    cid,
+                },
+                signal_dbm: None,
+                timing_advance: None,
+            }
+        }
+
+        fn request(seconds: &[i64]) -> LocalizeTrackRequest {
+            LocalizeTrackRequest {
+                points: timestamps(seconds)
+                    .into_iter()
+                    .map(|timestamp| TrackPoint {
+                        timestamp,
+                        cells: vec![observation(1)],
+                    })
+                    .collect(),
+                max_speed: None,
+            }
+        }
+
+        #[test]
+        fn test_valid_request() {
+            assert!(request(&[0, 10, 10, 20]).validate().is_ok());
+        }
+
+        #[test]
+        fn test_empty_track_is_rejected() {
+            let errors = request(&[]).validate().unwrap_err();
+
+            assert_eq!(errors[0].field, "points");
+        }
+
+        #[test]
+        fn test_unordered_points_are_rejected() {
+            let errors = request(&[0, 20, 10]).validate().unwrap_err();
+
+            assert_eq!(errors[0].field, "points[2].timestamp");
+        }
+
+        #[test]
+        fn test_too_many_points_are_rejected() {
+            let seconds: Vec<i64> = (0..=MAX_TRACK_POINTS as i64).collect();
+
+            let errors = request(&seconds).validate().unwrap_err();
+            assert_eq!(errors[0].field, "points");
+        }
+
+        #[test]
+        fn test_too_many_cells_are_rejected() {
+            let mut request = request(&[0]);
+            request.points[0].cells = (0..=MAX_TRACK_CELLS as u64).map(observation).collect();
+
+            let errors = request.validate().unwrap_err();
+            assert_eq!(errors[0].field, "points");
+        }
+
+        #[test]
+        fn test_max_speed_must_be_positive() {
+            let mut request = request(&[0]);
+            request.max_speed = Some(0.0);
+
+            let errors = request.validate().unwrap_err();
+            assert_eq!(errors[0].field, "maxSpeed");
+        }
+
+        #[test]
+        fn test_distinct_keys() {
+            let mut request = request(&[0, 10]);
+            request.points[1].cells.push(observation(2));
+
+            assert_eq!(request.distinct_keys().len(), 2);
+        }
+
+        #[test]
+        fn test_observations_are_flat_json() {
+            let request: LocalizeTrackRequest = serde_json::from_value(serde_json::json!({
+                "points": [{
+                    "timestamp": "2026-01-01T12:00:00Z",
+                    "cells": [{ "mcc": 262, "mnc": 1, "lac": 100, "cid": 5, "signalDbm": -85 }]
+                }],
+                "maxSpeed": 40.0
+            }))
+            .unwrap();
+
+            assert_eq!(request.points[0].cells[0].key.cid, 5);
+            assert_eq!(request.points[0].cells[0].signal_dbm, Some(-85));
+        }
+    }
+
+    #[cfg(feature = "integration_tests")]
+    mod tracks_integration {
+        use super::*;
+        use crate::models::Radio;
+        use crate::schema::cells;
+        use crate::utils::test_db::get_test_connection;
+        use diesel::prelude::*;
+
+        fn sample_cell(cell_val: u64, lat: f32) -> Cell {
+            let now = Utc
+                .with_ymd_and_hms(2025, 12, 20, 14, 0, 0)
+                .unwrap()
+                .naive_utc();
+            Cell {
+                radio: Radio::Lte,
+                mcc: 262,
+                net: 1,
+                area: 100,
+                cell: cell_val,
+                unit: None,
+                lon: 13.0,
+                lat,
+                cell_range: 500,
+                samples: 10,
+                changeable: true,
+                created: now,
+                updated: now,
+                average_signal: None,
+            }
+        }
+
+        fn point(second: i64, cids: &[u64]) -> TrackPoint {
+            TrackPoint {
+                timestamp: timestamps(&[second])[0],
+                cells: cids
+                    .iter()
+                    .map(|cid| Observation {
+                        key: CellKey {
+                            mcc: 262,
+                            mnc: 1,
+                            lac: 100,
+                            cid: *cid,
+                        },
+                        signal_dbm: Some(-80),
+                        timing_advance: None,
+                    })
+                    .collect(),
+            }
+        }
+
+        #[test]
+        fn test_track_is_localized_per_point() {
+            let (_container, mut conn) = get_test_connection();
+            diesel::insert_into(cells::table)
+                .values(&vec![sample_cell(1, 52.0), sample_cell(2, 52.01)])
+                .execute(&mut conn)
+                .unwrap();
+            let request = LocalizeTrackRequest {
+                points: vec![
+                    point(0, &[999]),
+                    point(30, &[1]),
+                    point(60, &[1, 2]),
+                    point(90, &[2]),
+                ],
+                max_speed: None,
+            };
+
+            let response = query_localize_track(&request, &mut conn).unwrap();
+
+            assert_eq!(response.points.len(), 4);
+            assert!(response.points[0].lat.is_none());
+            assert_eq!(response.points[2].cells_used, 2);
+            let last = &response.points[3];
+            assert!(last.lat.unwrap() > response.points[1].lat.unwrap());
+            assert!(last.accuracy.unwrap() > 0.0);
+        }
+    }
+}
@@ -0,0 +1,501 @@
+use std::collections::BTreeMap;
+
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use diesel::MysqlConnection;
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+use utoipa::ToSchema;
+
+use super::neighbours::within;
+use crate::models::{Cell, Radio};
+use crate::utils::config::Config;
+use crate::utils::db::try_establish_connection;
+use crate::utils::errors::{ApiError, ErrorBody, FieldError};
+use crate::utils::negotiation::{encode, Format};
+use crate::utils::polygon::Region;
+use crate::utils::query::{into_result, Validate};
+
+/// Surroundings counted if the request doesn't set a margin.
+pub const DEFAULT_VENUE_MARGIN_M: f64 = 500.0;
+/// Widest surroundings counted around a venue.
+pub const MAX_VENUE_MARGIN_M: f64 = 5_000.0;
+/// Cells around the venue count this much compared to cells inside.
+const AROUND_WEIGHT: f64 = 0.5;
+/// Weights of the normalized cell count, NR/LTE share, samples and freshness
+/// in the score.
+const SCORE_WEIGHTS: [f64; 4] = [0.4, 0.25, 0.2, 0.15];
+/// Age after which the freshness halves.
+const FRESHNESS_HALF_LIFE_DAYS: f64 = 365.0;
+
+/// A GeoJSON `Polygon`.
+#[derive(Deserialize, Serialize, Debug, Clone, ToSchema)]
+pub struct PolygonGeometry {
+    /// Must be `Polygon`
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// Closed rings of `[lon, lat]` positions, the exterior first, then holes
+    pub coordinates: Vec<Vec<Vec<f64>>>,
+}
+
+/// Request body of the venue operator ranking.
+#[derive(Deserialize, Serialize, Debug, Clone, ToSchema)]
+pub struct VenueOperatorsRequest {
+    /// The venue, up to 25 km across
+    pub polygon: PolygonGeometry,
+    /// Meters around the venue whose cells also count, at half weight (default: 500, max: 5000)
+    pub margin: Option<f64>,
+}
+
+impl VenueOperatorsRequest {
+    pub fn region(&self) -> Result<Region, String> {
+        Region::new(&self.polygon.coordinates)
+    }
+
+    pub fn margin(&self) -> f64 {
+        self.margin.unwrap_or(DEFAULT_VENUE_MARGIN_M)
+    }
+}
+
+impl Validate for VenueOperatorsRequest {
+    fn validate(&self) -> Result<(), Vec<FieldError>> {
+        let mut errors = vec![];
+        if self.polygon.kind != "Polygon" {
+            errors.push(FieldError::new("polygon.type", "must be Polygon"));
+        } else if let Err(message) = self.region() {
+            errors.push(FieldError::new("polygon.coordinates", message));
+        }
+        if let Some(margin) = self.margin {
+            if !(0.0..=MAX_VENUE_MARGIN_M).contains(&margin) {
+                errors.push(FieldError::new(
+                    "margin",
+                    format!("must be between 0 and {}", MAX_VENUE_MARGIN_M),
+                ));
+            }
+        }
+        into_result(errors)
+    }
+}
+
+/// One operator's place in the ranking.
+#[derive(Serialize, Debug, Clone, PartialEq, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct OperatorRanking {
+    /// 1 for the best operator
+    pub rank: u32,
+    pub mcc: u16,
+    pub net: u16,
+    /// Weighted sum of the normalized criteria, 0 to 1
+    pub score: f64,
+    pub cells_inside: u32,
+    pub cells_around: u32,
+    /// Share of NR and LTE cells inside and around, 0 to 1
+    pub nr_lte_share: f64,
+    /// Measurements behind the positions of the cells
+    pub samples: u64,
+    /// Median days since the cells were last updated in OpenCellID
+    pub median_age_days: u32,
+    pub explanation: String,
+}
+
+/// Response of the venue operator ranking.
+#[derive(Serialize, Debug, ToSchema)]
+pub struct VenueOperatorsResponse {
+    /// Best first. Operators without cells in or around the venue are missing
+    pub operators: Vec<OperatorRanking>,
+}
+
+/// The cells of one operator in and around the venue.
+#[derive(Debug, Default)]
+struct OperatorCells {
+    inside: u32,
+    around: u32,
+    nr_lte: u32,
+    samples: u64,
+    ages_days: Vec<f64>,
+}
+
+impl OperatorCells {
+    fn weighted_cells(&self) -> f64 {
+        f64::from(self.inside) + AROUND_WEIGHT * f64::from(self.around)
+    }
+
+    fn nr_lte_share(&self) -> f64 {
+        f64::from(self.nr_lte) / f64::from(self.inside + self.around)
+    }
+
+    fn median_age_days(&self) -> f64 {
+        let mut ages = self.ages_days.clone();
+        ages.sort_by(f64::total_cmp);
+        let middle = ages.len() / 2;
+        if ages.len().is_multiple_of(2) {
+            (ages[middle - 1] + ages[middle]) / 2.0
+        } else {
+            ages[middle]
+        }
+    }
+
+    fn freshness(&self) -> f64 {
+        0.5f64.powf(self.median_age_days() / FRESHNESS_HALF_LIFE_DAYS)
+    }
+}
+
+/// Samples on a log scale relative to the most, so that one operator with
+/// a heavily measured site doesn't flatten everyone else.
+fn samples_score(samples: u64, most: u64) -> f64 {
+    if most == 0 {
+        return 0.0;
+    }
+    (samples as f64).ln_1p() / (most as f64).ln_1p()
+}
+
+/// The best value of a criterion, unless all operators share it.
+fn lead(values: impl Iterator<Item = f64>) -> Option<f64> {
+    let (min, max) = values.fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), value| {
+        (min.min(value), max.max(value))
+    });
+    (max > min).then_some(max)
+}
+
+fn count_cells(count: u32) -> String {
+    match count {
+        1 => "1 cell".to_string(),
+        count => format!("{} cells", count),
+    }
+}
+
+/// What sets an operator apart, then the numbers behind the score.
+fn explanation(operator: &OperatorCells, leads: &[&str]) -> String {
+    let facts = format!(
+        "{} inside and {} around, {:.0}% NR/LTE, {} samples, median update {:.0} days ago.",
+        count_cells(operator.inside),
+        operator.around,
+        100.0 * operator.nr_lte_share(),
+        operator.samples,
+        operator.median_age_days()
+    );
+    if leads.is_empty() {
+        return facts;
+    }
+    let lead = leads.join(", ");
+    format!("{}{}. {}", lead[..1].to_uppercase(), &lead[1..], facts)
+}
+
+/// Ranks the operators of `cells` at a venue. Cell counts and samples are
+/// relative to the best operator, so scores only compare operators at the
+/// same venue.
+pub fn rank_operators(
+    region: &Region,
+    margin: f64,
+    cells: Vec<Cell>,
+    now: NaiveDateTime,
+) -> Vec<OperatorRanking> {
+    let mut operators: BTreeMap<(u16, u16), OperatorCells> = BTreeMap::new();
+    for cell in cells {
+        let distance = region.distance(f64::from(cell.lat), f64::from(cell.lon));
+        if distance > margin {
+            continue;
+        }
+        let operator = operators.entry((cell.mcc, cell.net)).or_default();
+        if distance == 0.0 {
+            operator.inside += 1;
+        } else {
+            operator.around += 1;
+        }
+        if matches!(cell.radio, Radio::Nr | Radio::Lte) {
+            operator.nr_lte += 1;
+        }
+        operator.samples += u64::from(cell.samples);
+        operator
+            .ages_days
+            .push((now - cell.updated).num_seconds().max(0) as f64 / 86_400.0);
+    }
+
+    let most_cells = operators
+        .values()
+        .map(OperatorCells::weighted_cells)
+        .fold(0.0, f64::max);
+    let most_samples = operators.values().map(|o| o.samples).max().unwrap_or(0);
+    let cells_lead = lead(operators.values().map(OperatorCells::weighted_cells));
+    let share_lead = lead(operators.values().map(OperatorCells::nr_lte_share));
+    let samples_lead = lead(operators.values().map(|o| o.samples as f64));
+    let freshness_lead = lead(operators.values().map(OperatorCells::freshness));
+
+    let mut rankings: Vec<OperatorRanking> = operators
+        .into_iter()
+        .map(|((mcc, net), operator)| {
+            let criteria = [
+                operator.weighted_cells() / most_cells,
+                operator.nr_lte_share(),
+                samples_score(operator.samples, most_samples),
+                operator.freshness(),
+            ];
+            let score = criteria
+                .iter()
+                .zip(SCORE_WEIGHTS)
+                .map(|(criterion, weight)| criterion * weight)
+                .sum();
+            let leads: Vec<&str> = [
+                (cells_lead, operator.weighted_cells(), "most cells"),
+                (share_lead, operator.nr_lte_share(), "highest NR/LTE share"),
+                (samples_lead, operator.samples as f64, "most samples"),
+                (freshness_lead, operator.freshness(), "freshest data"),
+            ]
+            .into_iter()
+            .filter(|(best, value, _)| *best == Some(*value))
+            .map(|(_, _, lead)| lead)
+            .collect();
+            OperatorRanking {
+                rank: 0,
+                mcc,
+                net,
+                score,
+                cells_inside: operator.inside,
+                cells_around: operator.around,
+                nr_lte_share: operator.nr_lte_share(),
+                samples: operator.samples,
+                median_age_days: operator.median_age_days().round() as u32,
+                explanation: explanation(&operator, &leads),
+            }
+        })
+        .collect();
+    rankings.sort_by(|a, b| b.score.total_cmp(&a.score));
+    for (index, ranking) in rankings.iter_mut().enumerate() {
+        ranking.rank = index as u32 + 1;
+    }
+    rankings
+}
+
+/// Loads the cells within the box around the venue and its margin, the
+/// polygon itself is applied in `rank_operators`.
+pub fn query_venue_operators(
+    region: &Region,
+    margin: f64,
+    connection: &mut MysqlConnection,
+) -> Result<VenueOperatorsResponse, diesel::result::Error> {
+    use crate::schema::cells::dsl;
+
+    let rows: Vec<Cell> = dsl::cells
+        .filter(within(&region.bbox(margin)))
+        .load(connection)?;
+    Ok(VenueOperatorsResponse {
+        operators: rank_operators(region, margin, rows, chrono::Utc::now().naive_utc()),
+    })
+}
+
+/// Rank the operators at a venue for picking SIM cards.
+#[utoipa::path(
+    post,
+    path = "/v1/venue/operators",
+    request_body = VenueOperatorsRequest,
+    responses(
+        (status = 200, description = "Operators by score, best first, each with an explanation. Protobuf responses are a `VenueOperatorsResponse` message", content((VenueOperatorsResponse = "application/json"), (VenueOperatorsResponse = "application/msgpack"), (VenueOperatorsResponse = "application/cbor"))),
+        (status = 400, description = "Invalid request body", body = ErrorBody),
+        (status = 406, description = "None of the accepted formats is supported", body = ErrorBody),
+        (status = 413, description = "The request body is too large", body = ErrorBody),
+        (status = 503, description = "The database is unavailable", body = ErrorBody),
+    ),
+    tag = "venues"
+)]
+#[instrument(skip_all)]
+pub async fn handle_venue_operators(
+    request: VenueOperatorsRequest,
+    format: Format,
+    config: Config,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let region = request.region().map_err(|message| {
+        ApiError::BadRequest(vec![FieldError::new("polygon.coordinates", message)])
+    })?;
+    let connection = &mut try_establish_connection(config.clone()).map_err(ApiError::from)?;
+
+    let response =
+        query_venue_operators(&region, request.margin(), connection).map_err(ApiError::from)?;
+    Ok(encode(format, &response))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::query::parse_json;
+    use chrono::Duration;
+
+    fn now() -> NaiveDateTime {
+        chrono::DateTime::from_timestamp(1_800_000_000, 0)
+            .unwrap()
+            .naive_utc()
+    }
+
+    fn cell(net: u16, radio: Radio, lat: f32, lon: f32, samples: u32, age_days: i64) -> Cell {
+        Cell {
+            radio,
+            mcc: 262,
+            net,
+            area: 1,
+            cell: 1,
+            unit: None,
+            lon,
+            lat,
+            cell_range: 1000,
+            samples,
+            changeable: true,
+            created: now() - Duration::days(age_days),
+            updated: now() - Duration::days(age_days),
+            average_signal: None,
+        }
+    }
+
+    /// The Olympiastadion Berlin, roughly.
+    const STADIUM: &str = r#"{"type": "Polygon", "coordinates": [[
+        [13.236, 52.513], [13.243, 52.513], [13.243, 52.517], [13.236, 52.517], [13.236, 52.513]
+    ]]}"#;
+
+    fn stadium() -> Region {
+        let polygon: PolygonGeometry = serde_json::from_str(STADIUM).unwrap();
+        Region::new(&polygon.coordinates).unwrap()
+    }
+
+    #[test]
+    fn test_request_validation() {
+        let request = format!(r#"{{"polygon": {}, "margin": 1000}}"#, STADIUM);
+        assert_eq!(
+            parse_json::<VenueOperatorsRequest>(request.as_bytes())
+                .unwrap()
+                .margin(),
+            1000.0
+        );
+
+        for (body, field) in [
+            (
+                r#"{"polygon": {"type": "Point", "coordinates": []}}"#,
+                "polygon.type",
+            ),
+            (
+                r#"{"polygon": {"type": "Polygon", "coordinates": [[[13.0, 52.0], [13.1, 52.0], [13.0, 52.0]]]}}"#,
+                "polygon.coordinates",
+            ),
+        ] {
+            let error = parse_json::<VenueOperatorsRequest>(body.as_bytes()).unwrap_err();
+            assert!(
+                matches!(error, ApiError::BadRequest(ref fields) if fields[0].field == field),
+                "{}",
+                body
+            );
+        }
+        let request = format!(r#"{{"polygon": {}, "margin": 6000}}"#, STADIUM);
+        let error = parse_json::<VenueOperatorsRequest>(request.as_bytes()).unwrap_err();
+        assert!(matches!(error, ApiError::BadRequest(fields) if fields[0].field == "margin"));
+    }
+
+    #[test]
+    fn test_cells_inside_outweigh_cells_around() {
+        let cells = vec![
+            // Operator 1: two modern cells inside
+            cell(1, Radio::Lte, 52.515, 13.24, 100, 30),
+            cell(1, Radio::Nr, 52.514, 13.238, 100, 30),
+            // Operator 2: three GSM cells around, one too far away
+            cell(2, Radio::Gsm, 52.519, 13.24, 100, 30),
+            cell(2, Radio::Gsm, 52.511, 13.24, 100, 30),
+            cell(2, Radio::Gsm, 52.515, 13.245, 100, 30),
+            cell(2, Radio::Gsm, 52.6, 13.24, 100, 30),
+        ];
+
+        let rankings = rank_operators(&stadium(), 500.0, cells, now());
+
+        assert_eq!(rankings.len(), 2);
+        let best = &rankings[0];
+        assert_eq!((best.rank, best.net), (1, 1));
+        assert_eq!((best.cells_inside, best.cells_around), (2, 0));
+        assert_eq!(best.nr_lte_share, 1.0);
+        assert!(
+            best.explanation
+                .starts_with("Most cells, highest NR/LTE share. 2 cells inside"),
+            "{}",
+            best.explanation
+        );
+        let second = &rankings[1];
+        assert_eq!((second.rank, second.net), (2, 2));
+        assert_eq!((second.cells_inside, second.cells_around), (0, 3));
+        assert_eq!(second.samples, 300);
+        assert!(second.score < best.score);
+        assert!(
+            second
+                .explanation
+                .starts_with("Most samples. 0 cells inside and 3 around, 0% NR/LTE"),
+            "{}",
+            second.explanation
+        );
+    }
+
+    #[test]
+    fn test_fresh_data_breaks_ties() {
+        let cells = vec![
+            cell(1, Radio::Lte, 52.515, 13.24, 50, 900),
+            cell(2, Radio::Lte, 52.515, 13.24, 50, 10),
+        ];
+
+        let rankings = rank_operators(&stadium(), 500.0, cells, now());
+
+        assert_eq!(rankings[0].net, 2);
+        assert_eq!(rankings[0].median_age_days, 10);
+        assert!(rankings[0].explanation.contains("Freshest data"));
+        assert!(!rankings[1].explanation.contains("Freshest data"));
+    }
+
+    #[test]
+    fn test_ties_are_no_lead() {
+        let rankings = rank_operators(
+            &stadium(),
+            500.0,
+            vec![cell(1, Radio::Lte, 52.515, 13.24, 7, 3)],
+            now(),
+        );
+
+        assert_eq!(
+            rankings[0].explanation,
+            "1 cell inside and 0 around, 100% NR/LTE, 7 samples, median update 3 days ago."
+        );
+    }
+
+    #[test]
+    fn test_median_age() {
+        let operator = OperatorCells {
+            ages_days: vec![10.0, 2.0, 30.0, 4.0],
+            ..OperatorCells::default()
+        };
+
+        assert_eq!(operator.median_age_days(), 7.0);
+    }
+
+    /// Integration tests for the venue query using testcontainers.
+    #[cfg(feature = "integration_tests")]
+    mod venue_integration {
+        use super::*;
+        use crate::schema::cells;
+        use crate::utils::test_db::get_test_connection;
+
+        #[test]
+        fn test_cells_outside_the_margin_are_ignored() {
+            let (_container, mut conn) = get_test_connection();
+            let now = chrono::Utc::now().naive_utc();
+            let inside = Cell {
+                updated: now,
+                ..cell(1, Radio::Lte, 52.515, 13.24, 10, 0)
+            };
+            let far = Cell {
+                cell: 2,
+                updated: now,
+                ..cell(2, Radio::Lte, 52.55, 13.24, 10, 0)
+            };
+            diesel::insert_into(cells::table)
+                .values(&vec![inside, far])
+                .execute(&mut conn)
+                .unwrap();
+
+            let response = query_venue_operators(&stadium(), 500.0, &mut conn).unwrap();
+
+            assert_eq!(response.operators.len(), 1);
+            assert_eq!(response.operators[0].net, 1);
+            assert_eq!(response.operators[0].cells_inside, 1);
+        }
+    }
+}
@@ -5,6 +5,7 @@ pub mod utils;
 
 use std::sync::Arc;
 
+use futures::FutureExt;
 use tokio::{
     signal::{
         ctrl_c,
@@ -15,14 +16,17 @@ use tokio::{
         Mutex,
     },
 };
-use tracing::info;
+use tracing::{info, warn};
 
 use utils::{
     config::CONFIG,
     data::update_loop,
+    events::SyncEvents,
+    grpc::start_grpc_server,
     server::start_server,
     telemetry::init_telemetry,
     utils::{flatten, FutureError},
+    webhooks::run_webhooks,
 };
 
 async fn process_handling(
@@ -64,17 +68,31 @@ async fn main() {
 
     // Initialize telemetry FIRST, before anything else
     init_telemetry(config.clone()).unwrap();
+    if config.random_cursor_secret {
+        warn!("CURSOR_SECRET not set, using a random secret. Cursors are only valid on this instance until it restarts");
+    }
 
     lazy_static::lazy_static! {
         static ref HALT: Arc<Mutex<bool>> = Arc::new(Mutex::new(false));
     }
     let (tx, rx) = oneshot::channel();
+    let shutdown = rx.shared();
+
+    let events = SyncEvents::new();
 
     let process = tokio::spawn(process_handling(&HALT, tx));
-    let update = tokio::spawn(update_loop(&HALT, config.clone()));
-    let server = tokio::spawn(start_server(rx, config.clone()));
+    let webhooks = tokio::spawn(run_webhooks(config.clone(), events.stream()));
+    let update = tokio::spawn(update_loop(&HALT, config.clone(), events.clone()));
+    let server = tokio::spawn(start_server(shutdown.clone(), config.clone(), events));
+    let grpc_server = tokio::spawn(start_grpc_server(shutdown, config.clone()));
 
-    match tokio::try_join!(flatten(update), flatten(process), flatten(server)) {
+    match tokio::try_join!(
+        flatten(update),
+        flatten(process),
+        flatten(server),
+        flatten(grpc_server),
+        flatten(webhooks)
+    ) {
         Ok(_) => {}
         Err(err) => {
             info!("Failed with {}.", err);
@@ -1,4 +1,7 @@
-use crate::schema::sql_types::{CellsRadioEnum, LastUpdatesUpdateTypeEnum};
+use crate::schema::sql_types::{
+    CellChangesRadioEnum, CellDensityRadioEnum, CellsRadioEnum, LastUpdatesUpdateTypeEnum,
+};
+use crate::utils::confidence::confidence;
 use chrono::NaiveDateTime;
 use diesel::deserialize::FromSql;
 use diesel::mysql::{Mysql, MysqlValue};
@@ -9,8 +12,20 @@ use diesel::*;
 use serde_with::BoolFromInt;
 use std::io::Write;
 
-#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, FromSqlRow, AsExpression)]
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Eq,
+    serde::Deserialize,
+    serde::Serialize,
+    FromSqlRow,
+    AsExpression,
+    utoipa::ToSchema,
+)]
 #[diesel(sql_type = CellsRadioEnum)]
+#[diesel(sql_type = CellChangesRadioEnum)]
+#[diesel(sql_type = CellDensityRadioEnum)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum Radio {
     Gsm,
@@ -20,6 +35,19 @@ pub enum Radio {
     Nr,
 }
 
+impl Radio {
+    /// Rank of the radio technology, higher is newer.
+    pub fn generation(&self) -> u8 {
+        match self {
+            Radio::Cdma => 0,
+            Radio::Gsm => 1,
+            Radio::Umts => 2,
+            Radio::Lte => 3,
+            Radio::Nr => 4,
+        }
+    }
+}
+
 impl ToSql<CellsRadioEnum, Mysql> for Radio {
     fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Mysql>) -> serialize::Result {
         match *self {
@@ -46,11 +74,35 @@ impl FromSql<CellsRadioEnum, Mysql> for Radio {
     }
 }
 
+impl ToSql<CellChangesRadioEnum, Mysql> for Radio {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Mysql>) -> serialize::Result {
+        <Radio as ToSql<CellsRadioEnum, Mysql>>::to_sql(self, out)
+    }
+}
+
+impl FromSql<CellChangesRadioEnum, Mysql> for Radio {
+    fn from_sql(bytes: MysqlValue<'_>) -> deserialize::Result<Self> {
+        <Radio as FromSql<CellsRadioEnum, Mysql>>::from_sql(bytes)
+    }
+}
+
+impl ToSql<CellDensityRadioEnum, Mysql> for Radio {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Mysql>) -> serialize::Result {
+        <Radio as ToSql<CellsRadioEnum, Mysql>>::to_sql(self, out)
+    }
+}
+
+impl FromSql<CellDensityRadioEnum, Mysql> for Radio {
+    fn from_sql(bytes: MysqlValue<'_>) -> deserialize::Result<Self> {
+        <Radio as FromSql<CellsRadioEnum, Mysql>>::from_sql(bytes)
+    }
+}
+
 #[serde_with::serde_as]
 #[derive(Queryable, Selectable, Insertable, QueryableByName)]
 #[diesel(table_name = crate::schema::cells)]
 #[diesel(check_for_backend(diesel::mysql::Mysql))]
-#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[derive(Debug, Clone, serde::Deserialize, utoipa::ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct Cell {
     pub radio: Radio,
@@ -65,6 +117,7 @@ pub struct Cell {
     pub cell_range: u32,
     pub samples: u32,
     #[serde_as(as = "BoolFromInt")]
+    #[schema(value_type = u8, minimum = 0, maximum = 1)]
     pub changeable: bool,
     #[serde_as(as = "chrono::DateTime<chrono::Utc>")]
     pub created: NaiveDateTime,
@@ -73,8 +126,45 @@ pub struct Cell {
     pub average_signal: Option<i16>,
 }
 
-#[derive(Debug, FromSqlRow, AsExpression, PartialEq, Eq)]
+/// Serialized with the derived `confidence`, see `utils::confidence`.
+impl serde::Serialize for Cell {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut state = serializer.serialize_struct("Cell", 15)?;
+        state.serialize_field("radio", &self.radio)?;
+        state.serialize_field("mcc", &self.mcc)?;
+        state.serialize_field("net", &self.net)?;
+        state.serialize_field("area", &self.area)?;
+        state.serialize_field("cell", &self.cell)?;
+        state.serialize_field("unit", &self.unit)?;
+        state.serialize_field("lon", &self.lon)?;
+        state.serialize_field("lat", &self.lat)?;
+        state.serialize_field("cellRange", &self.cell_range)?;
+        state.serialize_field("samples", &self.samples)?;
+        state.serialize_field("changeable", &u8::from(self.changeable))?;
+        state.serialize_field("created", &self.created.and_utc())?;
+        state.serialize_field("updated", &self.updated.and_utc())?;
+        state.serialize_field("averageSignal", &self.average_signal)?;
+        state.serialize_field("confidence", &confidence(self))?;
+        state.end()
+    }
+}
+
+#[derive(
+    Debug,
+    FromSqlRow,
+    AsExpression,
+    PartialEq,
+    Eq,
+    Hash,
+    Clone,
+    Copy,
+    serde::Serialize,
+    utoipa::ToSchema,
+)]
 #[diesel(sql_type = LastUpdatesUpdateTypeEnum)]
+#[serde(rename_all = "lowercase")]
 pub enum LastUpdatesType {
     Full,
     Diff,
@@ -108,6 +198,38 @@ pub struct LastUpdates {
     pub update_type: LastUpdatesType,
 }
 
+/// A cell inserted, modified or removed by a sync.
+#[derive(Queryable, Selectable, Insertable)]
+#[diesel(table_name = crate::schema::cell_changes)]
+#[diesel(check_for_backend(diesel::mysql::Mysql))]
+#[derive(Debug, Clone)]
+pub struct CellChange {
+    pub radio: Radio,
+    pub mcc: u16,
+    pub net: u16,
+    pub area: u32,
+    pub cell: u64,
+    pub changed_at: NaiveDateTime,
+    pub removed: bool,
+}
+
+/// One attempt to deliver a webhook.
+#[derive(Queryable, Selectable, Insertable)]
+#[diesel(table_name = crate::schema::webhook_deliveries)]
+#[diesel(check_for_backend(diesel::mysql::Mysql))]
+#[derive(Debug, Clone)]
+pub struct WebhookDeliveryAttempt {
+    pub delivery_id: String,
+    pub url: String,
+    pub event: String,
+    pub attempt: u32,
+    pub status_code: Option<u16>,
+    pub error: Option<String>,
+    pub succeeded: bool,
+    pub payload: String,
+    pub attempted_at: NaiveDateTime,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -230,6 +352,20 @@ mod tests {
             assert!(json.contains("\"radio\":\"LTE\""));
         }
 
+        #[test]
+        fn test_serialize_round_trips_with_confidence() {
+            let cell = sample_cell();
+            let json = serde_json::to_value(&cell).unwrap();
+
+            assert_eq!(json["changeable"], 1);
+            assert_eq!(json["created"], "2024-01-15T10:30:00Z");
+            let confidence = json["confidence"].as_f64().unwrap();
+            assert!(confidence > 0.0 && confidence < 1.0);
+            let decoded: Cell = serde_json::from_value(json).unwrap();
+            assert_eq!(decoded.created, cell.created);
+            assert!(decoded.changeable);
+        }
+
         #[test]
         fn test_serialize_optional_fields() {
             let mut cell = sample_cell();
@@ -1,6 +1,14 @@
 // @generated automatically by Diesel CLI.
 
 pub mod sql_types {
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(mysql_type(name = "Enum"))]
+    pub struct CellChangesRadioEnum;
+
+    #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
+    #[diesel(mysql_type(name = "Enum"))]
+    pub struct CellDensityRadioEnum;
+
     #[derive(diesel::query_builder::QueryId, diesel::sql_types::SqlType)]
     #[diesel(mysql_type(name = "Enum"))]
     pub struct CellsRadioEnum;
@@ -10,6 +18,39 @@ pub mod sql_types {
     pub struct LastUpdatesUpdateTypeEnum;
 }
 
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::CellChangesRadioEnum;
+
+    cell_changes (mcc, net, area, cell, radio) {
+        #[max_length = 4]
+        radio -> CellChangesRadioEnum,
+        mcc -> Unsigned<Smallint>,
+        net -> Unsigned<Smallint>,
+        area -> Unsigned<Integer>,
+        cell -> Unsigned<Bigint>,
+        changed_at -> Datetime,
+        removed -> Bool,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::CellDensityRadioEnum;
+
+    cell_density (resolution, lat_index, lon_index, radio, mcc, net) {
+        resolution -> Unsigned<Tinyint>,
+        lat_index -> Bigint,
+        lon_index -> Bigint,
+        #[max_length = 4]
+        radio -> CellDensityRadioEnum,
+        mcc -> Unsigned<Smallint>,
+        net -> Unsigned<Smallint>,
+        cells -> Bigint,
+        samples -> Unsigned<Bigint>,
+    }
+}
+
 diesel::table! {
     use diesel::sql_types::*;
     use super::sql_types::CellsRadioEnum;
@@ -44,4 +85,28 @@ diesel::table! {
     }
 }
 
-diesel::allow_tables_to_appear_in_same_query!(cells, last_updates,);
+diesel::table! {
+    webhook_deliveries (id) {
+        id -> Unsigned<Bigint>,
+        #[max_length = 32]
+        delivery_id -> Char,
+        #[max_length = 2048]
+        url -> Varchar,
+        #[max_length = 32]
+        event -> Varchar,
+        attempt -> Unsigned<Integer>,
+        status_code -> Nullable<Unsigned<Smallint>>,
+        error -> Nullable<Text>,
+        succeeded -> Bool,
+        payload -> Text,
+        attempted_at -> Datetime,
+    }
+}
+
+diesel::allow_tables_to_appear_in_same_query!(
+    cell_changes,
+    cell_density,
+    cells,
+    last_updates,
+    webhook_deliveries,
+);
@@ -0,0 +1,272 @@
+use std::convert::Infallible;
+
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use warp::http::{header, HeaderValue, StatusCode};
+use warp::reply::Response;
+use warp::{Filter, Reply};
+
+use super::update_type::{latest_update_window, next_update_window};
+
+const HTTP_DATE_FORMAT: &str = "%a, %d %b %Y %H:%M:%S GMT";
+/// Seconds responses may be cached while the sync of the current update
+/// window hasn't landed yet, the data may change any moment.
+const SYNC_PENDING_MAX_AGE_S: i64 = 60;
+
+/// Conditional request headers sent by the client.
+#[derive(Debug, Default, Clone)]
+pub struct Conditions {
+    pub if_none_match: Option<String>,
+    pub if_modified_since: Option<String>,
+}
+
+/// Extracts the conditional request headers.
+pub fn conditions() -> impl Filter<Extract = (Conditions,), Error = Infallible> + Clone {
+    warp::header::optional::<String>("if-none-match")
+        .and(warp::header::optional::<String>("if-modified-since"))
+        .map(|if_none_match, if_modified_since| Conditions {
+            if_none_match,
+            if_modified_since,
+        })
+        .or(warp::any().map(Conditions::default))
+        .unify()
+}
+
+/// Cache validators of a response, derived from the dataset version and the request.
+#[derive(Debug, Clone)]
+pub struct CacheValidators {
+    pub etag: String,
+    pub last_modified: DateTime<Utc>,
+    pub expires: DateTime<Utc>,
+    now: DateTime<Utc>,
+}
+
+impl CacheValidators {
+    /// Builds the validators for a request on `route` with the given (parsed) query.
+    /// `last_update` is the time of the latest sync, the data can't change in between syncs.
+    /// Once an update window has started, responses stay fresh only briefly
+    /// until its sync has landed.
+    pub fn new<Q: Serialize>(
+        route: &str,
+        query: &Q,
+        last_update: NaiveDateTime,
+        now: DateTime<Utc>,
+    ) -> Self {
+        // Re-serializing the parsed query gives a canonical form, independent of
+        // parameter order or encoding details in the original request
+        let canonical_query = serde_urlencoded::to_string(query).unwrap_or_default();
+
+        let mut hasher = Sha256::new();
+        hasher.update(last_update.and_utc().timestamp_micros().to_be_bytes());
+        hasher.update(route.as_bytes());
+        hasher.update([0]);
+        hasher.update(canonical_query.as_bytes());
+        let digest = hasher.finalize();
+        let hash: String = digest[..16].iter().map(|b| format!("{:02x}", b)).collect();
+
+        CacheValidators {
+            etag: format!("\"{}\"", hash),
+            // HTTP dates have second precision
+            last_modified: DateTime::from_timestamp(last_update.and_utc().timestamp(), 0)
+                .unwrap_or_default(),
+            expires: if last_update.and_utc() < latest_update_window(now) {
+                now + Duration::seconds(SYNC_PENDING_MAX_AGE_S)
+            } else {
+                next_update_window(now)
+            },
+            now,
+        }
+    }
+
+    /// Whether the client's cached copy is still fresh.
+    /// `If-None-Match` takes precedence over `If-Modified-Since` (RFC 9110, 13.2.2).
+    pub fn is_not_modified(&self, conditions: &Conditions) -> bool {
+        if let Some(ref if_none_match) = conditions.if_none_match {
+            return if_none_match
+                .split(',')
+                .map(str::trim)
+                .any(|tag| tag == "*" || tag.strip_prefix("W/").unwrap_or(tag) == self.etag);
+        }
+
+        if let Some(ref if_modified_since) = conditions.if_modified_since {
+            if let Ok(since) = DateTime::parse_from_rfc2822(if_modified_since) {
+                return self.last_modified <= since.with_timezone(&Utc);
+            }
+        }
+
+        false
+    }
+
+    fn max_age(&self) -> i64 {
+        (self.expires - self.now).num_seconds().max(0)
+    }
+
+    /// Adds `ETag`, `Last-Modified` and `Cache-Control` to a reply.
+    /// Responses are negotiated on `Accept`, so caches are told to key on it.
+    pub fn apply(&self, reply: impl Reply) -> Response {
+        let mut response = reply.into_response();
+        let headers = response.headers_mut();
+
+        let values = [
+            (header::ETAG, self.etag.clone()),
+            (
+                header::LAST_MODIFIED,
+                self.last_modified.format(HTTP_DATE_FORMAT).to_string(),
+            ),
+            (
+                header::CACHE_CONTROL,
+                format!("public, max-age={}", self.max_age()),
+            ),
+            (header::VARY, "Accept".to_string()),
+        ];
+        for (name, value) in values {
+            if let Ok(value) = HeaderValue::from_str(&value) {
+                headers.insert(name, value);
+            }
+        }
+
+        response
+    }
+
+    /// An empty `304 Not Modified` response carrying the validators.
+    pub fn not_modified(&self) -> Response {
+        self.apply(StatusCode::NOT_MODIFIED)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[derive(Serialize)]
+    struct Query {
+        mcc: u16,
+        radio: Option<String>,
+    }
+
+    fn last_update() -> NaiveDateTime {
+        Utc.with_ymd_and_hms(2025, 12, 20, 5, 12, 30)
+            .unwrap()
+            .naive_utc()
+    }
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 12, 20, 10, 0, 0).unwrap()
+    }
+
+    fn validators(mcc: u16) -> CacheValidators {
+        CacheValidators::new("cells", &Query { mcc, radio: None }, last_update(), now())
+    }
+
+    #[test]
+    fn test_etag_is_stable_for_same_request() {
+        assert_eq!(validators(262).etag, validators(262).etag);
+    }
+
+    #[test]
+    fn test_etag_differs_per_query() {
+        assert_ne!(validators(262).etag, validators(310).etag);
+    }
+
+    #[test]
+    fn test_etag_differs_per_route() {
+        let query = Query {
+            mcc: 262,
+            radio: None,
+        };
+        let cell = CacheValidators::new("cell", &query, last_update(), now());
+        let cells = CacheValidators::new("cells", &query, last_update(), now());
+
+        assert_ne!(cell.etag, cells.etag);
+    }
+
+    #[test]
+    fn test_etag_changes_with_dataset_version() {
+        let query = Query {
+            mcc: 262,
+            radio: None,
+        };
+        let newer = last_update() + chrono::Duration::days(1);
+
+        assert_ne!(
+            CacheValidators::new("cells", &query, last_update(), now()).etag,
+            CacheValidators::new("cells", &query, newer, now()).etag
+        );
+    }
+
+    #[test]
+    fn test_if_none_match_matches_etag() {
+        let validators = validators(262);
+        let conditions = Conditions {
+            if_none_match: Some(format!("\"other\", W/{}", validators.etag)),
+            if_modified_since: None,
+        };
+
+        assert!(validators.is_not_modified(&conditions));
+    }
+
+    #[test]
+    fn test_if_none_match_takes_precedence() {
+        let conditions = Conditions {
+            if_none_match: Some("\"other\"".to_string()),
+            if_modified_since: Some("Sat, 20 Dec 2025 06:00:00 GMT".to_string()),
+        };
+
+        assert!(!validators(262).is_not_modified(&conditions));
+    }
+
+    #[test]
+    fn test_if_modified_since() {
+        let validators = validators(262);
+        let at = |value: &str| Conditions {
+            if_none_match: None,
+            if_modified_since: Some(value.to_string()),
+        };
+
+        assert!(validators.is_not_modified(&at("Sat, 20 Dec 2025 05:12:30 GMT")));
+        assert!(validators.is_not_modified(&at("Sat, 20 Dec 2025 06:00:00 GMT")));
+        assert!(!validators.is_not_modified(&at("Sat, 20 Dec 2025 05:00:00 GMT")));
+        assert!(!validators.is_not_modified(&at("not a date")));
+    }
+
+    #[test]
+    fn test_apply_sets_headers() {
+        let response = validators(262).apply(warp::reply());
+        let headers = response.headers();
+
+        assert_eq!(headers[header::ETAG], validators(262).etag.as_str());
+        assert_eq!(
+            headers[header::LAST_MODIFIED],
+            "Sat, 20 Dec 2025 05:12:30 GMT"
+        );
+        // 10:00 until the next update window at 04:00 the next day
+        assert_eq!(headers[header::CACHE_CONTROL], "public, max-age=64800");
+        assert_eq!(headers[header::VARY], "Accept");
+    }
+
+    #[test]
+    fn test_max_age_is_short_while_the_sync_is_pending() {
+        let query = Query {
+            mcc: 262,
+            radio: None,
+        };
+        let yesterday = last_update() - chrono::Duration::days(1);
+        let pending = Utc.with_ymd_and_hms(2025, 12, 20, 4, 30, 0).unwrap();
+
+        let stale = CacheValidators::new("cells", &query, yesterday, pending);
+        let synced = CacheValidators::new("cells", &query, last_update(), now());
+
+        assert_eq!(stale.max_age(), SYNC_PENDING_MAX_AGE_S);
+        assert_eq!(synced.max_age(), 64800);
+    }
+
+    #[test]
+    fn test_not_modified_has_304_status() {
+        let response = validators(262).not_modified();
+
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert!(response.headers().contains_key(header::ETAG));
+    }
+}
@@ -0,0 +1,396 @@
+use std::convert::Infallible;
+use std::io;
+
+use async_compression::tokio::write::{BrotliEncoder, GzipEncoder, ZstdEncoder};
+use futures::{stream, TryStreamExt};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tracing::error;
+use warp::http::header::{self, HeaderMap, HeaderValue};
+use warp::http::StatusCode;
+use warp::hyper::body::{self, Body, Bytes, HttpBody};
+use warp::reply::Response;
+use warp::{Filter, Reply};
+
+use super::negotiation::weighted_values;
+
+/// A content coding the server can compress responses with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Brotli,
+    Zstd,
+    Gzip,
+}
+
+impl Encoding {
+    /// The server's preference, used when the client accepts several equally.
+    const PREFERRED: [Encoding; 3] = [Encoding::Brotli, Encoding::Zstd, Encoding::Gzip];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Encoding::Brotli => "br",
+            Encoding::Zstd => "zstd",
+            Encoding::Gzip => "gzip",
+        }
+    }
+}
+
+/// Picks the encoding with the highest quality from an `Accept-Encoding` header.
+/// `*` covers the encodings that are not listed, `q=0` excludes one.
+pub fn negotiate_encoding(accept_encoding: &str) -> Option<Encoding> {
+    let values = weighted_values(accept_encoding);
+    let quality_of = |name: &str| {
+        values
+            .iter()
+            .find(|(value, _)| value == name)
+            .map(|(_, quality)| *quality)
+    };
+    let wildcard = quality_of("*");
+
+    let mut best: Option<(Encoding, f32)> = None;
+    for encoding in Encoding::PREFERRED {
+        let Some(quality) = quality_of(encoding.name()).or(wildcard) else {
+            continue;
+        };
+        if quality > 0.0 && best.is_none_or(|(_, best_quality)| quality > best_quality) {
+            best = Some((encoding, quality));
+        }
+    }
+    best.map(|(encoding, _)| encoding)
+}
+
+/// Extracts the raw `Accept-Encoding` header, ignoring values that aren't valid text.
+pub fn accept_encoding() -> impl Filter<Extract = (Option<String>,), Error = Infallible> + Clone {
+    warp::header::headers_cloned().map(|headers: HeaderMap| {
+        headers
+            .get(header::ACCEPT_ENCODING)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string)
+    })
+}
+
+/// Media types that are already compressed and don't shrink any further.
+fn is_compressible(content_type: Option<&HeaderValue>) -> bool {
+    let Some(content_type) = content_type.and_then(|value| value.to_str().ok()) else {
+        return true;
+    };
+    let content_type = content_type.to_ascii_lowercase();
+    if content_type.starts_with("image/svg") {
+        return true;
+    }
+    ![
+        "image/",
+        "audio/",
+        "video/",
+        "application/zip",
+        "application/gzip",
+    ]
+    .iter()
+    .any(|prefix| content_type.starts_with(prefix))
+}
+
+/// Compresses a response with the encoding negotiated from `Accept-Encoding`.
+///
+/// Bodies of a known size are only compressed from `min_size` bytes on. Streamed
+/// bodies, like the event stream, are always compressed and flushed after every
+/// chunk, so clients receive each chunk as soon as it's sent.
+pub async fn compress(
+    mut response: Response,
+    accept_encoding: Option<String>,
+    min_size: usize,
+) -> Response {
+    let status = response.status();
+    if status == StatusCode::NO_CONTENT
+        || status == StatusCode::NOT_MODIFIED
+        || response.headers().contains_key(header::CONTENT_ENCODING)
+        || !is_compressible(response.headers().get(header::CONTENT_TYPE))
+    {
+        return response;
+    }
+
+    // The decision depends on the header even if this response isn't compressed
+    response
+        .headers_mut()
+        .append(header::VARY, HeaderValue::from_static("Accept-Encoding"));
+
+    let Some(encoding) = accept_encoding.as_deref().and_then(negotiate_encoding) else {
+        return response;
+    };
+    let size = response.body().size_hint().exact();
+    if size.is_some_and(|size| size < min_size as u64) {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let body = match size {
+        Some(_) => match compress_full(body, encoding).await {
+            Ok(body) => body,
+            Err(e) => {
+                error!(
+                    "Failed to compress response with {}: {}",
+                    encoding.name(),
+                    e
+                );
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+        },
+        None => compress_stream(body, encoding),
+    };
+
+    let headers = &mut parts.headers;
+    headers.insert(
+        header::CONTENT_ENCODING,
+        HeaderValue::from_static(encoding.name()),
+    );
+    headers.remove(header::CONTENT_LENGTH);
+    // The compressed bytes differ from the uncompressed representation
+    if let Some(etag) = headers.get(header::ETAG).and_then(|v| v.to_str().ok()) {
+        if !etag.starts_with("W/") {
+            if let Ok(weak) = HeaderValue::from_str(&format!("W/{}", etag)) {
+                headers.insert(header::ETAG, weak);
+            }
+        }
+    }
+
+    Response::from_parts(parts, body)
+}
+
+/// An encoder writing into a buffer the compressed output is taken from.
+trait CompressedWriter: AsyncWrite + Unpin + Send {
+    fn output(&mut self) -> &mut Vec<u8>;
+
+    fn take_output(&mut self) -> Bytes {
+        Bytes::from(std::mem::take(self.output()))
+    }
+}
+
+impl CompressedWriter for BrotliEncoder<Vec<u8>> {
+    fn output(&mut self) -> &mut Vec<u8> {
+        self.get_mut()
+    }
+}
+
+impl CompressedWriter for ZstdEncoder<Vec<u8>> {
+    fn output(&mut self) -> &mut Vec<u8> {
+        self.get_mut()
+    }
+}
+
+impl CompressedWriter for GzipEncoder<Vec<u8>> {
+    fn output(&mut self) -> &mut Vec<u8> {
+        self.get_mut()
+    }
+}
+
+fn encoder(encoding: Encoding) -> Box<dyn CompressedWriter> {
+    match encoding {
+        Encoding::Brotli => Box::new(BrotliEncoder::new(vec![])),
+        Encoding::Zstd => Box::new(ZstdEncoder::new(vec![])),
+        Encoding::Gzip => Box::new(GzipEncoder::new(vec![])),
+    }
+}
+
+async fn compress_full(body: Body, encoding: Encoding) -> io::Result<Body> {
+    let bytes = body::to_bytes(body).await.map_err(io::Error::other)?;
+    let mut writer = encoder(encoding);
+    writer.write_all(&bytes).await?;
+    writer.shutdown().await?;
+    Ok(Body::from(writer.take_output()))
+}
+
+fn compress_stream(body: Body, encoding: Encoding) -> Body {
+    let compressed = stream::try_unfold(Some((body, encoder(encoding))), |state| async move {
+        let Some((mut body, mut writer)) = state else {
+            return Ok::<_, io::Error>(None);
+        };
+        match body.data().await {
+            Some(chunk) => {
+                writer.write_all(&chunk.map_err(io::Error::other)?).await?;
+                writer.flush().await?;
+                Ok(Some((writer.take_output(), Some((body, writer)))))
+            }
+            None => {
+                writer.shutdown().await?;
+                Ok(Some((writer.take_output(), None)))
+            }
+        }
+    })
+    .try_filter(|chunk| futures::future::ready(!chunk.is_empty()));
+    Body::wrap_stream(compressed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_compression::tokio::bufread::{BrotliDecoder, GzipDecoder, ZstdDecoder};
+    use tokio::io::{AsyncRead, AsyncReadExt};
+
+    async fn decode(encoding: &str, bytes: &[u8]) -> Vec<u8> {
+        let mut decoder: Box<dyn AsyncRead + Unpin> = match encoding {
+            "br" => Box::new(BrotliDecoder::new(bytes)),
+            "zstd" => Box::new(ZstdDecoder::new(bytes)),
+            "gzip" => Box::new(GzipDecoder::new(bytes)),
+            other => panic!("unexpected encoding {}", other),
+        };
+        let mut decoded = vec![];
+        decoder.read_to_end(&mut decoded).await.unwrap();
+        decoded
+    }
+
+    fn json_response(size: usize) -> Response {
+        let mut response = Response::new(Body::from("x".repeat(size)));
+        response.headers_mut().insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("application/json"),
+        );
+        response
+    }
+
+    mod negotiate_encoding {
+        use super::*;
+
+        #[test]
+        fn test_single_encodings() {
+            assert_eq!(negotiate_encoding("gzip"), Some(Encoding::Gzip));
+            assert_eq!(negotiate_encoding("br"), Some(Encoding::Brotli));
+            assert_eq!(negotiate_encoding("zstd"), Some(Encoding::Zstd));
+        }
+
+        #[test]
+        fn test_server_preference_breaks_ties() {
+            assert_eq!(
+                negotiate_encoding("gzip, deflate, br, zstd"),
+                Some(Encoding::Brotli)
+            );
+        }
+
+        #[test]
+        fn test_highest_quality_wins() {
+            assert_eq!(
+                negotiate_encoding("br;q=0.2, gzip;q=0.8"),
+                Some(Encoding::Gzip)
+            );
+        }
+
+        #[test]
+        fn test_wildcard_and_exclusions() {
+            assert_eq!(negotiate_encoding("*"), Some(Encoding::Brotli));
+            assert_eq!(negotiate_encoding("*, br;q=0"), Some(Encoding::Zstd));
+            assert_eq!(negotiate_encoding("gzip;q=0"), None);
+        }
+
+        #[test]
+        fn test_unsupported_encodings() {
+            assert_eq!(negotiate_encoding("identity"), None);
+            assert_eq!(negotiate_encoding("deflate, compress"), None);
+            assert_eq!(negotiate_encoding(""), None);
+        }
+    }
+
+    mod compress {
+        use super::*;
+
+        #[tokio::test]
+        async fn test_compresses_large_bodies() {
+            for encoding in ["br", "zstd", "gzip"] {
+                let response =
+                    compress(json_response(4096), Some(encoding.to_string()), 1024).await;
+
+                assert_eq!(response.headers()[header::CONTENT_ENCODING], encoding);
+                assert_eq!(response.headers()[header::VARY], "Accept-Encoding");
+                let bytes = body::to_bytes(response.into_body()).await.unwrap();
+                assert!(bytes.len() < 4096);
+                assert_eq!(
+                    decode(encoding, &bytes).await,
+                    "x".repeat(4096).into_bytes()
+                );
+            }
+        }
+
+        #[tokio::test]
+        async fn test_skips_small_bodies() {
+            let response = compress(json_response(100), Some("gzip".to_string()), 1024).await;
+
+            assert!(!response.headers().contains_key(header::CONTENT_ENCODING));
+            assert_eq!(response.headers()[header::VARY], "Accept-Encoding");
+        }
+
+        #[tokio::test]
+        async fn test_skips_without_accept_encoding() {
+            let response = compress(json_response(4096), None, 1024).await;
+
+            assert!(!response.headers().contains_key(header::CONTENT_ENCODING));
+            let bytes = body::to_bytes(response.into_body()).await.unwrap();
+            assert_eq!(bytes.len(), 4096);
+        }
+
+        #[tokio::test]
+        async fn test_skips_compressed_media() {
+            let mut response = json_response(4096);
+            response
+                .headers_mut()
+                .insert(header::CONTENT_TYPE, HeaderValue::from_static("image/png"));
+
+            let response = compress(response, Some("gzip".to_string()), 1024).await;
+
+            assert!(!response.headers().contains_key(header::CONTENT_ENCODING));
+            assert!(!response.headers().contains_key(header::VARY));
+        }
+
+        #[tokio::test]
+        async fn test_skips_not_modified() {
+            let mut response = json_response(0);
+            *response.status_mut() = StatusCode::NOT_MODIFIED;
+
+            let response = compress(response, Some("gzip".to_string()), 0).await;
+
+            assert!(!response.headers().contains_key(header::CONTENT_ENCODING));
+        }
+
+        #[tokio::test]
+        async fn test_weakens_etag_and_keeps_vary() {
+            let mut response = json_response(4096);
+            let headers = response.headers_mut();
+            headers.insert(header::ETAG, HeaderValue::from_static("\"abc\""));
+            headers.insert(header::VARY, HeaderValue::from_static("Accept"));
+            headers.insert(header::CONTENT_LENGTH, HeaderValue::from_static("4096"));
+
+            let response = compress(response, Some("gzip".to_string()), 1024).await;
+
+            let headers = response.headers();
+            assert_eq!(headers[header::ETAG], "W/\"abc\"");
+            let vary: Vec<_> = headers.get_all(header::VARY).iter().collect();
+            assert_eq!(vary, ["Accept", "Accept-Encoding"]);
+            assert!(!headers.contains_key(header::CONTENT_LENGTH));
+        }
+
+        #[tokio::test]
+        async fn test_streams_are_flushed_per_chunk() {
+            let (mut sender, body) = Body::channel();
+            let response = Response::new(body);
+
+            let response = compress(response, Some("gzip".to_string()), 1024).await;
+            assert_eq!(response.headers()[header::CONTENT_ENCODING], "gzip");
+            let mut body = response.into_body();
+
+            // The first chunk arrives while the stream is still open
+            sender
+                .send_data(Bytes::from("data: first\n\n"))
+                .await
+                .unwrap();
+            let mut compressed = body.data().await.unwrap().unwrap().to_vec();
+            assert!(!compressed.is_empty());
+
+            sender
+                .send_data(Bytes::from("data: second\n\n"))
+                .await
+                .unwrap();
+            drop(sender);
+            compressed.extend(body::to_bytes(body).await.unwrap());
+
+            assert_eq!(
+                decode("gzip", &compressed).await,
+                b"data: first\n\ndata: second\n\n"
+            );
+        }
+    }
+}
@@ -0,0 +1,197 @@
+//! How much a cell's position can be trusted, from the metadata OpenCellID
+//! publishes with it.
+//!
+//! The confidence is the product of three factors in `[0, 1]`:
+//!
+//! - **Position**: `samples / (samples + 5)` for positions averaged from
+//!   measurements. Cells with `changeable = 0` have an exact position from the
+//!   operator and get `1`.
+//! - **Range**: `1` up to 5 km, `5000 / cell_range` above, `0.5` for the
+//!   unknown range `0`. Huge ranges come from few or bad samples.
+//! - **Age**: halves every two years since `updated`.
+
+use chrono::NaiveDateTime;
+use diesel::dsl::sql;
+use diesel::mysql::Mysql;
+use diesel::prelude::*;
+use diesel::sql_types::{Bool, Datetime, Double};
+
+use crate::models::Cell;
+use crate::schema::cells;
+
+/// Samples at which an averaged position gets half the position factor.
+const HALF_CONFIDENCE_SAMPLES: f64 = 5.0;
+/// Ranges up to this are not penalized.
+const MAX_CONFIDENT_RANGE_M: f64 = 5_000.0;
+/// Range factor of cells without a known range.
+const UNKNOWN_RANGE_FACTOR: f64 = 0.5;
+/// Age after which the age factor halves.
+const HALF_LIFE_DAYS: f64 = 730.0;
+
+/// The confidence of a cell at `now`.
+pub fn confidence_at(cell: &Cell, now: NaiveDateTime) -> f64 {
+    let position = if cell.changeable {
+        let samples = f64::from(cell.samples);
+        samples / (samples + HALF_CONFIDENCE_SAMPLES)
+    } else {
+        1.0
+    };
+    let range = match cell.cell_range {
+        0 => UNKNOWN_RANGE_FACTOR,
+        range => (MAX_CONFIDENT_RANGE_M / f64::from(range)).min(1.0),
+    };
+    let age_days = (now - cell.updated).num_seconds().max(0) as f64 / 86_400.0;
+    let age = 0.5f64.powf(age_days / HALF_LIFE_DAYS);
+    position * range * age
+}
+
+/// The confidence of a cell now.
+pub fn confidence(cell: &Cell) -> f64 {
+    confidence_at(cell, chrono::Utc::now().naive_utc())
+}
+
+/// Cells with a confidence of at least `min` at `now`, the same formula as
+/// `confidence_at` in SQL so that filters apply before pagination. The
+/// constants are bound as doubles, integer literals would make MySQL divide in
+/// `DECIMAL` with a few digits only.
+pub fn min_confidence(
+    min: f64,
+    now: NaiveDateTime,
+) -> Box<dyn BoxableExpression<cells::table, Mysql, SqlType = Bool>> {
+    Box::new(
+        sql::<Bool>("IF(changeable, samples / (samples + ")
+            .bind::<Double, _>(HALF_CONFIDENCE_SAMPLES)
+            .sql("), 1) * CASE WHEN cell_range = 0 THEN ")
+            .bind::<Double, _>(UNKNOWN_RANGE_FACTOR)
+            .sql(" ELSE LEAST(")
+            .bind::<Double, _>(MAX_CONFIDENT_RANGE_M)
+            .sql(" / cell_range, 1) END * POW(0.5, GREATEST(TIMESTAMPDIFF(SECOND, updated, ")
+            .bind::<Datetime, _>(now)
+            .sql("), 0) / ")
+            .bind::<Double, _>(HALF_LIFE_DAYS * 86_400.0)
+            .sql(") >= ")
+            .bind::<Double, _>(min),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn cell(samples: u32, range: u32, changeable: bool, age_days: i64, now: NaiveDateTime) -> Cell {
+        Cell {
+            radio: crate::models::Radio::Lte,
+            mcc: 262,
+            net: 1,
+            area: 1,
+            cell: 1,
+            unit: None,
+            lon: 13.4,
+            lat: 52.5,
+            cell_range: range,
+            samples,
+            changeable,
+            created: now - Duration::days(age_days),
+            updated: now - Duration::days(age_days),
+            average_signal: None,
+        }
+    }
+
+    fn now() -> NaiveDateTime {
+        chrono::DateTime::from_timestamp(1_800_000_000, 0)
+            .unwrap()
+            .naive_utc()
+    }
+
+    #[test]
+    fn test_well_measured_fresh_cell() {
+        let confidence = confidence_at(&cell(95, 1000, true, 0, now()), now());
+
+        assert!((confidence - 0.95).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_single_sample_is_weak() {
+        let confidence = confidence_at(&cell(1, 1000, true, 0, now()), now());
+
+        assert!((confidence - 1.0 / 6.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_exact_positions_ignore_samples() {
+        assert_eq!(confidence_at(&cell(1, 1000, false, 0, now()), now()), 1.0);
+    }
+
+    #[test]
+    fn test_huge_and_unknown_ranges_are_penalized() {
+        assert_eq!(
+            confidence_at(&cell(1, 20_000, false, 0, now()), now()),
+            0.25
+        );
+        assert_eq!(confidence_at(&cell(1, 0, false, 0, now()), now()), 0.5);
+    }
+
+    #[test]
+    fn test_confidence_halves_every_two_years() {
+        let confidence = confidence_at(&cell(1, 1000, false, 1460, now()), now());
+
+        assert!((confidence - 0.25).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_future_updates_count_as_fresh() {
+        assert_eq!(confidence_at(&cell(1, 1000, false, -1, now()), now()), 1.0);
+    }
+
+    /// Integration tests comparing the SQL formula with the Rust one.
+    #[cfg(feature = "integration_tests")]
+    mod confidence_integration {
+        use super::*;
+        use crate::utils::test_db::get_test_connection;
+
+        #[test]
+        fn test_sql_filter_matches_the_formula() {
+            let (_container, mut conn) = get_test_connection();
+            let now = chrono::Utc::now().naive_utc();
+            let rows = vec![
+                Cell {
+                    cell: 1,
+                    ..cell(95, 1000, true, 0, now)
+                },
+                Cell {
+                    cell: 2,
+                    ..cell(1, 1000, true, 0, now)
+                },
+                Cell {
+                    cell: 3,
+                    ..cell(50, 20_000, false, 365, now)
+                },
+                Cell {
+                    cell: 4,
+                    ..cell(10, 0, true, 1000, now)
+                },
+            ];
+            diesel::insert_into(cells::table)
+                .values(&rows)
+                .execute(&mut conn)
+                .unwrap();
+
+            for min in [0.1, 0.15, 0.2, 0.5, 0.9] {
+                let mut ids: Vec<u64> = cells::table
+                    .filter(min_confidence(min, now))
+                    .select(cells::cell)
+                    .load(&mut conn)
+                    .unwrap();
+                ids.sort();
+                let expected: Vec<u64> = rows
+                    .iter()
+                    .filter(|row| confidence_at(row, now) >= min)
+                    .map(|row| row.cell)
+                    .collect();
+
+                assert_eq!(ids, expected, "min_confidence={}", min);
+            }
+        }
+    }
+}
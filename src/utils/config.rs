@@ -1,3 +1,5 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Utc};
 use dotenvy::dotenv;
 use once_cell::sync::Lazy;
 use std::{env, net::Ipv4Addr};
@@ -14,8 +16,20 @@ pub struct Config {
     pub otlp_endpoint: Option<String>,
     pub traces_endpoint: Option<String>,
     pub port: u16,
+    pub grpc_port: u16,
     pub bind: Ipv4Addr,
     pub cors_origins: Vec<String>,
+    pub compression_min_size: usize,
+    pub legacy_not_found_null: bool,
+    pub cursor_secret: String,
+    /// Whether `cursor_secret` was generated for this process because `CURSOR_SECRET` isn't set
+    pub random_cursor_secret: bool,
+    pub webhook_urls: Vec<String>,
+    pub webhook_secret: String,
+    pub webhook_max_attempts: u32,
+    /// When the unversioned aliases were deprecated, they aren't if unset
+    pub unversioned_deprecation: Option<DateTime<Utc>>,
+    pub unversioned_sunset: Option<DateTime<Utc>>,
 }
 
 // Initialize dotenv and config only once
@@ -35,13 +49,37 @@ pub static CONFIG: Lazy<Config> = Lazy::new(|| {
         otlp_endpoint: get_non_empty_env_var("OTEL_EXPORTER_OTLP_ENDPOINT"),
         traces_endpoint: get_non_empty_env_var("OTEL_TRACES_COLLECTOR_URL"),
         port: parse_env_var::<u16>("PORT").unwrap_or(3000),
+        grpc_port: parse_env_var::<u16>("GRPC_PORT").unwrap_or(50051),
         bind: parse_env_var::<Ipv4Addr>("BIND").unwrap_or(Ipv4Addr::new(0, 0, 0, 0)),
         cors_origins: get_non_empty_env_var("CORS_ORIGINS")
             .map(|s| s.split(',').map(|o| o.trim().to_string()).collect())
             .unwrap_or_default(),
+        compression_min_size: parse_env_var::<usize>("COMPRESSION_MIN_SIZE").unwrap_or(1024),
+        legacy_not_found_null: parse_env_var::<bool>("LEGACY_NOT_FOUND_NULL").unwrap_or(false),
+        cursor_secret: get_non_empty_env_var("CURSOR_SECRET").unwrap_or_else(random_secret),
+        random_cursor_secret: get_non_empty_env_var("CURSOR_SECRET").is_none(),
+        webhook_urls: get_non_empty_env_var("WEBHOOK_URLS")
+            .map(|s| s.split(',').map(|o| o.trim().to_string()).collect())
+            .unwrap_or_default(),
+        webhook_secret: match get_non_empty_env_var("WEBHOOK_URLS") {
+            Some(_) => get_non_empty_env_var("WEBHOOK_SECRET")
+                .expect("WEBHOOK_SECRET must be set when WEBHOOK_URLS is"),
+            None => String::new(),
+        },
+        webhook_max_attempts: parse_env_var::<u32>("WEBHOOK_MAX_ATTEMPTS").unwrap_or(6),
+        unversioned_deprecation: parse_env_var::<DateTime<Utc>>("UNVERSIONED_DEPRECATION"),
+        unversioned_sunset: parse_env_var::<DateTime<Utc>>("UNVERSIONED_SUNSET"),
     }
 });
 
+/// A random secret for this process, used where instances only need to
+/// share a secret if it is configured.
+fn random_secret() -> String {
+    let mut bytes = [0u8; 32];
+    getrandom::getrandom(&mut bytes).expect("Failed to read random bytes from the OS");
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
 /// Helper function to get environment variable, treating empty strings as None
 /// This handles Docker Compose behavior where unset variables become empty strings
 fn get_non_empty_env_var(key: &str) -> Option<String> {
@@ -59,3 +97,31 @@ fn get_non_empty_env_var(key: &str) -> Option<String> {
 fn parse_env_var<T: std::str::FromStr>(key: &str) -> Option<T> {
     get_non_empty_env_var(key).and_then(|s| s.parse().ok())
 }
+
+/// A configuration with placeholder values for unit tests.
+#[cfg(test)]
+pub fn test_config() -> Config {
+    Config {
+        output_folder: String::from("/tmp/racemap-cell-service/test"),
+        db_url: String::from("mysql://test@127.0.0.1:1/test"),
+        download_source_url: String::from("https://example.com/downloads"),
+        download_source_token: String::from("test-token"),
+        service_name: String::from("cell-service-test"),
+        debug_traces: false,
+        otlp_endpoint: None,
+        traces_endpoint: None,
+        port: 3000,
+        grpc_port: 50051,
+        bind: Ipv4Addr::new(127, 0, 0, 1),
+        cors_origins: vec![],
+        compression_min_size: 1024,
+        legacy_not_found_null: false,
+        cursor_secret: String::from("test-cursor-secret"),
+        random_cursor_secret: false,
+        webhook_urls: vec![],
+        webhook_secret: String::from("test-webhook-secret"),
+        webhook_max_attempts: 3,
+        unversioned_deprecation: None,
+        unversioned_sunset: None,
+    }
+}
@@ -0,0 +1,255 @@
+//! Estimated coverage areas of cells: a circle of the cell's range, optionally
+//! clipped to its Voronoi region among same-operator cells of the same radio.
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::geo::{haversine_distance, LocalFrame};
+use super::geojson::{Polygon, Ring};
+use super::ranging::MIN_RANGE_M;
+use crate::models::Cell;
+
+/// Vertices of a coverage circle.
+const CIRCLE_SEGMENTS: usize = 64;
+/// Upper bound for coverage radii, the reach of GSM timing advance. Larger
+/// ranges in OpenCellID come from bad samples.
+pub const MAX_COVERAGE_RADIUS_M: f64 = 35_000.0;
+/// Cells closer than this are taken to be sectors of the same site. They
+/// cover different directions, not different areas, so they don't clip each other.
+pub const SAME_SITE_DISTANCE_M: f64 = 100.0;
+
+/// How a coverage area is estimated.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum CoverageShape {
+    /// A circle with the cell's range
+    #[default]
+    Circle,
+    /// The circle, clipped to the area closer to the cell than to any
+    /// same-operator cell of the same radio
+    Voronoi,
+}
+
+/// The radius of a cell's coverage circle in meters.
+pub fn coverage_radius(cell: &Cell) -> f64 {
+    f64::from(cell.cell_range).clamp(MIN_RANGE_M, MAX_COVERAGE_RADIUS_M)
+}
+
+/// Whether `other` partitions the plane with `cell` in Voronoi mode.
+pub fn is_voronoi_neighbour(cell: &Cell, other: &Cell) -> bool {
+    other.radio == cell.radio
+        && other.mcc == cell.mcc
+        && other.net == cell.net
+        && !(other.area == cell.area && other.cell == cell.cell)
+}
+
+/// Keeps the part of a convex polygon where `a·x + b·y <= c` (Sutherland-Hodgman).
+fn clip(polygon: &[(f64, f64)], (a, b, c): (f64, f64, f64)) -> Vec<(f64, f64)> {
+    let inside = |(x, y): (f64, f64)| a * x + b * y <= c;
+    let mut clipped = Vec::with_capacity(polygon.len() + 1);
+    for (index, &current) in polygon.iter().enumerate() {
+        let previous = polygon[(index + polygon.len() - 1) % polygon.len()];
+        if inside(current) != inside(previous) {
+            let (dx, dy) = (current.0 - previous.0, current.1 - previous.1);
+            let t = (c - a * previous.0 - b * previous.1) / (a * dx + b * dy);
+            clipped.push((previous.0 + t * dx, previous.1 + t * dy));
+        }
+        if inside(current) {
+            clipped.push(current);
+        }
+    }
+    clipped
+}
+
+/// The estimated coverage polygon of a cell. In Voronoi mode, the circle is
+/// clipped by the perpendicular bisector to every other cell in `others`
+/// that `is_voronoi_neighbour`, unless it is on the same site.
+pub fn coverage_polygon(cell: &Cell, shape: CoverageShape, others: &[Cell]) -> Polygon {
+    let (lat, lon) = (f64::from(cell.lat), f64::from(cell.lon));
+    let frame = LocalFrame::new(lat, lon);
+    let radius = coverage_radius(cell);
+
+    // Counterclockwise, as RFC 7946 asks for exterior rings
+    let mut polygon: Vec<(f64, f64)> = (0..CIRCLE_SEGMENTS)
+        .map(|i| {
+            let angle = std::f64::consts::TAU * i as f64 / CIRCLE_SEGMENTS as f64;
+            (radius * angle.cos(), radius * angle.sin())
+        })
+        .collect();
+
+    if shape == CoverageShape::Voronoi {
+        for other in others
+            .iter()
+            .filter(|other| is_voronoi_neighbour(cell, other))
+        {
+            let (other_lat, other_lon) = (f64::from(other.lat), f64::from(other.lon));
+            let distance = haversine_distance(lat, lon, other_lat, other_lon);
+            if distance < SAME_SITE_DISTANCE_M || distance >= 2.0 * radius {
+                continue;
+            }
+            // Closer to the cell at the origin than to the other cell
+            let (x, y) = frame.to_local(other_lat, other_lon);
+            polygon = clip(&polygon, (x, y, (x * x + y * y) / 2.0));
+        }
+    }
+
+    let mut ring: Ring = polygon
+        .iter()
+        .map(|(east, north)| {
+            let (lat, lon) = frame.to_geo(*east, *north);
+            [lon, lat]
+        })
+        .collect();
+    if let Some(first) = ring.first().copied() {
+        ring.push(first);
+    }
+    Polygon::new(ring)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Radio;
+
+    fn cell_at(east: f64, north: f64, id: u64, range: u32) -> Cell {
+        let (lat, lon) = LocalFrame::new(52.52, 13.405).to_geo(east, north);
+        let now = chrono::Utc::now().naive_utc();
+        Cell {
+            radio: Radio::Lte,
+            mcc: 262,
+            net: 1,
+            area: 100,
+            cell: id,
+            unit: None,
+            lon: lon as f32,
+            lat: lat as f32,
+            cell_range: range,
+            samples: 10,
+            changeable: true,
+            created: now,
+            updated: now,
+            average_signal: None,
+        }
+    }
+
+    /// Ring vertices in meters east and north of the cell, without the closing one.
+    fn local_ring(cell: &Cell, polygon: &Polygon) -> Vec<(f64, f64)> {
+        let frame = LocalFrame::new(f64::from(cell.lat), f64::from(cell.lon));
+        let ring = &polygon.coordinates[0];
+        ring[..ring.len() - 1]
+            .iter()
+            .map(|[lon, lat]| frame.to_local(*lat, *lon))
+            .collect()
+    }
+
+    /// Shoelace formula, positive for counterclockwise rings.
+    fn signed_area(ring: &[(f64, f64)]) -> f64 {
+        ring.iter()
+            .zip(ring.iter().cycle().skip(1))
+            .map(|((x1, y1), (x2, y2))| x1 * y2 - x2 * y1)
+            .sum::<f64>()
+            / 2.0
+    }
+
+    #[test]
+    fn test_circle_has_the_cell_range() {
+        let cell = cell_at(0.0, 0.0, 1, 1000);
+
+        let polygon = coverage_polygon(&cell, CoverageShape::Circle, &[]);
+
+        let ring = &polygon.coordinates[0];
+        assert_eq!(ring.len(), CIRCLE_SEGMENTS + 1);
+        assert_eq!(ring.first(), ring.last());
+        for (east, north) in local_ring(&cell, &polygon) {
+            assert!((east.hypot(north) - 1000.0).abs() < 1.0);
+        }
+    }
+
+    #[test]
+    fn test_circle_is_counterclockwise() {
+        let cell = cell_at(0.0, 0.0, 1, 1000);
+
+        let polygon = coverage_polygon(&cell, CoverageShape::Circle, &[]);
+
+        assert!(signed_area(&local_ring(&cell, &polygon)) > 0.0);
+    }
+
+    #[test]
+    fn test_radius_is_bounded() {
+        assert_eq!(coverage_radius(&cell_at(0.0, 0.0, 1, 0)), MIN_RANGE_M);
+        assert_eq!(
+            coverage_radius(&cell_at(0.0, 0.0, 1, 100_000)),
+            MAX_COVERAGE_RADIUS_M
+        );
+    }
+
+    #[test]
+    fn test_circle_ignores_neighbours() {
+        let cell = cell_at(0.0, 0.0, 1, 1000);
+        let others = [cell_at(1000.0, 0.0, 2, 1000)];
+
+        let polygon = coverage_polygon(&cell, CoverageShape::Circle, &others);
+
+        assert_eq!(polygon, coverage_polygon(&cell, CoverageShape::Circle, &[]));
+    }
+
+    #[test]
+    fn test_voronoi_stops_halfway_to_the_neighbour() {
+        let cell = cell_at(0.0, 0.0, 1, 1000);
+        let others = [cell_at(1000.0, 0.0, 2, 1000)];
+
+        let polygon = coverage_polygon(&cell, CoverageShape::Voronoi, &others);
+        let ring = local_ring(&cell, &polygon);
+
+        let max_east = ring.iter().map(|(east, _)| *east).fold(f64::MIN, f64::max);
+        let min_east = ring.iter().map(|(east, _)| *east).fold(f64::MAX, f64::min);
+        assert!((max_east - 500.0).abs() < 1.0, "{}", max_east);
+        assert!((min_east + 1000.0).abs() < 1.0, "{}", min_east);
+        assert!(signed_area(&ring) > 0.0);
+    }
+
+    #[test]
+    fn test_voronoi_between_three_cells() {
+        let cell = cell_at(0.0, 0.0, 1, 2000);
+        let others = [cell_at(1000.0, 0.0, 2, 1000), cell_at(0.0, 1000.0, 3, 1000)];
+
+        let polygon = coverage_polygon(&cell, CoverageShape::Voronoi, &others);
+        let ring = local_ring(&cell, &polygon);
+
+        for (east, north) in &ring {
+            assert!(
+                *east <= 500.0 + 1.0 && *north <= 500.0 + 1.0,
+                "{} {}",
+                east,
+                north
+            );
+        }
+        assert!(ring
+            .iter()
+            .any(|(east, north)| (east - 500.0).abs() < 1.0 && (north - 500.0).abs() < 1.0));
+    }
+
+    #[test]
+    fn test_voronoi_ignores_other_operators_and_distant_cells() {
+        let cell = cell_at(0.0, 0.0, 1, 1000);
+        let mut other_operator = cell_at(1000.0, 0.0, 2, 1000);
+        other_operator.net = 2;
+        let mut other_radio = cell_at(1000.0, 0.0, 3, 1000);
+        other_radio.radio = Radio::Gsm;
+        let others = [other_operator, other_radio, cell_at(2500.0, 0.0, 4, 1000)];
+
+        let polygon = coverage_polygon(&cell, CoverageShape::Voronoi, &others);
+
+        assert_eq!(polygon, coverage_polygon(&cell, CoverageShape::Circle, &[]));
+    }
+
+    #[test]
+    fn test_voronoi_ignores_sectors_of_the_same_site() {
+        let cell = cell_at(0.0, 0.0, 1, 1000);
+        let others = [cell.clone(), cell_at(30.0, 0.0, 2, 1000)];
+
+        let polygon = coverage_polygon(&cell, CoverageShape::Voronoi, &others);
+
+        assert_eq!(polygon, coverage_polygon(&cell, CoverageShape::Circle, &[]));
+    }
+}
@@ -1,17 +1,20 @@
 use std::env;
 use std::io::Error;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 
 use crate::models::LastUpdatesType;
 use crate::utils::config::Config;
+use crate::utils::events::{SyncEventKind, SyncEvents, SyncStage};
 use async_compression::tokio::bufread::GzipDecoder;
-use chrono::DateTime;
+use chrono::NaiveDateTime;
 use chrono::TimeZone;
+use chrono::Timelike;
 use chrono::Utc;
 
 use super::update_type::get_update_type;
 use super::url_builder::{get_url_of_diff_package, get_url_of_full_package};
-use diesel::RunQueryDsl;
+use diesel::{Connection, RunQueryDsl};
 use futures::stream::TryStreamExt;
 use tokio::sync::Mutex;
 use tokio_util::compat::FuturesAsyncReadCompatExt;
@@ -27,7 +30,15 @@ use super::db::get_last_update;
 use super::db::set_last_update;
 use super::utils::Promise;
 
-async fn load_url(url: String, output: String) -> Promise<()> {
+/// Download progress is reported whenever this many more bytes arrived.
+const PROGRESS_STEP_BYTES: u64 = 16 * 1024 * 1024;
+
+async fn load_url(
+    url: String,
+    output: String,
+    update_type: LastUpdatesType,
+    events: &SyncEvents,
+) -> Promise<()> {
     let response = reqwest::get(url.clone()).await?;
     let status_code = response.status();
     let content_type = response.headers().get("Content-Type").unwrap().to_str()?;
@@ -43,8 +54,29 @@ async fn load_url(url: String, output: String) -> Promise<()> {
         }
     }
 
+    let total_bytes = response.content_length();
+    let downloaded = Arc::new(AtomicU64::new(0));
+    let progress = {
+        let downloaded = downloaded.clone();
+        let events = events.clone();
+        let mut reported = 0;
+        move |chunk: &warp::hyper::body::Bytes| {
+            let bytes =
+                downloaded.fetch_add(chunk.len() as u64, Ordering::Relaxed) + chunk.len() as u64;
+            if bytes - reported >= PROGRESS_STEP_BYTES {
+                reported = bytes;
+                events.emit(SyncEventKind::DownloadProgress {
+                    update_type,
+                    bytes,
+                    total_bytes,
+                });
+            }
+        }
+    };
+
     let stream = response
         .bytes_stream()
+        .inspect_ok(progress)
         .map_err(convert_error)
         .into_async_read()
         .compat();
@@ -54,6 +86,12 @@ async fn load_url(url: String, output: String) -> Promise<()> {
     let mut file_2 = tokio::fs::File::create(output).await?;
     tokio::io::copy(&mut buf_reader, &mut file_2).await?;
 
+    events.emit(SyncEventKind::DownloadProgress {
+        update_type,
+        bytes: downloaded.load(Ordering::Relaxed),
+        total_bytes,
+    });
+
     Ok(())
 }
 
@@ -61,75 +99,171 @@ fn convert_error(_err: reqwest::Error) -> std::io::Error {
     todo!()
 }
 
-pub async fn load_last_full(config: Config) -> Promise<()> {
+/// The current time, truncated to the second precision of `DATETIME` columns,
+/// so change records and `last_updates` compare equal.
+fn sync_timestamp() -> NaiveDateTime {
+    let now = chrono::offset::Utc::now().naive_utc();
+    now.with_nanosecond(0).unwrap_or(now)
+}
+
+fn failed(update_type: Option<LastUpdatesType>, stage: SyncStage, reason: String) -> SyncEventKind {
+    SyncEventKind::Failed {
+        update_type,
+        stage,
+        reason,
+    }
+}
+
+/// Imports a downloaded data set and records the update.
+fn import(
+    output_path: String,
+    update_type: LastUpdatesType,
+    config: Config,
+    events: &SyncEvents,
+) -> Promise<()> {
+    events.emit(SyncEventKind::ImportStarted { update_type });
+
+    let synced_at = sync_timestamp();
+    let result =
+        load_data(output_path, &update_type, synced_at, config.clone()).and_then(|stats| {
+            info!("Upload the data set to the database.");
+            set_last_update(update_type, synced_at, config.clone())?;
+            Ok(stats)
+        });
+
+    match result {
+        Ok(stats) => {
+            events.emit(SyncEventKind::ImportFinished {
+                update_type,
+                rows: stats.rows,
+                changed: stats.changed,
+                removed: stats.removed,
+                dataset_timestamp: synced_at.and_utc(),
+            });
+            Ok(())
+        }
+        Err(e) => {
+            events.emit(failed(Some(update_type), SyncStage::Import, e.to_string()));
+            Err(e.into())
+        }
+    }
+}
+
+pub async fn load_last_full(config: Config, events: &SyncEvents) -> Promise<()> {
+    let update_type = LastUpdatesType::Full;
     let url = get_url_of_full_package(config.clone());
     let output_folder = config.output_folder.clone();
-    let output_path = String::from(format!("{}/full-cell-export.csv", output_folder));
+    let output_path = format!("{}/full-cell-export.csv", output_folder);
     info!("Start to load the last full data set.");
+    events.emit(SyncEventKind::DownloadStarted { update_type });
 
-    match load_url(url, output_path.clone()).await {
+    match load_url(url, output_path.clone(), update_type, events).await {
         Ok(_) => {}
         Err(e) => {
             info!("Load Data Error: {}", e);
+            events.emit(failed(
+                Some(update_type),
+                SyncStage::Download,
+                e.to_string(),
+            ));
             return Ok(());
         }
     }
     info!("Load the full raw data set.");
-    load_data(output_path, config.clone())?;
-    info!("Upload the data set to the database.");
-
-    let today = chrono::offset::Utc::now();
-    set_last_update(LastUpdatesType::Full, today.naive_utc(), config.clone())?;
+    import(output_path, update_type, config.clone(), events)?;
     info!("Successfully update the full data set.");
     Ok(())
 }
 
-pub async fn load_last_diff(config: Config) -> Promise<()> {
+pub async fn load_last_diff(config: Config, events: &SyncEvents) -> Promise<()> {
+    let update_type = LastUpdatesType::Diff;
     let today = chrono::offset::Utc::now();
     let url = get_url_of_diff_package(today, config.clone());
     let output_folder = config.output_folder.clone();
-    let output_path = String::from(format!("{}/diff-cell-export.csv", output_folder));
+    let output_path = format!("{}/diff-cell-export.csv", output_folder);
     info!("Start to load the last diff data set.");
+    events.emit(SyncEventKind::DownloadStarted { update_type });
 
-    match load_url(url, output_path.clone()).await {
+    match load_url(url, output_path.clone(), update_type, events).await {
         Ok(_) => {}
         Err(e) => {
             error!("Load Data Error: {}", e);
+            events.emit(failed(
+                Some(update_type),
+                SyncStage::Download,
+                e.to_string(),
+            ));
             return Err(e);
         }
     }
     info!("Load the last diff raw data set.");
-    load_data(output_path, config.clone())?;
-    info!("Upload the data set to the database.");
-
-    set_last_update(LastUpdatesType::Diff, today.naive_utc(), config.clone())?;
+    import(output_path, update_type, config.clone(), events)?;
     info!("Successfully update the diff data set.");
 
     Ok(())
 }
 
-pub async fn update_local_database(config: Config) -> Promise<()> {
-    let last_update = Utc.from_utc_datetime(&get_last_update(config.clone()).unwrap());
+pub async fn update_local_database(config: Config, events: &SyncEvents) -> Promise<()> {
+    let last_update = match get_last_update(config.clone()) {
+        Ok(last_update) => Utc.from_utc_datetime(&last_update),
+        Err(e) => {
+            events.emit(failed(None, SyncStage::Check, e.to_string()));
+            return Err(e.into());
+        }
+    };
     let now = chrono::offset::Utc::now();
 
-    match get_update_type(DateTime::from(last_update), now) {
+    let update_type = get_update_type(last_update, now);
+    events.emit(SyncEventKind::Check { update_type });
+    match update_type {
         None => Ok(()),
-        Some(LastUpdatesType::Full) => load_last_full(config.clone()).await,
-        Some(LastUpdatesType::Diff) => load_last_diff(config.clone()).await,
+        Some(LastUpdatesType::Full) => load_last_full(config.clone(), events).await,
+        Some(LastUpdatesType::Diff) => load_last_diff(config.clone(), events).await,
     }
 }
 
-pub fn load_data(input_path: String, config: Config) -> Result<(), Error> {
+/// Row counts of a `load_data` run.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LoadStats {
+    /// Rows in the imported file
+    pub rows: u64,
+    /// Cells inserted or modified
+    pub changed: u64,
+    /// Cells removed because a full data set didn't contain them anymore
+    pub removed: u64,
+}
+
+pub fn load_data(
+    input_path: String,
+    update_type: &LastUpdatesType,
+    synced_at: NaiveDateTime,
+    config: Config,
+) -> Result<LoadStats, Error> {
     let connection = &mut establish_connection(config.clone());
-    load_data_with_connection(input_path, connection)
+    load_data_with_connection(input_path, update_type, synced_at, connection)
 }
 
+/// Compares a staged row `s` with the stored row `c`. `<=>` is NULL-safe.
+const ROW_DIFFERS: &str = "c.cell IS NULL OR NOT (
+    c.unit <=> s.unit AND c.lon = s.lon AND c.lat = s.lat AND c.cell_range = s.cell_range
+    AND c.samples = s.samples AND c.changeable = s.changeable AND c.created = s.created
+    AND c.updated = s.updated AND c.average_signal <=> s.average_signal)";
+
+const KEY_JOIN: &str =
+    "c.mcc = s.mcc AND c.net = s.net AND c.area = s.area AND c.cell = s.cell AND c.radio = s.radio";
+
 /// Load CSV data into the database using the provided connection.
 /// This is the testable version that accepts a connection parameter.
+///
+/// The file is staged in a temporary table first, so every cell it inserts or
+/// modifies can be recorded in `cell_changes` with `synced_at`. A full data set
+/// also removes all cells it doesn't contain anymore, leaving tombstones.
 pub fn load_data_with_connection(
     input_path: String,
+    update_type: &LastUpdatesType,
+    synced_at: NaiveDateTime,
     connection: &mut diesel::MysqlConnection,
-) -> Result<(), Error> {
+) -> Result<LoadStats, Error> {
     // TODO: make async
     let full_path = match input_path.starts_with("/") {
         true => input_path,
@@ -141,10 +275,38 @@ pub fn load_data_with_connection(
         }
     };
 
+    let stats = stage_and_apply(&full_path, update_type, synced_at, connection);
+
+    // The staging table is per connection, but connections may be reused
+    let _ = diesel::sql_query("DROP TEMPORARY TABLE IF EXISTS cells_staging").execute(connection);
+
+    match stats {
+        Ok(stats) => {
+            info!("Success: {:?}", stats);
+            Ok(stats)
+        }
+        Err(e) => Err(Error::other(e.to_string())),
+    }
+}
+
+fn stage_and_apply(
+    full_path: &str,
+    update_type: &LastUpdatesType,
+    synced_at: NaiveDateTime,
+    connection: &mut diesel::MysqlConnection,
+) -> Result<LoadStats, diesel::result::Error> {
+    use crate::handlers::density::refresh_density;
+    use crate::schema::{cell_changes, cell_density};
+    use diesel::dsl::count_star;
+    use diesel::{ExpressionMethods, QueryDsl};
+
+    diesel::sql_query("DROP TEMPORARY TABLE IF EXISTS cells_staging").execute(connection)?;
+    diesel::sql_query("CREATE TEMPORARY TABLE cells_staging LIKE cells").execute(connection)?;
+
     info!("Load data from: {:?}", full_path);
-    let res = diesel::sql_query(format!("
+    diesel::sql_query(format!("
     LOAD DATA INFILE {:?}
-    REPLACE INTO TABLE cells
+    REPLACE INTO TABLE cells_staging
     FIELDS TERMINATED BY ','
     LINES TERMINATED BY '\n'
     IGNORE 1 LINES
@@ -153,13 +315,95 @@ pub fn load_data_with_connection(
     unit = NULLIF(@unit, '-1'),
     average_signal = NULLIF(@average_signal, ''),
     created = FROM_UNIXTIME(@created),
-    updated = FROM_UNIXTIME(@updated);", full_path)).execute(connection);
+    updated = FROM_UNIXTIME(@updated);", full_path)).execute(connection)?;
+
+    let rows: i64 = diesel::sql_query("SELECT COUNT(*) AS count FROM cells_staging")
+        .get_result::<CountRow>(connection)?
+        .count;
+
+    // LOAD DATA can't run in a transaction, but the change log and the cells
+    // must change together, or the feed lists changes that were never applied
+    connection.transaction::<_, diesel::result::Error, _>(|connection| {
+        debug!("Record changed cells.");
+        diesel::sql_query(format!(
+            "INSERT INTO cell_changes (radio, mcc, net, area, cell, changed_at, removed)
+            SELECT s.radio, s.mcc, s.net, s.area, s.cell, ?, FALSE
+            FROM cells_staging s LEFT JOIN cells c ON {}
+            WHERE {}
+            ON DUPLICATE KEY UPDATE changed_at = VALUES(changed_at), removed = FALSE",
+            KEY_JOIN, ROW_DIFFERS
+        ))
+        .bind::<diesel::sql_types::Datetime, _>(synced_at)
+        .execute(connection)?;
+
+        // An empty file is far more likely a broken download than an empty world
+        if *update_type == LastUpdatesType::Full && rows > 0 {
+            debug!("Remove cells missing from the full data set.");
+            diesel::sql_query(format!(
+                "INSERT INTO cell_changes (radio, mcc, net, area, cell, changed_at, removed)
+                SELECT c.radio, c.mcc, c.net, c.area, c.cell, ?, TRUE
+                FROM cells c LEFT JOIN cells_staging s ON {}
+                WHERE s.cell IS NULL
+                ON DUPLICATE KEY UPDATE changed_at = VALUES(changed_at), removed = TRUE",
+                KEY_JOIN
+            ))
+            .bind::<diesel::sql_types::Datetime, _>(synced_at)
+            .execute(connection)?;
+            diesel::sql_query(format!(
+                "DELETE c FROM cells c LEFT JOIN cells_staging s ON {} WHERE s.cell IS NULL",
+                KEY_JOIN
+            ))
+            .execute(connection)?;
+        }
+
+        // Only the changed rows, most of a full data set is unchanged and
+        // rewriting it would bloat the transaction
+        debug!("Apply changed cells.");
+        diesel::sql_query(format!(
+            "INSERT INTO cells
+            SELECT * FROM (
+                SELECT s.* FROM cells_staging s LEFT JOIN cells c ON {}
+                WHERE {}
+            ) AS changed
+            ON DUPLICATE KEY UPDATE
+                unit = changed.unit, lon = changed.lon, lat = changed.lat,
+                cell_range = changed.cell_range, samples = changed.samples,
+                changeable = changed.changeable, created = changed.created,
+                updated = changed.updated, average_signal = changed.average_signal",
+            KEY_JOIN, ROW_DIFFERS
+        ))
+        .execute(connection)?;
+        Ok(())
+    })?;
+
+    let count_changes = |removed: bool, connection: &mut diesel::MysqlConnection| {
+        cell_changes::table
+            .filter(cell_changes::changed_at.eq(synced_at))
+            .filter(cell_changes::removed.eq(removed))
+            .select(count_star())
+            .first::<i64>(connection)
+    };
+
+    let stats = LoadStats {
+        rows: rows as u64,
+        changed: count_changes(false, connection)? as u64,
+        removed: count_changes(true, connection)? as u64,
+    };
 
-    match res {
-        Ok(writes) => info!("Success: {:?} writes.", writes),
-        Err(e) => return Err(Error::new(std::io::ErrorKind::Other, e.to_string())),
+    // Also builds it the first time, when a sync changed nothing
+    let density_exists =
+        diesel::select(diesel::dsl::exists(cell_density::table)).get_result::<bool>(connection)?;
+    if stats.changed + stats.removed > 0 || !density_exists {
+        debug!("Refresh cell density.");
+        refresh_density(connection)?;
     }
-    Ok(())
+    Ok(stats)
+}
+
+#[derive(diesel::QueryableByName)]
+struct CountRow {
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    count: i64,
 }
 
 // create output folder if not exists
@@ -171,7 +415,11 @@ pub async fn init(config: Config) -> Promise<()> {
     Ok(())
 }
 
-pub async fn update_loop(halt: &Arc<Mutex<bool>>, config: Config) -> Promise<()> {
+pub async fn update_loop(
+    halt: &Arc<Mutex<bool>>,
+    config: Config,
+    events: SyncEvents,
+) -> Promise<()> {
     info!("Init update loop.");
     init(config.clone()).await?;
 
@@ -183,7 +431,7 @@ pub async fn update_loop(halt: &Arc<Mutex<bool>>, config: Config) -> Promise<()>
 
         if (count % 600) == 0 {
             debug!("Check for updates!");
-            if let Err(e) = update_local_database(config.clone()).await {
+            if let Err(e) = update_local_database(config.clone(), &events).await {
                 warn!("Update check failed: {}. Will retry later.", e);
             }
             count = 0;
@@ -227,12 +475,27 @@ mod tests {
         })
     }
 
+    fn synced_at(day: u32) -> NaiveDateTime {
+        Utc.with_ymd_and_hms(2025, 12, day, 4, 0, 0)
+            .unwrap()
+            .naive_utc()
+    }
+
+    fn load_full(conn: &mut MysqlConnection, at: NaiveDateTime) -> Result<LoadStats, Error> {
+        load_data_with_connection(
+            CONTAINER_CSV_PATH.to_string(),
+            &LastUpdatesType::Full,
+            at,
+            conn,
+        )
+    }
+
     #[test]
     fn test_load_data_imports_csv_file() {
         let (_container, mut conn) = get_test_connection();
 
         // Use the actual load_data_with_connection function
-        let result = load_data_with_connection(CONTAINER_CSV_PATH.to_string(), &mut conn);
+        let result = load_full(&mut conn, synced_at(1));
         assert!(result.is_ok(), "Failed to load CSV: {:?}", result.err());
 
         // Verify data was loaded - test CSV has 99 data rows (100 lines - 1 header)
@@ -250,8 +513,7 @@ mod tests {
         let (_container, mut conn) = get_test_connection();
 
         // Load using the actual function
-        load_data_with_connection(CONTAINER_CSV_PATH.to_string(), &mut conn)
-            .expect("Failed to load CSV data");
+        load_full(&mut conn, synced_at(1)).expect("Failed to load CSV data");
 
         // Query for the first cell from the test CSV:
         // GSM,262,2,317,11911,0,13.4524,52.5075,1454,122,1,1288894949,1724275323,0
@@ -278,8 +540,7 @@ mod tests {
         let (_container, mut conn) = get_test_connection();
 
         // Load using the actual function
-        load_data_with_connection(CONTAINER_CSV_PATH.to_string(), &mut conn)
-            .expect("Failed to load CSV data");
+        load_full(&mut conn, synced_at(1)).expect("Failed to load CSV data");
 
         // Query 3 random cells from the database
         let random_cells: Vec<Cell> = diesel::sql_query(
@@ -321,4 +582,61 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn test_first_load_records_all_cells_as_changed() {
+        let (_container, mut conn) = get_test_connection();
+
+        let stats = load_full(&mut conn, synced_at(1)).expect("Failed to load CSV data");
+
+        assert_eq!(
+            stats,
+            LoadStats {
+                rows: 99,
+                changed: 99,
+                removed: 0
+            }
+        );
+    }
+
+    #[test]
+    fn test_unchanged_reload_records_no_changes() {
+        let (_container, mut conn) = get_test_connection();
+        load_full(&mut conn, synced_at(1)).expect("Failed to load CSV data");
+
+        let stats = load_full(&mut conn, synced_at(2)).expect("Failed to reload CSV data");
+
+        assert_eq!(stats.changed, 0);
+        assert_eq!(stats.removed, 0);
+    }
+
+    #[test]
+    fn test_full_load_removes_missing_cells() {
+        use crate::schema::cell_changes;
+
+        let (_container, mut conn) = get_test_connection();
+        load_full(&mut conn, synced_at(1)).expect("Failed to load CSV data");
+        diesel::sql_query(
+            "INSERT INTO cells SELECT 'LTE', 999, 1, 1, 1, unit, lon, lat, cell_range, samples,
+             changeable, created, updated, average_signal FROM cells LIMIT 1",
+        )
+        .execute(&mut conn)
+        .expect("Failed to insert stale cell");
+
+        let stats = load_full(&mut conn, synced_at(2)).expect("Failed to reload CSV data");
+
+        assert_eq!(stats.removed, 1);
+        let remaining: i64 = cells
+            .filter(mcc.eq(999_u16))
+            .count()
+            .get_result(&mut conn)
+            .unwrap();
+        assert_eq!(remaining, 0);
+        let tombstone: bool = cell_changes::table
+            .filter(cell_changes::mcc.eq(999_u16))
+            .select(cell_changes::removed)
+            .first(&mut conn)
+            .unwrap();
+        assert!(tombstone);
+    }
 }
@@ -7,14 +7,20 @@ use crate::utils::config::Config;
 use chrono::{DateTime, NaiveDateTime, Utc};
 use diesel::prelude::*;
 use diesel::result::Error::NotFound;
-use diesel::{Connection, MysqlConnection, RunQueryDsl};
+use diesel::{Connection, ConnectionError, MysqlConnection, RunQueryDsl};
 
 pub fn establish_connection(config: Config) -> MysqlConnection {
-    let database_url = config.db_url;
-    MysqlConnection::establish(&database_url)
+    let database_url = config.db_url.clone();
+    try_establish_connection(config)
         .unwrap_or_else(|_| panic!("Error connecting to {}", database_url))
 }
 
+/// Like `establish_connection`, but returns the error instead of panicking.
+/// Used by request handlers so an unreachable database surfaces as a 503.
+pub fn try_establish_connection(config: Config) -> Result<MysqlConnection, ConnectionError> {
+    MysqlConnection::establish(&config.db_url)
+}
+
 pub fn set_last_update(
     target_type: LastUpdatesType,
     date: chrono::NaiveDateTime,
@@ -40,6 +46,14 @@ pub fn set_last_update(
 
 pub fn get_last_update(config: Config) -> Result<NaiveDateTime, diesel::result::Error> {
     let connection = &mut establish_connection(config);
+    get_last_update_with_connection(connection)
+}
+
+/// Returns the time of the last successful sync using the provided connection.
+/// Falls back to the unix epoch if no sync has happened yet.
+pub fn get_last_update_with_connection(
+    connection: &mut MysqlConnection,
+) -> Result<NaiveDateTime, diesel::result::Error> {
     let last_update: Result<LastUpdates, diesel::result::Error> =
         last_updates.order(value.desc()).first(connection);
 
@@ -0,0 +1,236 @@
+use std::convert::Infallible;
+
+use serde::Serialize;
+use tracing::{debug, error};
+use utoipa::ToSchema;
+use warp::{http::StatusCode, reject::Reject, Rejection, Reply};
+
+/// A single validation problem with a request parameter.
+#[derive(Debug, Clone, Serialize, PartialEq, ToSchema)]
+pub struct FieldError {
+    /// Name of the offending parameter
+    pub field: String,
+    /// Human readable description of the problem
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: &str, message: impl Into<String>) -> Self {
+        FieldError {
+            field: field.to_string(),
+            message: message.into(),
+        }
+    }
+}
+
+/// Errors surfaced by the API handlers as warp rejections.
+#[derive(Debug)]
+pub enum ApiError {
+    /// The request was malformed or failed validation.
+    BadRequest(Vec<FieldError>),
+    /// The requested resource does not exist.
+    NotFound(String),
+    /// None of the formats in `Accept` can be produced.
+    NotAcceptable(String),
+    /// The database could not be reached or failed to answer.
+    ServiceUnavailable(String),
+}
+
+impl Reject for ApiError {}
+
+impl From<diesel::result::Error> for ApiError {
+    fn from(err: diesel::result::Error) -> Self {
+        ApiError::ServiceUnavailable(err.to_string())
+    }
+}
+
+impl From<diesel::ConnectionError> for ApiError {
+    fn from(err: diesel::ConnectionError) -> Self {
+        ApiError::ServiceUnavailable(err.to_string())
+    }
+}
+
+/// JSON body returned for every error response.
+#[derive(Serialize, Debug, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ErrorBody {
+    /// The HTTP status code
+    pub code: u16,
+    /// Short, stable error identifier
+    pub error: String,
+    /// Human readable description
+    pub message: String,
+    /// Field-level validation errors, if any
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<FieldError>,
+}
+
+impl ErrorBody {
+    fn new(status: StatusCode, error: &str, message: impl Into<String>) -> Self {
+        ErrorBody {
+            code: status.as_u16(),
+            error: error.to_string(),
+            message: message.into(),
+            fields: vec![],
+        }
+    }
+}
+
+impl ApiError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::NotAcceptable(_) => StatusCode::NOT_ACCEPTABLE,
+            ApiError::ServiceUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    pub fn body(&self) -> ErrorBody {
+        match self {
+            ApiError::BadRequest(fields) => ErrorBody {
+                fields: fields.clone(),
+                ..ErrorBody::new(self.status(), "bad_request", "Invalid request parameters")
+            },
+            ApiError::NotFound(message) => ErrorBody::new(self.status(), "not_found", message),
+            ApiError::NotAcceptable(message) => {
+                ErrorBody::new(self.status(), "not_acceptable", message)
+            }
+            // Don't leak database details to clients, they are logged instead
+            ApiError::ServiceUnavailable(_) => ErrorBody::new(
+                self.status(),
+                "service_unavailable",
+                "The database is currently unavailable",
+            ),
+        }
+    }
+}
+
+/// Converts rejections into JSON error responses with a matching status code.
+pub async fn handle_rejection(err: Rejection) -> Result<impl Reply, Infallible> {
+    let body = if let Some(api_error) = err.find::<ApiError>() {
+        if let ApiError::ServiceUnavailable(reason) = api_error {
+            error!("Database unavailable: {}", reason);
+        }
+        api_error.body()
+    } else if err.is_not_found() {
+        ErrorBody::new(StatusCode::NOT_FOUND, "not_found", "Route not found")
+    } else if let Some(e) = err.find::<warp::reject::InvalidQuery>() {
+        ErrorBody::new(StatusCode::BAD_REQUEST, "bad_request", e.to_string())
+    } else if let Some(e) = err.find::<warp::filters::body::BodyDeserializeError>() {
+        ErrorBody::new(StatusCode::BAD_REQUEST, "bad_request", e.to_string())
+    } else if let Some(e) = err.find::<async_graphql_warp::GraphQLBadRequest>() {
+        let error = match e.status() {
+            StatusCode::PAYLOAD_TOO_LARGE => "payload_too_large",
+            _ => "bad_request",
+        };
+        ErrorBody::new(e.status(), error, e.to_string())
+    } else if let Some(e) = err.find::<warp::reject::PayloadTooLarge>() {
+        ErrorBody::new(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            "payload_too_large",
+            e.to_string(),
+        )
+    } else if let Some(e) = err.find::<warp::reject::LengthRequired>() {
+        ErrorBody::new(
+            StatusCode::LENGTH_REQUIRED,
+            "length_required",
+            e.to_string(),
+        )
+    } else if let Some(e) = err.find::<warp::reject::MethodNotAllowed>() {
+        ErrorBody::new(
+            StatusCode::METHOD_NOT_ALLOWED,
+            "method_not_allowed",
+            e.to_string(),
+        )
+    } else {
+        debug!("Unhandled rejection: {:?}", err);
+        ErrorBody::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "internal_error",
+            "Unhandled error",
+        )
+    };
+
+    let status = StatusCode::from_u16(body.code).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    Ok(warp::reply::with_status(warp::reply::json(&body), status))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use warp::http::Response;
+    use warp::hyper::body::Bytes;
+    use warp::test::request;
+    use warp::Filter;
+
+    async fn reply_for(make_error: fn() -> ApiError) -> Response<Bytes> {
+        let route = warp::path!("test")
+            .and_then(move || async move { Err::<String, Rejection>(make_error().into()) })
+            .recover(handle_rejection);
+
+        request().method("GET").path("/test").reply(&route).await
+    }
+
+    fn body_json(response: &Response<Bytes>) -> serde_json::Value {
+        serde_json::from_slice(response.body()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_bad_request_lists_fields() {
+        let response = reply_for(|| {
+            ApiError::BadRequest(vec![FieldError::new(
+                "min_lat",
+                "must be between -90 and 90",
+            )])
+        })
+        .await;
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = body_json(&response);
+        assert_eq!(body["code"], 400);
+        assert_eq!(body["error"], "bad_request");
+        assert_eq!(body["fields"][0]["field"], "min_lat");
+        assert_eq!(body["fields"][0]["message"], "must be between -90 and 90");
+    }
+
+    #[tokio::test]
+    async fn test_not_found_returns_404_with_json_body() {
+        let response = reply_for(|| ApiError::NotFound("Cell not found".to_string())).await;
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let body = body_json(&response);
+        assert_eq!(body["error"], "not_found");
+        assert_eq!(body["message"], "Cell not found");
+        assert!(body.get("fields").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_service_unavailable_hides_details() {
+        let response =
+            reply_for(|| ApiError::ServiceUnavailable("secret connection string".to_string()))
+                .await;
+
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body = body_json(&response);
+        assert_eq!(body["error"], "service_unavailable");
+        assert!(!body["message"].as_str().unwrap().contains("secret"));
+    }
+
+    #[tokio::test]
+    async fn test_unknown_route_returns_json_404() {
+        let route = warp::path!("test").map(|| "OK").recover(handle_rejection);
+
+        let response = request().method("GET").path("/other").reply(&route).await;
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(body_json(&response)["error"], "not_found");
+    }
+
+    #[test]
+    fn test_diesel_errors_map_to_service_unavailable() {
+        let error = ApiError::from(diesel::result::Error::BrokenTransactionManager);
+
+        assert_eq!(error.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+}
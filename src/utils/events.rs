@@ -0,0 +1,277 @@
+use std::convert::Infallible;
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use futures::{stream, Stream};
+use serde::Serialize;
+use tokio::sync::{broadcast, watch};
+use tracing::debug;
+use utoipa::ToSchema;
+
+use crate::models::LastUpdatesType;
+
+/// Number of events buffered per subscriber. Slower subscribers skip the oldest events.
+const EVENT_BUFFER: usize = 256;
+
+/// The stage of a sync a failure happened in.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SyncStage {
+    Check,
+    Download,
+    Import,
+}
+
+/// What happened during a sync.
+#[derive(Serialize, Debug, Clone, PartialEq, ToSchema)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum SyncEventKind {
+    /// The update loop checked whether an update is due
+    #[serde(rename_all = "camelCase")]
+    Check {
+        /// The update that is due, `null` if the data is up to date
+        update_type: Option<LastUpdatesType>,
+    },
+    /// The download of a data set started
+    #[serde(rename_all = "camelCase")]
+    DownloadStarted { update_type: LastUpdatesType },
+    /// Bytes of the (compressed) data set downloaded so far
+    #[serde(rename_all = "camelCase")]
+    DownloadProgress {
+        update_type: LastUpdatesType,
+        bytes: u64,
+        total_bytes: Option<u64>,
+    },
+    /// The import of a downloaded data set into the database started
+    #[serde(rename_all = "camelCase")]
+    ImportStarted { update_type: LastUpdatesType },
+    /// The data set was imported
+    #[serde(rename_all = "camelCase")]
+    ImportFinished {
+        update_type: LastUpdatesType,
+        /// Rows in the data set
+        rows: u64,
+        /// Cells inserted or modified
+        changed: u64,
+        /// Cells removed
+        removed: u64,
+        /// The version of the data set now served, see `last_updates`
+        dataset_timestamp: DateTime<Utc>,
+    },
+    /// The sync failed
+    #[serde(rename_all = "camelCase")]
+    Failed {
+        update_type: Option<LastUpdatesType>,
+        stage: SyncStage,
+        reason: String,
+    },
+}
+
+impl SyncEventKind {
+    /// The SSE event name.
+    pub fn name(&self) -> &'static str {
+        match self {
+            SyncEventKind::Check { .. } => "check",
+            SyncEventKind::DownloadStarted { .. } => "downloadStarted",
+            SyncEventKind::DownloadProgress { .. } => "downloadProgress",
+            SyncEventKind::ImportStarted { .. } => "importStarted",
+            SyncEventKind::ImportFinished { .. } => "importFinished",
+            SyncEventKind::Failed { .. } => "failed",
+        }
+    }
+}
+
+/// A sync lifecycle event with the time it happened.
+#[derive(Serialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct SyncEvent {
+    pub at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub kind: SyncEventKind,
+}
+
+/// Fan-out of sync lifecycle events to any number of subscribers.
+///
+/// Emitting never blocks and events without subscribers are dropped.
+#[derive(Debug, Clone)]
+pub struct SyncEvents {
+    sender: broadcast::Sender<SyncEvent>,
+    closed: Arc<watch::Sender<bool>>,
+}
+
+impl Default for SyncEvents {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SyncEvents {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(EVENT_BUFFER);
+        let (closed, _) = watch::channel(false);
+        SyncEvents {
+            sender,
+            closed: Arc::new(closed),
+        }
+    }
+
+    pub fn emit(&self, kind: SyncEventKind) {
+        debug!("Sync event: {:?}", kind);
+        // Only fails if nobody is subscribed
+        let _ = self.sender.send(SyncEvent {
+            at: Utc::now(),
+            kind,
+        });
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<SyncEvent> {
+        self.sender.subscribe()
+    }
+
+    /// Ends all subscriber streams, e.g. so open connections don't delay a shutdown.
+    pub fn close(&self) {
+        self.closed.send_replace(true);
+    }
+
+    /// A stream of all events emitted from now on, until `close` is called.
+    /// Events a slow subscriber missed are skipped.
+    pub fn stream(&self) -> impl Stream<Item = SyncEvent> + Send + 'static {
+        let state = (self.subscribe(), self.closed.subscribe());
+        stream::unfold(state, |(mut receiver, mut closed)| async move {
+            loop {
+                // Deliver what was emitted before closing first
+                match receiver.try_recv() {
+                    Ok(event) => return Some((event, (receiver, closed))),
+                    Err(broadcast::error::TryRecvError::Lagged(skipped)) => {
+                        debug!("Event subscriber lagged, skipped {} events", skipped);
+                        continue;
+                    }
+                    Err(broadcast::error::TryRecvError::Closed) => return None,
+                    Err(broadcast::error::TryRecvError::Empty) => {}
+                }
+                if *closed.borrow_and_update() {
+                    return None;
+                }
+                tokio::select! {
+                    result = receiver.recv() => match result {
+                        Ok(event) => return Some((event, (receiver, closed))),
+                        Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                            debug!("Event subscriber lagged, skipped {} events", skipped);
+                        }
+                        Err(broadcast::error::RecvError::Closed) => return None,
+                    },
+                    changed = closed.changed() => {
+                        if changed.is_err() {
+                            return None;
+                        }
+                    }
+                }
+            }
+        })
+    }
+}
+
+/// Maps the events to SSE events, named after their type.
+pub fn sse_events(
+    events: &SyncEvents,
+) -> impl Stream<Item = Result<warp::sse::Event, Infallible>> + Send + 'static {
+    use futures::StreamExt;
+
+    events.stream().map(|event| {
+        Ok(warp::sse::Event::default()
+            .event(event.kind.name())
+            .json_data(&event)
+            .unwrap_or_default())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+
+    #[test]
+    fn test_event_serialization() {
+        let event = SyncEvent {
+            at: DateTime::from_timestamp(1_766_203_200, 0).unwrap(),
+            kind: SyncEventKind::ImportFinished {
+                update_type: LastUpdatesType::Full,
+                rows: 99,
+                changed: 10,
+                removed: 1,
+                dataset_timestamp: DateTime::from_timestamp(1_766_203_140, 0).unwrap(),
+            },
+        };
+
+        let json = serde_json::to_value(&event).unwrap();
+
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "at": "2025-12-20T04:00:00Z",
+                "type": "importFinished",
+                "updateType": "full",
+                "rows": 99,
+                "changed": 10,
+                "removed": 1,
+                "datasetTimestamp": "2025-12-20T03:59:00Z"
+            })
+        );
+    }
+
+    #[test]
+    fn test_event_name_matches_type() {
+        let kind = SyncEventKind::Failed {
+            update_type: None,
+            stage: SyncStage::Download,
+            reason: "timeout".to_string(),
+        };
+
+        let json = serde_json::to_value(&kind).unwrap();
+
+        assert_eq!(json["type"], kind.name());
+        assert_eq!(json["stage"], "download");
+    }
+
+    #[tokio::test]
+    async fn test_all_subscribers_receive_events() {
+        let events = SyncEvents::new();
+        let first = events.stream();
+        let second = events.stream();
+
+        events.emit(SyncEventKind::Check { update_type: None });
+        events.close();
+
+        let first: Vec<SyncEvent> = first.collect().await;
+        let second: Vec<SyncEvent> = second.collect().await;
+        assert_eq!(first.len(), 1);
+        assert_eq!(first, second);
+    }
+
+    #[tokio::test]
+    async fn test_close_ends_streams() {
+        let events = SyncEvents::new();
+        let stream = events.stream();
+
+        events.close();
+
+        assert_eq!(stream.count().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_lagging_subscriber_skips_old_events() {
+        let events = SyncEvents::new();
+        let stream = events.stream();
+
+        for bytes in 0..(EVENT_BUFFER as u64 + 10) {
+            events.emit(SyncEventKind::DownloadProgress {
+                update_type: LastUpdatesType::Full,
+                bytes,
+                total_bytes: None,
+            });
+        }
+        events.close();
+
+        let received: Vec<SyncEvent> = stream.collect().await;
+        assert_eq!(received.len(), EVENT_BUFFER);
+    }
+}
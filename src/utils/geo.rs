@@ -0,0 +1,231 @@
+/// Mean earth radius in meters.
+pub const EARTH_RADIUS_M: f64 = 6_371_000.0;
+
+/// Great-circle distance between two coordinates in meters.
+pub fn haversine_distance(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    let (phi1, phi2) = (lat1.to_radians(), lat2.to_radians());
+    let d_phi = (lat2 - lat1).to_radians();
+    let d_lambda = (lon2 - lon1).to_radians();
+
+    let a = (d_phi / 2.0).sin().powi(2) + phi1.cos() * phi2.cos() * (d_lambda / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_M * a.sqrt().asin()
+}
+
+/// Wraps a longitude, or a difference of longitudes, into `[-180, 180)`.
+fn wrap_longitude(lon: f64) -> f64 {
+    (lon + 540.0).rem_euclid(360.0) - 180.0
+}
+
+/// A flat east/north frame in meters around an origin, an equirectangular
+/// projection that is accurate enough within a few hundred kilometers.
+#[derive(Debug, Clone, Copy)]
+pub struct LocalFrame {
+    lat: f64,
+    lon: f64,
+    cos_lat: f64,
+}
+
+impl LocalFrame {
+    pub fn new(lat: f64, lon: f64) -> Self {
+        LocalFrame {
+            lat,
+            lon,
+            cos_lat: lat.to_radians().cos(),
+        }
+    }
+
+    /// East and north offset of a coordinate from the origin in meters.
+    pub fn to_local(&self, lat: f64, lon: f64) -> (f64, f64) {
+        let east = wrap_longitude(lon - self.lon).to_radians() * EARTH_RADIUS_M * self.cos_lat;
+        let north = (lat - self.lat).to_radians() * EARTH_RADIUS_M;
+        (east, north)
+    }
+
+    /// The coordinate at an east and north offset from the origin.
+    pub fn to_geo(&self, east: f64, north: f64) -> (f64, f64) {
+        let lat = self.lat + (north / EARTH_RADIUS_M).to_degrees();
+        let lon = self.lon + (east / (EARTH_RADIUS_M * self.cos_lat)).to_degrees();
+        (lat, wrap_longitude(lon))
+    }
+}
+
+/// A latitude/longitude box. Boxes across the antimeridian have
+/// `min_lon > max_lon`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingBox {
+    pub min_lat: f64,
+    pub max_lat: f64,
+    pub min_lon: f64,
+    pub max_lon: f64,
+}
+
+impl BoundingBox {
+    /// The smallest box containing the circle of `radius` meters around a coordinate.
+    pub fn around(lat: f64, lon: f64, radius: f64) -> Self {
+        let d_lat = (radius / EARTH_RADIUS_M).to_degrees();
+        let min_lat = (lat - d_lat).max(-90.0);
+        let max_lat = (lat + d_lat).min(90.0);
+        // The circle is widest at the latitude closest to a pole
+        let cos_lat = min_lat.abs().max(max_lat.abs()).to_radians().cos();
+        let d_lon = if cos_lat <= f64::EPSILON {
+            180.0
+        } else {
+            d_lat / cos_lat
+        };
+        if d_lon >= 180.0 {
+            return BoundingBox {
+                min_lat,
+                max_lat,
+                min_lon: -180.0,
+                max_lon: 180.0,
+            };
+        }
+        BoundingBox {
+            min_lat,
+            max_lat,
+            min_lon: wrap_longitude(lon - d_lon),
+            max_lon: wrap_longitude(lon + d_lon),
+        }
+    }
+
+    /// The smallest box containing the circles of `radius` meters around all
+    /// coordinates. If any circle crosses the antimeridian, the box spans all
+    /// longitudes.
+    pub fn around_all(points: &[(f64, f64)], radius: f64) -> Self {
+        let boxes: Vec<BoundingBox> = points
+            .iter()
+            .map(|&(lat, lon)| BoundingBox::around(lat, lon, radius))
+            .collect();
+        let wraps = boxes.iter().any(BoundingBox::crosses_antimeridian);
+        BoundingBox {
+            min_lat: boxes.iter().map(|b| b.min_lat).fold(90.0, f64::min),
+            max_lat: boxes.iter().map(|b| b.max_lat).fold(-90.0, f64::max),
+            min_lon: if wraps {
+                -180.0
+            } else {
+                boxes.iter().map(|b| b.min_lon).fold(180.0, f64::min)
+            },
+            max_lon: if wraps {
+                180.0
+            } else {
+                boxes.iter().map(|b| b.max_lon).fold(-180.0, f64::max)
+            },
+        }
+    }
+
+    pub fn crosses_antimeridian(&self) -> bool {
+        self.min_lon > self.max_lon
+    }
+
+    /// Whether a coordinate lies within the box, edges included.
+    pub fn contains(&self, lat: f64, lon: f64) -> bool {
+        let within_lon = if self.crosses_antimeridian() {
+            lon >= self.min_lon || lon <= self.max_lon
+        } else {
+            (self.min_lon..=self.max_lon).contains(&lon)
+        };
+        (self.min_lat..=self.max_lat).contains(&lat) && within_lon
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_same_point_is_zero() {
+        assert_eq!(haversine_distance(52.52, 13.405, 52.52, 13.405), 0.0);
+    }
+
+    #[test]
+    fn test_berlin_to_munich() {
+        let distance = haversine_distance(52.52, 13.405, 48.1351, 11.582);
+        assert!((distance - 504_000.0).abs() < 2_000.0, "{}", distance);
+    }
+
+    #[test]
+    fn test_one_degree_latitude() {
+        let distance = haversine_distance(0.0, 0.0, 1.0, 0.0);
+        assert!((distance - 111_195.0).abs() < 1.0, "{}", distance);
+    }
+
+    #[test]
+    fn test_local_frame_roundtrip() {
+        let frame = LocalFrame::new(52.52, 13.405);
+
+        let (east, north) = frame.to_local(52.6, 13.5);
+        let (lat, lon) = frame.to_geo(east, north);
+
+        assert!((lat - 52.6).abs() < 1e-9);
+        assert!((lon - 13.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_local_frame_matches_distance() {
+        let frame = LocalFrame::new(52.52, 13.405);
+
+        let (east, north) = frame.to_local(52.53, 13.42);
+        let distance = haversine_distance(52.52, 13.405, 52.53, 13.42);
+
+        assert!((east.hypot(north) - distance).abs() < 1.0);
+    }
+
+    #[test]
+    fn test_bounding_box_contains_circle() {
+        let bbox = BoundingBox::around(52.52, 13.405, 10_000.0);
+
+        let north = haversine_distance(52.52, 13.405, bbox.max_lat, 13.405);
+        let east = haversine_distance(52.52, 13.405, 52.52, bbox.max_lon);
+        assert!((north - 10_000.0).abs() < 1.0, "{}", north);
+        assert!(east > 10_000.0, "{}", east);
+        assert!(!bbox.crosses_antimeridian());
+    }
+
+    #[test]
+    fn test_bounding_box_across_the_antimeridian() {
+        let bbox = BoundingBox::around(-17.7, 179.99, 10_000.0);
+
+        assert!(bbox.crosses_antimeridian());
+        assert!(bbox.min_lon > 179.8 && bbox.max_lon < -179.8, "{:?}", bbox);
+    }
+
+    #[test]
+    fn test_bounding_box_at_the_pole() {
+        let bbox = BoundingBox::around(89.99, 0.0, 10_000.0);
+
+        assert_eq!(bbox.max_lat, 90.0);
+        assert_eq!((bbox.min_lon, bbox.max_lon), (-180.0, 180.0));
+    }
+
+    #[test]
+    fn test_bounding_box_contains() {
+        let bbox = BoundingBox::around(52.52, 13.405, 10_000.0);
+        let across = BoundingBox::around(-17.7, 179.99, 10_000.0);
+
+        assert!(bbox.contains(52.52, 13.405));
+        assert!(!bbox.contains(52.52, 14.0));
+        assert!(across.contains(-17.7, -179.99));
+        assert!(!across.contains(-17.7, 179.0));
+    }
+
+    #[test]
+    fn test_bounding_box_around_all_points() {
+        let bbox = BoundingBox::around_all(&[(52.0, 13.0), (52.5, 13.5)], 1_000.0);
+
+        assert!(bbox.min_lat < 52.0 && bbox.max_lat > 52.5, "{:?}", bbox);
+        assert!(bbox.min_lon < 13.0 && bbox.max_lon > 13.5, "{:?}", bbox);
+
+        let wrapping = BoundingBox::around_all(&[(-17.7, 179.99), (-17.8, 179.0)], 10_000.0);
+        assert_eq!((wrapping.min_lon, wrapping.max_lon), (-180.0, 180.0));
+    }
+
+    #[test]
+    fn test_local_frame_across_the_antimeridian() {
+        let frame = LocalFrame::new(0.0, 179.99);
+
+        let (east, _) = frame.to_local(0.0, -179.99);
+        assert!((east - 2_224.0).abs() < 1.0, "{}", east);
+        let (_, lon) = frame.to_geo(east, 0.0);
+        assert!((lon + 179.99).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,220 @@
+//! Geohash buckets. A geohash of a given length is a regular latitude/longitude
+//! grid, so buckets can be computed from grid indices and encoded afterwards.
+
+use std::collections::BTreeMap;
+
+use super::geo::BoundingBox;
+
+/// The geohash alphabet, without `a`, `i`, `l` and `o`.
+const BASE32: &[u8; 32] = b"0123456789bcdefghjkmnpqrstuvwxyz";
+/// Longest supported geohash, about 38 × 19 m.
+pub const MAX_PRECISION: u8 = 8;
+/// Geohash length of `tiles`, about 20 × 20 km in central Europe.
+const TILE_PRECISION: u8 = 4;
+/// Tile boxes combined into one query.
+pub const TILES_PER_QUERY: usize = 25;
+
+/// The grid of geohashes with `precision` characters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GeohashGrid {
+    pub precision: u8,
+    lat_bits: u32,
+    lon_bits: u32,
+}
+
+impl GeohashGrid {
+    /// Geohashes interleave longitude and latitude bits, starting with longitude.
+    pub fn new(precision: u8) -> Self {
+        let total = 5 * u32::from(precision);
+        GeohashGrid {
+            precision,
+            lat_bits: total / 2,
+            lon_bits: total - total / 2,
+        }
+    }
+
+    /// Height of a bucket in degrees.
+    pub fn lat_step(&self) -> f64 {
+        180.0 / f64::from(1u32 << self.lat_bits)
+    }
+
+    /// Width of a bucket in degrees.
+    pub fn lon_step(&self) -> f64 {
+        360.0 / f64::from(1u32 << self.lon_bits)
+    }
+
+    /// The row and column of the bucket containing a coordinate. Coordinates on
+    /// the north pole and the antimeridian belong to the last row and column.
+    pub fn index(&self, lat: f64, lon: f64) -> (u32, u32) {
+        (
+            Self::clamp((lat + 90.0) / self.lat_step(), self.lat_bits),
+            Self::clamp((lon + 180.0) / self.lon_step(), self.lon_bits),
+        )
+    }
+
+    fn clamp(position: f64, bits: u32) -> u32 {
+        (position.floor().max(0.0) as u32).min((1 << bits) - 1)
+    }
+
+    /// Clamps indices as computed by the database to the grid.
+    pub fn clamp_index(&self, lat_index: i64, lon_index: i64) -> (u32, u32) {
+        (
+            lat_index.clamp(0, (1 << self.lat_bits) - 1) as u32,
+            lon_index.clamp(0, (1 << self.lon_bits) - 1) as u32,
+        )
+    }
+
+    /// Number of buckets a box overlaps.
+    pub fn bucket_count(&self, bbox: &BoundingBox) -> u64 {
+        let (min_row, min_column) = self.index(bbox.min_lat, bbox.min_lon);
+        let (max_row, max_column) = self.index(bbox.max_lat, bbox.max_lon);
+        let rows = u64::from(max_row - min_row) + 1;
+        let columns = if bbox.crosses_antimeridian() {
+            u64::from(1u32 << self.lon_bits) - u64::from(min_column) + u64::from(max_column) + 1
+        } else {
+            u64::from(max_column - min_column) + 1
+        };
+        rows * columns
+    }
+
+    /// The geohash of a bucket.
+    pub fn geohash(&self, lat_index: u32, lon_index: u32) -> String {
+        let total = self.lat_bits + self.lon_bits;
+        let mut hash = String::with_capacity(usize::from(self.precision));
+        let mut chunk = 0;
+        for bit in 0..total {
+            let value = if bit % 2 == 0 {
+                (lon_index >> (self.lon_bits - 1 - bit / 2)) & 1
+            } else {
+                (lat_index >> (self.lat_bits - 1 - bit / 2)) & 1
+            };
+            chunk = (chunk << 1) | value;
+            if bit % 5 == 4 {
+                hash.push(char::from(BASE32[chunk as usize]));
+                chunk = 0;
+            }
+        }
+        hash
+    }
+
+    /// The area of a bucket.
+    pub fn bounds(&self, lat_index: u32, lon_index: u32) -> BoundingBox {
+        let min_lat = f64::from(lat_index) * self.lat_step() - 90.0;
+        let min_lon = f64::from(lon_index) * self.lon_step() - 180.0;
+        BoundingBox {
+            min_lat,
+            max_lat: min_lat + self.lat_step(),
+            min_lon,
+            max_lon: min_lon + self.lon_step(),
+        }
+    }
+}
+
+/// Nearby coordinates, grouped by geohash.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tile {
+    /// The box around the coordinates, extended by the tile's margin
+    pub bbox: BoundingBox,
+    /// The ids of the coordinates, in the order they were given
+    pub ids: Vec<usize>,
+}
+
+/// Groups `(id, lat, lon)` coordinates by geohash of about 20 × 20 km, so
+/// nearby coordinates can share one box query. Each tile's box is extended by
+/// the `margin` its ids need.
+pub fn tiles(
+    points: impl IntoIterator<Item = (usize, f64, f64)>,
+    margin: impl Fn(&[usize]) -> f64,
+) -> Vec<Tile> {
+    let grid = GeohashGrid::new(TILE_PRECISION);
+    let mut tiles: BTreeMap<(u32, u32), Vec<(usize, (f64, f64))>> = BTreeMap::new();
+    for (id, lat, lon) in points {
+        tiles
+            .entry(grid.index(lat, lon))
+            .or_default()
+            .push((id, (lat, lon)));
+    }
+    tiles
+        .into_values()
+        .map(|members| {
+            let (ids, coordinates): (Vec<usize>, Vec<(f64, f64)>) = members.into_iter().unzip();
+            Tile {
+                bbox: BoundingBox::around_all(&coordinates, margin(&ids)),
+                ids,
+            }
+        })
+        .collect()
+}
+
+/// The geohash of a coordinate.
+pub fn encode(lat: f64, lon: f64, precision: u8) -> String {
+    let grid = GeohashGrid::new(precision);
+    let (lat_index, lon_index) = grid.index(lat, lon);
+    grid.geohash(lat_index, lon_index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_known_geohashes() {
+        assert_eq!(encode(57.64911, 10.40744, 8), "u4pruydq");
+        assert_eq!(encode(52.52, 13.405, 5), "u33dc");
+        assert_eq!(encode(-33.8688, 151.2093, 4), "r3gx");
+        assert_eq!(encode(-90.0, -180.0, 3), "000");
+        assert_eq!(encode(90.0, 180.0, 3), "zzz");
+    }
+
+    #[test]
+    fn test_bucket_sizes() {
+        let grid = GeohashGrid::new(5);
+
+        assert_eq!(grid.lat_step(), 180.0 / 4096.0);
+        assert_eq!(grid.lon_step(), 360.0 / 8192.0);
+    }
+
+    #[test]
+    fn test_bounds_contain_the_coordinate() {
+        let grid = GeohashGrid::new(6);
+        let (lat_index, lon_index) = grid.index(52.52, 13.405);
+
+        let bounds = grid.bounds(lat_index, lon_index);
+
+        assert!(bounds.min_lat <= 52.52 && 52.52 < bounds.max_lat);
+        assert!(bounds.min_lon <= 13.405 && 13.405 < bounds.max_lon);
+    }
+
+    #[test]
+    fn test_tiles_group_nearby_coordinates() {
+        let points = [(0, 52.5, 13.4), (1, 48.1, 11.6), (2, 52.501, 13.401)];
+
+        let tiles = tiles(points, |ids| 1_000.0 * ids.len() as f64);
+
+        assert_eq!(tiles.len(), 2);
+        let berlin = tiles.iter().find(|tile| tile.ids.contains(&0)).unwrap();
+        assert_eq!(berlin.ids, vec![0, 2]);
+        // Two ids, 2 km margin
+        assert!(berlin.bbox.contains(52.5, 13.4) && berlin.bbox.contains(52.518, 13.41));
+        assert!(!berlin.bbox.contains(52.53, 13.4));
+    }
+
+    #[test]
+    fn test_bucket_count() {
+        let grid = GeohashGrid::new(1);
+        let europe = BoundingBox {
+            min_lat: 35.0,
+            max_lat: 70.0,
+            min_lon: -10.0,
+            max_lon: 50.0,
+        };
+        let pacific = BoundingBox {
+            min_lon: 170.0,
+            max_lon: -170.0,
+            ..europe
+        };
+
+        assert_eq!(grid.bucket_count(&europe), 2 * 3);
+        assert_eq!(grid.bucket_count(&pacific), 2 * 2);
+    }
+}
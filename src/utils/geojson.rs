@@ -0,0 +1,199 @@
+//! The subset of GeoJSON (RFC 7946) the service produces.
+
+use serde::Serialize;
+use tracing::error;
+use utoipa::ToSchema;
+use warp::http::{header, HeaderValue, StatusCode};
+use warp::reply::Response;
+use warp::Reply;
+
+/// Media type of GeoJSON responses.
+pub const GEOJSON_CONTENT_TYPE: &str = "application/geo+json";
+
+/// A linear ring of `[lon, lat]` positions, first and last are equal.
+pub type Ring = Vec<[f64; 2]>;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, ToSchema)]
+pub enum GeometryType {
+    Polygon,
+    LineString,
+}
+
+/// A polygon without holes.
+#[derive(Serialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct Polygon {
+    #[serde(rename = "type")]
+    pub kind: GeometryType,
+    /// The exterior ring, counterclockwise
+    #[schema(value_type = Vec<Vec<Vec<f64>>>)]
+    pub coordinates: Vec<Ring>,
+}
+
+impl Polygon {
+    pub fn new(exterior: Ring) -> Self {
+        Polygon {
+            kind: GeometryType::Polygon,
+            coordinates: vec![exterior],
+        }
+    }
+}
+
+/// A line through `[lon, lat]` positions.
+#[derive(Serialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct LineString {
+    #[serde(rename = "type")]
+    pub kind: GeometryType,
+    #[schema(value_type = Vec<Vec<f64>>)]
+    pub coordinates: Vec<[f64; 2]>,
+}
+
+impl LineString {
+    /// A line through `(lat, lon)` points.
+    pub fn new(points: &[(f64, f64)]) -> Self {
+        LineString {
+            kind: GeometryType::LineString,
+            coordinates: points.iter().map(|&(lat, lon)| [lon, lat]).collect(),
+        }
+    }
+}
+
+/// The geometries features can have.
+#[derive(Serialize, Debug, Clone, PartialEq, ToSchema)]
+#[serde(untagged)]
+pub enum Geometry {
+    Polygon(Polygon),
+    LineString(LineString),
+}
+
+impl From<Polygon> for Geometry {
+    fn from(polygon: Polygon) -> Self {
+        Geometry::Polygon(polygon)
+    }
+}
+
+impl From<LineString> for Geometry {
+    fn from(line: LineString) -> Self {
+        Geometry::LineString(line)
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, ToSchema)]
+pub enum FeatureType {
+    Feature,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct Feature<P> {
+    #[serde(rename = "type")]
+    pub kind: FeatureType,
+    pub geometry: Geometry,
+    pub properties: P,
+}
+
+impl<P> Feature<P> {
+    pub fn new(geometry: impl Into<Geometry>, properties: P) -> Self {
+        Feature {
+            kind: FeatureType::Feature,
+            geometry: geometry.into(),
+            properties,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, ToSchema)]
+pub enum FeatureCollectionType {
+    FeatureCollection,
+}
+
+/// A collection of features. Paging fields are foreign members, which GeoJSON
+/// readers ignore.
+#[derive(Serialize, Debug, Clone, PartialEq, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct FeatureCollection<P> {
+    #[serde(rename = "type")]
+    pub kind: FeatureCollectionType,
+    pub features: Vec<Feature<P>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub has_more: Option<bool>,
+}
+
+impl<P> FeatureCollection<P> {
+    pub fn new(features: Vec<Feature<P>>) -> Self {
+        FeatureCollection {
+            kind: FeatureCollectionType::FeatureCollection,
+            features,
+            next_cursor: None,
+            has_more: None,
+        }
+    }
+}
+
+/// Encodes a GeoJSON response. GeoJSON is always JSON, whatever `Accept` asks for.
+pub fn geojson_reply<T: Serialize>(value: &T) -> Response {
+    match serde_json::to_vec(value) {
+        Ok(body) => {
+            let mut response = body.into_response();
+            response.headers_mut().insert(
+                header::CONTENT_TYPE,
+                HeaderValue::from_static(GEOJSON_CONTENT_TYPE),
+            );
+            response
+        }
+        Err(reason) => {
+            error!("Failed to encode GeoJSON response: {}", reason);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_feature_collection_shape() {
+        let ring = vec![[0.0, 0.0], [1.0, 0.0], [0.0, 1.0], [0.0, 0.0]];
+        let collection = FeatureCollection::new(vec![Feature::new(Polygon::new(ring), "a")]);
+
+        let json = serde_json::to_value(&collection).unwrap();
+
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "type": "FeatureCollection",
+                "features": [{
+                    "type": "Feature",
+                    "geometry": {
+                        "type": "Polygon",
+                        "coordinates": [[[0.0, 0.0], [1.0, 0.0], [0.0, 1.0], [0.0, 0.0]]]
+                    },
+                    "properties": "a"
+                }]
+            })
+        );
+    }
+
+    #[test]
+    fn test_line_string_has_lon_lat_positions() {
+        let feature = Feature::new(LineString::new(&[(52.5, 13.4), (52.6, 13.5)]), ());
+
+        let json = serde_json::to_value(&feature).unwrap();
+
+        assert_eq!(
+            json["geometry"],
+            serde_json::json!({"type": "LineString", "coordinates": [[13.4, 52.5], [13.5, 52.6]]})
+        );
+    }
+
+    #[test]
+    fn test_reply_is_geojson() {
+        let response = geojson_reply(&FeatureCollection::<()>::new(vec![]));
+
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            GEOJSON_CONTENT_TYPE
+        );
+    }
+}
@@ -0,0 +1,443 @@
+use std::net::SocketAddr;
+use std::pin::Pin;
+
+use futures::{stream, Stream, StreamExt, TryStreamExt};
+use tonic::transport::Server;
+use tonic::{Request, Response, Status};
+use tracing::{debug, error, info, instrument};
+
+use crate::handlers::cell::{query_cell, GetCellQuery};
+use crate::handlers::cells::{query_cells, GetCellsQuery, MAX_PAGE_SIZE};
+use crate::handlers::geolocate::{query_geolocate, GeolocateRequest, Observation};
+use crate::handlers::lookup::{query_lookup, CellKey};
+use crate::models::Radio;
+use crate::utils::config::Config;
+use crate::utils::db::try_establish_connection;
+use crate::utils::errors::{ApiError, FieldError};
+use crate::utils::proto;
+use crate::utils::query::Validate;
+use crate::utils::utils::{Promise, Shutdown};
+
+use proto::cell_service_server::{CellService, CellServiceServer};
+
+impl From<ApiError> for Status {
+    fn from(err: ApiError) -> Self {
+        match err {
+            ApiError::BadRequest(fields) => Status::invalid_argument(
+                fields
+                    .iter()
+                    .map(|f| format!("{}: {}", f.field, f.message))
+                    .collect::<Vec<_>>()
+                    .join("; "),
+            ),
+            ApiError::NotFound(message) => Status::not_found(message),
+            ApiError::NotAcceptable(message) => Status::invalid_argument(message),
+            ApiError::ServiceUnavailable(reason) => {
+                // Don't leak database details to clients, they are logged instead
+                error!("Database unavailable: {}", reason);
+                Status::unavailable("The database is currently unavailable")
+            }
+        }
+    }
+}
+
+/// Narrows a protobuf integer to the width used by the database.
+fn narrow<S: Copy, T: TryFrom<S>>(field: &str, value: S) -> Result<T, ApiError> {
+    T::try_from(value)
+        .map_err(|_| ApiError::BadRequest(vec![FieldError::new(field, "is out of range")]))
+}
+
+/// Decodes a radio field, unknown values are rejected.
+fn radio(value: i32) -> Result<Option<Radio>, ApiError> {
+    proto::Radio::try_from(value)
+        .map(Option::<Radio>::from)
+        .map_err(|_| ApiError::BadRequest(vec![FieldError::new("radio", "is not a known radio")]))
+}
+
+fn cell_key(key: &proto::CellKey) -> Result<CellKey, ApiError> {
+    Ok(CellKey {
+        mcc: narrow("mcc", key.mcc)?,
+        mnc: narrow("mnc", key.mnc)?,
+        lac: key.lac,
+        cid: key.cid,
+    })
+}
+
+fn list_query(request: &proto::ListCellsRequest) -> Result<GetCellsQuery, ApiError> {
+    let query = GetCellsQuery {
+        mcc: request.mcc.map(|mcc| narrow("mcc", mcc)).transpose()?,
+        mnc: request.mnc.map(|mnc| narrow("mnc", mnc)).transpose()?,
+        area: request.area,
+        min_lat: request.min_lat,
+        max_lat: request.max_lat,
+        min_lon: request.min_lon,
+        max_lon: request.max_lon,
+        radio: radio(request.radio)?,
+        min_confidence: request.min_confidence,
+        cursor: None,
+        limit: Some(
+            request
+                .limit
+                .unwrap_or(MAX_PAGE_SIZE)
+                .clamp(1, MAX_PAGE_SIZE),
+        ),
+    };
+    query.validate().map_err(ApiError::BadRequest)?;
+    Ok(query)
+}
+
+fn geolocate_request(request: &proto::GeolocateRequest) -> Result<GeolocateRequest, ApiError> {
+    let observations = request
+        .observations
+        .iter()
+        .map(|observation| {
+            let key = observation
+                .key
+                .as_ref()
+                .ok_or_else(|| ApiError::BadRequest(vec![FieldError::new("key", "must be set")]))?;
+            Ok(Observation {
+                key: cell_key(key)?,
+                signal_dbm: observation
+                    .signal_dbm
+                    .map(|signal| narrow("signal_dbm", signal))
+                    .transpose()?,
+                timing_advance: observation
+                    .timing_advance
+                    .map(|timing_advance| narrow("timing_advance", timing_advance))
+                    .transpose()?,
+            })
+        })
+        .collect::<Result<Vec<_>, ApiError>>()?;
+
+    let request = GeolocateRequest { observations };
+    request.validate().map_err(ApiError::BadRequest)?;
+    Ok(request)
+}
+
+/// The gRPC cell service, answering from the same queries as the REST API.
+#[derive(Debug)]
+pub struct GrpcCellService {
+    config: Config,
+}
+
+impl GrpcCellService {
+    pub fn new(config: Config) -> Self {
+        GrpcCellService { config }
+    }
+}
+
+type CellStream = Pin<Box<dyn Stream<Item = Result<proto::Cell, Status>> + Send>>;
+
+#[tonic::async_trait]
+impl CellService for GrpcCellService {
+    #[instrument(skip(self))]
+    async fn get_cell(
+        &self,
+        request: Request<proto::GetCellRequest>,
+    ) -> Result<Response<proto::Cell>, Status> {
+        let request = request.into_inner();
+        let query = GetCellQuery {
+            mcc: narrow("mcc", request.mcc)?,
+            net: narrow("net", request.net)?,
+            area: request.area,
+            cell: request.cell,
+            radio: radio(request.radio)?,
+            match_mode: None,
+        };
+
+        let connection =
+            &mut try_establish_connection(self.config.clone()).map_err(ApiError::from)?;
+        match query_cell(&query, connection).map_err(ApiError::from)? {
+            Some(cell) => Ok(Response::new(cell.into())),
+            None => Err(ApiError::NotFound("Cell not found".to_string()).into()),
+        }
+    }
+
+    type ListCellsStream = CellStream;
+
+    #[instrument(skip(self))]
+    async fn list_cells(
+        &self,
+        request: Request<proto::ListCellsRequest>,
+    ) -> Result<Response<Self::ListCellsStream>, Status> {
+        let request = request.into_inner();
+        let query = list_query(&request)?;
+        let limit = request.limit.map_or(usize::MAX, |limit| limit as usize);
+
+        let connection = try_establish_connection(self.config.clone()).map_err(ApiError::from)?;
+        let secret = self.config.cursor_secret.clone();
+        // Pages through the cells with the same cursors as `/cells`
+        let pages =
+            stream::try_unfold((Some(query), connection), move |(query, mut connection)| {
+                let secret = secret.clone();
+                async move {
+                    let Some(query) = query else {
+                        return Ok(None);
+                    };
+                    let page = query_cells(&query, secret.as_bytes(), &mut connection)?;
+                    let next = page.next_cursor.map(|cursor| GetCellsQuery {
+                        cursor: Some(cursor),
+                        ..query
+                    });
+                    Ok::<_, Status>(Some((page.cells, (next, connection))))
+                }
+            });
+        let cells = pages
+            .map_ok(|cells| stream::iter(cells.into_iter().map(proto::Cell::from).map(Ok)))
+            .try_flatten()
+            .take(limit);
+
+        Ok(Response::new(Box::pin(cells)))
+    }
+
+    #[instrument(skip(self))]
+    async fn lookup_cells(
+        &self,
+        request: Request<proto::LookupCellsRequest>,
+    ) -> Result<Response<proto::LookupCellsResponse>, Status> {
+        let keys = request
+            .into_inner()
+            .cells
+            .iter()
+            .map(cell_key)
+            .collect::<Result<Vec<_>, ApiError>>()?;
+
+        let connection =
+            &mut try_establish_connection(self.config.clone()).map_err(ApiError::from)?;
+        let results = query_lookup(&keys, connection)
+            .map_err(ApiError::from)?
+            .into_iter()
+            .map(|cell| proto::LookupResult {
+                cell: cell.map(proto::Cell::from),
+            })
+            .collect();
+
+        Ok(Response::new(proto::LookupCellsResponse {
+            results,
+            candidates: vec![],
+        }))
+    }
+
+    #[instrument(skip(self))]
+    async fn geolocate(
+        &self,
+        request: Request<proto::GeolocateRequest>,
+    ) -> Result<Response<proto::GeolocateResponse>, Status> {
+        let request = geolocate_request(&request.into_inner())?;
+
+        let connection =
+            &mut try_establish_connection(self.config.clone()).map_err(ApiError::from)?;
+        match query_geolocate(&request, connection).map_err(ApiError::from)? {
+            Some(position) => Ok(Response::new(proto::GeolocateResponse {
+                lat: position.lat,
+                lon: position.lon,
+                accuracy: position.accuracy,
+                cells_used: position.cells_used,
+            })),
+            None => {
+                Err(ApiError::NotFound("None of the observed cells are known".to_string()).into())
+            }
+        }
+    }
+}
+
+pub async fn start_grpc_server(shutdown: Shutdown, config: Config) -> Promise<()> {
+    let address = SocketAddr::from((config.bind, config.grpc_port));
+
+    info!("Start gRPC server.");
+    debug!("gRPC Address: {}", address);
+
+    Server::builder()
+        .add_service(CellServiceServer::new(GrpcCellService::new(config)))
+        .serve_with_shutdown(address, async move {
+            shutdown.await.ok();
+        })
+        .await?;
+
+    info!("gRPC server stopped.");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::config::test_config;
+    use futures::FutureExt;
+    use tonic::Code;
+
+    fn service() -> GrpcCellService {
+        GrpcCellService::new(test_config())
+    }
+
+    mod conversion {
+        use super::*;
+
+        #[test]
+        fn test_unspecified_radio_matches_any() {
+            assert_eq!(radio(proto::Radio::Unspecified.into()).unwrap(), None);
+            assert_eq!(radio(proto::Radio::Nr.into()).unwrap(), Some(Radio::Nr));
+        }
+
+        #[test]
+        fn test_unknown_radio_is_rejected() {
+            assert!(radio(42).is_err());
+        }
+
+        #[test]
+        fn test_out_of_range_mcc_is_rejected() {
+            let key = proto::CellKey {
+                mcc: 70_000,
+                mnc: 1,
+                lac: 1,
+                cid: 1,
+            };
+
+            match cell_key(&key) {
+                Err(ApiError::BadRequest(fields)) => assert_eq!(fields[0].field, "mcc"),
+                other => panic!("Expected a bad request, got {:?}", other),
+            }
+        }
+
+        #[test]
+        fn test_list_limit_caps_the_page_size() {
+            let query = list_query(&proto::ListCellsRequest {
+                limit: Some(5),
+                ..Default::default()
+            })
+            .unwrap();
+            assert_eq!(query.limit, Some(5));
+
+            let query = list_query(&proto::ListCellsRequest::default()).unwrap();
+            assert_eq!(query.limit, Some(MAX_PAGE_SIZE));
+        }
+
+        #[test]
+        fn test_list_min_confidence_is_validated() {
+            let query = list_query(&proto::ListCellsRequest {
+                min_confidence: Some(0.5),
+                ..Default::default()
+            })
+            .unwrap();
+            assert_eq!(query.min_confidence, Some(0.5));
+
+            match list_query(&proto::ListCellsRequest {
+                min_confidence: Some(1.5),
+                ..Default::default()
+            }) {
+                Err(ApiError::BadRequest(fields)) => assert_eq!(fields[0].field, "min_confidence"),
+                other => panic!("Expected a bad request, got {:?}", other),
+            }
+        }
+    }
+
+    mod status {
+        use super::*;
+
+        #[test]
+        fn test_bad_request_lists_the_fields() {
+            let status = Status::from(ApiError::BadRequest(vec![
+                FieldError::new("min_lat", "must be between -90 and 90"),
+                FieldError::new("limit", "must be at least 1"),
+            ]));
+
+            assert_eq!(status.code(), Code::InvalidArgument);
+            assert_eq!(
+                status.message(),
+                "min_lat: must be between -90 and 90; limit: must be at least 1"
+            );
+        }
+
+        #[test]
+        fn test_database_errors_are_not_leaked() {
+            let status = Status::from(ApiError::ServiceUnavailable("secret dsn".to_string()));
+
+            assert_eq!(status.code(), Code::Unavailable);
+            assert!(!status.message().contains("secret"));
+        }
+    }
+
+    // These fail validation before the database is touched
+    mod validation {
+        use super::*;
+
+        #[tokio::test]
+        async fn test_list_cells_rejects_inverted_geofence() {
+            let request = proto::ListCellsRequest {
+                min_lat: Some(53.0),
+                max_lat: Some(52.0),
+                ..Default::default()
+            };
+
+            let status = service()
+                .list_cells(Request::new(request))
+                .await
+                .err()
+                .unwrap();
+
+            assert_eq!(status.code(), Code::InvalidArgument);
+            assert!(status.message().contains("min_lat"));
+        }
+
+        #[tokio::test]
+        async fn test_get_cell_rejects_out_of_range_net() {
+            let request = proto::GetCellRequest {
+                mcc: 262,
+                net: 100_000,
+                area: 1,
+                cell: 1,
+                radio: 0,
+            };
+
+            let status = service().get_cell(Request::new(request)).await.unwrap_err();
+
+            assert_eq!(status.code(), Code::InvalidArgument);
+            assert!(status.message().contains("net"));
+        }
+
+        #[tokio::test]
+        async fn test_geolocate_requires_observations() {
+            let status = service()
+                .geolocate(Request::new(proto::GeolocateRequest::default()))
+                .await
+                .unwrap_err();
+
+            assert_eq!(status.code(), Code::InvalidArgument);
+            assert!(status.message().contains("observations"));
+        }
+
+        #[tokio::test]
+        async fn test_geolocate_requires_keys() {
+            let request = proto::GeolocateRequest {
+                observations: vec![proto::Observation {
+                    key: None,
+                    signal_dbm: Some(-80),
+                    timing_advance: None,
+                }],
+            };
+
+            let status = service()
+                .geolocate(Request::new(request))
+                .await
+                .unwrap_err();
+
+            assert_eq!(status.code(), Code::InvalidArgument);
+            assert!(status.message().contains("key"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_server_stops_on_shutdown() {
+        let mut config = test_config();
+        config.grpc_port = 0;
+        let (tx, rx) = tokio::sync::oneshot::channel();
+
+        let server = tokio::spawn(start_grpc_server(rx.shared(), config));
+        tx.send(()).unwrap();
+
+        let result = tokio::time::timeout(std::time::Duration::from_secs(5), server)
+            .await
+            .expect("The server did not stop");
+        assert!(result.unwrap().is_ok());
+    }
+}
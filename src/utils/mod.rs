@@ -1,10 +1,31 @@
+pub mod caching;
+pub mod compression;
+pub mod confidence;
 pub mod config;
+pub mod coverage;
 pub mod data;
 pub mod db;
+pub mod errors;
+pub mod events;
+pub mod geo;
+pub mod geohash;
+pub mod geojson;
+pub mod grpc;
+pub mod modem;
+pub mod negotiation;
+pub mod openapi;
+pub mod polygon;
+pub mod proto;
+pub mod query;
+pub mod ranging;
+pub mod route;
 pub mod server;
 pub mod telemetry;
 #[cfg(feature = "integration_tests")]
 pub mod test_db;
 pub mod update_type;
 pub mod url_builder;
+#[allow(clippy::module_inception)]
 pub mod utils;
+pub mod versioning;
+pub mod webhooks;
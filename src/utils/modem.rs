@@ -0,0 +1,714 @@
+//! Parsers for the cell information modems print in response to AT commands:
+//! Quectel `AT+QENG="servingcell"` / `"neighbourcell"`, SIMCom `AT+CPSI?` and
+//! u-blox `AT+UCGED?` (mode 2, LTE).
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::models::Radio;
+
+/// A cell as reported by a modem. Neighbours are often only known by their
+/// physical identity (`unit` and `channel`), not their global cell ID.
+#[derive(Serialize, Debug, Clone, PartialEq, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ModemCell {
+    pub radio: Radio,
+    pub mcc: Option<u16>,
+    pub net: Option<u16>,
+    /// Location or Tracking Area Code
+    pub area: Option<u32>,
+    /// Global cell ID
+    pub cell: Option<u64>,
+    /// Primary Scrambling Code (UMTS) or Physical Cell ID (LTE/NR)
+    pub unit: Option<u16>,
+    /// ARFCN, UARFCN, EARFCN or NR-ARFCN
+    pub channel: Option<u32>,
+    pub band: Option<u16>,
+    /// RSRP (LTE/NR), RSCP (UMTS) or RSSI (GSM) in dBm
+    pub signal_dbm: Option<i16>,
+}
+
+impl ModemCell {
+    fn new(radio: Radio) -> Self {
+        ModemCell {
+            radio,
+            mcc: None,
+            net: None,
+            area: None,
+            cell: None,
+            unit: None,
+            channel: None,
+            band: None,
+            signal_dbm: None,
+        }
+    }
+}
+
+/// The cells found in a modem response.
+#[derive(Serialize, Debug, Clone, Default, PartialEq, ToSchema)]
+pub struct ModemCells {
+    /// Usually one cell, two with LTE/NR dual connectivity
+    pub serving: Vec<ModemCell>,
+    pub neighbours: Vec<ModemCell>,
+}
+
+/// A line of a modem response that couldn't be parsed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    /// 1-based line number, `0` if the output as a whole is unusable
+    pub line: usize,
+    pub message: String,
+}
+
+impl ParseError {
+    fn new(line: usize, message: impl Into<String>) -> Self {
+        ParseError {
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            0 => write!(f, "{}", self.message),
+            line => write!(f, "line {}: {}", line, self.message),
+        }
+    }
+}
+
+/// Comma separated fields of a response line, unquoted and trimmed.
+struct Fields<'a> {
+    values: Vec<&'a str>,
+}
+
+impl<'a> Fields<'a> {
+    fn new(line: &'a str) -> Self {
+        Fields {
+            values: line
+                .split(',')
+                .map(|v| v.trim().trim_matches('"'))
+                .collect(),
+        }
+    }
+
+    fn get(&self, index: usize) -> Result<&'a str, String> {
+        self.values
+            .get(index)
+            .copied()
+            .ok_or_else(|| format!("expected at least {} fields", index + 1))
+    }
+
+    /// A decimal value, `None` for the placeholders modems print for unknown values.
+    fn number<T: FromStr>(&self, index: usize) -> Result<Option<T>, String> {
+        let value = self.get(index)?;
+        if matches!(value, "" | "-" | "--") {
+            return Ok(None);
+        }
+        value
+            .parse()
+            .map(Some)
+            .map_err(|_| format!("field {} is not a number: {:?}", index + 1, value))
+    }
+
+    /// A hexadecimal value, with or without `0x` prefix.
+    fn hex(&self, index: usize) -> Result<Option<u64>, String> {
+        let value = self.get(index)?;
+        if matches!(value, "" | "-" | "--") {
+            return Ok(None);
+        }
+        let digits = value
+            .strip_prefix("0x")
+            .or_else(|| value.strip_prefix("0X"))
+            .unwrap_or(value);
+        u64::from_str_radix(digits, 16)
+            .map(Some)
+            .map_err(|_| format!("field {} is not hexadecimal: {:?}", index + 1, value))
+    }
+
+    fn hex_u32(&self, index: usize) -> Result<Option<u32>, String> {
+        self.hex(index)?
+            .map(|value| {
+                u32::try_from(value).map_err(|_| format!("field {} is out of range", index + 1))
+            })
+            .transpose()
+    }
+
+    /// The number in a field like `27 EGSM 900` or `NR5G_BAND78`, the one
+    /// after `BAND` if there is one, otherwise the first.
+    fn embedded_number<T: FromStr>(&self, index: usize) -> Result<Option<T>, String> {
+        let value = self.get(index)?;
+        let value = value.rsplit_once("BAND").map_or(value, |(_, band)| band);
+        let digits: String = value
+            .chars()
+            .skip_while(|c| !c.is_ascii_digit())
+            .take_while(char::is_ascii_digit)
+            .collect();
+        Ok(digits.parse().ok())
+    }
+}
+
+/// Signal values outside this range are placeholders for unknown values.
+fn plausible_dbm(value: Option<i32>) -> Option<i16> {
+    value
+        .filter(|dbm| (-160..0).contains(dbm))
+        .map(|dbm| dbm as i16)
+}
+
+/// Parses Quectel `+QENG` lines, the prefix already removed.
+fn parse_quectel(line: &str, cells: &mut ModemCells) -> Result<(), String> {
+    let fields = Fields::new(line);
+    let kind = fields.get(0)?;
+
+    if kind == "servingcell" {
+        // States like `SEARCH` or `LIMSRV` come without a cell
+        if fields.values.len() <= 2 {
+            return Ok(());
+        }
+        let rat = fields.get(2)?;
+        cells.serving.push(quectel_serving(rat, &fields, 3)?);
+    } else if kind == "LTE" || kind == "NR5G-NSA" {
+        // Follow-up lines of `servingcell` with LTE/NR dual connectivity
+        cells.serving.push(quectel_serving(kind, &fields, 1)?);
+    } else if kind.starts_with("neighbourcell") {
+        cells.neighbours.push(quectel_neighbour(&fields)?);
+    } else {
+        return Err(format!("unknown +QENG record {:?}", kind));
+    }
+    Ok(())
+}
+
+/// A serving cell, whose RAT specific fields start at `offset`.
+fn quectel_serving(rat: &str, fields: &Fields, offset: usize) -> Result<ModemCell, String> {
+    let at = |index: usize| offset + index;
+    let cell = match rat {
+        "GSM" => ModemCell {
+            mcc: fields.number(at(0))?,
+            net: fields.number(at(1))?,
+            area: fields.hex_u32(at(2))?,
+            cell: fields.hex(at(3))?,
+            channel: fields.number(at(5))?,
+            band: fields.number(at(6)).unwrap_or(None),
+            signal_dbm: plausible_dbm(fields.number(at(7))?),
+            ..ModemCell::new(Radio::Gsm)
+        },
+        "WCDMA" => ModemCell {
+            mcc: fields.number(at(0))?,
+            net: fields.number(at(1))?,
+            area: fields.hex_u32(at(2))?,
+            cell: fields.hex(at(3))?,
+            channel: fields.number(at(4))?,
+            unit: fields.number(at(5))?,
+            signal_dbm: plausible_dbm(fields.number(at(7))?),
+            ..ModemCell::new(Radio::Umts)
+        },
+        "LTE" | "CAT-M" | "CAT-NB" | "eMTC" | "NBIoT" => ModemCell {
+            mcc: fields.number(at(1))?,
+            net: fields.number(at(2))?,
+            cell: fields.hex(at(3))?,
+            unit: fields.number(at(4))?,
+            channel: fields.number(at(5))?,
+            band: fields.number(at(6))?,
+            area: fields.hex_u32(at(9))?,
+            signal_dbm: plausible_dbm(fields.number(at(10))?),
+            ..ModemCell::new(Radio::Lte)
+        },
+        "NR5G-SA" => ModemCell {
+            mcc: fields.number(at(1))?,
+            net: fields.number(at(2))?,
+            cell: fields.hex(at(3))?,
+            unit: fields.number(at(4))?,
+            area: fields.hex_u32(at(5))?,
+            channel: fields.number(at(6))?,
+            band: fields.number(at(7))?,
+            signal_dbm: plausible_dbm(fields.number(at(9))?),
+            ..ModemCell::new(Radio::Nr)
+        },
+        // The NR leg of dual connectivity has no cell ID of its own
+        "NR5G-NSA" => ModemCell {
+            mcc: fields.number(at(0))?,
+            net: fields.number(at(1))?,
+            unit: fields.number(at(2))?,
+            signal_dbm: plausible_dbm(fields.number(at(3))?),
+            channel: fields.number(at(6))?,
+            band: fields.number(at(7))?,
+            ..ModemCell::new(Radio::Nr)
+        },
+        other => return Err(format!("unsupported radio {:?}", other)),
+    };
+    Ok(cell)
+}
+
+fn quectel_neighbour(fields: &Fields) -> Result<ModemCell, String> {
+    let cell = match fields.get(1)? {
+        "GSM" => ModemCell {
+            mcc: fields.number(2)?,
+            net: fields.number(3)?,
+            area: fields.hex_u32(4)?,
+            cell: fields.hex(5)?,
+            channel: fields.number(7)?,
+            signal_dbm: plausible_dbm(fields.number(8)?),
+            ..ModemCell::new(Radio::Gsm)
+        },
+        "WCDMA" => ModemCell {
+            channel: fields.number(2)?,
+            unit: fields.number(6)?,
+            signal_dbm: plausible_dbm(fields.number(7)?),
+            ..ModemCell::new(Radio::Umts)
+        },
+        "LTE" => ModemCell {
+            channel: fields.number(2)?,
+            unit: fields.number(3)?,
+            signal_dbm: plausible_dbm(fields.number(5)?),
+            ..ModemCell::new(Radio::Lte)
+        },
+        other => return Err(format!("unsupported radio {:?}", other)),
+    };
+    Ok(cell)
+}
+
+/// SIMCom reports LTE and NR levels in tenths of a dB on most modules.
+fn simcom_dbm(value: Option<i32>) -> Option<i16> {
+    plausible_dbm(value.map(|v| if v < -200 { v / 10 } else { v }))
+}
+
+/// Parses a SIMCom `+CPSI` line, the prefix already removed.
+fn parse_simcom(line: &str, cells: &mut ModemCells) -> Result<(), String> {
+    let fields = Fields::new(line);
+    let mode = fields.get(0)?;
+    if mode == "NO SERVICE" {
+        return Ok(());
+    }
+
+    let (mcc, net) = match fields.get(2)?.split_once('-') {
+        Some((mcc, net)) => (
+            mcc.parse().map_err(|_| format!("invalid MCC {:?}", mcc))?,
+            net.parse().map_err(|_| format!("invalid MNC {:?}", net))?,
+        ),
+        None => return Err(format!("expected MCC-MNC, got {:?}", fields.get(2)?)),
+    };
+    let base = |radio| ModemCell {
+        mcc: Some(mcc),
+        net: Some(net),
+        ..ModemCell::new(radio)
+    };
+
+    let cell = if mode == "GSM" {
+        ModemCell {
+            area: fields.hex_u32(3)?,
+            cell: fields.number(4)?,
+            channel: fields.embedded_number(5)?,
+            signal_dbm: plausible_dbm(fields.number(6)?),
+            ..base(Radio::Gsm)
+        }
+    } else if mode == "WCDMA" {
+        ModemCell {
+            area: fields.hex_u32(3)?,
+            cell: fields.number(4)?,
+            unit: fields.number(6)?,
+            channel: fields.number(7)?,
+            // RSCP is printed without its sign
+            signal_dbm: plausible_dbm(fields.number::<i32>(10)?.map(|v| -v.abs())),
+            ..base(Radio::Umts)
+        }
+    } else if mode.starts_with("LTE") {
+        ModemCell {
+            area: fields.hex_u32(3)?,
+            cell: fields.number(4)?,
+            unit: fields.number(5)?,
+            band: fields.embedded_number(6)?,
+            channel: fields.number(7)?,
+            signal_dbm: simcom_dbm(fields.number(11)?),
+            ..base(Radio::Lte)
+        }
+    } else if mode.starts_with("NR5G_SA") {
+        ModemCell {
+            area: fields.hex_u32(3)?,
+            cell: fields.number(4)?,
+            unit: fields.number(5)?,
+            band: fields.embedded_number(6)?,
+            channel: fields.number(7)?,
+            signal_dbm: simcom_dbm(fields.number(8)?),
+            ..base(Radio::Nr)
+        }
+    } else {
+        return Err(format!("unsupported system mode {:?}", mode));
+    };
+    cells.serving.push(cell);
+    Ok(())
+}
+
+/// Where the parser is within a multi-line `+UCGED: 2` response.
+enum Ucged {
+    /// Expecting `<rat>,<svc>,<MCC>,<MNC>`
+    Header,
+    /// Expecting the LTE cell line
+    Lte { mcc: Option<u16>, net: Option<u16> },
+}
+
+fn parse_ucged_header(line: &str) -> Result<Ucged, String> {
+    let fields = Fields::new(line);
+    match fields.number::<u8>(0)? {
+        // LTE, LTE Cat-M1 and NB-IoT
+        Some(4 | 6 | 7) => Ok(Ucged::Lte {
+            mcc: fields.number(2)?,
+            net: fields.number(3)?,
+        }),
+        other => Err(format!("unsupported radio access technology {:?}", other)),
+    }
+}
+
+fn parse_ucged_lte(line: &str, mcc: Option<u16>, net: Option<u16>) -> Result<ModemCell, String> {
+    let fields = Fields::new(line);
+    Ok(ModemCell {
+        mcc,
+        net,
+        channel: fields.number(0)?,
+        band: fields.number(1)?,
+        area: fields.hex_u32(4)?,
+        cell: fields.hex(5)?,
+        unit: fields.number(6)?,
+        // An RSRP index as defined in 3GPP TS 36.133, 255 if unknown
+        signal_dbm: fields
+            .number::<i32>(10)?
+            .filter(|index| *index <= 97)
+            .map(|index| (index - 140) as i16),
+        ..ModemCell::new(Radio::Lte)
+    })
+}
+
+/// Parses the raw output of the supported AT commands. Lines without a
+/// known prefix, like echoed commands or `OK`, are ignored. Responses of
+/// several commands may be concatenated.
+pub fn parse_modem_output(output: &str) -> Result<ModemCells, ParseError> {
+    let mut cells = ModemCells::default();
+    let mut recognized = false;
+    let mut ucged: Option<Ucged> = None;
+
+    for (index, line) in output.lines().enumerate() {
+        let number = index + 1;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let error = |message: String| ParseError::new(number, message);
+
+        if let Some(state) = ucged.take() {
+            match state {
+                Ucged::Header => ucged = Some(parse_ucged_header(line).map_err(error)?),
+                Ucged::Lte { mcc, net } => cells
+                    .serving
+                    .push(parse_ucged_lte(line, mcc, net).map_err(error)?),
+            }
+            continue;
+        }
+
+        if let Some(rest) = line.strip_prefix("+QENG:") {
+            recognized = true;
+            parse_quectel(rest, &mut cells).map_err(error)?;
+        } else if let Some(rest) = line.strip_prefix("+CPSI:") {
+            recognized = true;
+            parse_simcom(rest, &mut cells).map_err(error)?;
+        } else if let Some(rest) = line.strip_prefix("+UCGED:") {
+            recognized = true;
+            match rest.trim() {
+                "2" => ucged = Some(Ucged::Header),
+                mode => return Err(error(format!("unsupported +UCGED mode {:?}", mode))),
+            }
+        }
+    }
+
+    if ucged.is_some() {
+        return Err(ParseError::new(0, "the +UCGED response is incomplete"));
+    }
+    if !recognized {
+        return Err(ParseError::new(
+            0,
+            "no +QENG, +CPSI or +UCGED response found",
+        ));
+    }
+    Ok(cells)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod quectel {
+        use super::*;
+
+        #[test]
+        fn test_lte_serving_cell() {
+            let output = "AT+QENG=\"servingcell\"\r\n+QENG: \"servingcell\",\"NOCONN\",\"LTE\",\"FDD\",262,01,1A2D003,123,1300,3,5,5,3A7D,-97,-11,-67,16,36\r\n\r\nOK\r\n";
+
+            let cells = parse_modem_output(output).unwrap();
+
+            assert_eq!(
+                cells.serving,
+                vec![ModemCell {
+                    mcc: Some(262),
+                    net: Some(1),
+                    area: Some(0x3A7D),
+                    cell: Some(0x1A2D003),
+                    unit: Some(123),
+                    channel: Some(1300),
+                    band: Some(3),
+                    signal_dbm: Some(-97),
+                    ..ModemCell::new(Radio::Lte)
+                }]
+            );
+            assert!(cells.neighbours.is_empty());
+        }
+
+        #[test]
+        fn test_gsm_serving_cell() {
+            let output = "+QENG: \"servingcell\",\"NOCONN\",\"GSM\",460,00,550A,2BDA,26,50,-,-67,255,255,0,38,38,1,-,-,-,-,-,-,-,-,-,\"-\"";
+
+            let cell = &parse_modem_output(output).unwrap().serving[0];
+
+            assert_eq!(cell.radio, Radio::Gsm);
+            assert_eq!(cell.area, Some(0x550A));
+            assert_eq!(cell.cell, Some(0x2BDA));
+            assert_eq!(cell.channel, Some(50));
+            assert_eq!(cell.band, None);
+            assert_eq!(cell.signal_dbm, Some(-67));
+        }
+
+        #[test]
+        fn test_wcdma_serving_cell() {
+            let output = "+QENG: \"servingcell\",\"NOCONN\",\"WCDMA\",262,02,A5B2,3A5F06,10737,279,1,-85,-6,-,-,-,-,-";
+
+            let cell = &parse_modem_output(output).unwrap().serving[0];
+
+            assert_eq!(cell.radio, Radio::Umts);
+            assert_eq!(cell.cell, Some(0x3A5F06));
+            assert_eq!(cell.unit, Some(279));
+            assert_eq!(cell.signal_dbm, Some(-85));
+        }
+
+        #[test]
+        fn test_nr_standalone_serving_cell() {
+            let output = "+QENG: \"servingcell\",\"NOCONN\",\"NR5G-SA\",\"TDD\",262,01,B7A5C6001,512,2A1B,636768,78,12,-88,-11,15,1,-";
+
+            let cell = &parse_modem_output(output).unwrap().serving[0];
+
+            assert_eq!(cell.radio, Radio::Nr);
+            assert_eq!(cell.cell, Some(0xB7A5C6001));
+            assert_eq!(cell.unit, Some(512));
+            assert_eq!(cell.area, Some(0x2A1B));
+            assert_eq!(cell.channel, Some(636768));
+            assert_eq!(cell.band, Some(78));
+            assert_eq!(cell.signal_dbm, Some(-88));
+        }
+
+        #[test]
+        fn test_dual_connectivity() {
+            let output = "+QENG: \"servingcell\",\"NOCONN\"\n+QENG: \"LTE\",\"FDD\",262,01,1A2D003,123,1300,3,5,5,3A7D,-97,-11,-67,16,10,-,36\n+QENG: \"NR5G-NSA\",262,01,512,-90,14,-11,636768,78";
+
+            let cells = parse_modem_output(output).unwrap();
+
+            assert_eq!(cells.serving.len(), 2);
+            assert_eq!(cells.serving[0].cell, Some(0x1A2D003));
+            assert_eq!(cells.serving[1].radio, Radio::Nr);
+            assert_eq!(cells.serving[1].cell, None);
+            assert_eq!(cells.serving[1].unit, Some(512));
+        }
+
+        #[test]
+        fn test_searching_has_no_cell() {
+            let cells = parse_modem_output("+QENG: \"servingcell\",\"SEARCH\"").unwrap();
+
+            assert!(cells.serving.is_empty());
+        }
+
+        #[test]
+        fn test_neighbour_cells() {
+            let output = "+QENG: \"neighbourcell intra\",\"LTE\",1300,124,-12,-101,-71,8,30,5,12,2,62\n+QENG: \"neighbourcell inter\",\"LTE\",6300,301,-14,-108,-80,-,20,3,6,10\n+QENG: \"neighbourcell\",\"WCDMA\",10737,0,-,-,280,-95,-9,20\n+QENG: \"neighbourcell\",\"GSM\",262,01,550A,2BDB,20,52,-80,25,25,0,0\nOK";
+
+            let neighbours = parse_modem_output(output).unwrap().neighbours;
+
+            assert_eq!(neighbours.len(), 4);
+            assert_eq!(neighbours[0].unit, Some(124));
+            assert_eq!(neighbours[0].channel, Some(1300));
+            assert_eq!(neighbours[0].signal_dbm, Some(-101));
+            assert_eq!(neighbours[1].channel, Some(6300));
+            assert_eq!(neighbours[2].radio, Radio::Umts);
+            assert_eq!(neighbours[2].unit, Some(280));
+            assert_eq!(neighbours[3].cell, Some(0x2BDB));
+            assert_eq!(neighbours[3].signal_dbm, Some(-80));
+        }
+
+        #[test]
+        fn test_unknown_radio_is_an_error() {
+            let error = parse_modem_output("OK\n+QENG: \"servingcell\",\"NOCONN\",\"CDMA\",1,2")
+                .unwrap_err();
+
+            assert_eq!(error.line, 2);
+            assert!(error.message.contains("CDMA"));
+        }
+
+        #[test]
+        fn test_truncated_line_is_an_error() {
+            let error = parse_modem_output("+QENG: \"servingcell\",\"NOCONN\",\"LTE\",\"FDD\",262")
+                .unwrap_err();
+
+            assert_eq!(error.line, 1);
+            assert!(error.message.contains("fields"), "{}", error);
+        }
+    }
+
+    mod simcom {
+        use super::*;
+
+        #[test]
+        fn test_lte_in_tenths_of_db() {
+            let output = "+CPSI: LTE,Online,460-11,0x5A1E,187214780,257,EUTRAN-BAND3,1850,5,5,-94,-850,-545,15\r\nOK";
+
+            let cell = &parse_modem_output(output).unwrap().serving[0];
+
+            assert_eq!(
+                cell,
+                &ModemCell {
+                    mcc: Some(460),
+                    net: Some(11),
+                    area: Some(0x5A1E),
+                    cell: Some(187214780),
+                    unit: Some(257),
+                    channel: Some(1850),
+                    band: Some(3),
+                    signal_dbm: Some(-85),
+                    ..ModemCell::new(Radio::Lte)
+                }
+            );
+        }
+
+        #[test]
+        fn test_lte_cat_m_in_db() {
+            let output = "+CPSI: LTE CAT-M1,Online,262-01,0x1816,12345678,236,EUTRAN-BAND8,3740,3,3,-10,-84,-57,13";
+
+            let cell = &parse_modem_output(output).unwrap().serving[0];
+
+            assert_eq!(cell.radio, Radio::Lte);
+            assert_eq!(cell.band, Some(8));
+            assert_eq!(cell.signal_dbm, Some(-84));
+        }
+
+        #[test]
+        fn test_gsm() {
+            let output = "+CPSI: GSM,Online,460-00,0x182d,12401,27 EGSM 900,-64,2110,42-42";
+
+            let cell = &parse_modem_output(output).unwrap().serving[0];
+
+            assert_eq!(cell.radio, Radio::Gsm);
+            assert_eq!(cell.area, Some(0x182D));
+            assert_eq!(cell.cell, Some(12401));
+            assert_eq!(cell.channel, Some(27));
+            assert_eq!(cell.signal_dbm, Some(-64));
+        }
+
+        #[test]
+        fn test_wcdma() {
+            let output = "+CPSI: WCDMA,Online,460-01,0xA809,11122855,WCDMA IMT 2000,279,10663,0,1.5,62,33,52,500";
+
+            let cell = &parse_modem_output(output).unwrap().serving[0];
+
+            assert_eq!(cell.radio, Radio::Umts);
+            assert_eq!(cell.unit, Some(279));
+            assert_eq!(cell.channel, Some(10663));
+            assert_eq!(cell.signal_dbm, Some(-62));
+        }
+
+        #[test]
+        fn test_nr_standalone() {
+            let output = "+CPSI: NR5G_SA,Online,262-01,0x2A1B,12345678901,512,NR5G_BAND78,636768,-880,-110,150";
+
+            let cell = &parse_modem_output(output).unwrap().serving[0];
+
+            assert_eq!(cell.radio, Radio::Nr);
+            assert_eq!(cell.cell, Some(12345678901));
+            assert_eq!(cell.band, Some(78));
+            assert_eq!(cell.signal_dbm, Some(-88));
+        }
+
+        #[test]
+        fn test_no_service() {
+            let cells = parse_modem_output("+CPSI: NO SERVICE,Online").unwrap();
+
+            assert!(cells.serving.is_empty());
+        }
+    }
+
+    mod ublox {
+        use super::*;
+
+        #[test]
+        fn test_lte() {
+            let output = "+UCGED: 2\r\n6,4,262,01\r\n2525,5,25,50,2b67,69f6bc7,111,00000000,ffff,ff,67,19,0.00,255,255,255,67,11,255,0,255,255,0,0\r\n\r\nOK";
+
+            let cells = parse_modem_output(output).unwrap();
+
+            assert_eq!(
+                cells.serving,
+                vec![ModemCell {
+                    mcc: Some(262),
+                    net: Some(1),
+                    area: Some(0x2B67),
+                    cell: Some(0x69F6BC7),
+                    unit: Some(111),
+                    channel: Some(2525),
+                    band: Some(5),
+                    signal_dbm: Some(-73),
+                    ..ModemCell::new(Radio::Lte)
+                }]
+            );
+        }
+
+        #[test]
+        fn test_unknown_rsrp() {
+            let output =
+                "+UCGED: 2\n7,4,262,01\n6300,20,0,0,2b67,69f6bc7,111,00000000,ffff,ff,255,255";
+
+            let cell = &parse_modem_output(output).unwrap().serving[0];
+
+            assert_eq!(cell.signal_dbm, None);
+        }
+
+        #[test]
+        fn test_incomplete_response() {
+            let error = parse_modem_output("+UCGED: 2\n6,4,262,01").unwrap_err();
+
+            assert_eq!(error.line, 0);
+        }
+
+        #[test]
+        fn test_other_modes_are_unsupported() {
+            let error = parse_modem_output("+UCGED: 5").unwrap_err();
+
+            assert_eq!(error.line, 1);
+        }
+    }
+
+    #[test]
+    fn test_concatenated_responses() {
+        let output = "+CPSI: LTE,Online,262-01,0x3A7D,27447299,123,EUTRAN-BAND3,1300,5,5,-110,-970,-670,16\n+QENG: \"neighbourcell intra\",\"LTE\",1300,124,-12,-101,-71,8,30,5,12,2,62";
+
+        let cells = parse_modem_output(output).unwrap();
+
+        assert_eq!(cells.serving.len(), 1);
+        assert_eq!(cells.neighbours.len(), 1);
+    }
+
+    #[test]
+    fn test_unrecognized_output() {
+        let error = parse_modem_output("ERROR").unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "no +QENG, +CPSI or +UCGED response found"
+        );
+    }
+}
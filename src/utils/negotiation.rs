@@ -0,0 +1,393 @@
+use serde::{Deserialize, Serialize};
+use tracing::error;
+use warp::http::{header, HeaderValue, StatusCode};
+use warp::reply::Response;
+use warp::{Filter, Rejection, Reply};
+
+use super::errors::ApiError;
+use crate::handlers::cells::CellsFormat;
+
+/// A response encoding clients can ask for with `Accept`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Format {
+    #[default]
+    Json,
+    MessagePack,
+    Cbor,
+    /// The messages of `proto/cells.proto`
+    Protobuf,
+}
+
+impl Format {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Format::Json => "application/json",
+            Format::MessagePack => "application/msgpack",
+            Format::Cbor => "application/cbor",
+            Format::Protobuf => "application/x-protobuf",
+        }
+    }
+
+    fn from_media_type(media_type: &str) -> Option<Self> {
+        match media_type {
+            "application/json" | "application/*" | "*/*" => Some(Format::Json),
+            "application/msgpack" | "application/x-msgpack" | "application/vnd.msgpack" => {
+                Some(Format::MessagePack)
+            }
+            "application/cbor" => Some(Format::Cbor),
+            "application/x-protobuf" | "application/protobuf" => Some(Format::Protobuf),
+            _ => None,
+        }
+    }
+
+    /// The route name cache validators are derived from, so each format of a
+    /// resource has its own `ETag`. JSON keeps the plain route name.
+    pub fn cache_route(self, route: &str) -> String {
+        match self {
+            Format::Json => route.to_string(),
+            _ => format!("{}+{}", route, self.content_type()),
+        }
+    }
+}
+
+/// Splits a header like `Accept` or `Accept-Encoding` into its lowercased values
+/// and their quality, which defaults to 1.
+pub fn weighted_values(header: &str) -> Vec<(String, f32)> {
+    header
+        .split(',')
+        .filter_map(|item| {
+            let mut parts = item.split(';').map(str::trim);
+            let value = parts.next().filter(|value| !value.is_empty())?;
+            let quality = parts
+                .filter_map(|param| param.strip_prefix("q="))
+                .find_map(|q| q.parse::<f32>().ok())
+                .unwrap_or(1.0);
+            Some((value.to_ascii_lowercase(), quality))
+        })
+        .collect()
+}
+
+/// Picks the supported format with the highest quality from an `Accept` header.
+/// Ties go to the type listed first. Returns `None` if nothing acceptable is supported.
+pub fn negotiate(accept: &str) -> Option<Format> {
+    let mut best: Option<(Format, f32)> = None;
+    for (media_type, quality) in weighted_values(accept) {
+        if quality <= 0.0 {
+            continue;
+        }
+        if let Some(format) = Format::from_media_type(&media_type) {
+            if best.is_none_or(|(_, best_quality)| quality > best_quality) {
+                best = Some((format, quality));
+            }
+        }
+    }
+    best.map(|(format, _)| format)
+}
+
+/// The format of an `Accept` header, JSON if there is none.
+/// Rejects with `406 Not Acceptable` if only unsupported formats are accepted.
+fn accepted_format(accept: Option<String>) -> Result<Format, Rejection> {
+    match accept.as_deref().map(str::trim) {
+        None | Some("") => Ok(Format::Json),
+        Some(accept) => negotiate(accept).ok_or_else(|| {
+            warp::reject::custom(ApiError::NotAcceptable(format!(
+                "Supported formats are {}",
+                [
+                    Format::Json,
+                    Format::MessagePack,
+                    Format::Cbor,
+                    Format::Protobuf
+                ]
+                .map(Format::content_type)
+                .join(", ")
+            )))
+        }),
+    }
+}
+
+/// Extracts the response format from the `Accept` header, JSON if there is none.
+/// Rejects with `406 Not Acceptable` if only unsupported formats are accepted.
+pub fn format() -> impl Filter<Extract = (Format,), Error = Rejection> + Clone {
+    warp::header::optional::<String>("accept")
+        .and_then(|accept: Option<String>| async move { accepted_format(accept) })
+}
+
+/// The `format` query parameter of routes that can answer with GeoJSON.
+#[derive(Deserialize)]
+struct OutputQuery {
+    format: Option<CellsFormat>,
+}
+
+/// Like `format`, for routes that answer with GeoJSON on `format=geojson`.
+/// That answer doesn't depend on `Accept`, so it isn't negotiated and e.g.
+/// `Accept: application/geo+json` is not turned away.
+pub fn format_unless_geojson() -> impl Filter<Extract = (Format,), Error = Rejection> + Clone {
+    warp::query::<OutputQuery>()
+        .and(warp::header::optional::<String>("accept"))
+        .and_then(|output: OutputQuery, accept: Option<String>| async move {
+            match output.format {
+                Some(CellsFormat::Geojson) => Ok(Format::Json),
+                None => accepted_format(accept),
+            }
+        })
+}
+
+/// Types that can be encoded as one of the messages of `proto/cells.proto`.
+pub trait ToProtobuf {
+    fn to_protobuf(&self) -> Vec<u8>;
+}
+
+/// Encodes a response body in the negotiated format.
+pub fn encode<T: Serialize + ToProtobuf>(format: Format, value: &T) -> Response {
+    let body = match format {
+        Format::Json => serde_json::to_vec(value).map_err(|e| e.to_string()),
+        // Named fields keep the shape of the JSON responses
+        Format::MessagePack => rmp_serde::to_vec_named(value).map_err(|e| e.to_string()),
+        Format::Cbor => {
+            let mut body = vec![];
+            ciborium::into_writer(value, &mut body)
+                .map(|_| body)
+                .map_err(|e| e.to_string())
+        }
+        Format::Protobuf => Ok(value.to_protobuf()),
+    };
+
+    match body {
+        Ok(body) => {
+            let mut response = body.into_response();
+            response.headers_mut().insert(
+                header::CONTENT_TYPE,
+                HeaderValue::from_static(format.content_type()),
+            );
+            response
+        }
+        Err(reason) => {
+            error!("Failed to encode response as {:?}: {}", format, reason);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// An absent value, e.g. the legacy `null` of `/cell`, is an empty message.
+impl<T: ToProtobuf> ToProtobuf for Option<T> {
+    fn to_protobuf(&self) -> Vec<u8> {
+        self.as_ref().map(T::to_protobuf).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use warp::test::request;
+
+    mod negotiate {
+        use super::*;
+
+        #[test]
+        fn test_exact_types() {
+            assert_eq!(negotiate("application/json"), Some(Format::Json));
+            assert_eq!(negotiate("application/msgpack"), Some(Format::MessagePack));
+            assert_eq!(negotiate("application/cbor"), Some(Format::Cbor));
+            assert_eq!(negotiate("application/x-protobuf"), Some(Format::Protobuf));
+        }
+
+        #[test]
+        fn test_wildcards_are_json() {
+            assert_eq!(negotiate("*/*"), Some(Format::Json));
+            assert_eq!(negotiate("application/*"), Some(Format::Json));
+        }
+
+        #[test]
+        fn test_browser_accept_is_json() {
+            let accept = "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8";
+            assert_eq!(negotiate(accept), Some(Format::Json));
+        }
+
+        #[test]
+        fn test_highest_quality_wins() {
+            let accept = "application/json;q=0.5, application/cbor;q=0.9, */*;q=0.1";
+            assert_eq!(negotiate(accept), Some(Format::Cbor));
+        }
+
+        #[test]
+        fn test_first_wins_on_ties() {
+            assert_eq!(
+                negotiate("application/msgpack, application/json"),
+                Some(Format::MessagePack)
+            );
+        }
+
+        #[test]
+        fn test_zero_quality_is_excluded() {
+            assert_eq!(
+                negotiate("application/json;q=0, application/cbor;q=0.2"),
+                Some(Format::Cbor)
+            );
+        }
+
+        #[test]
+        fn test_case_and_whitespace_are_ignored() {
+            assert_eq!(
+                negotiate("  Application/X-Protobuf ; q=1"),
+                Some(Format::Protobuf)
+            );
+        }
+
+        #[test]
+        fn test_unsupported_types() {
+            assert_eq!(negotiate("text/html"), None);
+            assert_eq!(negotiate("application/xml, text/csv"), None);
+        }
+    }
+
+    #[test]
+    fn test_weighted_values() {
+        assert_eq!(
+            weighted_values("gzip;q=0.5, BR,, zstd ; q=0"),
+            vec![
+                ("gzip".to_string(), 0.5),
+                ("br".to_string(), 1.0),
+                ("zstd".to_string(), 0.0)
+            ]
+        );
+    }
+
+    mod format_filter {
+        use super::*;
+
+        async fn format_for(accept: Option<&str>) -> Result<Format, Rejection> {
+            let mut builder = request();
+            if let Some(accept) = accept {
+                builder = builder.header("accept", accept);
+            }
+            builder.filter(&format()).await
+        }
+
+        #[tokio::test]
+        async fn test_missing_accept_is_json() {
+            assert_eq!(format_for(None).await.unwrap(), Format::Json);
+        }
+
+        #[tokio::test]
+        async fn test_accept_is_negotiated() {
+            assert_eq!(
+                format_for(Some("application/msgpack")).await.unwrap(),
+                Format::MessagePack
+            );
+        }
+
+        #[tokio::test]
+        async fn test_unsupported_accept_is_rejected() {
+            let rejection = format_for(Some("text/html")).await.unwrap_err();
+
+            assert!(matches!(
+                rejection.find::<ApiError>(),
+                Some(ApiError::NotAcceptable(_))
+            ));
+        }
+
+        #[tokio::test]
+        async fn test_geojson_output_is_not_negotiated() {
+            let format = request()
+                .path("/?format=geojson")
+                .header("accept", "application/geo+json")
+                .filter(&format_unless_geojson())
+                .await;
+
+            assert_eq!(format.unwrap(), Format::Json);
+        }
+
+        #[tokio::test]
+        async fn test_other_outputs_are_negotiated() {
+            let rejection = request()
+                .path("/?limit=10")
+                .header("accept", "application/geo+json")
+                .filter(&format_unless_geojson())
+                .await
+                .unwrap_err();
+
+            assert!(matches!(
+                rejection.find::<ApiError>(),
+                Some(ApiError::NotAcceptable(_))
+            ));
+        }
+    }
+
+    mod encode {
+        use super::*;
+
+        #[derive(Serialize, serde::Deserialize, Debug, PartialEq)]
+        #[serde(rename_all = "camelCase")]
+        struct Sample {
+            has_more: bool,
+            value: Option<u16>,
+        }
+
+        impl ToProtobuf for Sample {
+            fn to_protobuf(&self) -> Vec<u8> {
+                vec![8, 1]
+            }
+        }
+
+        fn sample() -> Sample {
+            Sample {
+                has_more: true,
+                value: Some(262),
+            }
+        }
+
+        #[test]
+        fn test_json() {
+            let response = encode(Format::Json, &sample());
+
+            assert_eq!(response.headers()["content-type"], "application/json");
+        }
+
+        #[tokio::test]
+        async fn test_msgpack_keeps_field_names() {
+            let response = encode(Format::MessagePack, &sample());
+            assert_eq!(response.headers()["content-type"], "application/msgpack");
+
+            let body = warp::hyper::body::to_bytes(response.into_body())
+                .await
+                .unwrap();
+            let decoded: Sample = rmp_serde::from_slice(&body).unwrap();
+            assert_eq!(decoded, sample());
+            assert!(body.windows(7).any(|w| w == b"hasMore"));
+        }
+
+        #[tokio::test]
+        async fn test_cbor_roundtrip() {
+            let response = encode(Format::Cbor, &sample());
+            assert_eq!(response.headers()["content-type"], "application/cbor");
+
+            let body = warp::hyper::body::to_bytes(response.into_body())
+                .await
+                .unwrap();
+            let decoded: Sample = ciborium::from_reader(body.as_ref()).unwrap();
+            assert_eq!(decoded, sample());
+        }
+
+        #[tokio::test]
+        async fn test_protobuf() {
+            let response = encode(Format::Protobuf, &sample());
+            assert_eq!(response.headers()["content-type"], "application/x-protobuf");
+
+            let body = warp::hyper::body::to_bytes(response.into_body())
+                .await
+                .unwrap();
+            assert_eq!(body.as_ref(), &[8, 1]);
+        }
+
+        #[test]
+        fn test_absent_value_is_an_empty_message() {
+            assert!(None::<Sample>.to_protobuf().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_formats_have_their_own_cache_route() {
+        assert_eq!(Format::Json.cache_route("cells"), "cells");
+        assert_eq!(Format::Cbor.cache_route("cells"), "cells+application/cbor");
+    }
+}
@@ -0,0 +1,320 @@
+use std::sync::Arc;
+
+use utoipa::openapi::schema::{KnownFormat, ObjectBuilder, Schema, SchemaFormat, Type};
+use utoipa::openapi::RefOr;
+use utoipa::{Modify, OpenApi};
+use warp::http::{StatusCode, Uri};
+use warp::path::{FullPath, Tail};
+use warp::reply::Response;
+use warp::{Filter, Rejection, Reply};
+
+use crate::handlers;
+use crate::utils::server;
+
+/// The OpenAPI document of the REST API, generated from the handler annotations.
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Cell Service",
+        description = "Cell tower locations from OpenCellID, kept in sync daily."
+    ),
+    paths(
+        server::health,
+        server::sync_events,
+        handlers::cell::handle_get_cell,
+        handlers::coverage::handle_get_cell_coverage,
+        handlers::coverage::handle_get_point_coverage,
+        handlers::density::handle_get_density,
+        handlers::cells::handle_get_cells,
+        handlers::changes::handle_get_changes,
+        handlers::lookup::handle_lookup_cells,
+        handlers::neighbours::handle_resolve_neighbours,
+        handlers::nearest::handle_nearest_cells,
+        handlers::tracks::handle_localize_track,
+        handlers::route::handle_route_cells,
+        handlers::route::handle_coverage_report,
+        handlers::venue::handle_venue_operators,
+        handlers::modem::handle_parse_modem,
+    ),
+    tags(
+        (name = "cells", description = "Look up and list cells"),
+        (name = "tracks", description = "Localize devices from the cells they observe"),
+        (name = "routes", description = "Cells and coverage along uploaded routes"),
+        (name = "venues", description = "Pick operators for event venues"),
+        (name = "modem", description = "Parse the cell information modems report"),
+        (name = "service", description = "Service status and sync events"),
+    ),
+    modifiers(&CellConfidence)
+)]
+pub struct ApiDoc;
+
+/// Documents the `confidence` every serialized cell carries. It is derived,
+/// not a column, so the `Cell` schema does not know it.
+struct CellConfidence;
+
+impl Modify for CellConfidence {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let Some(components) = openapi.components.as_mut() else {
+            return;
+        };
+        if let Some(RefOr::T(Schema::Object(cell))) = components.schemas.get_mut("Cell") {
+            cell.properties.insert(
+                "confidence".to_string(),
+                ObjectBuilder::new()
+                    .schema_type(Type::Number)
+                    .format(Some(SchemaFormat::KnownFormat(KnownFormat::Double)))
+                    .minimum(Some(0.0))
+                    .maximum(Some(1.0))
+                    .description(Some(
+                        "Trust in the position from 0 to 1, derived from samples, range and age",
+                    ))
+                    .into(),
+            );
+            cell.required.push("confidence".to_string());
+        }
+    }
+}
+
+/// Serves the OpenAPI document at `/openapi.json`.
+pub fn openapi_route() -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    let spec = Arc::new(ApiDoc::openapi());
+    warp::path!("openapi.json")
+        .and(warp::get())
+        .map(move || warp::reply::json(spec.as_ref()))
+}
+
+/// Serves the bundled Swagger UI at `/docs/`, it works without internet access.
+pub fn docs_route() -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    let config = Arc::new(utoipa_swagger_ui::Config::from("/openapi.json"));
+    warp::path("docs")
+        .and(warp::get())
+        .and(warp::path::full())
+        .and(warp::path::tail())
+        .map(move |full_path: FullPath, tail: Tail| serve_docs(full_path, tail, config.clone()))
+}
+
+fn serve_docs(
+    full_path: FullPath,
+    tail: Tail,
+    config: Arc<utoipa_swagger_ui::Config<'static>>,
+) -> Response {
+    // Relative asset paths only resolve below `/docs/`
+    if full_path.as_str() == "/docs" {
+        return warp::redirect::found(Uri::from_static("/docs/")).into_response();
+    }
+
+    match utoipa_swagger_ui::serve(tail.as_str(), config) {
+        Ok(Some(file)) => {
+            warp::reply::with_header(file.bytes.into_owned(), "Content-Type", file.content_type)
+                .into_response()
+        }
+        Ok(None) => StatusCode::NOT_FOUND.into_response(),
+        Err(e) => warp::reply::with_status(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR)
+            .into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::config::test_config;
+    use crate::utils::events::SyncEvents;
+    use crate::utils::server::routes;
+    use crate::utils::versioning::is_unversioned_alias;
+    use warp::http::Method;
+    use warp::test::request;
+
+    const METHODS: [Method; 4] = [Method::GET, Method::POST, Method::PUT, Method::DELETE];
+
+    /// The paths `routes` should serve, besides the unversioned aliases. The
+    /// tests check that a path is listed exactly when it is documented (or in
+    /// `UNDOCUMENTED_PATHS`) and that every listed path is routed. A route
+    /// that is neither listed nor documented goes unnoticed.
+    const ROUTED_PATHS: [&str; 19] = [
+        "/health",
+        "/v1/events",
+        "/v1/cell",
+        "/v1/cell/coverage",
+        "/v1/cells",
+        "/v1/cells/changes",
+        "/v1/cells/density",
+        "/v1/cells/lookup",
+        "/v1/cells/neighbours",
+        "/v1/coverage",
+        "/v1/points/nearest-cells",
+        "/v1/tracks/localize",
+        "/v1/route/cells",
+        "/v1/route/coverage-report",
+        "/v1/venue/operators",
+        "/v1/modem/parse",
+        "/openapi.json",
+        "/docs",
+        "/graphql",
+    ];
+
+    /// Routed paths that aren't part of the REST API and aren't documented.
+    const UNDOCUMENTED_PATHS: [&str; 3] = ["/openapi.json", "/docs", "/graphql"];
+
+    /// All documented operations as (path, method).
+    fn documented_operations() -> Vec<(String, Method)> {
+        let spec = ApiDoc::openapi();
+        let mut operations = vec![];
+        for (path, item) in spec.paths.paths {
+            for (method, operation) in [
+                (Method::GET, &item.get),
+                (Method::POST, &item.post),
+                (Method::PUT, &item.put),
+                (Method::DELETE, &item.delete),
+            ] {
+                if operation.is_some() {
+                    operations.push((path.clone(), method));
+                }
+            }
+        }
+        operations
+    }
+
+    /// Sends a request that fails validation on every data route, so no
+    /// database access is needed to tell whether the route exists.
+    async fn status_of(path: &str, method: Method) -> StatusCode {
+        let events = SyncEvents::new();
+        // Lets the event stream end right away
+        events.close();
+
+        request()
+            .method(method.as_str())
+            .path(&format!("{}?limit=-1", path))
+            .body("not json")
+            .reply(&routes(test_config(), events))
+            .await
+            .status()
+    }
+
+    #[tokio::test]
+    async fn test_documented_operations_are_routed() {
+        let operations = documented_operations();
+        assert!(!operations.is_empty());
+
+        for (path, method) in operations {
+            let status = status_of(&path, method.clone()).await;
+            assert!(
+                status != StatusCode::NOT_FOUND && status != StatusCode::METHOD_NOT_ALLOWED,
+                "{} {} is documented but not routed ({})",
+                method,
+                path,
+                status
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_versioned_operations_have_unversioned_aliases() {
+        for (path, method) in documented_operations() {
+            let Some(alias) = path
+                .strip_prefix("/v1")
+                .filter(|alias| is_unversioned_alias(alias))
+            else {
+                continue;
+            };
+            let status = status_of(alias, method.clone()).await;
+            assert!(
+                status != StatusCode::NOT_FOUND && status != StatusCode::METHOD_NOT_ALLOWED,
+                "{} {} has no unversioned alias ({})",
+                method,
+                alias,
+                status
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_routed_paths_are_documented() {
+        let operations = documented_operations();
+
+        for path in ROUTED_PATHS {
+            let mut routed = false;
+            for method in METHODS {
+                let status = status_of(path, method.clone()).await;
+                if status == StatusCode::NOT_FOUND {
+                    continue;
+                }
+                routed = true;
+                if status == StatusCode::METHOD_NOT_ALLOWED || UNDOCUMENTED_PATHS.contains(&path) {
+                    continue;
+                }
+                assert!(
+                    operations.contains(&(path.to_string(), method.clone())),
+                    "{} {} is routed but not documented ({})",
+                    method,
+                    path,
+                    status
+                );
+            }
+            assert!(routed, "{} is listed but not routed", path);
+        }
+    }
+
+    #[test]
+    fn test_documented_paths_are_listed() {
+        for (path, method) in documented_operations() {
+            assert!(
+                ROUTED_PATHS.contains(&path.as_str()),
+                "{} {} is documented but missing from ROUTED_PATHS",
+                method,
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn test_spec_documents_the_cell_schemas() {
+        let spec = ApiDoc::openapi();
+        let schemas = spec.components.unwrap().schemas;
+
+        for name in ["Cell", "Radio", "GetCellsResponse", "ErrorBody"] {
+            assert!(schemas.contains_key(name), "{} is missing", name);
+        }
+        let Some(RefOr::T(Schema::Object(cell))) = schemas.get("Cell") else {
+            panic!("Cell is not an object");
+        };
+        assert!(cell.properties.contains_key("confidence"));
+    }
+
+    #[tokio::test]
+    async fn test_openapi_json_is_served() {
+        let response = request()
+            .method("GET")
+            .path("/openapi.json")
+            .reply(&openapi_route())
+            .await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let spec: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert!(spec["openapi"].as_str().unwrap().starts_with("3."));
+        assert!(spec["paths"]["/v1/cells"]["get"].is_object());
+    }
+
+    #[tokio::test]
+    async fn test_docs_are_served_offline() {
+        let response = request()
+            .method("GET")
+            .path("/docs/")
+            .reply(&docs_route())
+            .await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(String::from_utf8_lossy(response.body()).contains("swagger"));
+    }
+
+    #[tokio::test]
+    async fn test_docs_redirects_to_trailing_slash() {
+        let response = request()
+            .method("GET")
+            .path("/docs")
+            .reply(&docs_route())
+            .await;
+
+        assert_eq!(response.status(), StatusCode::FOUND);
+        assert_eq!(response.headers()["location"], "/docs/");
+    }
+}
@@ -0,0 +1,204 @@
+//! Polygons from requests, and which coordinates lie in or near them.
+
+use super::geo::{haversine_distance, BoundingBox, LocalFrame};
+
+/// Maximum number of positions of all rings together.
+pub const MAX_REGION_POSITIONS: usize = 10_000;
+/// Maximum distance of any position from the first, regions are venues, not countries.
+pub const MAX_REGION_EXTENT_M: f64 = 25_000.0;
+
+/// A polygon with optional holes, as GeoJSON rings of `[lon, lat]` positions.
+/// Containment and distances are computed in a flat frame around the first
+/// position, which is accurate at the size of a venue.
+#[derive(Debug, Clone)]
+pub struct Region {
+    frame: LocalFrame,
+    /// `(lat, lon)` positions of the exterior ring
+    exterior: Vec<(f64, f64)>,
+    /// All rings in the local frame, the exterior first
+    rings: Vec<Vec<(f64, f64)>>,
+}
+
+impl Region {
+    /// A region from the `coordinates` of a GeoJSON `Polygon`. Rings must be
+    /// closed, the first is the exterior, the others are holes.
+    pub fn new(coordinates: &[Vec<Vec<f64>>]) -> Result<Self, String> {
+        if coordinates.is_empty() {
+            return Err("must have an exterior ring".to_string());
+        }
+        if coordinates.iter().map(Vec::len).sum::<usize>() > MAX_REGION_POSITIONS {
+            return Err(format!(
+                "must not have more than {} positions",
+                MAX_REGION_POSITIONS
+            ));
+        }
+
+        let mut rings = vec![];
+        for (index, ring) in coordinates.iter().enumerate() {
+            let mut positions = vec![];
+            for position in ring {
+                let [lon, lat, ..] = position[..] else {
+                    return Err(format!("ring {} has a position without [lon, lat]", index));
+                };
+                if !(-90.0..=90.0).contains(&lat) || !(-180.0..=180.0).contains(&lon) {
+                    return Err(format!("ring {} has coordinates out of range", index));
+                }
+                positions.push((lat, lon));
+            }
+            if positions.len() < 4 {
+                return Err(format!("ring {} must have at least 4 positions", index));
+            }
+            if positions.first() != positions.last() {
+                return Err(format!("ring {} must end at its first position", index));
+            }
+            rings.push(positions);
+        }
+
+        let (origin_lat, origin_lon) = rings[0][0];
+        if rings.iter().flatten().any(|&(lat, lon)| {
+            haversine_distance(origin_lat, origin_lon, lat, lon) > MAX_REGION_EXTENT_M
+        }) {
+            return Err(format!(
+                "must fit within {} km of its first position",
+                MAX_REGION_EXTENT_M / 1000.0
+            ));
+        }
+        let frame = LocalFrame::new(origin_lat, origin_lon);
+        Ok(Region {
+            frame,
+            rings: rings
+                .iter()
+                .map(|ring| {
+                    ring.iter()
+                        .map(|&(lat, lon)| frame.to_local(lat, lon))
+                        .collect()
+                })
+                .collect(),
+            exterior: rings.swap_remove(0),
+        })
+    }
+
+    /// Whether a coordinate lies inside, by the even-odd rule over all rings.
+    pub fn contains(&self, lat: f64, lon: f64) -> bool {
+        let (x, y) = self.frame.to_local(lat, lon);
+        let mut inside = false;
+        for ring in &self.rings {
+            for edge in ring.windows(2) {
+                let ((x1, y1), (x2, y2)) = (edge[0], edge[1]);
+                if (y1 > y) != (y2 > y) && x < x1 + (y - y1) / (y2 - y1) * (x2 - x1) {
+                    inside = !inside;
+                }
+            }
+        }
+        inside
+    }
+
+    /// Meters from a coordinate to the region, `0` inside.
+    pub fn distance(&self, lat: f64, lon: f64) -> f64 {
+        if self.contains(lat, lon) {
+            return 0.0;
+        }
+        let (x, y) = self.frame.to_local(lat, lon);
+        self.rings
+            .iter()
+            .flat_map(|ring| ring.windows(2))
+            .map(|edge| {
+                let ((x1, y1), (x2, y2)) = (edge[0], edge[1]);
+                let (dx, dy) = (x2 - x1, y2 - y1);
+                let squared_length = dx * dx + dy * dy;
+                let t = if squared_length > 0.0 {
+                    (((x - x1) * dx + (y - y1) * dy) / squared_length).clamp(0.0, 1.0)
+                } else {
+                    0.0
+                };
+                (x - x1 - t * dx).hypot(y - y1 - t * dy)
+            })
+            .fold(f64::INFINITY, f64::min)
+    }
+
+    /// The box around the region, extended by `margin` meters.
+    pub fn bbox(&self, margin: f64) -> BoundingBox {
+        BoundingBox::around_all(&self.exterior, margin)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A square of about 1.1 × 0.7 km with a hole in the middle.
+    fn square_with_hole() -> Vec<Vec<Vec<f64>>> {
+        vec![
+            vec![
+                vec![13.0, 52.0],
+                vec![13.01, 52.0],
+                vec![13.01, 52.01],
+                vec![13.0, 52.01],
+                vec![13.0, 52.0],
+            ],
+            vec![
+                vec![13.004, 52.004],
+                vec![13.006, 52.004],
+                vec![13.006, 52.006],
+                vec![13.004, 52.006],
+                vec![13.004, 52.004],
+            ],
+        ]
+    }
+
+    #[test]
+    fn test_contains_respects_holes() {
+        let region = Region::new(&square_with_hole()).unwrap();
+
+        assert!(region.contains(52.002, 13.002));
+        assert!(!region.contains(52.005, 13.005));
+        assert!(!region.contains(52.02, 13.005));
+    }
+
+    #[test]
+    fn test_distance_to_the_boundary() {
+        let region = Region::new(&square_with_hole()).unwrap();
+
+        assert_eq!(region.distance(52.002, 13.002), 0.0);
+        // 0.001° of latitude south of the square
+        let south = region.distance(51.999, 13.005);
+        assert!((south - 111.2).abs() < 0.5, "{}", south);
+        // In the hole, 0.001° from its edges
+        let hole = region.distance(52.005, 13.005);
+        assert!(hole > 60.0 && hole < 70.0, "{}", hole);
+    }
+
+    #[test]
+    fn test_invalid_rings_are_rejected() {
+        let open = vec![vec![
+            vec![13.0, 52.0],
+            vec![13.01, 52.0],
+            vec![13.01, 52.01],
+            vec![13.0, 52.01],
+        ]];
+        let huge = vec![vec![
+            vec![13.0, 52.0],
+            vec![14.0, 52.0],
+            vec![14.0, 53.0],
+            vec![13.0, 52.0],
+        ]];
+
+        assert_eq!(
+            Region::new(&open).unwrap_err(),
+            "ring 0 must end at its first position"
+        );
+        assert!(Region::new(&huge).unwrap_err().contains("25 km"));
+        assert!(Region::new(&[]).is_err());
+        assert!(Region::new(&[vec![vec![13.0]; 4]]).is_err());
+    }
+
+    #[test]
+    fn test_bbox_includes_the_margin() {
+        let region = Region::new(&square_with_hole()).unwrap();
+
+        let bbox = region.bbox(500.0);
+
+        assert!(bbox.min_lat < 51.996 && bbox.max_lat > 52.014);
+        assert!(bbox.min_lon < 12.993 && bbox.max_lon > 13.017);
+    }
+}
@@ -0,0 +1,492 @@
+//! Protobuf messages generated from `proto/cells.proto`, shared by the gRPC
+//! service and the `application/x-protobuf` REST responses.
+
+use prost::Message;
+
+use crate::handlers::{
+    cell, cells, changes, coverage, density, lookup, matching, modem, nearest, neighbours, route,
+    tracks, venue,
+};
+use crate::models;
+use crate::utils::confidence::confidence;
+use crate::utils::modem as modem_output;
+use crate::utils::negotiation::ToProtobuf;
+
+tonic::include_proto!("racemap.cells.v1");
+
+impl From<Radio> for Option<models::Radio> {
+    fn from(radio: Radio) -> Self {
+        match radio {
+            Radio::Unspecified => None,
+            Radio::Gsm => Some(models::Radio::Gsm),
+            Radio::Umts => Some(models::Radio::Umts),
+            Radio::Cdma => Some(models::Radio::Cdma),
+            Radio::Lte => Some(models::Radio::Lte),
+            Radio::Nr => Some(models::Radio::Nr),
+        }
+    }
+}
+
+impl From<models::Radio> for Radio {
+    fn from(radio: models::Radio) -> Self {
+        match radio {
+            models::Radio::Gsm => Radio::Gsm,
+            models::Radio::Umts => Radio::Umts,
+            models::Radio::Cdma => Radio::Cdma,
+            models::Radio::Lte => Radio::Lte,
+            models::Radio::Nr => Radio::Nr,
+        }
+    }
+}
+
+impl From<models::Cell> for Cell {
+    fn from(cell: models::Cell) -> Self {
+        let confidence = confidence(&cell);
+        Cell {
+            radio: Radio::from(cell.radio).into(),
+            mcc: cell.mcc.into(),
+            net: cell.net.into(),
+            area: cell.area,
+            cell: cell.cell,
+            unit: cell.unit.map(u32::from),
+            lon: cell.lon,
+            lat: cell.lat,
+            range: cell.cell_range,
+            samples: cell.samples,
+            changeable: cell.changeable,
+            created: cell.created.and_utc().timestamp(),
+            updated: cell.updated.and_utc().timestamp(),
+            average_signal: cell.average_signal.map(i32::from),
+            confidence,
+        }
+    }
+}
+
+impl From<matching::MatchReason> for MatchReason {
+    fn from(reason: matching::MatchReason) -> Self {
+        match reason {
+            matching::MatchReason::RadioMismatch => MatchReason::RadioMismatch,
+            matching::MatchReason::AreaMismatch => MatchReason::AreaMismatch,
+            matching::MatchReason::AreaAndRadioMismatch => MatchReason::AreaAndRadioMismatch,
+        }
+    }
+}
+
+impl From<&matching::CellCandidate> for CellCandidate {
+    fn from(candidate: &matching::CellCandidate) -> Self {
+        CellCandidate {
+            reason: MatchReason::from(candidate.reason).into(),
+            cell: Some(candidate.cell.clone().into()),
+        }
+    }
+}
+
+impl From<&changes::ChangeKey> for ChangeKey {
+    fn from(key: &changes::ChangeKey) -> Self {
+        ChangeKey {
+            radio: Radio::from(key.radio.clone()).into(),
+            mcc: key.mcc.into(),
+            net: key.net.into(),
+            area: key.area,
+            cell: key.cell,
+        }
+    }
+}
+
+impl From<&changes::CellChangeEntry> for CellChangeEntry {
+    fn from(entry: &changes::CellChangeEntry) -> Self {
+        CellChangeEntry {
+            changed_at: entry.changed_at.and_utc().timestamp(),
+            removed: entry.removed,
+            key: Some((&entry.key).into()),
+            cell: entry.cell.clone().map(Cell::from),
+        }
+    }
+}
+
+fn candidates(candidates: &[matching::CellCandidate]) -> Vec<CellCandidate> {
+    candidates.iter().map(CellCandidate::from).collect()
+}
+
+impl ToProtobuf for models::Cell {
+    fn to_protobuf(&self) -> Vec<u8> {
+        Cell::from(self.clone()).encode_to_vec()
+    }
+}
+
+impl ToProtobuf for cell::FuzzyCellResponse {
+    fn to_protobuf(&self) -> Vec<u8> {
+        FuzzyCellResponse {
+            cell: self.cell.clone().map(Cell::from),
+            candidates: candidates(&self.candidates),
+        }
+        .encode_to_vec()
+    }
+}
+
+impl ToProtobuf for cell::GetCellResponse {
+    fn to_protobuf(&self) -> Vec<u8> {
+        match self {
+            cell::GetCellResponse::Cell(cell) => cell.to_protobuf(),
+            cell::GetCellResponse::Fuzzy(response) => response.to_protobuf(),
+        }
+    }
+}
+
+impl ToProtobuf for cells::GetCellsResponse {
+    fn to_protobuf(&self) -> Vec<u8> {
+        GetCellsResponse {
+            cells: self.cells.iter().cloned().map(Cell::from).collect(),
+            next_cursor: self.next_cursor.clone(),
+            has_more: self.has_more,
+        }
+        .encode_to_vec()
+    }
+}
+
+impl ToProtobuf for lookup::LookupCellsResponse {
+    fn to_protobuf(&self) -> Vec<u8> {
+        LookupCellsResponse {
+            results: lookup_results(&self.cells),
+            candidates: self
+                .candidates
+                .iter()
+                .flatten()
+                .map(|list| CandidateList {
+                    candidates: candidates(list),
+                })
+                .collect(),
+        }
+        .encode_to_vec()
+    }
+}
+
+impl ToProtobuf for changes::GetChangesResponse {
+    fn to_protobuf(&self) -> Vec<u8> {
+        GetChangesResponse {
+            changes: self.changes.iter().map(CellChangeEntry::from).collect(),
+            next_cursor: self.next_cursor.clone(),
+            has_more: self.has_more,
+        }
+        .encode_to_vec()
+    }
+}
+
+impl ToProtobuf for tracks::LocalizeTrackResponse {
+    fn to_protobuf(&self) -> Vec<u8> {
+        LocalizeTrackResponse {
+            points: self
+                .points
+                .iter()
+                .map(|point| LocalizedPoint {
+                    timestamp: point.timestamp.timestamp(),
+                    lat: point.lat,
+                    lon: point.lon,
+                    accuracy: point.accuracy,
+                    cells_used: point.cells_used,
+                })
+                .collect(),
+        }
+        .encode_to_vec()
+    }
+}
+
+impl From<&modem_output::ModemCell> for ModemCell {
+    fn from(cell: &modem_output::ModemCell) -> Self {
+        ModemCell {
+            radio: Radio::from(cell.radio.clone()).into(),
+            mcc: cell.mcc.map(u32::from),
+            net: cell.net.map(u32::from),
+            area: cell.area,
+            cell: cell.cell,
+            unit: cell.unit.map(u32::from),
+            channel: cell.channel,
+            band: cell.band.map(u32::from),
+            signal_dbm: cell.signal_dbm.map(i32::from),
+        }
+    }
+}
+
+fn lookup_results(cells: &[Option<models::Cell>]) -> Vec<LookupResult> {
+    cells
+        .iter()
+        .map(|cell| LookupResult {
+            cell: cell.clone().map(Cell::from),
+        })
+        .collect()
+}
+
+fn neighbour_candidates(
+    lists: &[Vec<neighbours::NeighbourCandidate>],
+) -> Vec<NeighbourCandidateList> {
+    lists
+        .iter()
+        .map(|list| NeighbourCandidateList {
+            candidates: list
+                .iter()
+                .map(|candidate| NeighbourCandidate {
+                    confidence: candidate.confidence,
+                    distance: candidate.distance,
+                    cell: Some(Cell::from(candidate.cell.clone())),
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+impl ToProtobuf for neighbours::ResolveNeighboursResponse {
+    fn to_protobuf(&self) -> Vec<u8> {
+        ResolveNeighboursResponse {
+            serving: self.serving.clone().map(Cell::from),
+            candidates: neighbour_candidates(&self.candidates),
+        }
+        .encode_to_vec()
+    }
+}
+
+impl ToProtobuf for modem::ParseModemResponse {
+    fn to_protobuf(&self) -> Vec<u8> {
+        ParseModemResponse {
+            serving: self.serving.iter().map(ModemCell::from).collect(),
+            neighbours: self.neighbours.iter().map(ModemCell::from).collect(),
+            resolved: self.resolved.as_ref().map(|resolved| ResolvedModemCells {
+                serving: lookup_results(&resolved.serving),
+                neighbours: lookup_results(&resolved.neighbours),
+                candidates: neighbour_candidates(&resolved.candidates),
+            }),
+        }
+        .encode_to_vec()
+    }
+}
+
+impl ToProtobuf for coverage::PointCoverageResponse {
+    fn to_protobuf(&self) -> Vec<u8> {
+        PointCoverageResponse {
+            groups: self
+                .groups
+                .iter()
+                .map(|group| CoverageGroup {
+                    mcc: group.mcc.into(),
+                    net: group.net.into(),
+                    radio: Radio::from(group.radio.clone()).into(),
+                    cells: group
+                        .cells
+                        .iter()
+                        .map(|covering| CoveringCell {
+                            distance: covering.distance,
+                            signal_dbm: covering.signal_dbm.into(),
+                            cell: Some(Cell::from(covering.cell.clone())),
+                        })
+                        .collect(),
+                })
+                .collect(),
+        }
+        .encode_to_vec()
+    }
+}
+
+impl ToProtobuf for density::GetDensityResponse {
+    fn to_protobuf(&self) -> Vec<u8> {
+        GetDensityResponse {
+            resolution: self.resolution.into(),
+            buckets: self
+                .buckets
+                .iter()
+                .map(|bucket| DensityBucket {
+                    geohash: bucket.geohash.clone(),
+                    lat: bucket.lat,
+                    lon: bucket.lon,
+                    cells: bucket.cells,
+                    samples: bucket.samples,
+                    radios: bucket
+                        .radios
+                        .iter()
+                        .map(|count| RadioCount {
+                            radio: Radio::from(count.radio.clone()).into(),
+                            cells: count.cells,
+                        })
+                        .collect(),
+                    operators: bucket
+                        .operators
+                        .iter()
+                        .map(|count| OperatorCount {
+                            mcc: count.mcc.into(),
+                            net: count.net.into(),
+                            cells: count.cells,
+                        })
+                        .collect(),
+                })
+                .collect(),
+        }
+        .encode_to_vec()
+    }
+}
+
+impl ToProtobuf for route::RouteCellsResponse {
+    fn to_protobuf(&self) -> Vec<u8> {
+        RouteCellsResponse {
+            length_km: self.length_km,
+            cells: self
+                .cells
+                .iter()
+                .map(|route_cell| RouteCell {
+                    route_km: route_cell.route_km,
+                    distance: route_cell.distance,
+                    cell: Some(Cell::from(route_cell.cell.clone())),
+                })
+                .collect(),
+        }
+        .encode_to_vec()
+    }
+}
+
+impl ToProtobuf for route::CoverageReportResponse {
+    fn to_protobuf(&self) -> Vec<u8> {
+        CoverageReportResponse {
+            length_km: self.length_km,
+            operators: self
+                .operators
+                .iter()
+                .map(|operator| OperatorCoverage {
+                    mcc: operator.mcc.into(),
+                    net: operator.net.into(),
+                    radio: Radio::from(operator.radio.clone()).into(),
+                    cells: operator.cells,
+                    covered_percent: operator.covered_percent,
+                    longest_gap_km: operator.longest_gap_km,
+                    gaps: operator
+                        .gaps
+                        .iter()
+                        .map(|gap| CoverageGap {
+                            start_km: gap.start_km,
+                            end_km: gap.end_km,
+                            length_km: gap.length_km,
+                        })
+                        .collect(),
+                })
+                .collect(),
+        }
+        .encode_to_vec()
+    }
+}
+
+impl ToProtobuf for venue::VenueOperatorsResponse {
+    fn to_protobuf(&self) -> Vec<u8> {
+        VenueOperatorsResponse {
+            operators: self
+                .operators
+                .iter()
+                .map(|operator| OperatorRanking {
+                    rank: operator.rank,
+                    mcc: operator.mcc.into(),
+                    net: operator.net.into(),
+                    score: operator.score,
+                    cells_inside: operator.cells_inside,
+                    cells_around: operator.cells_around,
+                    nr_lte_share: operator.nr_lte_share,
+                    samples: operator.samples,
+                    median_age_days: operator.median_age_days,
+                    explanation: operator.explanation.clone(),
+                })
+                .collect(),
+        }
+        .encode_to_vec()
+    }
+}
+
+impl ToProtobuf for nearest::NearestCellsResponse {
+    fn to_protobuf(&self) -> Vec<u8> {
+        NearestCellsResponse {
+            points: self
+                .points
+                .iter()
+                .map(|point| PointNearestCells {
+                    cells: point
+                        .cells
+                        .iter()
+                        .map(|nearest| NearestCell {
+                            distance: nearest.distance,
+                            cell: Some(Cell::from(nearest.cell.clone())),
+                        })
+                        .collect(),
+                })
+                .collect(),
+        }
+        .encode_to_vec()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_cell() -> models::Cell {
+        let created = chrono::DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        models::Cell {
+            radio: models::Radio::Lte,
+            mcc: 262,
+            net: 1,
+            area: 12345,
+            cell: 67890,
+            unit: Some(7),
+            lon: 13.4,
+            lat: 52.5,
+            cell_range: 1000,
+            samples: 12,
+            changeable: true,
+            created: created.naive_utc(),
+            updated: created.naive_utc(),
+            average_signal: Some(-80),
+        }
+    }
+
+    #[test]
+    fn test_cell_to_proto() {
+        let proto_cell = Cell::from(sample_cell());
+
+        assert_eq!(proto_cell.radio(), Radio::Lte);
+        assert_eq!(proto_cell.mcc, 262);
+        assert_eq!(proto_cell.unit, Some(7));
+        assert_eq!(proto_cell.range, 1000);
+        assert_eq!(proto_cell.created, 1_700_000_000);
+        assert_eq!(proto_cell.average_signal, Some(-80));
+        assert!(proto_cell.confidence > 0.0);
+    }
+
+    #[test]
+    fn test_cell_response_decodes_as_cell() {
+        let bytes = cell::GetCellResponse::Cell(sample_cell()).to_protobuf();
+
+        let decoded = Cell::decode(bytes.as_slice()).unwrap();
+        assert_eq!(decoded.cell, 67890);
+    }
+
+    #[test]
+    fn test_lookup_response_keeps_misses() {
+        let response = lookup::LookupCellsResponse {
+            cells: vec![None, Some(sample_cell())],
+            candidates: None,
+        };
+
+        let decoded = LookupCellsResponse::decode(response.to_protobuf().as_slice()).unwrap();
+        assert_eq!(decoded.results.len(), 2);
+        assert!(decoded.results[0].cell.is_none());
+        assert_eq!(decoded.results[1].cell.as_ref().unwrap().mcc, 262);
+        assert!(decoded.candidates.is_empty());
+    }
+
+    #[test]
+    fn test_fuzzy_candidates_carry_their_reason() {
+        let response = cell::FuzzyCellResponse {
+            cell: None,
+            candidates: vec![matching::CellCandidate {
+                reason: matching::MatchReason::AreaMismatch,
+                cell: sample_cell(),
+            }],
+        };
+
+        let decoded = FuzzyCellResponse::decode(response.to_protobuf().as_slice()).unwrap();
+        assert!(decoded.cell.is_none());
+        assert_eq!(decoded.candidates[0].reason(), MatchReason::AreaMismatch);
+    }
+}
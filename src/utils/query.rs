@@ -0,0 +1,226 @@
+use serde::de::DeserializeOwned;
+use warp::hyper::body::Bytes;
+use warp::{Filter, Rejection};
+
+use super::errors::{ApiError, FieldError};
+
+/// Maximum accepted size of JSON request bodies.
+pub const MAX_BODY_BYTES: u64 = 256 * 1024;
+
+/// Semantic checks on top of what deserialization already guarantees.
+pub trait Validate {
+    fn validate(&self) -> Result<(), Vec<FieldError>> {
+        Ok(())
+    }
+}
+
+/// Parses a raw query string into `T`, reporting the offending field on failure.
+pub fn parse_query<T: DeserializeOwned + Validate>(raw: &str) -> Result<T, ApiError> {
+    let deserializer = serde_urlencoded::Deserializer::new(form_urlencoded::parse(raw.as_bytes()));
+
+    let query: T = serde_path_to_error::deserialize(deserializer).map_err(into_api_error)?;
+    query.validate().map_err(ApiError::BadRequest)?;
+    Ok(query)
+}
+
+/// Parses a JSON request body into `T`, reporting the offending field on failure.
+pub fn parse_json<T: DeserializeOwned + Validate>(body: &[u8]) -> Result<T, ApiError> {
+    let deserializer = &mut serde_json::Deserializer::from_slice(body);
+
+    let value: T = serde_path_to_error::deserialize(deserializer).map_err(into_api_error)?;
+    value.validate().map_err(ApiError::BadRequest)?;
+    Ok(value)
+}
+
+fn into_api_error<E: std::fmt::Display>(err: serde_path_to_error::Error<E>) -> ApiError {
+    let message = err.inner().to_string();
+    let path = err.path().to_string();
+    let field = if path == "." {
+        // Missing fields are reported on the parent, the name is in the message
+        missing_field_name(&message).unwrap_or(path)
+    } else {
+        path
+    };
+    ApiError::BadRequest(vec![FieldError { field, message }])
+}
+
+fn missing_field_name(message: &str) -> Option<String> {
+    let rest = message.strip_prefix("missing field `")?;
+    rest.split('`').next().map(String::from)
+}
+
+/// Like `warp::query`, but rejects with field-level `ApiError::BadRequest`s.
+/// An absent query string is treated as an empty one.
+pub fn validated_query<T>() -> impl Filter<Extract = (T,), Error = Rejection> + Clone
+where
+    T: DeserializeOwned + Validate + Send + 'static,
+{
+    warp::query::raw()
+        .or(warp::any().map(String::new))
+        .unify()
+        .and_then(|raw: String| async move { parse_query::<T>(&raw).map_err(Rejection::from) })
+}
+
+/// Like `warp::body::json`, but rejects with field-level `ApiError::BadRequest`s.
+pub fn validated_json<T>() -> impl Filter<Extract = (T,), Error = Rejection> + Clone
+where
+    T: DeserializeOwned + Validate + Send + 'static,
+{
+    warp::body::content_length_limit(MAX_BODY_BYTES)
+        .and(warp::body::bytes())
+        .and_then(|body: Bytes| async move { parse_json::<T>(&body).map_err(Rejection::from) })
+}
+
+/// Checks that an optional coordinate lies within `[-limit, limit]`.
+pub fn check_coordinate(errors: &mut Vec<FieldError>, field: &str, value: Option<f32>, limit: f32) {
+    if let Some(value) = value {
+        if !(-limit..=limit).contains(&value) {
+            errors.push(FieldError::new(
+                field,
+                format!("must be between -{} and {}", limit, limit),
+            ));
+        }
+    }
+}
+
+/// Checks that an optional `min_confidence` is a valid confidence.
+pub fn check_confidence(errors: &mut Vec<FieldError>, value: Option<f64>) {
+    if let Some(value) = value {
+        if !(0.0..=1.0).contains(&value) {
+            errors.push(FieldError::new("min_confidence", "must be between 0 and 1"));
+        }
+    }
+}
+
+/// Checks that an optional lower bound does not exceed its upper bound.
+pub fn check_range(
+    errors: &mut Vec<FieldError>,
+    min_field: &str,
+    min: Option<f32>,
+    max_field: &str,
+    max: Option<f32>,
+) {
+    if let (Some(min), Some(max)) = (min, max) {
+        if min > max {
+            errors.push(FieldError::new(
+                min_field,
+                format!("must not be greater than {}", max_field),
+            ));
+        }
+    }
+}
+
+/// Turns a list of collected errors into a validation result.
+pub fn into_result(errors: Vec<FieldError>) -> Result<(), Vec<FieldError>> {
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Deserialize, Debug)]
+    struct TestQuery {
+        id: u16,
+        lat: Option<f32>,
+    }
+
+    impl Validate for TestQuery {
+        fn validate(&self) -> Result<(), Vec<FieldError>> {
+            let mut errors = vec![];
+            check_coordinate(&mut errors, "lat", self.lat, 90.0);
+            into_result(errors)
+        }
+    }
+
+    fn field_errors(result: Result<TestQuery, ApiError>) -> Vec<FieldError> {
+        match result {
+            Err(ApiError::BadRequest(fields)) => fields,
+            other => panic!("expected bad request, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_valid_query() {
+        let query: TestQuery = parse_query("id=5&lat=52.5").unwrap();
+
+        assert_eq!(query.id, 5);
+        assert_eq!(query.lat, Some(52.5));
+    }
+
+    #[test]
+    fn test_invalid_number_names_field() {
+        let fields = field_errors(parse_query("id=abc"));
+
+        assert_eq!(fields.len(), 1);
+        assert_eq!(fields[0].field, "id");
+    }
+
+    #[test]
+    fn test_missing_field_names_field() {
+        let fields = field_errors(parse_query("lat=1.0"));
+
+        assert_eq!(fields[0].field, "id");
+        assert!(fields[0].message.contains("missing field"));
+    }
+
+    #[test]
+    fn test_validation_errors_are_reported() {
+        let fields = field_errors(parse_query("id=1&lat=91"));
+
+        assert_eq!(fields[0].field, "lat");
+        assert_eq!(fields[0].message, "must be between -90 and 90");
+    }
+
+    #[test]
+    fn test_parse_json_names_nested_field() {
+        #[derive(Deserialize, Debug)]
+        struct Body {
+            #[allow(dead_code)]
+            items: Vec<TestQuery>,
+        }
+        impl Validate for Body {}
+
+        match parse_json::<Body>(br#"{"items": [{"id": 1}, {"id": "x"}]}"#) {
+            Err(ApiError::BadRequest(fields)) => assert_eq!(fields[0].field, "items[1].id"),
+            other => panic!("expected bad request, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_check_range_rejects_inverted_bounds() {
+        let mut errors = vec![];
+        check_range(&mut errors, "min_lat", Some(53.0), "max_lat", Some(52.0));
+
+        assert_eq!(
+            errors,
+            vec![FieldError::new(
+                "min_lat",
+                "must not be greater than max_lat"
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_filter_accepts_missing_query_string() {
+        #[derive(Deserialize)]
+        struct Optional {
+            id: Option<u16>,
+        }
+        impl Validate for Optional {}
+
+        let filter = validated_query::<Optional>();
+        let query = warp::test::request()
+            .path("/")
+            .filter(&filter)
+            .await
+            .unwrap();
+
+        assert!(query.id.is_none());
+    }
+}
@@ -0,0 +1,349 @@
+//! Distance of a device from a cell, estimated from the timing advance and
+//! the received signal strength the device reports.
+
+use crate::models::{Cell, Radio};
+
+/// Speed of light in m/s.
+const SPEED_OF_LIGHT_MPS: f64 = 299_792_458.0;
+/// Lower bound for cell ranges, OpenCellID reports `0` for unknown ranges.
+pub const MIN_RANGE_M: f64 = 100.0;
+/// Lower bound for any uncertainty, no measurement is better than multipath allows.
+const MIN_UNCERTAINTY_M: f64 = 50.0;
+/// Typical error of timing advance ranging caused by multipath and
+/// non-line-of-sight propagation.
+const MULTIPATH_ERROR_M: f64 = 100.0;
+/// Standard deviation of log-normal shadowing in dB.
+const SHADOWING_DB: f64 = 8.0;
+
+/// What a device measured about a cell.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Measurement {
+    /// RSRP (LTE/NR), RSCP (UMTS) or RSSI (GSM, CDMA) in dBm
+    pub signal_dbm: Option<i16>,
+    /// Timing advance in the units of the radio's specification
+    pub timing_advance: Option<u16>,
+}
+
+/// An estimated distance from a cell.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Range {
+    pub distance: f64,
+    /// One standard deviation in meters
+    pub uncertainty: f64,
+}
+
+impl Range {
+    fn new(distance: f64, uncertainty: f64) -> Self {
+        Range {
+            distance: distance.max(0.0),
+            uncertainty: uncertainty.max(MIN_UNCERTAINTY_M),
+        }
+    }
+
+    /// Combines two independent estimates, weighted by their inverse variance.
+    pub fn fuse(self, other: Range) -> Range {
+        let (a, b) = (
+            self.uncertainty.powi(2).recip(),
+            other.uncertainty.powi(2).recip(),
+        );
+        Range::new(
+            (self.distance * a + other.distance * b) / (a + b),
+            (a + b).recip().sqrt(),
+        )
+    }
+}
+
+/// The cell's range, or the minimum if it is unknown.
+pub fn cell_range(cell: &Cell) -> f64 {
+    f64::from(cell.cell_range).max(MIN_RANGE_M)
+}
+
+/// Distance per timing advance step and the largest valid value, `None` for
+/// radios without timing advance.
+fn timing_advance_step(radio: &Radio) -> Option<(f64, u16)> {
+    match radio {
+        // One bit period, 48/13 µs, for the round trip
+        Radio::Gsm => Some((SPEED_OF_LIGHT_MPS * 48.0 / 13.0 * 1e-6 / 2.0, 63)),
+        // 16 Ts with Ts = 1 / 30.72 MHz, for the round trip
+        Radio::Lte => Some((SPEED_OF_LIGHT_MPS * 16.0 / 30.72e6 / 2.0, 1282)),
+        // 16 · 64 Tc / 2^µ with Tc = 1 / (480 kHz · 4096), assuming the
+        // 30 kHz subcarrier spacing (µ = 1) of most FR1 deployments
+        Radio::Nr => Some((
+            SPEED_OF_LIGHT_MPS * 16.0 * 64.0 / (480e3 * 4096.0) / 2.0 / 2.0,
+            3846,
+        )),
+        Radio::Umts | Radio::Cdma => None,
+    }
+}
+
+/// The distance a timing advance stands for. `None` for radios without timing
+/// advance and values beyond the specification.
+pub fn range_from_timing_advance(radio: &Radio, timing_advance: u16) -> Option<Range> {
+    let (step, max) = timing_advance_step(radio)?;
+    if timing_advance > max {
+        return None;
+    }
+    // Half a step of rounding error on top of multipath
+    let uncertainty = (step / 2.0).hypot(MULTIPATH_ERROR_M);
+    Some(Range::new(f64::from(timing_advance) * step, uncertainty))
+}
+
+/// Path loss exponent and typical signal in dBm at half the cell range, for
+/// cells without an average signal.
+fn path_loss(radio: &Radio) -> (f64, f64) {
+    match radio {
+        Radio::Gsm => (3.0, -75.0),
+        Radio::Cdma => (3.0, -80.0),
+        Radio::Umts => (3.5, -85.0),
+        Radio::Lte => (3.5, -95.0),
+        // Higher frequencies are attenuated more by buildings
+        Radio::Nr => (4.0, -95.0),
+    }
+}
+
+/// The distance a signal strength stands for, from a log-distance path loss
+/// model. The cell's samples are spread over its range, so its average signal
+/// is taken to be received at half the range. Distances are capped at twice
+/// the range.
+pub fn range_from_signal(cell: &Cell, signal_dbm: i16) -> Range {
+    let (exponent, typical_dbm) = path_loss(&cell.radio);
+    let reference_dbm = cell.average_signal.map_or(typical_dbm, f64::from);
+    let range = cell_range(cell);
+
+    let distance =
+        (range / 2.0) * 10f64.powf((reference_dbm - f64::from(signal_dbm)) / (10.0 * exponent));
+    let distance = distance.min(2.0 * range);
+    // Shadowing scales the distance by a log-normal factor
+    let spread = 10f64.powf(SHADOWING_DB / (10.0 * exponent)) - 1.0;
+    Range::new(distance, distance * spread)
+}
+
+/// The signal expected at `distance` from a cell in dBm, the inverse of
+/// `range_from_signal`. Distances below `MIN_UNCERTAINTY_M` count as that, the
+/// model does not hold next to the antenna.
+pub fn signal_at(cell: &Cell, distance: f64) -> f64 {
+    let (exponent, typical_dbm) = path_loss(&cell.radio);
+    let reference_dbm = cell.average_signal.map_or(typical_dbm, f64::from);
+    let reference_distance = cell_range(cell) / 2.0;
+    reference_dbm - 10.0 * exponent * (distance.max(MIN_UNCERTAINTY_M) / reference_distance).log10()
+}
+
+/// Combines everything the device measured about a cell into one range.
+/// `None` if it measured nothing usable.
+pub fn estimate_range(cell: &Cell, measurement: Measurement) -> Option<Range> {
+    let timing = measurement
+        .timing_advance
+        .and_then(|ta| range_from_timing_advance(&cell.radio, ta));
+    let signal = measurement
+        .signal_dbm
+        .map(|signal| range_from_signal(cell, signal));
+    match (timing, signal) {
+        (Some(timing), Some(signal)) => Some(timing.fuse(signal)),
+        (timing, signal) => timing.or(signal),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cell(radio: Radio, range: u32, average_signal: Option<i16>) -> Cell {
+        let now = chrono::Utc::now().naive_utc();
+        Cell {
+            radio,
+            mcc: 262,
+            net: 1,
+            area: 1,
+            cell: 1,
+            unit: None,
+            lon: 13.4,
+            lat: 52.5,
+            cell_range: range,
+            samples: 10,
+            changeable: true,
+            created: now,
+            updated: now,
+            average_signal,
+        }
+    }
+
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "expected {} ± {}, got {}",
+            expected,
+            tolerance,
+            actual
+        );
+    }
+
+    mod timing_advance {
+        use super::*;
+
+        #[test]
+        fn test_gsm_step_is_554_meters() {
+            let range = range_from_timing_advance(&Radio::Gsm, 4).unwrap();
+
+            assert_close(range.distance, 4.0 * 553.5, 1.0);
+            assert_close(range.uncertainty, 294.3, 1.0);
+        }
+
+        #[test]
+        fn test_gsm_maximum_is_35_km() {
+            let range = range_from_timing_advance(&Radio::Gsm, 63).unwrap();
+
+            assert_close(range.distance, 34_871.0, 10.0);
+            assert_eq!(range_from_timing_advance(&Radio::Gsm, 64), None);
+        }
+
+        #[test]
+        fn test_lte_step_is_78_meters() {
+            let range = range_from_timing_advance(&Radio::Lte, 10).unwrap();
+
+            assert_close(range.distance, 780.7, 1.0);
+            assert_close(range.uncertainty, 107.3, 1.0);
+            assert_eq!(range_from_timing_advance(&Radio::Lte, 1283), None);
+        }
+
+        #[test]
+        fn test_nr_step_is_39_meters() {
+            let range = range_from_timing_advance(&Radio::Nr, 10).unwrap();
+
+            assert_close(range.distance, 390.3, 1.0);
+            assert_eq!(range_from_timing_advance(&Radio::Nr, 3847), None);
+        }
+
+        #[test]
+        fn test_umts_and_cdma_have_none() {
+            assert_eq!(range_from_timing_advance(&Radio::Umts, 1), None);
+            assert_eq!(range_from_timing_advance(&Radio::Cdma, 1), None);
+        }
+
+        #[test]
+        fn test_zero_is_next_to_the_cell() {
+            let range = range_from_timing_advance(&Radio::Lte, 0).unwrap();
+
+            assert_eq!(range.distance, 0.0);
+        }
+    }
+
+    mod signal {
+        use super::*;
+
+        #[test]
+        fn test_average_signal_is_half_the_range() {
+            let range = range_from_signal(&cell(Radio::Lte, 2000, Some(-90)), -90);
+
+            assert_close(range.distance, 1000.0, 1e-6);
+        }
+
+        #[test]
+        fn test_lte_10_db_stronger_is_half_as_far() {
+            // 10^(10 / 35)
+            let range = range_from_signal(&cell(Radio::Lte, 2000, Some(-90)), -80);
+
+            assert_close(range.distance, 1000.0 / 1.930, 1.0);
+        }
+
+        #[test]
+        fn test_gsm_falls_off_slower() {
+            // 10^(-10 / 30)
+            let range = range_from_signal(&cell(Radio::Gsm, 4000, None), -85);
+
+            assert_close(range.distance, 2000.0 * 2.154, 1.0);
+        }
+
+        #[test]
+        fn test_umts_uses_typical_signal() {
+            let range = range_from_signal(&cell(Radio::Umts, 3000, None), -85);
+
+            assert_close(range.distance, 1500.0, 1e-6);
+        }
+
+        #[test]
+        fn test_nr_falls_off_faster() {
+            // 10^(10 / 40)
+            let range = range_from_signal(&cell(Radio::Nr, 1000, None), -85);
+
+            assert_close(range.distance, 500.0 / 1.778, 1.0);
+        }
+
+        #[test]
+        fn test_weak_signal_is_capped() {
+            let range = range_from_signal(&cell(Radio::Lte, 1000, None), -140);
+
+            assert_close(range.distance, 2000.0, 1e-6);
+        }
+
+        #[test]
+        fn test_uncertainty_grows_with_distance() {
+            let near = range_from_signal(&cell(Radio::Lte, 4000, None), -70);
+            let far = range_from_signal(&cell(Radio::Lte, 4000, None), -100);
+
+            assert!(near.uncertainty < far.uncertainty);
+            assert_close(far.uncertainty / far.distance, 0.693, 0.001);
+        }
+
+        #[test]
+        fn test_signal_at_inverts_the_range() {
+            let cell = cell(Radio::Lte, 2000, Some(-90));
+
+            assert_close(signal_at(&cell, 1000.0), -90.0, 1e-6);
+            assert_close(range_from_signal(&cell, -80).distance, 518.0, 1.0);
+            assert_close(signal_at(&cell, 518.0), -80.0, 0.1);
+            assert_eq!(signal_at(&cell, 0.0), signal_at(&cell, MIN_UNCERTAINTY_M));
+        }
+
+        #[test]
+        fn test_unknown_range_uses_the_minimum() {
+            let range = range_from_signal(&cell(Radio::Lte, 0, None), -95);
+
+            assert_close(range.distance, MIN_RANGE_M / 2.0, 1e-6);
+        }
+    }
+
+    mod estimate {
+        use super::*;
+
+        #[test]
+        fn test_nothing_measured() {
+            let range = estimate_range(&cell(Radio::Lte, 1000, None), Measurement::default());
+
+            assert_eq!(range, None);
+        }
+
+        #[test]
+        fn test_timing_advance_dominates() {
+            let measurement = Measurement {
+                signal_dbm: Some(-110),
+                timing_advance: Some(10),
+            };
+
+            let range = estimate_range(&cell(Radio::Lte, 5000, None), measurement).unwrap();
+
+            let timing_only = range_from_timing_advance(&Radio::Lte, 10).unwrap();
+            assert_close(range.distance, timing_only.distance, 5.0);
+            assert!(range.uncertainty < timing_only.uncertainty);
+        }
+
+        #[test]
+        fn test_umts_ignores_timing_advance() {
+            let measurement = Measurement {
+                signal_dbm: Some(-85),
+                timing_advance: Some(10),
+            };
+
+            let range = estimate_range(&cell(Radio::Umts, 3000, None), measurement).unwrap();
+
+            assert_close(range.distance, 1500.0, 1e-6);
+        }
+
+        #[test]
+        fn test_fuse_equal_estimates() {
+            let fused = Range::new(1000.0, 200.0).fuse(Range::new(2000.0, 200.0));
+
+            assert_close(fused.distance, 1500.0, 1e-6);
+            assert_close(fused.uncertainty, 141.4, 0.1);
+        }
+    }
+}
@@ -0,0 +1,567 @@
+//! Routes uploaded as GPX or GeoJSON, and where coordinates lie along them.
+
+use std::ops::Range;
+
+use serde_json::Value;
+use warp::hyper::body::Bytes;
+use warp::{Filter, Rejection};
+
+use super::errors::{ApiError, FieldError};
+use super::geo::{haversine_distance, BoundingBox, LocalFrame};
+
+/// Maximum accepted size of route uploads, recorded GPX tracks are verbose.
+pub const MAX_ROUTE_BYTES: u64 = 4 * 1024 * 1024;
+/// Maximum number of points of an uploaded route.
+pub const MAX_ROUTE_POINTS: usize = 50_000;
+/// Maximum length of a route in meters.
+pub const MAX_ROUTE_LENGTH_M: f64 = 1_000_000.0;
+/// Longer segments are split, so that the boxes around route chunks stay tight.
+const MAX_SEGMENT_M: f64 = 1_000.0;
+
+/// A route as a polyline of `(lat, lon)` points.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Route {
+    points: Vec<(f64, f64)>,
+    /// Meters from the start to each point
+    along: Vec<f64>,
+}
+
+/// Where a coordinate lies relative to a route.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RoutePosition {
+    /// Meters from the start of the route to the closest point
+    pub along: f64,
+    /// Meters from the closest point of the route
+    pub offset: f64,
+}
+
+impl Route {
+    /// A route through `points`, with long segments split.
+    pub fn new(points: Vec<(f64, f64)>) -> Result<Self, String> {
+        if points.len() < 2 {
+            return Err("must have at least 2 points".to_string());
+        }
+        if points.len() > MAX_ROUTE_POINTS {
+            return Err(format!(
+                "must not have more than {} points",
+                MAX_ROUTE_POINTS
+            ));
+        }
+        if points
+            .iter()
+            .any(|(lat, lon)| !(-90.0..=90.0).contains(lat) || !(-180.0..=180.0).contains(lon))
+        {
+            return Err("has coordinates out of range".to_string());
+        }
+
+        let mut dense = vec![points[0]];
+        let mut along = vec![0.0];
+        for pair in points.windows(2) {
+            let ((lat1, lon1), (lat2, lon2)) = (pair[0], pair[1]);
+            let length = haversine_distance(lat1, lon1, lat2, lon2);
+            let steps = (length / MAX_SEGMENT_M).ceil().max(1.0) as usize;
+            let frame = LocalFrame::new(lat1, lon1);
+            let (east, north) = frame.to_local(lat2, lon2);
+            let start = *along.last().unwrap_or(&0.0);
+            for step in 1..steps {
+                let fraction = step as f64 / steps as f64;
+                dense.push(frame.to_geo(east * fraction, north * fraction));
+                along.push(start + length * fraction);
+            }
+            dense.push((lat2, lon2));
+            along.push(start + length);
+        }
+
+        let route = Route {
+            points: dense,
+            along,
+        };
+        if route.length() == 0.0 {
+            return Err("must not have zero length".to_string());
+        }
+        if route.length() > MAX_ROUTE_LENGTH_M {
+            return Err(format!(
+                "must not be longer than {} km",
+                MAX_ROUTE_LENGTH_M / 1000.0
+            ));
+        }
+        Ok(route)
+    }
+
+    /// Parses a GPX document or a GeoJSON line, see `parse_route`.
+    pub fn parse(body: &[u8]) -> Result<Self, String> {
+        Route::new(parse_route(body)?)
+    }
+
+    /// Length in meters.
+    pub fn length(&self) -> f64 {
+        *self.along.last().unwrap_or(&0.0)
+    }
+
+    /// The points after splitting long segments.
+    pub fn points(&self) -> &[(f64, f64)] {
+        &self.points
+    }
+
+    /// Splits the segments into consecutive runs of about `length` meters.
+    /// Segment `i` runs from point `i` to point `i + 1`.
+    pub fn chunks(&self, length: f64) -> Vec<Range<usize>> {
+        let mut chunks = vec![];
+        let mut start = 0;
+        for end in 1..self.points.len() {
+            if self.along[end] - self.along[start] >= length || end == self.points.len() - 1 {
+                chunks.push(start..end);
+                start = end;
+            }
+        }
+        chunks
+    }
+
+    /// The box around `segments`, extended by `margin` meters.
+    pub fn bbox(&self, segments: &Range<usize>, margin: f64) -> BoundingBox {
+        BoundingBox::around_all(&self.points[segments.start..=segments.end], margin)
+    }
+
+    /// The closest point to a coordinate on `segments`.
+    pub fn locate(&self, lat: f64, lon: f64, segments: Range<usize>) -> RoutePosition {
+        let mut closest = RoutePosition {
+            along: 0.0,
+            offset: f64::INFINITY,
+        };
+        for index in segments {
+            let (start_lat, start_lon) = self.points[index];
+            let (end_lat, end_lon) = self.points[index + 1];
+            let frame = LocalFrame::new(start_lat, start_lon);
+            let (bx, by) = frame.to_local(end_lat, end_lon);
+            let (px, py) = frame.to_local(lat, lon);
+            let squared_length = bx * bx + by * by;
+            let t = if squared_length > 0.0 {
+                ((px * bx + py * by) / squared_length).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            let offset = (px - t * bx).hypot(py - t * by);
+            if offset < closest.offset {
+                closest = RoutePosition {
+                    along: self.along[index] + t * (self.along[index + 1] - self.along[index]),
+                    offset,
+                };
+            }
+        }
+        closest
+    }
+
+    /// The closest point to a coordinate on the whole route.
+    pub fn locate_on_route(&self, lat: f64, lon: f64) -> RoutePosition {
+        self.locate(lat, lon, 0..self.points.len() - 1)
+    }
+
+    /// The stretches of `segments` within `radius` meters of a coordinate, as
+    /// `(from, to)` meters along the route.
+    pub fn within_radius(
+        &self,
+        lat: f64,
+        lon: f64,
+        radius: f64,
+        segments: Range<usize>,
+    ) -> Vec<(f64, f64)> {
+        let mut stretches = vec![];
+        for index in segments {
+            let (start_lat, start_lon) = self.points[index];
+            let (end_lat, end_lon) = self.points[index + 1];
+            let frame = LocalFrame::new(start_lat, start_lon);
+            let (bx, by) = frame.to_local(end_lat, end_lon);
+            let (cx, cy) = frame.to_local(lat, lon);
+            // |t·b - c|² = r² for the points of the segment at t ∈ [0, 1]
+            let a = bx * bx + by * by;
+            let half_b = bx * cx + by * cy;
+            let discriminant = half_b * half_b - a * (cx * cx + cy * cy - radius * radius);
+            if a == 0.0 || discriminant < 0.0 {
+                continue;
+            }
+            let enter = ((half_b - discriminant.sqrt()) / a).max(0.0);
+            let leave = ((half_b + discriminant.sqrt()) / a).min(1.0);
+            if enter <= leave {
+                // Exact at the segment ends, so that stretches of adjacent segments touch
+                let at = |t: f64| {
+                    if t >= 1.0 {
+                        self.along[index + 1]
+                    } else {
+                        self.along[index] + t * (self.along[index + 1] - self.along[index])
+                    }
+                };
+                stretches.push((at(enter), at(leave)));
+            }
+        }
+        stretches
+    }
+
+    /// The coordinate `along` meters from the start.
+    pub fn point_at(&self, along: f64) -> (f64, f64) {
+        let along = along.clamp(0.0, self.length());
+        let index = self
+            .along
+            .partition_point(|&distance| distance <= along)
+            .clamp(1, self.points.len() - 1);
+        let (start_lat, start_lon) = self.points[index - 1];
+        let (end_lat, end_lon) = self.points[index];
+        let length = self.along[index] - self.along[index - 1];
+        let fraction = if length > 0.0 {
+            (along - self.along[index - 1]) / length
+        } else {
+            0.0
+        };
+        let frame = LocalFrame::new(start_lat, start_lon);
+        let (east, north) = frame.to_local(end_lat, end_lon);
+        frame.to_geo(east * fraction, north * fraction)
+    }
+
+    /// The points of the route between `from` and `to` meters along it.
+    pub fn section(&self, from: f64, to: f64) -> Vec<(f64, f64)> {
+        let mut points = vec![self.point_at(from)];
+        points.extend(
+            self.points
+                .iter()
+                .zip(&self.along)
+                .filter(|(_, &along)| along > from && along < to)
+                .map(|(&point, _)| point),
+        );
+        points.push(self.point_at(to));
+        points
+    }
+}
+
+/// The parts of `[0, length]` outside all `stretches`, in order.
+pub fn gaps(mut stretches: Vec<(f64, f64)>, length: f64) -> Vec<(f64, f64)> {
+    stretches.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let mut gaps = vec![];
+    let mut covered_to = 0.0;
+    for (from, to) in stretches {
+        if from > covered_to {
+            gaps.push((covered_to, from));
+        }
+        covered_to = f64::max(covered_to, to);
+    }
+    if covered_to < length {
+        gaps.push((covered_to, length));
+    }
+    gaps
+}
+
+/// Parses a GPX document or GeoJSON into `(lat, lon)` points, told apart by
+/// the first character. GPX uses the track points, or the route points if
+/// there are none. GeoJSON may be a `LineString` or `MultiLineString`, a
+/// `Feature` or a `FeatureCollection`, whose lines are joined in order.
+pub fn parse_route(body: &[u8]) -> Result<Vec<(f64, f64)>, String> {
+    let text = std::str::from_utf8(body).map_err(|_| "must be UTF-8".to_string())?;
+    if text.trim_start().starts_with('<') {
+        parse_gpx(text)
+    } else {
+        let value: Value = serde_json::from_str(text)
+            .map_err(|e| format!("must be a GPX document or GeoJSON: {}", e))?;
+        let points = geojson_points(&value)?;
+        if points.is_empty() {
+            return Err("GeoJSON has no LineString".to_string());
+        }
+        Ok(points)
+    }
+}
+
+/// The attributes of an XML start tag, without entity decoding.
+fn attributes(tag: &str) -> Vec<(&str, &str)> {
+    let mut attributes = vec![];
+    let mut rest = tag;
+    while let Some(equals) = rest.find('=') {
+        let name = rest[..equals].trim();
+        let value = rest[equals + 1..].trim_start();
+        let Some(quote) = value.chars().next().filter(|c| *c == '"' || *c == '\'') else {
+            break;
+        };
+        let Some(end) = value[1..].find(quote) else {
+            break;
+        };
+        attributes.push((name, &value[1..end + 1]));
+        rest = &value[end + 2..];
+    }
+    attributes
+}
+
+fn parse_gpx(text: &str) -> Result<Vec<(f64, f64)>, String> {
+    for element in ["trkpt", "rtept"] {
+        let mut points = vec![];
+        let start_tag = format!("<{}", element);
+        for (index, _) in text.match_indices(&start_tag) {
+            let tag = &text[index + start_tag.len()..];
+            // `<trkpt` but not `<trkpts`
+            if !tag.starts_with(|c: char| c.is_whitespace()) {
+                continue;
+            }
+            let tag = &tag[..tag.find('>').unwrap_or(tag.len())];
+            let attributes = attributes(tag);
+            let coordinate = |name: &str| {
+                attributes
+                    .iter()
+                    .find(|(key, _)| *key == name)
+                    .and_then(|(_, value)| value.trim().parse::<f64>().ok())
+                    .ok_or_else(|| {
+                        format!(
+                            "GPX {} #{} has no valid {}",
+                            element,
+                            points.len() + 1,
+                            name
+                        )
+                    })
+            };
+            points.push((coordinate("lat")?, coordinate("lon")?));
+        }
+        if !points.is_empty() {
+            return Ok(points);
+        }
+    }
+    Err("GPX has no track or route points".to_string())
+}
+
+fn geojson_line(coordinates: &Value) -> Result<Vec<(f64, f64)>, String> {
+    let positions = coordinates
+        .as_array()
+        .ok_or("LineString coordinates must be an array")?;
+    positions
+        .iter()
+        .map(|position| match position.as_array().map(Vec::as_slice) {
+            Some([lon, lat, ..]) => match (lat.as_f64(), lon.as_f64()) {
+                (Some(lat), Some(lon)) => Ok((lat, lon)),
+                _ => Err("positions must be numbers".to_string()),
+            },
+            _ => Err("positions must be [lon, lat]".to_string()),
+        })
+        .collect()
+}
+
+fn geojson_points(value: &Value) -> Result<Vec<(f64, f64)>, String> {
+    match value.get("type").and_then(Value::as_str) {
+        Some("LineString") => geojson_line(&value["coordinates"]),
+        Some("MultiLineString") => {
+            let lines = value["coordinates"]
+                .as_array()
+                .ok_or("MultiLineString coordinates must be an array")?;
+            let mut points = vec![];
+            for line in lines {
+                points.extend(geojson_line(line)?);
+            }
+            Ok(points)
+        }
+        Some("Feature") => geojson_points(&value["geometry"]),
+        Some("FeatureCollection") => {
+            let features = value["features"]
+                .as_array()
+                .ok_or("FeatureCollection features must be an array")?;
+            let mut points = vec![];
+            for feature in features {
+                points.extend(geojson_points(feature)?);
+            }
+            Ok(points)
+        }
+        // Other geometries, like start and finish markers, are no route
+        Some(_) | None if value.is_object() || value.is_null() => Ok(vec![]),
+        _ => Err("must be a GeoJSON object".to_string()),
+    }
+}
+
+/// Extracts a `Route` from a GPX or GeoJSON request body, rejecting with a
+/// field-level `ApiError::BadRequest` on the `route` field.
+pub fn route_body() -> impl Filter<Extract = (Route,), Error = Rejection> + Clone {
+    warp::body::content_length_limit(MAX_ROUTE_BYTES)
+        .and(warp::body::bytes())
+        .and_then(|body: Bytes| async move {
+            Route::parse(&body).map_err(|message| {
+                Rejection::from(ApiError::BadRequest(vec![FieldError::new(
+                    "route", message,
+                )]))
+            })
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GPX: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<gpx version="1.1" creator="test" xmlns="http://www.topografix.com/GPX/1/1">
+  <trk><name>Course</name><trkseg>
+    <trkpt lat="52.5000" lon="13.4000"><ele>34</ele></trkpt>
+    <trkpt lon='13.4100' lat='52.5000'/>
+    <trkpt  lat = "52.5100"  lon = "13.4100" ></trkpt>
+  </trkseg></trk>
+</gpx>"#;
+
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "expected {} ± {}, got {}",
+            expected,
+            tolerance,
+            actual
+        );
+    }
+
+    #[test]
+    fn test_gpx_track_points() {
+        let points = parse_route(GPX.as_bytes()).unwrap();
+
+        assert_eq!(points, vec![(52.5, 13.4), (52.5, 13.41), (52.51, 13.41)]);
+    }
+
+    #[test]
+    fn test_gpx_route_points() {
+        let gpx = r#"<gpx><rte><rtept lat="1" lon="2"/><rtept lat="3" lon="4"/></rte></gpx>"#;
+
+        assert_eq!(
+            parse_route(gpx.as_bytes()).unwrap(),
+            vec![(1.0, 2.0), (3.0, 4.0)]
+        );
+    }
+
+    #[test]
+    fn test_gpx_without_points_is_rejected() {
+        let error = parse_route(b"<gpx><wpt lat=\"1\" lon=\"2\"/></gpx>").unwrap_err();
+
+        assert!(error.contains("no track or route points"), "{}", error);
+    }
+
+    #[test]
+    fn test_gpx_point_without_longitude_is_rejected() {
+        let error = parse_route(b"<gpx><trkpt lat=\"1\"/></gpx>").unwrap_err();
+
+        assert_eq!(error, "GPX trkpt #1 has no valid lon");
+    }
+
+    #[test]
+    fn test_geojson_line_string() {
+        let json = r#"{"type": "LineString", "coordinates": [[13.4, 52.5, 34.0], [13.41, 52.5]]}"#;
+
+        assert_eq!(
+            parse_route(json.as_bytes()).unwrap(),
+            vec![(52.5, 13.4), (52.5, 13.41)]
+        );
+    }
+
+    #[test]
+    fn test_geojson_feature_collection_joins_lines() {
+        let json = r#"{"type": "FeatureCollection", "features": [
+            {"type": "Feature", "properties": {}, "geometry": {"type": "Point", "coordinates": [0, 0]}},
+            {"type": "Feature", "properties": {}, "geometry": {"type": "LineString", "coordinates": [[1, 2], [3, 4]]}},
+            {"type": "Feature", "properties": {}, "geometry": {"type": "MultiLineString", "coordinates": [[[5, 6]], [[7, 8]]]}}
+        ]}"#;
+
+        assert_eq!(
+            parse_route(json.as_bytes()).unwrap(),
+            vec![(2.0, 1.0), (4.0, 3.0), (6.0, 5.0), (8.0, 7.0)]
+        );
+    }
+
+    #[test]
+    fn test_geojson_without_line_is_rejected() {
+        let json = r#"{"type": "Point", "coordinates": [1, 2]}"#;
+
+        assert_eq!(
+            parse_route(json.as_bytes()).unwrap_err(),
+            "GeoJSON has no LineString"
+        );
+    }
+
+    #[test]
+    fn test_route_needs_two_points() {
+        assert!(Route::new(vec![(52.5, 13.4)]).is_err());
+        assert!(Route::new(vec![(91.0, 0.0), (0.0, 0.0)]).is_err());
+        assert!(Route::new(vec![(52.5, 13.4), (52.5, 13.4)]).is_err());
+    }
+
+    #[test]
+    fn test_long_segments_are_split() {
+        // About 11.1 km due north
+        let route = Route::new(vec![(52.0, 13.0), (52.1, 13.0)]).unwrap();
+
+        assert_eq!(route.points().len(), 13);
+        assert_close(route.length(), 11_119.5, 1.0);
+    }
+
+    #[test]
+    fn test_chunks_cover_all_segments() {
+        let route = Route::new(vec![(52.0, 13.0), (52.1, 13.0)]).unwrap();
+
+        let chunks = route.chunks(5_000.0);
+
+        assert_eq!(chunks, vec![0..6, 6..12]);
+    }
+
+    #[test]
+    fn test_locate_projects_onto_the_route() {
+        let route = Route::new(vec![(52.0, 13.0), (52.0, 13.1), (52.1, 13.1)]).unwrap();
+        let first_leg = haversine_distance(52.0, 13.0, 52.0, 13.1);
+
+        // 500 m south of the middle of the first leg
+        let position = route.locate_on_route(52.0 - 0.0045, 13.05);
+        assert_close(position.along, first_leg / 2.0, 5.0);
+        assert_close(position.offset, 500.0, 5.0);
+
+        // Beyond the end, the end is closest
+        let position = route.locate_on_route(52.2, 13.1);
+        assert_close(position.along, route.length(), 1e-6);
+    }
+
+    #[test]
+    fn test_within_radius_crosses_the_circle() {
+        let route = Route::new(vec![(52.0, 13.0), (52.1, 13.0)]).unwrap();
+        let center = haversine_distance(52.0, 13.0, 52.05, 13.0);
+
+        let stretches = route.within_radius(52.05, 13.0, 2_000.0, 0..route.points().len() - 1);
+        let (from, to) = (stretches[0].0, stretches.last().unwrap().1);
+
+        assert_close(from, center - 2_000.0, 1.0);
+        assert_close(to, center + 2_000.0, 1.0);
+        assert!(route
+            .within_radius(52.05, 13.5, 2_000.0, 0..route.points().len() - 1)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_point_at_and_section() {
+        let route = Route::new(vec![(52.0, 13.0), (52.1, 13.0)]).unwrap();
+
+        let (lat, lon) = route.point_at(route.length() / 2.0);
+        assert_close(lat, 52.05, 1e-6);
+        assert_close(lon, 13.0, 1e-9);
+        let (lat, _) = route.point_at(route.length() + 10.0);
+        assert_close(lat, 52.1, 1e-9);
+
+        let section = route.section(1_500.0, 3_500.0);
+        assert_eq!(section.len(), 4);
+        assert_close(
+            haversine_distance(52.0, 13.0, section[0].0, section[0].1),
+            1_500.0,
+            1.0,
+        );
+    }
+
+    #[test]
+    fn test_gaps_between_stretches() {
+        let stretches = vec![(500.0, 1_000.0), (0.0, 200.0), (800.0, 1_500.0)];
+
+        assert_eq!(
+            gaps(stretches, 2_000.0),
+            vec![(200.0, 500.0), (1_500.0, 2_000.0)]
+        );
+        assert_eq!(gaps(vec![], 100.0), vec![(0.0, 100.0)]);
+        assert!(gaps(vec![(0.0, 100.0)], 100.0).is_empty());
+    }
+
+    #[test]
+    fn test_bbox_covers_the_corridor() {
+        let route = Route::new(vec![(52.0, 13.0), (52.1, 13.0)]).unwrap();
+
+        let bbox = route.bbox(&(0..6), 1000.0);
+
+        assert!(bbox.min_lat < 52.0 - 0.008 && bbox.max_lat > 52.045);
+        assert!(bbox.min_lon < 12.986 && bbox.max_lon > 13.014);
+    }
+}
@@ -1,15 +1,44 @@
 use tracing::{debug, info};
-use warp::{cors::Cors, http::Method, Filter};
-
-use tokio::sync::oneshot::Receiver;
+use warp::{cors::Cors, http::Method, path::FullPath, Filter};
 
 use crate::{handlers, utils::config::Config};
 
-use super::utils::Promise;
+use super::caching::conditions;
+use super::compression::{accept_encoding, compress};
+use super::errors::handle_rejection;
+use super::events::{sse_events, SyncEvent, SyncEvents};
+use super::negotiation::{format, format_unless_geojson};
+use super::openapi::{docs_route, openapi_route};
+use super::query::{validated_json, validated_query};
+use super::route::route_body;
+use super::utils::{Promise, Shutdown};
+use super::versioning::{signal_deprecation, Deprecation, API_VERSION};
+
+/// Check whether the service is up.
+#[utoipa::path(
+    get,
+    path = "/health",
+    responses((status = 200, description = "The service is up", body = String, content_type = "text/plain")),
+    tag = "service"
+)]
+pub fn health() -> &'static str {
+    "OK"
+}
+
+/// Stream sync lifecycle events as Server-Sent Events, named after their `type`.
+#[utoipa::path(
+    get,
+    path = "/v1/events",
+    responses((status = 200, description = "An endless stream of sync events", body = SyncEvent, content_type = "text/event-stream")),
+    tag = "service"
+)]
+pub fn sync_events(events: &SyncEvents) -> impl warp::Reply {
+    warp::sse::reply(warp::sse::keep_alive().stream(sse_events(events)))
+}
 
 /// Returns the health check route filter.
 pub fn health_route() -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
-    warp::path!("health").map(|| "OK")
+    warp::path!("health").and(warp::get()).map(health)
 }
 
 /// Creates a CORS filter based on the configured origins.
@@ -17,8 +46,22 @@ pub fn health_route() -> impl Filter<Extract = impl warp::Reply, Error = warp::R
 /// If CORS_ORIGINS is not set or empty, all origins are allowed.
 pub fn cors_filter(cors_origins: Vec<String>) -> Cors {
     let cors = warp::cors()
-        .allow_methods(vec![Method::GET, Method::OPTIONS])
-        .allow_headers(vec!["Content-Type", "Traceparent", "Authorization"]);
+        .allow_methods(vec![Method::GET, Method::POST, Method::OPTIONS])
+        .allow_headers(vec![
+            "Content-Type",
+            "Traceparent",
+            "Authorization",
+            "If-None-Match",
+            "If-Modified-Since",
+        ])
+        .expose_headers(vec![
+            "ETag",
+            "Last-Modified",
+            "Cache-Control",
+            "Deprecation",
+            "Sunset",
+            "Link",
+        ]);
 
     if cors_origins.is_empty() {
         debug!("CORS configured to allow any origin");
@@ -35,39 +78,221 @@ pub fn cors_filter(cors_origins: Vec<String>) -> Cors {
     }
 }
 
-pub async fn start_server(shutdown_receiver: Receiver<()>, config: Config) -> Promise<()> {
-    let port = config.port;
-    let bind = config.bind;
+/// Builds all API routes, including error handling and CORS.
+pub fn routes(
+    config: Config,
+    events: SyncEvents,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
     let cors_origins = config.cors_origins.clone();
-
-    info!("Start server.");
-    debug!("Port: {}", port);
-    debug!("Bind Address: {:?}", bind);
-
+    let deprecation = Deprecation::unversioned(&config);
+    let compression_min_size = config.compression_min_size;
+    let schema = handlers::graphql::build_schema(config.clone());
     let config_filter = warp::any().map(move || config.clone());
 
     let get_cell = warp::path!("cell")
-        .and(warp::query::<handlers::cell::GetCellQuery>())
+        .and(warp::get())
+        .and(validated_query::<handlers::cell::GetCellQuery>())
+        .and(conditions())
+        .and(format())
         .and(config_filter.clone())
-        .and_then(
-            |query, config| async move { handlers::cell::handle_get_cell(query, config).await },
-        );
+        .and_then(|query, conditions, format, config| async move {
+            handlers::cell::handle_get_cell(query, conditions, format, config).await
+        });
 
     let get_cells = warp::path!("cells")
-        .and(warp::query::<handlers::cells::GetCellsQuery>())
+        .and(warp::get())
+        .and(validated_query::<handlers::cells::GetCellsQuery>())
+        .and(validated_query::<handlers::cells::CellsOutputQuery>())
+        .and(conditions())
+        .and(format_unless_geojson())
+        .and(config_filter.clone())
+        .and_then(|query, output, conditions, format, config| async move {
+            handlers::cells::handle_get_cells(query, output, conditions, format, config).await
+        });
+
+    let get_density = warp::path!("cells" / "density")
+        .and(warp::get())
+        .and(validated_query::<handlers::density::GetDensityQuery>())
+        .and(conditions())
+        .and(format_unless_geojson())
+        .and(config_filter.clone())
+        .and_then(|query, conditions, format, config| async move {
+            handlers::density::handle_get_density(query, conditions, format, config).await
+        });
+
+    let get_point_coverage = warp::path!("coverage")
+        .and(warp::get())
+        .and(validated_query::<handlers::coverage::GetPointCoverageQuery>())
+        .and(conditions())
+        .and(format())
+        .and(config_filter.clone())
+        .and_then(|query, conditions, format, config| async move {
+            handlers::coverage::handle_get_point_coverage(query, conditions, format, config).await
+        });
+
+    let get_cell_coverage = warp::path!("cell" / "coverage")
+        .and(warp::get())
+        .and(validated_query::<handlers::coverage::GetCellCoverageQuery>())
+        .and(conditions())
+        .and(config_filter.clone())
+        .and_then(|query, conditions, config| async move {
+            handlers::coverage::handle_get_cell_coverage(query, conditions, config).await
+        });
+
+    let get_changes = warp::path!("cells" / "changes")
+        .and(warp::get())
+        .and(validated_query::<handlers::changes::GetChangesQuery>())
+        .and(conditions())
+        .and(format())
         .and(config_filter.clone())
-        .and_then(
-            |query, config| async move { handlers::cells::handle_get_cells(query, config).await },
-        );
+        .and_then(|query, conditions, format, config| async move {
+            handlers::changes::handle_get_changes(query, conditions, format, config).await
+        });
+
+    let lookup_cells = warp::path!("cells" / "lookup")
+        .and(warp::post())
+        .and(validated_query::<handlers::lookup::LookupCellsQuery>())
+        .and(validated_json::<handlers::lookup::LookupCellsRequest>())
+        .and(format())
+        .and(config_filter.clone())
+        .and_then(|query, request, format, config| async move {
+            handlers::lookup::handle_lookup_cells(query, request, format, config).await
+        });
+
+    let resolve_neighbours = warp::path!("cells" / "neighbours")
+        .and(warp::post())
+        .and(validated_json::<
+            handlers::neighbours::ResolveNeighboursRequest,
+        >())
+        .and(format())
+        .and(config_filter.clone())
+        .and_then(|request, format, config| async move {
+            handlers::neighbours::handle_resolve_neighbours(request, format, config).await
+        });
+
+    let nearest_cells = warp::path!("points" / "nearest-cells")
+        .and(warp::post())
+        .and(validated_json::<handlers::nearest::NearestCellsRequest>())
+        .and(format())
+        .and(config_filter.clone())
+        .and_then(|request, format, config| async move {
+            handlers::nearest::handle_nearest_cells(request, format, config).await
+        });
+
+    let localize_track = warp::path!("tracks" / "localize")
+        .and(warp::post())
+        .and(validated_json::<handlers::tracks::LocalizeTrackRequest>())
+        .and(format())
+        .and(config_filter.clone())
+        .and_then(|request, format, config| async move {
+            handlers::tracks::handle_localize_track(request, format, config).await
+        });
+
+    let route_cells = warp::path!("route" / "cells")
+        .and(warp::post())
+        .and(validated_query::<handlers::route::RouteCellsQuery>())
+        .and(route_body())
+        .and(format())
+        .and(config_filter.clone())
+        .and_then(|query, route, format, config| async move {
+            handlers::route::handle_route_cells(query, route, format, config).await
+        });
+
+    let route_coverage_report = warp::path!("route" / "coverage-report")
+        .and(warp::post())
+        .and(validated_query::<handlers::route::CoverageReportQuery>())
+        .and(route_body())
+        .and(format_unless_geojson())
+        .and(config_filter.clone())
+        .and_then(|query, route, format, config| async move {
+            handlers::route::handle_coverage_report(query, route, format, config).await
+        });
+
+    let venue_operators = warp::path!("venue" / "operators")
+        .and(warp::post())
+        .and(validated_json::<handlers::venue::VenueOperatorsRequest>())
+        .and(format())
+        .and(config_filter.clone())
+        .and_then(|request, format, config| async move {
+            handlers::venue::handle_venue_operators(request, format, config).await
+        });
+
+    let parse_modem = warp::path!("modem" / "parse")
+        .and(warp::post())
+        .and(validated_query::<handlers::modem::ParseModemQuery>())
+        .and(validated_json::<handlers::modem::ParseModemRequest>())
+        .and(format())
+        .and(config_filter.clone())
+        .and_then(|query, request, format, config| async move {
+            handlers::modem::handle_parse_modem(query, request, format, config).await
+        });
+
+    let graphql = warp::path!("graphql")
+        .and(async_graphql_warp::graphql(schema))
+        .and(config_filter.clone())
+        .and_then(|(schema, request), config| async move {
+            handlers::graphql::handle_graphql(schema, request, config).await
+        });
+
+    let events_stream = warp::path!("events")
+        .and(warp::get())
+        .map(move || sync_events(&events));
+
+    // Routes that predate `/v1` are also served without the version prefix
+    let unversioned = get_cell
+        .or(get_cells)
+        .or(get_changes)
+        .or(lookup_cells)
+        .or(events_stream);
+    let api = unversioned
+        .clone()
+        .or(get_cell_coverage)
+        .or(get_point_coverage)
+        .or(get_density)
+        .or(resolve_neighbours)
+        .or(nearest_cells)
+        .or(localize_track)
+        .or(route_cells)
+        .or(route_coverage_report)
+        .or(venue_operators)
+        .or(parse_modem);
 
     let cors = cors_filter(cors_origins);
-    let routes = warp::get()
-        .and(health_route().or(get_cell).or(get_cells))
-        .with(cors);
+    // Routes match on the path before the method, so unknown paths stay 404s
+    let routes = health_route()
+        .or(openapi_route())
+        .or(docs_route())
+        .or(graphql)
+        .or(warp::path(API_VERSION).and(api))
+        .or(unversioned)
+        .recover(handle_rejection);
+
+    warp::path::full()
+        .and(accept_encoding())
+        .and(routes)
+        .then(move |path: FullPath, accept_encoding, reply| {
+            let response = signal_deprecation(&path, deprecation, reply);
+            compress(response, accept_encoding, compression_min_size)
+        })
+        .with(cors)
+}
+
+pub async fn start_server(shutdown: Shutdown, config: Config, events: SyncEvents) -> Promise<()> {
+    let port = config.port;
+    let bind = config.bind;
 
-    let (_, server) = warp::serve(routes).bind_with_graceful_shutdown((bind, port), async {
-        shutdown_receiver.await.ok();
-    });
+    info!("Start server.");
+    debug!("Port: {}", port);
+    debug!("Bind Address: {:?}", bind);
+
+    let (_, server) = warp::serve(routes(config, events.clone())).bind_with_graceful_shutdown(
+        (bind, port),
+        async move {
+            shutdown.await.ok();
+            // Event streams never end on their own and would block the shutdown
+            events.close();
+        },
+    );
 
     server.await;
     info!("Server stopped.");
@@ -108,6 +333,422 @@ mod tests {
         }
     }
 
+    mod error_handling {
+        use super::*;
+        use crate::utils::config::test_config;
+        use warp::http::StatusCode;
+        use warp::test::request;
+
+        fn body_json(body: &[u8]) -> serde_json::Value {
+            serde_json::from_slice(body).unwrap()
+        }
+
+        #[tokio::test]
+        async fn test_invalid_cell_query_returns_400_with_field() {
+            let response = request()
+                .method("GET")
+                .path("/cell?mcc=abc&net=1&area=1&cell=1")
+                .reply(&routes(test_config(), SyncEvents::new()))
+                .await;
+
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+            let body = body_json(response.body());
+            assert_eq!(body["fields"][0]["field"], "mcc");
+        }
+
+        #[tokio::test]
+        async fn test_unsupported_accept_returns_406() {
+            let response = request()
+                .method("GET")
+                .path("/v1/cells?mcc=262")
+                .header("accept", "text/html")
+                .reply(&routes(test_config(), SyncEvents::new()))
+                .await;
+
+            assert_eq!(response.status(), StatusCode::NOT_ACCEPTABLE);
+            assert_eq!(response.headers()["content-type"], "application/json");
+            let body = body_json(response.body());
+            assert_eq!(body["error"], "not_acceptable");
+        }
+
+        #[tokio::test]
+        async fn test_geojson_outputs_accept_geojson() {
+            let route = r#"{"type": "LineString", "coordinates": [[13.4, 52.5], [13.5, 52.5]]}"#;
+            for (method, path, body) in [
+                ("GET", "/v1/cells?mcc=262&format=geojson", ""),
+                ("GET", "/v1/cells/density?resolution=2&format=geojson", ""),
+                ("POST", "/v1/route/coverage-report?format=geojson", route),
+            ] {
+                let response = request()
+                    .method(method)
+                    .path(path)
+                    .header("accept", "application/geo+json")
+                    .body(body)
+                    .reply(&routes(test_config(), SyncEvents::new()))
+                    .await;
+
+                // Negotiation passed, the test database is unreachable
+                assert_eq!(
+                    response.status(),
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    "{}",
+                    path
+                );
+            }
+        }
+
+        #[tokio::test]
+        async fn test_missing_cell_parameter_returns_400() {
+            let response = request()
+                .method("GET")
+                .path("/cell?mcc=262&net=1&area=1")
+                .reply(&routes(test_config(), SyncEvents::new()))
+                .await;
+
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+            let body = body_json(response.body());
+            assert_eq!(body["fields"][0]["field"], "cell");
+        }
+
+        #[tokio::test]
+        async fn test_changes_without_since_returns_400() {
+            let response = request()
+                .method("GET")
+                .path("/cells/changes?limit=10")
+                .reply(&routes(test_config(), SyncEvents::new()))
+                .await;
+
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+            let body = body_json(response.body());
+            assert_eq!(body["fields"][0]["field"], "since");
+        }
+
+        #[tokio::test]
+        async fn test_invalid_geofence_returns_400() {
+            let response = request()
+                .method("GET")
+                .path("/cells?min_lat=100")
+                .reply(&routes(test_config(), SyncEvents::new()))
+                .await;
+
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+            let body = body_json(response.body());
+            assert_eq!(body["fields"][0]["field"], "min_lat");
+        }
+
+        #[tokio::test]
+        async fn test_invalid_lookup_body_returns_400_with_field() {
+            let response = request()
+                .method("POST")
+                .path("/cells/lookup")
+                .json(&serde_json::json!({"cells": [{"mcc": 262, "mnc": 1, "lac": 1}]}))
+                .reply(&routes(test_config(), SyncEvents::new()))
+                .await;
+
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+            let body = body_json(response.body());
+            assert_eq!(body["fields"][0]["field"], "cells[0]");
+        }
+
+        #[tokio::test]
+        async fn test_invalid_match_mode_returns_400() {
+            let response = request()
+                .method("GET")
+                .path("/cell?mcc=262&net=1&area=1&cell=1&match=maybe")
+                .reply(&routes(test_config(), SyncEvents::new()))
+                .await;
+
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+            let body = body_json(response.body());
+            assert_eq!(body["fields"][0]["field"], "match");
+        }
+
+        #[tokio::test]
+        async fn test_wrong_method_returns_405() {
+            let response = request()
+                .method("GET")
+                .path("/cells/lookup")
+                .reply(&routes(test_config(), SyncEvents::new()))
+                .await;
+
+            assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+        }
+
+        #[tokio::test]
+        async fn test_unknown_route_returns_json_404() {
+            let response = request()
+                .method("GET")
+                .path("/unknown")
+                .reply(&routes(test_config(), SyncEvents::new()))
+                .await;
+
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+            assert_eq!(body_json(response.body())["error"], "not_found");
+        }
+    }
+
+    mod sync_events {
+        use super::*;
+        use crate::models::LastUpdatesType;
+        use crate::utils::config::test_config;
+        use crate::utils::events::SyncEventKind;
+        use warp::http::StatusCode;
+        use warp::test::request;
+
+        #[tokio::test]
+        async fn test_events_are_streamed_as_sse() {
+            let events = SyncEvents::new();
+            let emitter = events.clone();
+            tokio::spawn(async move {
+                // Give the request time to subscribe
+                tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+                emitter.emit(SyncEventKind::ImportStarted {
+                    update_type: LastUpdatesType::Diff,
+                });
+                emitter.close();
+            });
+
+            let response = request()
+                .method("GET")
+                .path("/events")
+                .reply(&routes(test_config(), events))
+                .await;
+
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.headers()["content-type"], "text/event-stream");
+            let body = String::from_utf8_lossy(response.body());
+            assert!(body.contains("event:importStarted"));
+            assert!(body.contains(r#""updateType":"diff""#));
+        }
+    }
+
+    mod graphql {
+        use super::*;
+        use crate::utils::config::test_config;
+        use warp::http::StatusCode;
+        use warp::test::request;
+
+        #[tokio::test]
+        async fn test_graphql_query_is_executed() {
+            let response = request()
+                .method("POST")
+                .path("/graphql")
+                .header("content-type", "application/json")
+                .body(r#"{"query": "{ operator(mcc: 262, net: 1) { mcc } }"}"#)
+                .reply(&routes(test_config(), SyncEvents::new()))
+                .await;
+
+            assert_eq!(response.status(), StatusCode::OK);
+            let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+            assert_eq!(body["data"]["operator"]["mcc"], 262);
+            assert!(!response.headers().contains_key("deprecation"));
+        }
+
+        #[tokio::test]
+        async fn test_malformed_graphql_request_returns_400() {
+            let response = request()
+                .method("POST")
+                .path("/graphql")
+                .header("content-type", "application/json")
+                .body("not json")
+                .reply(&routes(test_config(), SyncEvents::new()))
+                .await;
+
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+            let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+            assert_eq!(body["error"], "bad_request");
+        }
+    }
+
+    mod compression {
+        use super::*;
+        use crate::utils::config::test_config;
+        use warp::http::header;
+        use warp::test::request;
+
+        #[tokio::test]
+        async fn test_large_responses_are_compressed() {
+            let response = request()
+                .method("GET")
+                .path("/openapi.json")
+                .header("accept-encoding", "gzip, br")
+                .reply(&routes(test_config(), SyncEvents::new()))
+                .await;
+
+            assert_eq!(response.headers()[header::CONTENT_ENCODING], "br");
+            assert_eq!(response.headers()[header::VARY], "Accept-Encoding");
+        }
+
+        #[tokio::test]
+        async fn test_small_responses_are_not_compressed() {
+            let response = request()
+                .method("GET")
+                .path("/health")
+                .header("accept-encoding", "gzip")
+                .reply(&routes(test_config(), SyncEvents::new()))
+                .await;
+
+            assert!(!response.headers().contains_key(header::CONTENT_ENCODING));
+            assert_eq!(response.body().as_ref(), b"OK");
+        }
+    }
+
+    mod versioning {
+        use super::*;
+        use crate::utils::config::test_config;
+        use chrono::{TimeZone, Utc};
+        use warp::http::StatusCode;
+        use warp::test::request;
+
+        #[tokio::test]
+        async fn test_versioned_route_has_no_deprecation_headers() {
+            let response = request()
+                .method("GET")
+                .path("/v1/cell")
+                .reply(&routes(test_config(), SyncEvents::new()))
+                .await;
+
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+            assert!(!response.headers().contains_key("deprecation"));
+            assert!(!response.headers().contains_key("sunset"));
+        }
+
+        #[tokio::test]
+        async fn test_newer_routes_are_only_versioned() {
+            for (method, path) in [
+                ("GET", "/cell/coverage?mcc=262&net=1&area=1&cell=1"),
+                ("GET", "/coverage?lat=52.5&lon=13.4"),
+                ("GET", "/cells/density?resolution=2"),
+                ("POST", "/cells/neighbours"),
+                ("POST", "/points/nearest-cells"),
+                ("POST", "/tracks/localize"),
+                ("POST", "/route/cells"),
+                ("POST", "/route/coverage-report"),
+                ("POST", "/venue/operators"),
+                ("POST", "/modem/parse"),
+            ] {
+                let response = request()
+                    .method(method)
+                    .path(path)
+                    .body("{}")
+                    .reply(&routes(test_config(), SyncEvents::new()))
+                    .await;
+
+                assert_eq!(response.status(), StatusCode::NOT_FOUND, "{}", path);
+                assert!(!response.headers().contains_key("deprecation"));
+            }
+        }
+
+        #[tokio::test]
+        async fn test_modem_output_is_parsed_without_database() {
+            let response = request()
+                .method("POST")
+                .path("/v1/modem/parse")
+                .body(r#"{"output": "+CPSI: LTE,Online,262-01,0x3A7D,27447299,123,EUTRAN-BAND3,1300,5,5,-110,-970,-670,16\r\nOK"}"#)
+                .reply(&routes(test_config(), SyncEvents::new()))
+                .await;
+
+            assert_eq!(response.status(), StatusCode::OK);
+            let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+            assert_eq!(body["serving"][0]["cell"], 27447299);
+            assert_eq!(body["serving"][0]["signalDbm"], -97);
+            assert!(body.get("resolved").is_none());
+        }
+
+        #[tokio::test]
+        async fn test_coverage_shape_requires_geojson() {
+            let response = request()
+                .method("GET")
+                .path("/v1/cells?mcc=262&shape=voronoi")
+                .reply(&routes(test_config(), SyncEvents::new()))
+                .await;
+
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+            let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+            assert_eq!(body["fields"][0]["field"], "shape");
+        }
+
+        #[tokio::test]
+        async fn test_unversioned_alias_is_not_deprecated_by_default() {
+            let response = request()
+                .method("GET")
+                .path("/cell")
+                .reply(&routes(test_config(), SyncEvents::new()))
+                .await;
+
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+            assert!(!response.headers().contains_key("deprecation"));
+            assert!(!response.headers().contains_key("link"));
+            assert!(!response.headers().contains_key("sunset"));
+        }
+
+        #[tokio::test]
+        async fn test_unversioned_alias_is_deprecated() {
+            let mut config = test_config();
+            config.unversioned_deprecation =
+                Some(Utc.with_ymd_and_hms(2026, 10, 18, 0, 0, 0).unwrap());
+
+            let response = request()
+                .method("GET")
+                .path("/cell")
+                .reply(&routes(config, SyncEvents::new()))
+                .await;
+
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+            assert_eq!(response.headers()["deprecation"], "@1792281600");
+            assert_eq!(
+                response.headers()["link"],
+                r#"</v1/cell>; rel="successor-version""#
+            );
+            assert!(!response.headers().contains_key("sunset"));
+        }
+
+        #[tokio::test]
+        async fn test_unversioned_alias_announces_sunset() {
+            let mut config = test_config();
+            config.unversioned_deprecation =
+                Some(Utc.with_ymd_and_hms(2026, 10, 18, 0, 0, 0).unwrap());
+            config.unversioned_sunset = Some(Utc.with_ymd_and_hms(2027, 6, 30, 0, 0, 0).unwrap());
+
+            let response = request()
+                .method("GET")
+                .path("/cells/changes")
+                .reply(&routes(config, SyncEvents::new()))
+                .await;
+
+            assert_eq!(
+                response.headers()["sunset"],
+                "Wed, 30 Jun 2027 00:00:00 GMT"
+            );
+            assert_eq!(
+                response.headers()["link"],
+                r#"</v1/cells/changes>; rel="successor-version""#
+            );
+        }
+
+        #[tokio::test]
+        async fn test_versioned_wrong_method_returns_405() {
+            let response = request()
+                .method("GET")
+                .path("/v1/cells/lookup")
+                .reply(&routes(test_config(), SyncEvents::new()))
+                .await;
+
+            assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+        }
+
+        #[tokio::test]
+        async fn test_meta_routes_are_not_versioned() {
+            let response = request()
+                .method("GET")
+                .path("/v1/health")
+                .reply(&routes(test_config(), SyncEvents::new()))
+                .await;
+
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        }
+    }
+
     mod cors_filter_tests {
         use super::*;
         use warp::http::StatusCode;
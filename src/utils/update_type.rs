@@ -1,4 +1,4 @@
-use chrono::{DateTime, Datelike, Timelike, Utc};
+use chrono::{DateTime, Datelike, Duration, NaiveTime, Timelike, Utc};
 use tracing::{debug, info};
 
 use crate::models::LastUpdatesType;
@@ -53,6 +53,25 @@ pub fn get_update_type(last_update: DateTime<Utc>, now: DateTime<Utc>) -> Option
     Some(LastUpdatesType::Full)
 }
 
+/// Returns the start of the latest window in which a sync may have changed
+/// the data, at or before `now`.
+pub fn latest_update_window(now: DateTime<Utc>) -> DateTime<Utc> {
+    let window_start = NaiveTime::from_hms_opt(UPDATE_AVAILABLE_HOUR_UTC, 0, 0).unwrap();
+    let today = now.date_naive().and_time(window_start).and_utc();
+
+    if now < today {
+        today - Duration::days(1)
+    } else {
+        today
+    }
+}
+
+/// Returns the start of the next window in which a sync may change the data.
+/// Responses can be cached until then.
+pub fn next_update_window(now: DateTime<Utc>) -> DateTime<Utc> {
+    latest_update_window(now) + Duration::days(1)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -155,4 +174,30 @@ mod tests {
             Some(LastUpdatesType::Diff)
         );
     }
+
+    #[test]
+    fn test_next_update_window_before_4am_is_today() {
+        let now = utc(2025, 12, 20, 3, 0, 0);
+
+        assert_eq!(next_update_window(now), utc(2025, 12, 20, 4, 0, 0));
+    }
+
+    #[test]
+    fn test_next_update_window_after_4am_is_tomorrow() {
+        let now = utc(2025, 12, 31, 4, 0, 0);
+
+        assert_eq!(next_update_window(now), utc(2026, 1, 1, 4, 0, 0));
+    }
+
+    #[test]
+    fn test_latest_update_window() {
+        assert_eq!(
+            latest_update_window(utc(2025, 12, 20, 3, 0, 0)),
+            utc(2025, 12, 19, 4, 0, 0)
+        );
+        assert_eq!(
+            latest_update_window(utc(2025, 12, 20, 4, 30, 0)),
+            utc(2025, 12, 20, 4, 0, 0)
+        );
+    }
 }
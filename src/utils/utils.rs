@@ -1,7 +1,11 @@
+use futures::future::Shared;
+use tokio::sync::oneshot::Receiver;
 use tokio::task::JoinHandle;
 
 pub type FutureError = Box<dyn std::error::Error + Send + Sync>;
 pub type Promise<T> = std::result::Result<T, FutureError>;
+/// The shutdown signal, shared by all servers.
+pub type Shutdown = Shared<Receiver<()>>;
 
 pub async fn flatten<T>(handle: JoinHandle<Result<T, FutureError>>) -> Result<T, String> {
     match handle.await {
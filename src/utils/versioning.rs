@@ -0,0 +1,135 @@
+use chrono::{DateTime, Utc};
+
+use crate::utils::config::Config;
+use warp::http::HeaderValue;
+use warp::path::FullPath;
+use warp::reply::Response;
+use warp::Reply;
+
+/// The current API version, served at `/v1/...`.
+pub const API_VERSION: &str = "v1";
+
+/// Routes that are still served without a version prefix, the ones that
+/// predate `/v1`. Health checks and the API docs are not versioned and not
+/// listed here.
+pub const UNVERSIONED_ALIASES: [&str; 5] =
+    ["cell", "cells", "cells/changes", "cells/lookup", "events"];
+
+/// Signals the retirement of the unversioned aliases to clients, following
+/// RFC 9745 (`Deprecation`) and RFC 8594 (`Sunset`).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Deprecation {
+    /// When the route was deprecated, if we plan to retire it.
+    pub deprecated_at: Option<DateTime<Utc>>,
+    /// When the route will stop working, if already planned.
+    pub sunset: Option<DateTime<Utc>>,
+}
+
+impl Deprecation {
+    /// The configured deprecation of the unversioned aliases.
+    pub fn unversioned(config: &Config) -> Self {
+        Deprecation {
+            deprecated_at: config.unversioned_deprecation,
+            sunset: config.unversioned_sunset,
+        }
+    }
+}
+
+/// Whether a request path is an unversioned alias of a versioned route.
+pub fn is_unversioned_alias(path: &str) -> bool {
+    let route = path.trim_start_matches('/').trim_end_matches('/');
+    UNVERSIONED_ALIASES.contains(&route)
+}
+
+/// Adds the configured deprecation headers to replies from unversioned
+/// aliases, pointing clients to the versioned route. Other replies are passed
+/// through.
+pub fn signal_deprecation(
+    path: &FullPath,
+    deprecation: Deprecation,
+    reply: impl Reply,
+) -> Response {
+    let mut response = reply.into_response();
+    if !is_unversioned_alias(path.as_str()) {
+        return response;
+    }
+
+    let headers = response.headers_mut();
+    if let Some(sunset) = deprecation.sunset {
+        let sunset_value = sunset.format("%a, %d %b %Y %H:%M:%S GMT").to_string();
+        headers.insert("Sunset", HeaderValue::from_str(&sunset_value).unwrap());
+    }
+    let Some(deprecated_at) = deprecation.deprecated_at else {
+        return response;
+    };
+    let deprecation_value = format!("@{}", deprecated_at.timestamp());
+    headers.insert(
+        "Deprecation",
+        HeaderValue::from_str(&deprecation_value).unwrap(),
+    );
+    let link = format!(
+        "</{}{}>; rel=\"successor-version\"",
+        API_VERSION,
+        path.as_str()
+    );
+    if let Ok(link) = HeaderValue::from_str(&link) {
+        headers.append("Link", link);
+    }
+
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod is_unversioned_alias {
+        use super::*;
+
+        #[test]
+        fn test_data_routes_are_aliases() {
+            assert!(is_unversioned_alias("/cell"));
+            assert!(is_unversioned_alias("/cells"));
+            assert!(is_unversioned_alias("/cells/lookup"));
+            assert!(is_unversioned_alias("/events"));
+        }
+
+        #[test]
+        fn test_versioned_and_meta_routes_are_not_aliases() {
+            assert!(!is_unversioned_alias("/v1/cells"));
+            assert!(!is_unversioned_alias("/health"));
+            assert!(!is_unversioned_alias("/openapi.json"));
+            assert!(!is_unversioned_alias("/docs/"));
+            assert!(!is_unversioned_alias("/cellsx"));
+            assert!(!is_unversioned_alias("/cells/neighbours"));
+        }
+    }
+
+    mod unversioned {
+        use super::*;
+        use crate::utils::config::test_config;
+        use chrono::TimeZone;
+
+        #[test]
+        fn test_is_not_deprecated_by_default() {
+            assert_eq!(
+                Deprecation::unversioned(&test_config()),
+                Deprecation::default()
+            );
+        }
+
+        #[test]
+        fn test_takes_the_configured_dates() {
+            let deprecated_at = Utc.with_ymd_and_hms(2026, 10, 18, 0, 0, 0).unwrap();
+            let sunset = Utc.with_ymd_and_hms(2027, 6, 30, 0, 0, 0).unwrap();
+            let mut config = test_config();
+            config.unversioned_deprecation = Some(deprecated_at);
+            config.unversioned_sunset = Some(sunset);
+
+            let deprecation = Deprecation::unversioned(&config);
+
+            assert_eq!(deprecation.deprecated_at, Some(deprecated_at));
+            assert_eq!(deprecation.sunset, Some(sunset));
+        }
+    }
+}
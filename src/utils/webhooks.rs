@@ -0,0 +1,740 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use diesel::MysqlConnection;
+use futures::{Stream, StreamExt};
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tracing::{debug, info, warn};
+
+use super::config::Config;
+use super::db::try_establish_connection;
+use super::events::{SyncEvent, SyncEventKind, SyncStage};
+use super::utils::Promise;
+use crate::models::{LastUpdatesType, WebhookDeliveryAttempt};
+
+/// Timeout of a single delivery attempt.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// Delay before the first retry, doubled on every further attempt.
+const BASE_RETRY_DELAY: Duration = Duration::from_secs(2);
+/// Upper bound of the delay between two attempts.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(10 * 60);
+/// How far back the delivery log is searched for deliveries to resume at startup.
+const RESUME_WINDOW: Duration = Duration::from_secs(24 * 60 * 60);
+
+pub const SIGNATURE_HEADER: &str = "X-Webhook-Signature";
+pub const TIMESTAMP_HEADER: &str = "X-Webhook-Timestamp";
+pub const DELIVERY_HEADER: &str = "X-Webhook-Delivery";
+
+/// What a webhook reports.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WebhookEvent {
+    #[serde(rename = "sync.completed")]
+    SyncCompleted,
+    #[serde(rename = "sync.failed")]
+    SyncFailed,
+}
+
+impl WebhookEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            WebhookEvent::SyncCompleted => "sync.completed",
+            WebhookEvent::SyncFailed => "sync.failed",
+        }
+    }
+}
+
+/// JSON body POSTed to every webhook target.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookPayload {
+    pub event: WebhookEvent,
+    /// `null` if the update check failed before an update type was known
+    pub update_type: Option<LastUpdatesType>,
+    /// Time from the start of the download until the sync completed or failed
+    pub duration_ms: u64,
+    /// Cells inserted or modified, `null` on failure
+    pub rows_written: Option<u64>,
+    /// Cells removed, `null` on failure
+    pub rows_removed: Option<u64>,
+    /// The version of the data set now served, `null` on failure
+    pub dataset_timestamp: Option<DateTime<Utc>>,
+    /// Why the sync failed, `null` on success
+    pub error: Option<String>,
+}
+
+/// Turns the sync lifecycle events into webhook payloads.
+#[derive(Debug, Default)]
+pub struct SyncTracker {
+    started: HashMap<LastUpdatesType, DateTime<Utc>>,
+    /// Whether the last update check failed, so a database that stays down
+    /// is reported once, not on every check
+    check_failing: bool,
+}
+
+impl SyncTracker {
+    /// Returns a payload if the event completed or failed a sync, or is the
+    /// first of a series of failed update checks.
+    pub fn observe(&mut self, event: &SyncEvent) -> Option<WebhookPayload> {
+        let duration_since = |started: Option<DateTime<Utc>>| {
+            started
+                .map(|started| (event.at - started).num_milliseconds().max(0) as u64)
+                .unwrap_or(0)
+        };
+        let failure = |update_type, duration_ms, reason: &String| WebhookPayload {
+            event: WebhookEvent::SyncFailed,
+            update_type,
+            duration_ms,
+            rows_written: None,
+            rows_removed: None,
+            dataset_timestamp: None,
+            error: Some(reason.clone()),
+        };
+
+        match event.kind {
+            SyncEventKind::Check { .. } => {
+                self.check_failing = false;
+                None
+            }
+            SyncEventKind::DownloadStarted { update_type } => {
+                self.started.insert(update_type, event.at);
+                None
+            }
+            SyncEventKind::ImportFinished {
+                update_type,
+                changed,
+                removed,
+                dataset_timestamp,
+                ..
+            } => Some(WebhookPayload {
+                event: WebhookEvent::SyncCompleted,
+                update_type: Some(update_type),
+                duration_ms: duration_since(self.started.remove(&update_type)),
+                rows_written: Some(changed),
+                rows_removed: Some(removed),
+                dataset_timestamp: Some(dataset_timestamp),
+                error: None,
+            }),
+            SyncEventKind::Failed {
+                update_type,
+                stage: SyncStage::Check,
+                ref reason,
+            } => {
+                let first = !self.check_failing;
+                self.check_failing = true;
+                first.then(|| failure(update_type, 0, reason))
+            }
+            SyncEventKind::Failed {
+                update_type: Some(update_type),
+                ref reason,
+                ..
+            } => Some(failure(
+                Some(update_type),
+                duration_since(self.started.remove(&update_type)),
+                reason,
+            )),
+            _ => None,
+        }
+    }
+}
+
+/// Signs `"{timestamp}.{body}"` with HMAC-SHA256, so a captured request can't
+/// be replayed with another timestamp.
+pub fn sign(secret: &[u8], timestamp: i64, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts any key length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    let tag = mac.finalize().into_bytes();
+    let hex: String = tag.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("sha256={}", hex)
+}
+
+/// Delay after the given (1-based) failed attempt.
+pub fn retry_delay(attempt: u32) -> Duration {
+    let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+    BASE_RETRY_DELAY.saturating_mul(factor).min(MAX_RETRY_DELAY)
+}
+
+/// Whether a response status is worth retrying. Other client errors won't
+/// succeed on a retry either.
+fn is_retryable(status: reqwest::StatusCode) -> bool {
+    status.is_server_error()
+        || status == reqwest::StatusCode::REQUEST_TIMEOUT
+        || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+}
+
+fn delivery_id(url: &str, body: &[u8], now: DateTime<Utc>) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(now.timestamp_nanos_opt().unwrap_or_default().to_be_bytes());
+    hasher.update(url.as_bytes());
+    hasher.update([0]);
+    hasher.update(body);
+    let digest = hasher.finalize();
+    digest[..16].iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// A webhook on its way to one target.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Delivery {
+    pub id: String,
+    pub url: String,
+    pub event: String,
+    pub body: String,
+}
+
+impl Delivery {
+    fn new(url: &str, payload: &WebhookPayload) -> Result<Self, serde_json::Error> {
+        let body = serde_json::to_string(payload)?;
+        Ok(Delivery {
+            id: delivery_id(url, body.as_bytes(), Utc::now()),
+            url: url.to_string(),
+            event: payload.event.name().to_string(),
+            body,
+        })
+    }
+}
+
+/// A delivery interrupted by a restart, to be continued with `next_attempt`
+/// at `retry_at`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnfinishedDelivery {
+    pub delivery: Delivery,
+    pub next_attempt: u32,
+    pub retry_at: DateTime<Utc>,
+}
+
+/// The deliveries of the delivery log whose last attempt failed with a
+/// retryable error and that have attempts left.
+pub fn unfinished_deliveries(
+    attempts: Vec<WebhookDeliveryAttempt>,
+    max_attempts: u32,
+) -> Vec<UnfinishedDelivery> {
+    let mut latest: HashMap<String, WebhookDeliveryAttempt> = HashMap::new();
+    for attempt in attempts {
+        match latest.get(&attempt.delivery_id) {
+            Some(known) if known.attempt >= attempt.attempt => {}
+            _ => {
+                latest.insert(attempt.delivery_id.clone(), attempt);
+            }
+        }
+    }
+
+    let mut unfinished: Vec<UnfinishedDelivery> = latest
+        .into_values()
+        .filter(|attempt| {
+            let retryable = match attempt.status_code {
+                Some(code) => reqwest::StatusCode::from_u16(code).is_ok_and(is_retryable),
+                None => true,
+            };
+            !attempt.succeeded && retryable && attempt.attempt < max_attempts
+        })
+        .map(|attempt| UnfinishedDelivery {
+            retry_at: attempt.attempted_at.and_utc()
+                + chrono::Duration::from_std(retry_delay(attempt.attempt)).unwrap_or_default(),
+            next_attempt: attempt.attempt + 1,
+            delivery: Delivery {
+                id: attempt.delivery_id,
+                url: attempt.url,
+                event: attempt.event,
+                body: attempt.payload,
+            },
+        })
+        .collect();
+    unfinished.sort_by_key(|unfinished| unfinished.retry_at);
+    unfinished
+}
+
+/// Persists a delivery attempt in the delivery log.
+pub fn record_attempt(
+    attempt: &WebhookDeliveryAttempt,
+    connection: &mut MysqlConnection,
+) -> Result<(), diesel::result::Error> {
+    use crate::schema::webhook_deliveries;
+
+    diesel::insert_into(webhook_deliveries::table)
+        .values(attempt)
+        .execute(connection)?;
+    Ok(())
+}
+
+/// Loads the delivery attempts since `since`.
+pub fn attempts_since(
+    since: DateTime<Utc>,
+    connection: &mut MysqlConnection,
+) -> Result<Vec<WebhookDeliveryAttempt>, diesel::result::Error> {
+    use crate::schema::webhook_deliveries::dsl::*;
+
+    webhook_deliveries
+        .filter(attempted_at.ge(since.naive_utc()))
+        .select(WebhookDeliveryAttempt::as_select())
+        .load(connection)
+}
+
+/// The delivery log, on one connection shared by all deliveries. Queries run
+/// on the blocking thread pool, and the connection is re-established after
+/// an error.
+#[derive(Clone)]
+struct DeliveryLog {
+    config: Config,
+    connection: Arc<Mutex<Option<MysqlConnection>>>,
+}
+
+impl DeliveryLog {
+    fn new(config: Config) -> Self {
+        DeliveryLog {
+            config,
+            connection: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    async fn run<T, F>(&self, query: F) -> Result<T, String>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut MysqlConnection) -> Result<T, diesel::result::Error> + Send + 'static,
+    {
+        let config = self.config.clone();
+        let shared = self.connection.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut guard = shared.lock().unwrap_or_else(|e| e.into_inner());
+            let connection = match guard.as_mut() {
+                Some(connection) => connection,
+                None => guard.insert(try_establish_connection(config).map_err(|e| e.to_string())?),
+            };
+            let result = query(connection).map_err(|e| e.to_string());
+            if result.is_err() {
+                *guard = None;
+            }
+            result
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+}
+
+/// Delivers to one target, starting with attempt `first_attempt` and
+/// retrying with exponential backoff.
+async fn deliver(
+    client: reqwest::Client,
+    log: DeliveryLog,
+    delivery: Delivery,
+    first_attempt: u32,
+    config: Config,
+) {
+    let Delivery { id, url, .. } = delivery.clone();
+    let max_attempts = config.webhook_max_attempts.max(1);
+    for attempt in first_attempt..=max_attempts {
+        let now = Utc::now();
+        let timestamp = now.timestamp();
+        let result = client
+            .post(&url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(DELIVERY_HEADER, &id)
+            .header(TIMESTAMP_HEADER, timestamp.to_string())
+            .header(
+                SIGNATURE_HEADER,
+                sign(
+                    config.webhook_secret.as_bytes(),
+                    timestamp,
+                    delivery.body.as_bytes(),
+                ),
+            )
+            .body(delivery.body.clone())
+            .send()
+            .await;
+
+        let (status_code, error, retryable) = match result {
+            Ok(response) if response.status().is_success() => {
+                (Some(response.status()), None, false)
+            }
+            Ok(response) => (
+                Some(response.status()),
+                Some(format!("Unexpected status {}", response.status())),
+                is_retryable(response.status()),
+            ),
+            Err(e) => (None, Some(e.to_string()), true),
+        };
+        let succeeded = error.is_none();
+
+        let log_entry = WebhookDeliveryAttempt {
+            delivery_id: id.clone(),
+            url: url.clone(),
+            event: delivery.event.clone(),
+            attempt,
+            status_code: status_code.map(|s| s.as_u16()),
+            error: error.clone(),
+            succeeded,
+            payload: delivery.body.clone(),
+            attempted_at: now.naive_utc(),
+        };
+        let logged = log
+            .run(move |connection| record_attempt(&log_entry, connection))
+            .await;
+        if let Err(e) = logged {
+            warn!("Failed to record webhook delivery {}: {}", id, e);
+        }
+
+        if succeeded {
+            debug!("Delivered webhook {} to {}", id, url);
+            return;
+        }
+        if !retryable || attempt == max_attempts {
+            warn!(
+                "Giving up on webhook {} to {} after {} attempts: {}",
+                id,
+                url,
+                attempt,
+                error.unwrap_or_default()
+            );
+            return;
+        }
+        tokio::time::sleep(retry_delay(attempt)).await;
+    }
+}
+
+/// Continues the deliveries a restart interrupted, for targets that are
+/// still configured.
+async fn resume_deliveries(client: &reqwest::Client, log: &DeliveryLog, config: &Config) {
+    let since = Utc::now() - chrono::Duration::from_std(RESUME_WINDOW).unwrap_or_default();
+    let attempts = match log.run(move |connection| attempts_since(since, connection)).await {
+        Ok(attempts) => attempts,
+        Err(e) => {
+            warn!("Failed to load unfinished webhook deliveries: {}", e);
+            return;
+        }
+    };
+
+    for unfinished in unfinished_deliveries(attempts, config.webhook_max_attempts.max(1)) {
+        if !config.webhook_urls.contains(&unfinished.delivery.url) {
+            continue;
+        }
+        info!(
+            "Resume webhook {} to {} with attempt {}",
+            unfinished.delivery.id, unfinished.delivery.url, unfinished.next_attempt
+        );
+        let (client, log, config) = (client.clone(), log.clone(), config.clone());
+        tokio::spawn(async move {
+            let wait = (unfinished.retry_at - Utc::now()).to_std().unwrap_or_default();
+            tokio::time::sleep(wait).await;
+            deliver(
+                client,
+                log,
+                unfinished.delivery,
+                unfinished.next_attempt,
+                config,
+            )
+            .await;
+        });
+    }
+}
+
+/// Sends a webhook to every configured target for each completed or failed sync.
+/// Deliveries a restart interrupted are resumed first. Runs until the event
+/// stream ends.
+pub async fn run_webhooks(
+    config: Config,
+    events: impl Stream<Item = SyncEvent> + Send + 'static,
+) -> Promise<()> {
+    if config.webhook_urls.is_empty() {
+        debug!("No webhook targets configured.");
+        return Ok(());
+    }
+    info!("Send webhooks to {} targets.", config.webhook_urls.len());
+
+    let client = reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()?;
+    let log = DeliveryLog::new(config.clone());
+    let mut tracker = SyncTracker::default();
+    // Events of syncs meanwhile are buffered by the stream
+    let mut events = Box::pin(events);
+    resume_deliveries(&client, &log, &config).await;
+
+    while let Some(event) = events.next().await {
+        if let Some(payload) = tracker.observe(&event) {
+            for url in &config.webhook_urls {
+                let delivery = match Delivery::new(url, &payload) {
+                    Ok(delivery) => delivery,
+                    Err(e) => {
+                        warn!("Failed to serialize webhook payload: {}", e);
+                        continue;
+                    }
+                };
+                // Slow or failing targets must not hold back the others
+                tokio::spawn(deliver(
+                    client.clone(),
+                    log.clone(),
+                    delivery,
+                    1,
+                    config.clone(),
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(seconds: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(1_766_203_200 + seconds, 0).unwrap()
+    }
+
+    fn event(seconds: i64, kind: SyncEventKind) -> SyncEvent {
+        SyncEvent {
+            at: at(seconds),
+            kind,
+        }
+    }
+
+    mod sync_tracker {
+        use super::*;
+
+        #[test]
+        fn test_completed_sync() {
+            let mut tracker = SyncTracker::default();
+            let update_type = LastUpdatesType::Diff;
+
+            assert!(tracker
+                .observe(&event(0, SyncEventKind::DownloadStarted { update_type }))
+                .is_none());
+            assert!(tracker
+                .observe(&event(5, SyncEventKind::ImportStarted { update_type }))
+                .is_none());
+            let payload = tracker.observe(&event(
+                42,
+                SyncEventKind::ImportFinished {
+                    update_type,
+                    rows: 120,
+                    changed: 100,
+                    removed: 0,
+                    dataset_timestamp: at(1),
+                },
+            ));
+
+            assert_eq!(
+                payload,
+                Some(WebhookPayload {
+                    event: WebhookEvent::SyncCompleted,
+                    update_type: Some(update_type),
+                    duration_ms: 42_000,
+                    rows_written: Some(100),
+                    rows_removed: Some(0),
+                    dataset_timestamp: Some(at(1)),
+                    error: None,
+                })
+            );
+        }
+
+        #[test]
+        fn test_failed_sync() {
+            let mut tracker = SyncTracker::default();
+            let update_type = LastUpdatesType::Full;
+            tracker.observe(&event(0, SyncEventKind::DownloadStarted { update_type }));
+
+            let payload = tracker
+                .observe(&event(
+                    3,
+                    SyncEventKind::Failed {
+                        update_type: Some(update_type),
+                        stage: SyncStage::Download,
+                        reason: "RATE_LIMITED".to_string(),
+                    },
+                ))
+                .unwrap();
+
+            assert_eq!(payload.event, WebhookEvent::SyncFailed);
+            assert_eq!(payload.duration_ms, 3_000);
+            assert_eq!(payload.error.as_deref(), Some("RATE_LIMITED"));
+            assert!(payload.rows_written.is_none());
+        }
+
+        #[test]
+        fn test_failed_checks_are_reported_once() {
+            let mut tracker = SyncTracker::default();
+            let check_failed = |seconds| {
+                event(
+                    seconds,
+                    SyncEventKind::Failed {
+                        update_type: None,
+                        stage: SyncStage::Check,
+                        reason: "database unavailable".to_string(),
+                    },
+                )
+            };
+
+            let payload = tracker.observe(&check_failed(0)).unwrap();
+            assert_eq!(payload.event, WebhookEvent::SyncFailed);
+            assert_eq!(payload.update_type, None);
+            assert_eq!(payload.error.as_deref(), Some("database unavailable"));
+            assert!(tracker.observe(&check_failed(600)).is_none());
+
+            tracker.observe(&event(1200, SyncEventKind::Check { update_type: None }));
+            assert!(tracker.observe(&check_failed(1800)).is_some());
+        }
+    }
+
+    mod unfinished_deliveries {
+        use super::*;
+
+        fn attempt(id: &str, attempt: u32, status_code: Option<u16>) -> WebhookDeliveryAttempt {
+            WebhookDeliveryAttempt {
+                delivery_id: id.to_string(),
+                url: "https://example.com/hook".to_string(),
+                event: WebhookEvent::SyncCompleted.name().to_string(),
+                attempt,
+                status_code,
+                error: (status_code != Some(200)).then(|| "failed".to_string()),
+                succeeded: status_code == Some(200),
+                payload: "{}".to_string(),
+                attempted_at: at(i64::from(attempt) * 60).naive_utc(),
+            }
+        }
+
+        #[test]
+        fn test_resumes_after_the_latest_retryable_failure() {
+            let attempts = vec![attempt("a", 2, Some(503)), attempt("a", 1, None)];
+
+            let unfinished = unfinished_deliveries(attempts, 6);
+
+            assert_eq!(
+                unfinished,
+                vec![UnfinishedDelivery {
+                    delivery: Delivery {
+                        id: "a".to_string(),
+                        url: "https://example.com/hook".to_string(),
+                        event: "sync.completed".to_string(),
+                        body: "{}".to_string(),
+                    },
+                    next_attempt: 3,
+                    retry_at: at(120 + 4),
+                }]
+            );
+        }
+
+        #[test]
+        fn test_skips_finished_deliveries() {
+            let attempts = vec![
+                // Delivered on the second attempt
+                attempt("delivered", 1, Some(503)),
+                attempt("delivered", 2, Some(200)),
+                // Rejected for good
+                attempt("rejected", 1, Some(404)),
+                // Out of attempts
+                attempt("exhausted", 3, None),
+            ];
+
+            assert!(unfinished_deliveries(attempts, 3).is_empty());
+        }
+    }
+
+    #[test]
+    fn test_payload_serialization() {
+        let payload = WebhookPayload {
+            event: WebhookEvent::SyncFailed,
+            update_type: Some(LastUpdatesType::Full),
+            duration_ms: 1500,
+            rows_written: None,
+            rows_removed: None,
+            dataset_timestamp: None,
+            error: Some("timeout".to_string()),
+        };
+
+        let json = serde_json::to_value(&payload).unwrap();
+
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "event": "sync.failed",
+                "updateType": "full",
+                "durationMs": 1500,
+                "rowsWritten": null,
+                "rowsRemoved": null,
+                "datasetTimestamp": null,
+                "error": "timeout"
+            })
+        );
+    }
+
+    #[test]
+    fn test_signature() {
+        // echo -n '1766203200.{"a":1}' | openssl dgst -sha256 -hmac secret
+        assert_eq!(
+            sign(b"secret", 1_766_203_200, br#"{"a":1}"#),
+            "sha256=8144226232a382702e7115eb5be4673cfb91d6731c44340d593ccefa1f4dcf21"
+        );
+    }
+
+    #[test]
+    fn test_signature_covers_timestamp() {
+        assert_ne!(sign(b"secret", 1, b"body"), sign(b"secret", 2, b"body"));
+    }
+
+    #[test]
+    fn test_retry_delay_backs_off_exponentially() {
+        assert_eq!(retry_delay(1), Duration::from_secs(2));
+        assert_eq!(retry_delay(2), Duration::from_secs(4));
+        assert_eq!(retry_delay(5), Duration::from_secs(32));
+        assert_eq!(retry_delay(20), MAX_RETRY_DELAY);
+        assert_eq!(retry_delay(u32::MAX), MAX_RETRY_DELAY);
+    }
+
+    #[test]
+    fn test_retryable_statuses() {
+        assert!(is_retryable(reqwest::StatusCode::BAD_GATEWAY));
+        assert!(is_retryable(reqwest::StatusCode::TOO_MANY_REQUESTS));
+        assert!(!is_retryable(reqwest::StatusCode::NOT_FOUND));
+        assert!(!is_retryable(reqwest::StatusCode::UNAUTHORIZED));
+    }
+
+    #[tokio::test]
+    async fn test_without_targets_returns_immediately() {
+        let config = crate::utils::config::test_config();
+
+        let result = run_webhooks(config, futures::stream::pending()).await;
+
+        assert!(result.is_ok());
+    }
+
+    /// Integration tests for the delivery log using testcontainers.
+    #[cfg(feature = "integration_tests")]
+    mod delivery_log_integration {
+        use super::*;
+        use crate::schema::webhook_deliveries;
+        use crate::utils::test_db::get_test_connection;
+        use diesel::{QueryDsl, SelectableHelper};
+
+        #[test]
+        fn test_record_attempt() {
+            let (_container, mut conn) = get_test_connection();
+            let attempt = WebhookDeliveryAttempt {
+                delivery_id: "0123456789abcdef0123456789abcdef".to_string(),
+                url: "https://example.com/hook".to_string(),
+                event: WebhookEvent::SyncCompleted.name().to_string(),
+                attempt: 2,
+                status_code: Some(503),
+                error: Some("Unexpected status 503".to_string()),
+                succeeded: false,
+                payload: "{}".to_string(),
+                attempted_at: at(0).naive_utc(),
+            };
+
+            record_attempt(&attempt, &mut conn).unwrap();
+
+            let logged: WebhookDeliveryAttempt = webhook_deliveries::table
+                .select(WebhookDeliveryAttempt::as_select())
+                .first(&mut conn)
+                .unwrap();
+            assert_eq!(logged.attempt, 2);
+            assert_eq!(logged.status_code, Some(503));
+            assert!(!logged.succeeded);
+        }
+    }
+}